@@ -0,0 +1,95 @@
+/*
+ * C bindings for skyrim-cell-dump, built into a shared library with the `cdylib` feature:
+ *
+ *     cargo rustc --release --lib --features cdylib --crate-type cdylib
+ *
+ * Keep in sync with src/ffi.rs, which has tests that check the functions and structs declared here against it.
+ */
+#ifndef SKYRIM_CELL_DUMP_H
+#define SKYRIM_CELL_DUMP_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A parsed plugin, which owns all of the strings that its accessors return */
+typedef struct ScdPlugin ScdPlugin;
+
+/* A cell of a parsed plugin */
+typedef struct ScdCell {
+    uint32_t form_id;
+    /* Editor id of the cell, or NULL if it has none */
+    const char *editor_id;
+    /* Whether x and y are set, which they are for exterior cells */
+    bool has_coordinates;
+    int32_t x;
+    int32_t y;
+    /* Whether world_form_id is set, which it is for exterior cells */
+    bool has_world;
+    uint32_t world_form_id;
+    bool is_persistent;
+    /* Flags from the DATA field of the cell, e.g. 0x0001 for interior cells */
+    uint16_t flags;
+} ScdCell;
+
+/* A worldspace of a parsed plugin */
+typedef struct ScdWorld {
+    uint32_t form_id;
+    const char *editor_id;
+} ScdWorld;
+
+/*
+ * Parses a plugin from the len bytes at data. game is the name of the game the plugin was made for ("skyrim",
+ * "fallout4", "fallout3", "falloutnv", or "oblivion"), or NULL for Skyrim. Returns NULL if the plugin can't be parsed.
+ * The plugin must be freed with scd_free_plugin.
+ */
+ScdPlugin *scd_parse_plugin(const uint8_t *data, size_t len, const char *game);
+
+/*
+ * Parses a plugin like scd_parse_plugin, but returns the plugin serialized as JSON. Returns NULL if the plugin can't
+ * be parsed. The string must be freed with scd_free_string.
+ */
+char *scd_parse_plugin_json(const uint8_t *data, size_t len, const char *game);
+
+/* Frees a plugin, which invalidates all of the pointers returned by its accessors. Does nothing if plugin is NULL. */
+void scd_free_plugin(ScdPlugin *plugin);
+
+/* Frees a string returned by scd_parse_plugin_json. Does nothing if string is NULL. */
+void scd_free_string(char *string);
+
+/*
+ * Returns the message of the last error on the current thread, or NULL if there was none. The message is valid until
+ * the next call that fails on the same thread.
+ */
+const char *scd_last_error(void);
+
+/* Returns the version of the plugin file format from the plugin's header */
+float scd_plugin_version(const ScdPlugin *plugin);
+
+/* Returns the number of masters of the plugin */
+size_t scd_plugin_master_count(const ScdPlugin *plugin);
+
+/* Returns the file name of the master at index, or NULL if index is out of range */
+const char *scd_plugin_master(const ScdPlugin *plugin, size_t index);
+
+/* Returns the number of cells in the plugin */
+size_t scd_plugin_cell_count(const ScdPlugin *plugin);
+
+/* Returns the cell at index, or NULL if index is out of range */
+const ScdCell *scd_plugin_cell(const ScdPlugin *plugin, size_t index);
+
+/* Returns the number of worldspaces in the plugin */
+size_t scd_plugin_world_count(const ScdPlugin *plugin);
+
+/* Returns the worldspace at index, sorted by form id, or NULL if index is out of range */
+const ScdWorld *scd_plugin_world(const ScdPlugin *plugin, size_t index);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SKYRIM_CELL_DUMP_H */
@@ -0,0 +1,78 @@
+/**
+ * JavaScript bindings for the WebAssembly build of skyrim-cell-dump, built with:
+ *
+ *     cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib
+ *
+ * Usage:
+ *
+ *     import { load } from "./skyrim_cell_dump.js";
+ *
+ *     const skyrimCellDump = await load(fetch("skyrim_cell_dump.wasm"));
+ *     const plugin = skyrimCellDump.parsePlugin(new Uint8Array(await file.arrayBuffer()));
+ */
+
+/**
+ * Instantiates the WebAssembly module.
+ *
+ * @param source A `Response` (or a promise of one) for the .wasm file, its bytes, or a compiled `WebAssembly.Module`
+ */
+export async function load(source) {
+  const resolved = await source;
+  const result =
+    typeof Response !== "undefined" && resolved instanceof Response
+      ? await WebAssembly.instantiateStreaming(resolved, {})
+      : await WebAssembly.instantiate(resolved, {});
+  // instantiating a compiled module returns the instance itself
+  return new SkyrimCellDump((result.instance || result).exports);
+}
+
+export class SkyrimCellDump {
+  constructor(exports) {
+    this.exports = exports;
+  }
+
+  /**
+   * Parses a plugin file into an object with the same fields as the JSON output of the binary.
+   *
+   * Throws an `Error` if the plugin can't be parsed.
+   *
+   * @param bytes The contents of the plugin file, as a `Uint8Array` or `ArrayBuffer`
+   * @param game The game the plugin was made for ("skyrim", "fallout4", "fallout3", "falloutnv", or "oblivion")
+   */
+  parsePlugin(bytes, game = "skyrim") {
+    const exports = this.exports;
+    const data = bytes instanceof Uint8Array ? bytes : new Uint8Array(bytes);
+    const gameBytes = new TextEncoder().encode(`${game}\0`);
+    const dataPtr = this.copyIn(data);
+    const gamePtr = this.copyIn(gameBytes);
+    try {
+      const jsonPtr = exports.scd_parse_plugin_json(dataPtr, data.length, gamePtr);
+      if (jsonPtr === 0) {
+        throw new Error(this.readString(exports.scd_last_error()));
+      }
+      try {
+        return JSON.parse(this.readString(jsonPtr));
+      } finally {
+        exports.scd_free_string(jsonPtr);
+      }
+    } finally {
+      exports.scd_dealloc(dataPtr, data.length);
+      exports.scd_dealloc(gamePtr, gameBytes.length);
+    }
+  }
+
+  /** Copies bytes into memory allocated in the module, returning the pointer to them */
+  copyIn(bytes) {
+    const ptr = this.exports.scd_alloc(bytes.length);
+    new Uint8Array(this.exports.memory.buffer, ptr, bytes.length).set(bytes);
+    return ptr;
+  }
+
+  /** Reads a null-terminated UTF-8 string from the memory of the module */
+  readString(ptr) {
+    // the buffer is fetched again since it is replaced whenever the memory of the module grows
+    const memory = new Uint8Array(this.exports.memory.buffer);
+    const end = memory.indexOf(0, ptr);
+    return new TextDecoder().decode(memory.subarray(ptr, end));
+  }
+}
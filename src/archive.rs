@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::error::{ParseError, Result};
+use crate::parser::{parse_plugin_with_options, ParseOptions, Plugin};
+
+#[cfg(feature = "ba2")]
+use crate::ba2::Ba2;
+#[cfg(feature = "bsa")]
+use crate::bsa::Bsa;
+
+/// Extensions of the plugin files in an archive, matched case-insensitively
+const PLUGIN_EXTENSIONS: [&str; 3] = ["esp", "esm", "esl"];
+
+/// An opened game archive that files can be extracted from, like a [`Bsa`](crate::Bsa) or a [`Ba2`](crate::Ba2).
+///
+/// Paths in archives separate folders with backslashes. Paths passed to [`Archive::extract`] are matched
+/// case-insensitively, and may use either forward or backward slashes.
+pub trait Archive<'a> {
+    /// Paths of all of the files in the archive, like `textures\sky\skyrimcloudsupper01.dds`
+    fn paths(&self) -> Vec<&str>;
+
+    /// Extracts the file at `path`, decompressing it if it is compressed
+    fn extract(&self, path: &str) -> Result<Cow<'a, [u8]>>;
+
+    /// Paths of the plugin files (`.esp`, `.esm`, and `.esl`) in the archive
+    fn plugin_paths(&self) -> Vec<&str> {
+        self.paths()
+            .into_iter()
+            .filter(|path| is_plugin_path(path))
+            .collect()
+    }
+
+    /// Parses every plugin file in the archive, keyed by its path in the archive. A plugin that fails to extract or
+    /// parse doesn't stop the others from being parsed.
+    fn parse_plugins(&self, options: &ParseOptions) -> BTreeMap<String, Result<Plugin<'static>>> {
+        self.plugin_paths()
+            .into_iter()
+            .map(|path| {
+                let plugin = self.extract(path).and_then(|contents| {
+                    Ok(parse_plugin_with_options(&contents, options)?.into_owned())
+                });
+                (path.to_string(), plugin)
+            })
+            .collect()
+    }
+}
+
+/// Opens the BSA or BA2 archive at `path` and parses every plugin file in it, keyed by its path in the archive. The
+/// type of archive is detected from the start of the file.
+///
+/// # Arguments
+///
+/// * `path` - Path to the archive
+/// * `options` - Options that control what is extracted from the plugins
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_archive_plugins_file, ParseOptions};
+///
+/// let plugins = parse_archive_plugins_file("Plugin.bsa", &ParseOptions::default()).unwrap();
+/// ```
+pub fn parse_archive_plugins_file<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<BTreeMap<String, Result<Plugin<'static>>>> {
+    let contents = std::fs::read(path.as_ref())?;
+    match contents.get(..4) {
+        #[cfg(feature = "bsa")]
+        Some(b"BSA\0") => Ok(Bsa::parse(&contents)?.parse_plugins(options)),
+        #[cfg(feature = "ba2")]
+        Some(b"BTDX") => Ok(Ba2::parse(&contents)?.parse_plugins(options)),
+        _ => Err(ParseError::InvalidValue {
+            kind: "archive",
+            value: path.as_ref().display().to_string(),
+        }),
+    }
+}
+
+/// Converts a path passed to [`Archive::extract`] to the backslashes used by archives
+pub(crate) fn archive_path(path: &str) -> String {
+    path.replace('/', "\\")
+}
+
+fn is_plugin_path(path: &str) -> bool {
+    match path.rsplit_once('.') {
+        Some((_, extension)) => PLUGIN_EXTENSIONS
+            .iter()
+            .any(|plugin_extension| extension.eq_ignore_ascii_case(plugin_extension)),
+        None => false,
+    }
+}
@@ -0,0 +1,389 @@
+//! Reading plugins out of [Fallout 4 and Fallout 76 archives (BA2)](https://en.uesp.net/wiki/Fallout4Mod:Archive_File_Format).
+//!
+//! Only general (`GNRL`) archives are supported, since plugins are never stored in texture (`DX10`) archives. Files
+//! are compressed with zlib, except in version 3 archives which can use LZ4 blocks instead.
+use std::borrow::Cow;
+
+use nom::{
+    bytes::complete::take,
+    combinator::verify,
+    number::complete::{le_u16, le_u32, le_u64},
+};
+
+use crate::archive::{archive_path, Archive};
+use crate::compression::{decompress_sized, Compression};
+use crate::error::{expecting, in_record, IResult, ParseError, Result};
+
+/// Value of the compression format in version 3 headers for LZ4 blocks
+const LZ4_COMPRESSION_FORMAT: u32 = 3;
+
+/// An opened general BA2 archive, which borrows the contents of the archive file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{Archive, Ba2, Game, ParseOptions};
+///
+/// let contents = std::fs::read("Plugin - Main.ba2").unwrap();
+/// let ba2 = Ba2::parse(&contents).unwrap();
+/// let options = ParseOptions {
+///     game: Game::Fallout4,
+///     ..Default::default()
+/// };
+/// for (path, plugin) in ba2.parse_plugins(&options) {
+///     println!("{}: {:?}", path, plugin.map(|plugin| plugin.cells.len()));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Ba2<'a> {
+    input: &'a [u8],
+    version: u32,
+    compression: Compression,
+    files: Vec<Ba2File>,
+}
+
+#[derive(Debug)]
+struct Ba2File {
+    path: String,
+    offset: usize,
+    /// Size of the compressed data, or 0 if the file isn't compressed
+    packed_size: usize,
+    unpacked_size: usize,
+}
+
+struct Ba2Header<'a> {
+    version: u32,
+    archive_type: &'a [u8],
+    file_count: u32,
+    name_table_offset: u64,
+    compression: Compression,
+}
+
+impl<'a> Ba2<'a> {
+    /// Parses the header, file records, and file names of the archive in `input`. File data is only read once it is
+    /// extracted.
+    pub fn parse(input: &'a [u8]) -> Result<Ba2<'a>> {
+        let (records, header) =
+            parse_header(input).map_err(|err| ParseError::from_nom(err, input))?;
+        if !matches!(header.version, 1 | 2 | 3 | 7 | 8) {
+            return Err(ParseError::InvalidValue {
+                kind: "BA2 version",
+                value: header.version.to_string(),
+            });
+        }
+        if header.archive_type != b"GNRL" {
+            return Err(ParseError::InvalidValue {
+                kind: "BA2 archive type",
+                value: String::from_utf8_lossy(header.archive_type).into_owned(),
+            });
+        }
+        let (_, files) =
+            parse_files(input, records, &header).map_err(|err| ParseError::from_nom(err, input))?;
+        Ok(Ba2 {
+            input,
+            version: header.version,
+            compression: header.compression,
+            files,
+        })
+    }
+
+    /// Version of the archive format: 1, 7, or 8 for Fallout 4 and Fallout 76, or 2 or 3 for later games
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn extract_file(&self, file: &Ba2File) -> Result<Cow<'a, [u8]>> {
+        let size = if file.packed_size == 0 {
+            file.unpacked_size
+        } else {
+            file.packed_size
+        };
+        let data =
+            self.input
+                .get(file.offset..file.offset + size)
+                .ok_or(ParseError::TruncatedRecord {
+                    record_type: Some("BTDX".to_string()),
+                    offset: self.input.len(),
+                })?;
+        if file.packed_size == 0 {
+            return Ok(Cow::Borrowed(data));
+        }
+        decompress_sized(data, file.unpacked_size, self.compression)
+            .map(Cow::Owned)
+            .map_err(|source| ParseError::ExtractError {
+                path: file.path.clone(),
+                offset: file.offset,
+                source,
+            })
+    }
+}
+
+impl<'a> Archive<'a> for Ba2<'a> {
+    fn paths(&self) -> Vec<&str> {
+        self.files.iter().map(|file| file.path.as_str()).collect()
+    }
+
+    fn extract(&self, path: &str) -> Result<Cow<'a, [u8]>> {
+        let path = archive_path(path);
+        let file = self
+            .files
+            .iter()
+            .find(|file| file.path.eq_ignore_ascii_case(&path))
+            .ok_or(ParseError::InvalidValue {
+                kind: "path in archive",
+                value: path,
+            })?;
+        self.extract_file(file)
+    }
+}
+
+fn parse_header(input: &[u8]) -> IResult<&[u8], Ba2Header<'_>> {
+    let (input, _) =
+        verify(take(4usize), |magic: &[u8]| magic == b"BTDX")(input).map_err(expecting("BTDX"))?;
+    let (input, version) = le_u32(input).map_err(in_record("BTDX"))?;
+    let (input, archive_type) = take(4usize)(input).map_err(in_record("BTDX"))?;
+    let (input, file_count) = le_u32(input).map_err(in_record("BTDX"))?;
+    let (mut input, name_table_offset) = le_u64(input).map_err(in_record("BTDX"))?;
+    let mut compression = Compression::Zlib;
+    if version == 2 || version == 3 {
+        // two unknown values
+        let (remaining, _) = take(8usize)(input).map_err(in_record("BTDX"))?;
+        input = remaining;
+    }
+    if version == 3 {
+        let (remaining, compression_format) = le_u32(input).map_err(in_record("BTDX"))?;
+        if compression_format == LZ4_COMPRESSION_FORMAT {
+            compression = Compression::Lz4;
+        }
+        input = remaining;
+    }
+    Ok((
+        input,
+        Ba2Header {
+            version,
+            archive_type,
+            file_count,
+            name_table_offset,
+            compression,
+        },
+    ))
+}
+
+/// Parses the file records in `records`, which directly follow the header, and the file names in the name table at
+/// the end of the archive
+fn parse_files<'a>(
+    input: &'a [u8],
+    records: &'a [u8],
+    header: &Ba2Header,
+) -> IResult<&'a [u8], Vec<Ba2File>> {
+    // the records are read one at a time since the count comes from the file and may be garbage
+    let mut files = vec![];
+    let mut records = records;
+    for _ in 0..header.file_count {
+        // name hash, extension, directory hash, and flags
+        let (remaining, _) = take(16usize)(records).map_err(in_record("BTDX"))?;
+        let (remaining, offset) = le_u64(remaining).map_err(in_record("BTDX"))?;
+        let (remaining, packed_size) = le_u32(remaining).map_err(in_record("BTDX"))?;
+        let (remaining, unpacked_size) = le_u32(remaining).map_err(in_record("BTDX"))?;
+        // alignment, always 0xBAADF00D
+        let (remaining, _) = le_u32(remaining).map_err(in_record("BTDX"))?;
+        files.push(Ba2File {
+            path: String::new(),
+            offset: offset as usize,
+            packed_size: packed_size as usize,
+            unpacked_size: unpacked_size as usize,
+        });
+        records = remaining;
+    }
+
+    let (mut names, _) = take(header.name_table_offset)(input).map_err(in_record("BTDX"))?;
+    for file in files.iter_mut() {
+        let (remaining, length) = le_u16(names).map_err(in_record("BTDX"))?;
+        let (remaining, name) = take(length)(remaining).map_err(in_record("BTDX"))?;
+        file.path = String::from_utf8_lossy(name).replace('/', "\\");
+        names = remaining;
+    }
+    Ok((names, files))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::game::Game;
+    use crate::parser::{Cell, ParseOptions};
+
+    fn plugin() -> Vec<u8> {
+        PluginBuilder::new()
+            .game(Game::Fallout4)
+            .master("Fallout4.esm")
+            .cell(Cell::new_interior(0x0100_0D63, Some("Vault".into())))
+            .write()
+            .unwrap()
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// An LZ4 block of only literals
+    fn lz4_block(data: &[u8]) -> Vec<u8> {
+        let mut block = vec![0xF0];
+        let mut length = data.len() - 15;
+        while length >= 255 {
+            block.push(255);
+            length -= 255;
+        }
+        block.push(length as u8);
+        block.extend_from_slice(data);
+        block
+    }
+
+    /// Builds a general archive holding `files`, which are (path, stored data, unpacked size or 0 if not compressed)
+    fn general_archive(
+        version: u32,
+        compression_format: u32,
+        files: &[(&str, &[u8], usize)],
+    ) -> Vec<u8> {
+        let header_size = match version {
+            2 => 32,
+            3 => 36,
+            _ => 24,
+        };
+        let mut data_offset = header_size + 36 * files.len();
+        let name_table_offset =
+            data_offset + files.iter().map(|(_, data, _)| data.len()).sum::<usize>();
+
+        let mut bytes = b"BTDX".to_vec();
+        bytes.extend_from_slice(&version.to_le_bytes());
+        bytes.extend_from_slice(b"GNRL");
+        bytes.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(name_table_offset as u64).to_le_bytes());
+        if version == 2 || version == 3 {
+            bytes.extend_from_slice(&[0; 8]);
+        }
+        if version == 3 {
+            bytes.extend_from_slice(&compression_format.to_le_bytes());
+        }
+        for (_, data, unpacked_size) in files {
+            bytes.extend_from_slice(&[0; 4]);
+            bytes.extend_from_slice(b"esp\0");
+            bytes.extend_from_slice(&[0; 8]);
+            bytes.extend_from_slice(&(data_offset as u64).to_le_bytes());
+            let (packed_size, unpacked_size) = if *unpacked_size == 0 {
+                (0, data.len())
+            } else {
+                (data.len(), *unpacked_size)
+            };
+            bytes.extend_from_slice(&(packed_size as u32).to_le_bytes());
+            bytes.extend_from_slice(&(unpacked_size as u32).to_le_bytes());
+            bytes.extend_from_slice(&0xBAAD_F00Du32.to_le_bytes());
+            data_offset += data.len();
+        }
+        for (_, data, _) in files {
+            bytes.extend_from_slice(data);
+        }
+        for (path, _, _) in files {
+            bytes.extend_from_slice(&(path.len() as u16).to_le_bytes());
+            bytes.extend_from_slice(path.as_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn extracts_zlib_compressed_and_uncompressed_files() {
+        let plugin = plugin();
+        let bytes = general_archive(
+            1,
+            0,
+            &[
+                ("Test.esp", &zlib(&plugin), plugin.len()),
+                ("meshes/Test.nif", b"mesh", 0),
+            ],
+        );
+        let ba2 = Ba2::parse(&bytes).unwrap();
+        assert_eq!(ba2.version(), 1);
+        assert_eq!(ba2.paths(), vec!["Test.esp", "meshes\\Test.nif"]);
+        assert_eq!(ba2.plugin_paths(), vec!["Test.esp"]);
+        assert_eq!(ba2.extract("test.ESP").unwrap(), plugin);
+        assert!(
+            matches!(ba2.extract("meshes/test.nif").unwrap(), Cow::Borrowed(data) if data == b"mesh")
+        );
+        assert!(ba2.extract("Missing.esp").is_err());
+
+        let options = ParseOptions {
+            game: Game::Fallout4,
+            ..Default::default()
+        };
+        let plugins = ba2.parse_plugins(&options);
+        assert_eq!(plugins.len(), 1);
+        let cells = &plugins["Test.esp"].as_ref().unwrap().cells;
+        let cell = cells.iter().next().unwrap();
+        assert_eq!(cell.editor_id.as_deref(), Some("Vault"));
+    }
+
+    #[test]
+    fn extracts_lz4_compressed_files_from_version_3_archives() {
+        let plugin = plugin();
+        let bytes = general_archive(
+            3,
+            LZ4_COMPRESSION_FORMAT,
+            &[("Test.esp", &lz4_block(&plugin), plugin.len())],
+        );
+        let ba2 = Ba2::parse(&bytes).unwrap();
+        assert_eq!(ba2.extract("Test.esp").unwrap(), plugin);
+
+        // version 3 archives with any other compression format use zlib
+        let bytes = general_archive(3, 0, &[("Test.esp", &zlib(&plugin), plugin.len())]);
+        assert_eq!(
+            Ba2::parse(&bytes).unwrap().extract("Test.esp").unwrap(),
+            plugin
+        );
+        let bytes = general_archive(2, 0, &[("Test.esp", &zlib(&plugin), plugin.len())]);
+        assert_eq!(
+            Ba2::parse(&bytes).unwrap().extract("Test.esp").unwrap(),
+            plugin
+        );
+    }
+
+    #[test]
+    fn reports_files_that_fail_to_extract() {
+        let bytes = general_archive(8, 0, &[("Broken.esp", b"not zlib", 16)]);
+        let ba2 = Ba2::parse(&bytes).unwrap();
+        assert!(matches!(
+            ba2.extract("Broken.esp"),
+            Err(ParseError::ExtractError { .. })
+        ));
+        assert!(ba2.parse_plugins(&ParseOptions::default())["Broken.esp"].is_err());
+
+        let mut bytes = general_archive(1, 0, &[("Test.esp", b"Test", 0)]);
+        // the data of the file is past the end of the archive
+        bytes[40..48].copy_from_slice(&1000u64.to_le_bytes());
+        let ba2 = Ba2::parse(&bytes).unwrap();
+        assert!(matches!(
+            ba2.extract("Test.esp"),
+            Err(ParseError::TruncatedRecord { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_and_truncated_archives() {
+        let bytes = general_archive(1, 0, &[("Test.esp", b"Test", 0)]);
+        let mut unsupported_version = bytes.clone();
+        unsupported_version[4] = 4;
+        assert!(Ba2::parse(&unsupported_version).is_err());
+        let mut textures = bytes.clone();
+        textures[8..12].copy_from_slice(b"DX10");
+        assert!(Ba2::parse(&textures).is_err());
+        assert!(Ba2::parse(b"BSA\0\x68\0\0\0").is_err());
+        for length in [0, 10, 30, 60, 66] {
+            assert!(Ba2::parse(&bytes[..length]).is_err());
+        }
+    }
+}
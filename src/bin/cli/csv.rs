@@ -0,0 +1,70 @@
+use std::io::{self, Write};
+
+use skyrim_cell_dump::Plugin;
+
+use crate::sort::{sorted_cells, Sort};
+
+const HEADER: [&str; 6] = [
+    "form_id",
+    "editor_id",
+    "x",
+    "y",
+    "worldspace",
+    "is_persistent",
+];
+
+/// Writes one row for every cell of the plugins, in the `sort` order, after a header row with the column names.
+///
+/// With `include_plugin`, the rows start with a column with the name of the plugin that the cell is from.
+pub fn write_csv<W: Write>(
+    writer: &mut W,
+    plugins: &[(&str, Plugin)],
+    include_plugin: bool,
+    sort: Sort,
+) -> io::Result<()> {
+    write_row(writer, include_plugin.then_some("plugin"), &HEADER)?;
+    for (name, plugin) in plugins {
+        for cell in sorted_cells(plugin, sort) {
+            let worldspace = cell
+                .world_form_id
+                .and_then(|world_form_id| {
+                    plugin
+                        .worlds
+                        .iter()
+                        .find(|world| world.form_id == world_form_id)
+                })
+                .map(|world| world.editor_id.as_str());
+            write_row(
+                writer,
+                include_plugin.then_some(*name),
+                &[
+                    &format!("{:08X}", cell.form_id),
+                    cell.editor_id.as_deref().unwrap_or_default(),
+                    &cell.x.map(|x| x.to_string()).unwrap_or_default(),
+                    &cell.y.map(|y| y.to_string()).unwrap_or_default(),
+                    worldspace.unwrap_or_default(),
+                    &cell.is_persistent.to_string(),
+                ],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn write_row<W: Write>(writer: &mut W, plugin: Option<&str>, fields: &[&str]) -> io::Result<()> {
+    let fields: Vec<String> = plugin
+        .iter()
+        .chain(fields)
+        .map(|field| escape_field(field))
+        .collect();
+    writeln!(writer, "{}", fields.join(","))
+}
+
+/// Quotes a field if it contains characters that would otherwise split it, doubling any quotes in it
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
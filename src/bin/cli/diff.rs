@@ -0,0 +1,139 @@
+use serde_json::Value;
+
+use skyrim_cell_dump::{Cell, FormId, PluginHeader};
+
+/// Fields that are left out of diffs: the form id, which is matched through the masters instead, and the fields that
+/// change whenever the Creation Kit saves a plugin
+const UNCOMPARED_FIELDS: [&str; 3] = ["form_id", "metadata", "form_version"];
+
+/// The lowercase names of the masters of both plugins, new ones first, that the form ids of compared cells are made
+/// relative to
+pub struct Masters(Vec<String>);
+
+impl Masters {
+    pub fn new(old_header: &PluginHeader, new_header: &PluginHeader) -> Self {
+        let mut masters: Vec<String> = vec![];
+        for master in new_header.masters.iter().chain(old_header.masters.iter()) {
+            let master = master.to_ascii_lowercase();
+            if !masters.contains(&master) {
+                masters.push(master);
+            }
+        }
+        Masters(masters)
+    }
+
+    /// Rewrites a form id of the plugin with the `header` to index into the masters of both plugins. Records that the
+    /// plugin defines itself get the index past the masters.
+    fn resolve(&self, header: &PluginHeader, form_id: u32) -> u32 {
+        let form_id = FormId(form_id);
+        let index = form_id
+            .resolve_master(header)
+            .and_then(|master| {
+                self.0
+                    .iter()
+                    .position(|other| master.eq_ignore_ascii_case(other))
+            })
+            .unwrap_or(self.0.len());
+        ((index as u32) << 24) | form_id.local_id()
+    }
+}
+
+/// The fields that differ between two versions of a cell, with their values in each plugin. Form ids are compared
+/// through the masters of the plugins, so a plugin whose masters were added to or reordered doesn't differ.
+pub fn changed_fields(
+    masters: &Masters,
+    old_cell: &Cell,
+    old_header: &PluginHeader,
+    new_cell: &Cell,
+    new_header: &PluginHeader,
+) -> Vec<(String, Value, Value)> {
+    let resolved = |cell: &Cell, header: &PluginHeader| {
+        let mut cell = cell.clone();
+        cell.map_form_ids(|form_id| masters.resolve(header, form_id));
+        serde_json::to_value(cell).unwrap()
+    };
+    let old_resolved = resolved(old_cell, old_header);
+    let new_resolved = resolved(new_cell, new_header);
+    let old_fields = serde_json::to_value(old_cell).unwrap();
+    let new_fields = serde_json::to_value(new_cell).unwrap();
+    old_resolved
+        .as_object()
+        .unwrap()
+        .iter()
+        .filter(|&(field, _)| !UNCOMPARED_FIELDS.contains(&field.as_str()))
+        .filter(|&(field, old_value)| old_value != &new_resolved[field])
+        .map(|(field, _)| {
+            (
+                field.clone(),
+                old_fields[field].clone(),
+                new_fields[field].clone(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use skyrim_cell_dump::{parse_plugin, PluginBuilder, RecordMetadata};
+
+    fn header(masters: &[&str]) -> PluginHeader<'static> {
+        let bytes = masters
+            .iter()
+            .fold(PluginBuilder::new(), |builder, master| {
+                builder.master(*master)
+            })
+            .write()
+            .unwrap();
+        parse_plugin(&bytes).unwrap().into_owned().header
+    }
+
+    fn cell(form_id: u32, region: u32, child: u32) -> Cell<'static> {
+        let mut cell = Cell::new_exterior(form_id, 0x0000_003C, 3, -2);
+        cell.regions = vec![region];
+        cell.temporary_children = vec![child];
+        cell
+    }
+
+    fn field_names(changes: &[(String, Value, Value)]) -> Vec<&str> {
+        changes.iter().map(|(field, _, _)| field.as_str()).collect()
+    }
+
+    #[test]
+    fn form_ids_are_compared_through_the_masters() {
+        let old_header = header(&["Skyrim.esm", "Update.esm"]);
+        let new_header = header(&["Skyrim.esm", "Dawnguard.esm", "Update.esm"]);
+        let masters = Masters::new(&old_header, &new_header);
+        let old_cell = cell(0x0000_9B79, 0x0100_0D70, 0x0200_0800);
+        let new_cell = cell(0x0000_9B79, 0x0200_0D70, 0x0300_0800);
+        assert!(
+            changed_fields(&masters, &old_cell, &old_header, &new_cell, &new_header).is_empty()
+        );
+
+        // Dawnguard.esm is a different plugin than Update.esm, so the same index is a change
+        let new_cell = cell(0x0000_9B79, 0x0100_0D70, 0x0300_0800);
+        let changes = changed_fields(&masters, &old_cell, &old_header, &new_cell, &new_header);
+        assert_eq!(field_names(&changes), vec!["regions"]);
+        let (_, old_value, new_value) = &changes[0];
+        assert_eq!(old_value, &serde_json::json!([0x0100_0D70]));
+        assert_eq!(new_value, &serde_json::json!([0x0100_0D70]));
+    }
+
+    #[test]
+    fn save_metadata_is_not_compared() {
+        let header = header(&["Skyrim.esm"]);
+        let masters = Masters::new(&header, &header);
+        let old_cell = cell(0x0000_9B79, 0x0000_0D70, 0x0100_0800);
+        let mut new_cell = old_cell.clone();
+        new_cell.metadata = RecordMetadata {
+            timestamp: 0x1234,
+            ..RecordMetadata::default()
+        };
+        new_cell.form_version = Some(44);
+        assert!(changed_fields(&masters, &old_cell, &header, &new_cell, &header).is_empty());
+
+        new_cell.is_ignored = true;
+        let changes = changed_fields(&masters, &old_cell, &header, &new_cell, &header);
+        assert_eq!(field_names(&changes), vec!["is_ignored"]);
+    }
+}
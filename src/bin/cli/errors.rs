@@ -0,0 +1,156 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use anyhow::{anyhow, Error, Result};
+use serde::Serialize;
+
+use skyrim_cell_dump::ParseError;
+
+use crate::inputs::display_path;
+
+/// How errors are printed to stderr
+#[derive(Clone, Copy)]
+pub enum ErrorFormat {
+    /// One message per line
+    Text,
+    /// One JSON object per line, with the fields of [`Failure`]
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(ErrorFormat::Text),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(anyhow!("Unrecognized error format {}", s)),
+        }
+    }
+}
+
+/// What failed, which decides the exit code of the process
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// The arguments are invalid
+    Usage,
+    /// A plugin or another input file can't be read, or doesn't exist
+    Read,
+    /// A plugin can't be parsed
+    Parse,
+    /// The output can't be written
+    Write,
+}
+
+impl ErrorKind {
+    /// Exit code of the process when this is the first error. Invalid arguments exit with 1 like they do when argh
+    /// rejects them.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorKind::Usage => 1,
+            ErrorKind::Read => 2,
+            ErrorKind::Parse => 3,
+            ErrorKind::Write => 4,
+        }
+    }
+}
+
+/// An error to report to the user
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub kind: ErrorKind,
+    /// The error as it is printed in the text format
+    pub message: String,
+    /// Path of the file that failed, `stdin` for a plugin read from stdin
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Byte offset into the plugin where parsing failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<usize>,
+    /// Type of the record that failed to parse
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_type: Option<String>,
+    /// Form id of the record that failed to parse
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub form_id: Option<u32>,
+    /// The groups, records, and fields that parsing failed in, outermost first
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub breadcrumbs: Vec<String>,
+}
+
+impl Failure {
+    pub fn new<S: Into<String>>(kind: ErrorKind, message: S) -> Self {
+        Failure {
+            kind,
+            message: message.into(),
+            path: None,
+            offset: None,
+            record_type: None,
+            form_id: None,
+            breadcrumbs: vec![],
+        }
+    }
+
+    pub fn with_path(mut self, path: &Path) -> Self {
+        self.path = Some(display_path(path));
+        self
+    }
+
+    /// Adds where in the plugin parsing failed
+    pub fn with_parse_error(mut self, error: &ParseError) -> Self {
+        self.offset = error.offset();
+        self.record_type = error.record_type().map(str::to_string);
+        self.form_id = error.form_id();
+        self.breadcrumbs = error.breadcrumbs().to_vec();
+        self
+    }
+}
+
+/// Prints errors to stderr in the format passed to `--errors`, and keeps the exit code of the first one
+pub struct Reporter {
+    format: ErrorFormat,
+    exit_code: AtomicI32,
+}
+
+impl Reporter {
+    pub fn new(format: ErrorFormat) -> Self {
+        Reporter {
+            format,
+            exit_code: AtomicI32::new(0),
+        }
+    }
+
+    pub fn report(&self, failure: Failure) {
+        // only the first error sets the exit code, so later errors it causes don't hide it
+        let _ = self.exit_code.compare_exchange(
+            0,
+            failure.kind.exit_code(),
+            Ordering::SeqCst,
+            Ordering::SeqCst,
+        );
+        match self.format {
+            ErrorFormat::Text => eprintln!("{}", failure.message),
+            ErrorFormat::Json => eprintln!("{}", serde_json::to_string(&failure).unwrap()),
+        }
+    }
+
+    /// Prints a message about what the CLI is doing, which is left out of JSON errors so that every line is an error
+    pub fn status(&self, message: &str) {
+        if let ErrorFormat::Text = self.format {
+            eprintln!("{}", message);
+        }
+    }
+
+    /// Sets the exit code for a result that isn't an error, like plugins that differ, unless an error already set one
+    pub fn set_exit_code(&self, exit_code: i32) {
+        let _ = self
+            .exit_code
+            .compare_exchange(0, exit_code, Ordering::SeqCst, Ordering::SeqCst);
+    }
+
+    /// Exit code of the first error reported, or 0 if there were none
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code.load(Ordering::SeqCst)
+    }
+}
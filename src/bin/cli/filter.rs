@@ -0,0 +1,50 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+
+use skyrim_cell_dump::Cell;
+
+/// A rectangle of exterior cell grid coordinates, including its edges
+#[derive(Clone)]
+pub struct BoundingBox {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+impl FromStr for BoundingBox {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let coordinates = s
+            .split(',')
+            .map(|coordinate| coordinate.trim().parse())
+            .collect::<std::result::Result<Vec<i32>, _>>()
+            .map_err(|_| anyhow!("Unrecognized bounding box {}", s))?;
+        match coordinates[..] {
+            [x1, y1, x2, y2] => Ok(BoundingBox {
+                min_x: x1.min(x2),
+                min_y: y1.min(y2),
+                max_x: x1.max(x2),
+                max_y: y1.max(y2),
+            }),
+            _ => Err(anyhow!(
+                "Bounding box {} must have four coordinates: x1,y1,x2,y2",
+                s
+            )),
+        }
+    }
+}
+
+impl BoundingBox {
+    /// Whether the cell is an exterior cell inside of the bounding box. Persistent worldspace cells hold references
+    /// from all over their worldspace, so they are never inside of it.
+    pub fn contains(&self, cell: &Cell) -> bool {
+        match (cell.x, cell.y) {
+            (Some(x), Some(y)) if !cell.is_persistent => {
+                x >= self.min_x && x <= self.max_x && y >= self.min_y && y <= self.max_y
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+use skyrim_cell_dump::Plugin;
+
+use crate::csv::write_csv;
+use crate::map::render_map;
+use crate::msgpack::to_msgpack;
+use crate::sort::{Sort, SortedPlugin};
+use crate::sqlite::write_sqlite;
+use crate::summary::render_summary;
+use crate::toml::to_toml;
+use crate::xml::{plugins_to_xml, to_xml};
+
+pub enum Format {
+    Json,
+    PlainText,
+    Csv,
+    Toml,
+    Xml,
+    Sqlite,
+    MessagePack,
+    Map,
+    Summary,
+    Svg,
+    Png,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "text" | "plain" | "plain_text" | "plaintext" => Ok(Format::PlainText),
+            "csv" => Ok(Format::Csv),
+            "toml" => Ok(Format::Toml),
+            "xml" => Ok(Format::Xml),
+            "sqlite" | "sqlite3" | "db" => Ok(Format::Sqlite),
+            "msgpack" | "messagepack" => Ok(Format::MessagePack),
+            "map" => Ok(Format::Map),
+            "summary" => Ok(Format::Summary),
+            "svg" => Ok(Format::Svg),
+            "png" => Ok(Format::Png),
+            _ => Err(anyhow!("Unrecognized format {}", s)),
+        }
+    }
+}
+
+/// Formats the parsed plugins for output, with the cells of each plugin in the `sort` order if there is one.
+///
+/// With `batch`, the plugins are output as one report keyed by the plugin names. Otherwise there is only one plugin,
+/// which is output by itself.
+pub fn render(
+    format: &Format,
+    pretty: bool,
+    plugins: &[(&str, Plugin)],
+    batch: bool,
+    sort: Option<Sort>,
+) -> Vec<u8> {
+    if let Format::Csv = format {
+        let mut output = vec![];
+        write_csv(&mut output, plugins, batch, sort.unwrap_or(Sort::FormId)).unwrap();
+        return output;
+    }
+    if let Format::Sqlite = format {
+        return write_sqlite(plugins);
+    }
+    if let Format::Map = format {
+        return render_map(plugins, batch).into_bytes();
+    }
+    if let Format::Summary = format {
+        return render_summary(plugins).into_bytes();
+    }
+    let plugins: Vec<(&str, SortedPlugin)> = plugins
+        .iter()
+        .map(|(name, plugin)| (*name, SortedPlugin::new(plugin, sort)))
+        .collect();
+    if !batch {
+        return match plugins.first() {
+            Some((_, plugin)) => render_value(format, pretty, plugin),
+            None => vec![],
+        };
+    }
+    match format {
+        Format::Xml => plugins_to_xml(&plugins).unwrap().into_bytes(),
+        Format::PlainText => plugins
+            .iter()
+            .map(|(name, plugin)| format!("{}:\n{:#?}\n", name, plugin))
+            .collect::<String>()
+            .into_bytes(),
+        _ => {
+            let report: BTreeMap<&str, SortedPlugin> = plugins.into_iter().collect();
+            render_value(format, pretty, &report)
+        }
+    }
+}
+
+fn render_value<T: Serialize + std::fmt::Debug>(
+    format: &Format,
+    pretty: bool,
+    value: &T,
+) -> Vec<u8> {
+    match format {
+        Format::PlainText => format!("{:#?}\n", value).into_bytes(),
+        Format::Json if pretty => {
+            format!("{}\n", serde_json::to_string_pretty(value).unwrap()).into_bytes()
+        }
+        Format::Json => format!("{}\n", serde_json::to_string(value).unwrap()).into_bytes(),
+        Format::Toml => to_toml(value).unwrap().into_bytes(),
+        Format::Xml => to_xml(value, "plugin").unwrap().into_bytes(),
+        Format::MessagePack => to_msgpack(value).unwrap(),
+        Format::Csv | Format::Sqlite | Format::Map | Format::Summary => {
+            unreachable!("formats of all plugins are rendered by render")
+        }
+        Format::Svg | Format::Png => {
+            unreachable!("heatmaps are rendered per worldspace by render_heatmaps")
+        }
+    }
+}
+
+/// Serializes a value into a JSON value for the formats that are encoded from one
+pub fn to_json_value<T: Serialize>(value: &T) -> serde_json::Result<Value> {
+    // going through the JSON text keeps the shortest representation of `f32` fields, which `serde_json::to_value`
+    // would widen to `f64` (e.g. `1.71` to `1.7100000381469727`)
+    serde_json::from_str(&serde_json::to_string(value)?)
+}
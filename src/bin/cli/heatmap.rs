@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
+
+use skyrim_cell_dump::Plugin;
+
+use crate::filter::BoundingBox;
+use crate::format::Format;
+use crate::png::{decode_png, encode_png, Image};
+
+/// Width and height of a cell in SVG heatmaps, in user units
+const SVG_CELL_SIZE: usize = 16;
+/// Width and height of a cell in PNG heatmaps without a background, in pixels
+const PNG_CELL_SIZE: usize = 8;
+const BACKGROUND_COLOR: [u8; 3] = [0x20, 0x20, 0x20];
+/// Color of the cells edited by the fewest plugins
+const COOL_COLOR: [u8; 3] = [0xFF, 0xD7, 0x00];
+/// Color of the cells edited by the most plugins
+const HOT_COLOR: [u8; 3] = [0xD7, 0x19, 0x19];
+const CELL_OPACITY: f32 = 0.75;
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// An image of the map of a worldspace to draw a heatmap over
+pub struct Background {
+    /// Contents of the PNG or JPEG image file
+    pub contents: Vec<u8>,
+    /// The exterior cell grid coordinates at the edges of the image
+    pub bbox: BoundingBox,
+}
+
+/// Number of plugins that edit each exterior cell of a worldspace, keyed by the cell's grid coordinates
+type CellCounts = HashMap<(i32, i32), usize>;
+
+/// Draws a heatmap of the edited exterior cells of each worldspace in the SVG or PNG `format`, colored by how many of
+/// the plugins edit each cell.
+///
+/// Returns the file name and contents of the image of each worldspace, which is named after the editor id of the
+/// worldspace. Without a `background`, each image covers the cells that the plugins edit. With one, each image covers
+/// the cells of the background and the cells outside of it are left out.
+pub fn render_heatmaps(
+    format: &Format,
+    plugins: &[(&str, Plugin)],
+    background: Option<&Background>,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut worldspaces: BTreeMap<String, CellCounts> = BTreeMap::new();
+    for (_, plugin) in plugins {
+        // a plugin only counts once for each cell, even if it has more than one record for it
+        let mut edited: HashSet<(String, i32, i32)> = HashSet::new();
+        for cell in &plugin.cells {
+            if let (Some(world_form_id), Some(x), Some(y)) = (cell.world_form_id, cell.x, cell.y) {
+                if cell.is_persistent {
+                    continue;
+                }
+                let worldspace = plugin
+                    .worlds
+                    .iter()
+                    .find(|world| world.form_id == world_form_id)
+                    .map(|world| world.editor_id.clone())
+                    .unwrap_or_else(|| format!("{:08X}", world_form_id));
+                if edited.insert((worldspace.clone(), x, y)) {
+                    *worldspaces
+                        .entry(worldspace)
+                        .or_default()
+                        .entry((x, y))
+                        .or_default() += 1;
+                }
+            }
+        }
+    }
+
+    let background_image = match (format, background) {
+        (Format::Png, Some(background)) => Some(
+            decode_png(&background.contents)
+                .map_err(|error| format!("Failed to read background image: {}", error))?,
+        ),
+        _ => None,
+    };
+    let mut heatmaps = vec![];
+    for (worldspace, counts) in &worldspaces {
+        let bounds = match background {
+            Some(background) => Bounds::from_bbox(&background.bbox),
+            None => Bounds::around(counts),
+        };
+        let (extension, contents) = match format {
+            Format::Svg => (
+                "svg",
+                render_svg(worldspace, counts, &bounds, background)?.into_bytes(),
+            ),
+            _ => (
+                "png",
+                encode_png(&render_png(counts, &bounds, background_image.as_ref())),
+            ),
+        };
+        heatmaps.push((format!("{}.{}", file_stem(worldspace), extension), contents));
+    }
+    Ok(heatmaps)
+}
+
+/// The cell grid coordinates covered by a heatmap, including its edges
+struct Bounds {
+    min_x: i32,
+    max_y: i32,
+    columns: usize,
+    rows: usize,
+}
+
+impl Bounds {
+    fn from_bbox(bbox: &BoundingBox) -> Self {
+        Bounds {
+            min_x: bbox.min_x,
+            max_y: bbox.max_y,
+            columns: (bbox.max_x - bbox.min_x + 1) as usize,
+            rows: (bbox.max_y - bbox.min_y + 1) as usize,
+        }
+    }
+
+    fn around(counts: &CellCounts) -> Self {
+        let min_x = counts.keys().map(|&(x, _)| x).min().unwrap_or_default();
+        let max_x = counts.keys().map(|&(x, _)| x).max().unwrap_or_default();
+        let min_y = counts.keys().map(|&(_, y)| y).min().unwrap_or_default();
+        let max_y = counts.keys().map(|&(_, y)| y).max().unwrap_or_default();
+        Bounds::from_bbox(&BoundingBox {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        })
+    }
+
+    /// The column and row of a cell, counted from the top left, if it is within the bounds
+    fn position(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        let column = usize::try_from(x - self.min_x).ok()?;
+        let row = usize::try_from(self.max_y - y).ok()?;
+        (column < self.columns && row < self.rows).then_some((column, row))
+    }
+}
+
+fn render_svg(
+    worldspace: &str,
+    counts: &CellCounts,
+    bounds: &Bounds,
+    background: Option<&Background>,
+) -> Result<String, String> {
+    let width = bounds.columns * SVG_CELL_SIZE;
+    let height = bounds.rows * SVG_CELL_SIZE;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {0} {1}\" width=\"{0}\" height=\"{1}\">\n",
+        width, height
+    );
+    match background {
+        Some(background) => {
+            let media_type = image_media_type(&background.contents)
+                .ok_or("Background image must be a PNG or JPEG file")?;
+            svg.push_str(&format!(
+                "<image href=\"data:{};base64,{}\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\"/>\n",
+                media_type,
+                encode_base64(&background.contents),
+                width,
+                height
+            ));
+        }
+        None => svg.push_str(&format!(
+            "<rect width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            width,
+            height,
+            hex_color(BACKGROUND_COLOR)
+        )),
+    }
+    let max_count = counts.values().copied().max().unwrap_or_default();
+    for (&(x, y), &count) in sorted_cells(counts).iter() {
+        if let Some((column, row)) = bounds.position(x, y) {
+            svg.push_str(&format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{size}\" height=\"{size}\" fill=\"{fill}\" fill-opacity=\"{opacity}\"><title>{worldspace} ({cell_x}, {cell_y}): {count} {plugins}</title></rect>\n",
+                x = column * SVG_CELL_SIZE,
+                y = row * SVG_CELL_SIZE,
+                size = SVG_CELL_SIZE,
+                fill = hex_color(heat_color(count, max_count)),
+                opacity = CELL_OPACITY,
+                worldspace = escape_xml(worldspace),
+                cell_x = x,
+                cell_y = y,
+                count = count,
+                plugins = if count == 1 { "plugin" } else { "plugins" },
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+fn render_png(counts: &CellCounts, bounds: &Bounds, background: Option<&Image>) -> Image {
+    let mut image = match background {
+        Some(background) => Image {
+            width: background.width,
+            height: background.height,
+            pixels: background.pixels.clone(),
+        },
+        None => Image::new(
+            bounds.columns * PNG_CELL_SIZE,
+            bounds.rows * PNG_CELL_SIZE,
+            BACKGROUND_COLOR,
+        ),
+    };
+    let max_count = counts.values().copied().max().unwrap_or_default();
+    for (&(x, y), &count) in sorted_cells(counts).iter() {
+        if let Some((column, row)) = bounds.position(x, y) {
+            // cells are stretched over the image, which may not be a whole number of pixels per cell
+            image.blend_rect(
+                column * image.width / bounds.columns,
+                row * image.height / bounds.rows,
+                (column + 1) * image.width / bounds.columns,
+                (row + 1) * image.height / bounds.rows,
+                heat_color(count, max_count),
+                CELL_OPACITY,
+            );
+        }
+    }
+    image
+}
+
+/// Sorts the cells from the top left, so that the images are the same for the same plugins
+fn sorted_cells(counts: &CellCounts) -> Vec<(&(i32, i32), &usize)> {
+    let mut cells: Vec<_> = counts.iter().collect();
+    cells.sort_by_key(|&(&(x, y), _)| (-y, x));
+    cells
+}
+
+/// Blends from [`COOL_COLOR`] to [`HOT_COLOR`] by how many plugins edit the cell out of the most that edit any cell
+fn heat_color(count: usize, max_count: usize) -> [u8; 3] {
+    let heat = if max_count > 1 {
+        (count - 1) as f32 / (max_count - 1) as f32
+    } else {
+        1.0
+    };
+    let mut color = [0; 3];
+    for (channel, value) in color.iter_mut().enumerate() {
+        *value = (COOL_COLOR[channel] as f32 * (1.0 - heat) + HOT_COLOR[channel] as f32 * heat)
+            .round() as u8;
+    }
+    color
+}
+
+fn hex_color(color: [u8; 3]) -> String {
+    format!("#{:02X}{:02X}{:02X}", color[0], color[1], color[2])
+}
+
+fn image_media_type(contents: &[u8]) -> Option<&'static str> {
+    if contents.starts_with(b"\x89PNG") {
+        Some("image/png")
+    } else if contents.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else {
+        None
+    }
+}
+
+fn encode_base64(input: &[u8]) -> String {
+    let mut encoded = String::with_capacity(((input.len() + 2) / 3) * 4);
+    for chunk in input.chunks(3) {
+        let bytes = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Replaces the characters of a worldspace editor id that can't be in a file name
+fn file_stem(worldspace: &str) -> String {
+    worldspace
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,227 @@
+use std::fs::read_dir;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PLUGIN_EXTENSIONS: [&str; 3] = ["esp", "esm", "esl"];
+/// Argument that reads the plugin from stdin instead of a file
+pub const STDIN_ARG: &str = "-";
+/// Path that [`STDIN_ARG`] is replaced with before the arguments are parsed, since argh would parse `-` as an option.
+/// No file can have a null character in its path.
+pub const STDIN_PATH: &str = "\0-";
+/// Name of the plugin read from stdin
+const STDIN_NAME: &str = "stdin";
+
+/// A plugin file to parse
+pub struct PluginInput {
+    /// Name of the plugin in reports of multiple plugins
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Finds the plugin files to parse from the paths passed to the CLI, which can be plugin files, directories of plugins,
+/// glob patterns, or `-` for stdin.
+///
+/// Returns the plugins and whether they should be output as a batch keyed by plugin name, which is when anything other
+/// than a single plugin file was passed. The plugins found in a single directory are named by their paths relative to
+/// it, a single plugin file by its file name, and all other plugins by their full paths.
+pub fn find_inputs(paths: &[PathBuf], recursive: bool) -> Result<(Vec<PluginInput>, bool), String> {
+    if let [path] = paths {
+        if !is_glob(path) && !path.is_dir() {
+            let name = if is_stdin(path) {
+                STDIN_NAME.to_string()
+            } else {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            };
+            return Ok((
+                vec![PluginInput {
+                    name,
+                    path: path.clone(),
+                }],
+                false,
+            ));
+        }
+    }
+
+    let mut inputs: Vec<PluginInput> = vec![];
+    for path in paths {
+        let found_paths = if is_glob(path) {
+            let found_paths = expand_glob(&path.to_string_lossy());
+            if found_paths.is_empty() {
+                return Err(format!("No files match {}", path.to_string_lossy()));
+            }
+            found_paths
+        } else if path.is_dir() {
+            find_plugins(path, recursive).map_err(|error| {
+                format!(
+                    "Failed to read from directory {}: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            })?
+        } else {
+            vec![path.clone()]
+        };
+        let dir = if paths.len() == 1 && path.is_dir() {
+            Some(path.as_path())
+        } else {
+            None
+        };
+        for found_path in found_paths {
+            if inputs.iter().all(|input| input.path != found_path) {
+                inputs.push(PluginInput {
+                    name: plugin_name(&found_path, dir),
+                    path: found_path,
+                });
+            }
+        }
+    }
+    Ok((inputs, true))
+}
+
+/// Finds the plugin files in a directory, and in all of its subdirectories if `recursive`, sorted by path
+fn find_plugins(dir: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut plugins = vec![];
+    for entry in read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if recursive {
+                plugins.extend(find_plugins(&path, recursive)?);
+            }
+        } else if is_plugin_file(&path) {
+            plugins.push(path);
+        }
+    }
+    plugins.sort();
+    Ok(plugins)
+}
+
+fn is_plugin_file(path: &Path) -> bool {
+    match path.extension() {
+        Some(extension) => PLUGIN_EXTENSIONS
+            .iter()
+            .any(|plugin_extension| extension.eq_ignore_ascii_case(plugin_extension)),
+        None => false,
+    }
+}
+
+/// Whether the path was passed as `-`, which reads the plugin from stdin
+pub fn is_stdin(path: &Path) -> bool {
+    path == Path::new(STDIN_PATH)
+}
+
+/// Formats a plugin path for messages, with the path of stdin shown as `stdin`
+pub fn display_path(path: &Path) -> String {
+    if is_stdin(path) {
+        STDIN_NAME.to_string()
+    } else {
+        path.to_string_lossy().into_owned()
+    }
+}
+
+/// Names a plugin by its path relative to the directory it was found in, or by its full path
+fn plugin_name(path: &Path, dir: Option<&Path>) -> String {
+    if is_stdin(path) {
+        return STDIN_NAME.to_string();
+    }
+    let name = dir
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path);
+    name.to_string_lossy().replace('\\', "/")
+}
+
+fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?'])
+}
+
+/// Finds the files matching a glob pattern, sorted by path.
+///
+/// `*` matches any characters in a file or directory name, `?` matches one character, and a `**` directory matches
+/// any number of nested directories. Names are matched case-insensitively, like on Windows.
+fn expand_glob(pattern: &str) -> Vec<PathBuf> {
+    let components: Vec<&str> = pattern.split(['/', '\\']).collect();
+    let literal_components = components
+        .iter()
+        .take_while(|component| !component.contains(['*', '?']))
+        .count();
+    // the directory that the pattern starts from is kept as it was written, which is empty for relative patterns
+    let base = if literal_components == 0 {
+        PathBuf::new()
+    } else {
+        let base_len = components[..literal_components]
+            .iter()
+            .map(|component| component.len() + 1)
+            .sum();
+        PathBuf::from(&pattern[..base_len])
+    };
+    let mut matches = vec![];
+    match_components(&base, &components[literal_components..], &mut matches);
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+fn match_components(dir: &Path, components: &[&str], matches: &mut Vec<PathBuf>) {
+    let (component, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+    let entries = match read_dir(if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    }) {
+        Ok(entries) => entries,
+        // directories that can't be read have no matches, like in shells
+        Err(_) => return,
+    };
+    if *component == "**" {
+        match_components(dir, rest, matches);
+    }
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let path = dir.join(&name);
+        let is_dir = path.is_dir();
+        if *component == "**" {
+            if is_dir {
+                match_components(&path, components, matches);
+            }
+        } else if wildcard_match(component, &name) {
+            if rest.is_empty() {
+                if !is_dir {
+                    matches.push(path);
+                }
+            } else if is_dir {
+                match_components(&path, rest, matches);
+            }
+        }
+    }
+}
+
+/// Matches a name against a pattern with `*` and `?` wildcards, ignoring ASCII case
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // where the last `*` was in the pattern, and the position in the name it is currently matched up to
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if p < pattern.len()
+            && (pattern[p] == '?' || pattern[p].eq_ignore_ascii_case(&name[n]))
+        {
+            p += 1;
+            n += 1;
+        } else if let Some((star, matched)) = backtrack {
+            p = star + 1;
+            n = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,56 @@
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread::{available_parallelism, scope};
+
+/// Number of jobs to run at the same time when `--jobs` isn't passed, which is the number of CPUs
+pub fn default_jobs() -> usize {
+    available_parallelism().map(NonZeroUsize::get).unwrap_or(1)
+}
+
+/// Calls `map` on each of the `items` on up to `jobs` threads, and calls `on_result` with the index and result of each
+/// item on the current thread.
+///
+/// The results are passed to `on_result` in the order of the `items`, as soon as the item and all of the items before
+/// it are done, so results can be output while the rest of the items are still being worked on.
+pub fn map_in_parallel<'a, T, R, F, G>(items: &'a [T], jobs: usize, map: F, mut on_result: G)
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&'a T) -> R + Sync,
+    G: FnMut(usize, R),
+{
+    let next_index = AtomicUsize::new(0);
+    let (sender, receiver) = channel();
+    scope(|scope| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            let sender = sender.clone();
+            let (next_index, map) = (&next_index, &map);
+            scope.spawn(move || loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                match items.get(index) {
+                    Some(item) => {
+                        if sender.send((index, map(item))).is_err() {
+                            return;
+                        }
+                    }
+                    None => return,
+                }
+            });
+        }
+        // the threads hold the only senders left, so the receiver stops once all of them are done
+        drop(sender);
+
+        // results that finished before the results of earlier items, keyed by index
+        let mut pending: BTreeMap<usize, R> = BTreeMap::new();
+        let mut next_result = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&next_result) {
+                on_result(next_result, result);
+                next_result += 1;
+            }
+        }
+    });
+}
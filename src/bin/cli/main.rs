@@ -0,0 +1,702 @@
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[cfg(feature = "build-binary")]
+use argh::FromArgs;
+
+use skyrim_cell_dump::{
+    count_record_types_with_options, parse_group_tree_with_options, parse_plugin_lazy,
+    parse_plugin_partial, parse_plugin_with_options, Cell, Compression, FormId, Game, ParseError,
+    ParseOptions, Plugin,
+};
+
+mod csv;
+mod diff;
+mod errors;
+mod filter;
+mod format;
+mod heatmap;
+mod inputs;
+mod jobs;
+mod map;
+mod msgpack;
+mod output;
+mod png;
+mod sort;
+mod sqlite;
+mod stats;
+mod summary;
+mod toml;
+mod tree;
+mod watch;
+mod xml;
+
+use diff::{changed_fields, Masters};
+use errors::{ErrorFormat, ErrorKind, Failure, Reporter};
+use filter::BoundingBox;
+use format::{render, Format};
+use heatmap::{render_heatmaps, Background};
+use inputs::{display_path, find_inputs, is_stdin, STDIN_ARG, STDIN_PATH};
+use jobs::{default_jobs, map_in_parallel};
+use output::write_atomically;
+use sort::Sort;
+use stats::{render_record_counts, Stats};
+use tree::render_tree;
+use watch::watch_inputs;
+
+/// Exit code of `diff` when the plugins have different cells, unless an error set another one
+const DIFFERENCES_EXIT_CODE: i32 = 5;
+
+#[derive(FromArgs)]
+/// Extracts cell edits from a TES5 Skyrim plugin file
+struct Args {
+    #[argh(subcommand)]
+    command: Option<Command>,
+    /// paths to the plugins to parse, to directories of plugins, or glob
+    /// patterns like mods/**/*.esp, or - to read a plugin from stdin
+    #[argh(positional)]
+    plugins: Vec<PathBuf>,
+    /// also parse the plugins in the subdirectories of directories
+    #[argh(switch, short = 'r')]
+    recursive: bool,
+    /// format of the output (json, text, csv, toml, xml,
+    /// sqlite, msgpack, map, summary, svg, or png)
+    #[argh(option, short = 'f', default = "Format::PlainText")]
+    format: Format,
+    /// print one line for each plugin with its name, author, number of
+    /// masters and cells, and the worldspaces it edits (same as -f summary)
+    #[argh(switch)]
+    summary: bool,
+    /// pretty print json output
+    #[argh(switch, short = 'p')]
+    pretty: bool,
+    /// game the plugin was made for (skyrim, fallout4, fallout3, falloutnv, or
+    /// oblivion)
+    #[argh(option, short = 'g', default = "Game::Skyrim")]
+    game: Game,
+    /// compression used by compressed records (zlib or lz4)
+    #[argh(option, default = "Compression::Zlib")]
+    compression: Compression,
+    /// fail on structural problems that are otherwise skipped over, like
+    /// unknown group types and fields whose size doesn't match their data
+    #[argh(switch)]
+    strict: bool,
+    /// skip cells that can't be parsed instead of failing, and list them in
+    /// the issues of the plugin
+    #[argh(switch)]
+    lenient: bool,
+    /// list the problems that are skipped over while parsing, like fields of
+    /// unknown types, in the warnings of each plugin
+    #[argh(switch)]
+    warnings: bool,
+    /// output what could be parsed from plugins that fail partway through,
+    /// like cut off downloads, along with reporting the error
+    #[argh(switch)]
+    partial: bool,
+    /// also list the map markers placed in each cell
+    #[argh(switch)]
+    map_markers: bool,
+    /// also output the data of each cell and world record exactly as it is
+    /// stored in the plugin
+    #[argh(switch)]
+    raw_data: bool,
+    /// file to write the output to instead of stdout (or - for stdout), which
+    /// is only replaced once the output is complete, and is required for the
+    /// sqlite format, or the directory to write the images of the svg and png
+    /// formats to
+    #[argh(option, short = 'o')]
+    output: Option<PathBuf>,
+    /// only output the cells of the worldspace with this editor id (can be
+    /// repeated)
+    #[argh(option, short = 'w')]
+    worldspace: Vec<String>,
+    /// only output the exterior cells within the grid coordinates
+    /// x1,y1,x2,y2
+    #[argh(option)]
+    bbox: Option<BoundingBox>,
+    /// only output interior cells
+    #[argh(switch)]
+    interiors_only: bool,
+    /// only output exterior cells
+    #[argh(switch)]
+    exteriors_only: bool,
+    /// output the cells in the order of their form_id, editor_id, coords, or
+    /// worldspace and coords, and the worlds by form id, so that the output
+    /// is the same every time
+    #[argh(option)]
+    sort: Option<Sort>,
+    /// only parse and output the cells of the plugins, leaving out their
+    /// worlds, locations, and regions
+    #[argh(switch)]
+    cells_only: bool,
+    /// only parse and output the worlds of the plugins
+    #[argh(switch)]
+    worlds_only: bool,
+    /// only parse and output the headers of the plugins
+    #[argh(switch)]
+    header_only: bool,
+    /// image of the worldspace map to draw the svg and png heatmaps over (a
+    /// png or jpeg for svg, or a png for png)
+    #[argh(option)]
+    background: Option<PathBuf>,
+    /// the exterior cell grid coordinates x1,y1,x2,y2 at the edges of the
+    /// --background image
+    #[argh(option)]
+    background_bbox: Option<BoundingBox>,
+    /// keep running and output the plugins again whenever they change
+    #[argh(switch)]
+    watch: bool,
+    /// number of plugins to parse at the same time (defaults to the number of
+    /// CPUs)
+    #[argh(option, short = 'j')]
+    jobs: Option<usize>,
+    /// format of the errors printed to stderr (text, or json for one JSON
+    /// object per line)
+    #[argh(option, default = "ErrorFormat::Text")]
+    errors: ErrorFormat,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Diff(DiffArgs),
+    Stats(StatsArgs),
+    Tree(TreeArgs),
+}
+
+#[derive(FromArgs)]
+/// Compares the cells of two plugin files
+#[argh(subcommand, name = "diff")]
+struct DiffArgs {
+    /// path to the original plugin
+    #[argh(positional)]
+    old: PathBuf,
+    /// path to the changed plugin
+    #[argh(positional)]
+    new: PathBuf,
+    /// game the plugins were made for (skyrim, fallout4, fallout3, falloutnv,
+    /// or oblivion)
+    #[argh(option, short = 'g', default = "Game::Skyrim")]
+    game: Game,
+    /// compression used by compressed records (zlib or lz4)
+    #[argh(option, default = "Compression::Zlib")]
+    compression: Compression,
+}
+
+#[derive(FromArgs)]
+/// Prints counts of the cells in a plugin file, without parsing the cells
+#[argh(subcommand, name = "stats")]
+struct StatsArgs {
+    /// path to the plugin, or - to read it from stdin
+    #[argh(positional)]
+    plugin: PathBuf,
+    /// game the plugin was made for (skyrim, fallout4, fallout3, falloutnv, or
+    /// oblivion)
+    #[argh(option, short = 'g', default = "Game::Skyrim")]
+    game: Game,
+    /// compression used by compressed records (zlib or lz4)
+    #[argh(option, default = "Compression::Zlib")]
+    compression: Compression,
+    /// also print counts of the records of every type in the plugin
+    #[argh(switch)]
+    records: bool,
+}
+
+#[derive(FromArgs)]
+/// Prints the nested groups of a plugin file with the number of records of
+/// each type in them, without parsing the records
+#[argh(subcommand, name = "tree")]
+struct TreeArgs {
+    /// path to the plugin, or - to read it from stdin
+    #[argh(positional)]
+    plugin: PathBuf,
+    /// game the plugin was made for (skyrim, fallout4, fallout3, falloutnv, or
+    /// oblivion)
+    #[argh(option, short = 'g', default = "Game::Skyrim")]
+    game: Game,
+}
+
+fn main() {
+    let mut args = args_from_env();
+    if args.summary {
+        args.format = Format::Summary;
+    }
+    let reporter = Reporter::new(args.errors);
+    run(&args, &reporter);
+    process::exit(reporter.exit_code());
+}
+
+fn run(args: &Args, reporter: &Reporter) {
+    match &args.command {
+        Some(Command::Diff(diff_args)) => return diff(diff_args, reporter),
+        Some(Command::Stats(stats_args)) => return stats(stats_args, reporter),
+        Some(Command::Tree(tree_args)) => return tree(tree_args, reporter),
+        None => {}
+    }
+    if let Some(message) = usage_error(args) {
+        return reporter.report(Failure::new(ErrorKind::Usage, message));
+    }
+    let options = ParseOptions {
+        game: args.game,
+        compression: args.compression,
+        worldspace_filter: if args.worldspace.is_empty() {
+            None
+        } else {
+            Some(args.worldspace.clone())
+        },
+        include_cells: !args.worlds_only && !args.header_only,
+        include_worlds: !args.cells_only && !args.header_only,
+        include_interiors: !args.exteriors_only,
+        strict: args.strict,
+        lenient: args.lenient,
+        collect_warnings: args.warnings,
+        include_map_markers: args.map_markers,
+        include_raw_data: args.raw_data,
+        ..Default::default()
+    };
+    dump(args, &options, reporter);
+    if args.watch {
+        watch_inputs(&args.plugins, args.recursive, || {
+            reporter.status("Plugins changed, parsing them again");
+            dump(args, &options, reporter);
+        });
+    }
+}
+
+/// Checks the combinations of arguments that argh can't, returning the message of the first one that is invalid
+fn usage_error(args: &Args) -> Option<&'static str> {
+    if args.plugins.is_empty() {
+        Some("Required positional arguments not provided: plugins")
+    } else if let (Format::Sqlite, None) = (&args.format, &args.output) {
+        Some("The sqlite format requires an --output file")
+    } else if let (Format::Svg | Format::Png, None) = (&args.format, &args.output) {
+        Some("The svg and png formats require an --output directory")
+    } else if let (Some(_), None) = (&args.background, &args.background_bbox) {
+        Some("--background requires --background-bbox")
+    } else if args.interiors_only && args.exteriors_only {
+        Some("Only one of --interiors-only and --exteriors-only can be used")
+    } else if [args.cells_only, args.worlds_only, args.header_only]
+        .iter()
+        .filter(|&&only| only)
+        .count()
+        > 1
+    {
+        Some("Only one of --cells-only, --worlds-only, and --header-only can be used")
+    } else if args.jobs == Some(0) {
+        Some("--jobs must be at least 1")
+    } else if args.watch && args.plugins.iter().any(|path| is_stdin(path)) {
+        Some("--watch can't be used with a plugin read from stdin")
+    } else {
+        None
+    }
+}
+
+/// Parses the plugins and writes them to the output in the format of the arguments
+fn dump(args: &Args, options: &ParseOptions, reporter: &Reporter) {
+    let (inputs, batch) = match find_inputs(&args.plugins, args.recursive) {
+        Ok(inputs) => inputs,
+        Err(error) => return reporter.report(Failure::new(ErrorKind::Read, error)),
+    };
+
+    // every file is read before parsing, since the parsed plugins borrow from the file contents
+    let plugin_contents: Vec<(&str, &Path, Vec<u8>)> = inputs
+        .iter()
+        .filter_map(|input| {
+            read_plugin_file(&input.path, reporter)
+                .map(|contents| (input.name.as_str(), input.path.as_path(), contents))
+        })
+        .collect();
+    // the text of each plugin is written to stdout as soon as it is parsed, since the text of a batch is the text of
+    // each of its plugins one after the other
+    let stream = batch
+        && matches!(args.format, Format::PlainText | Format::Summary)
+        && match args.output.as_deref() {
+            Some(output) => is_stdin(output),
+            None => true,
+        };
+    let mut plugins: Vec<(&str, Plugin)> = vec![];
+    map_in_parallel(
+        &plugin_contents,
+        args.jobs.unwrap_or_else(default_jobs),
+        |(_, path, contents)| {
+            if args.partial {
+                parse_partial_plugin_file(contents, path, options, reporter)
+            } else {
+                parse_plugin_file(contents, path, options, reporter)
+            }
+        },
+        |index, plugin| {
+            if let Some(mut plugin) = plugin {
+                filter_cells(args, &mut plugin);
+                let name = plugin_contents[index].0;
+                if stream {
+                    write_stdout(
+                        &render(
+                            &args.format,
+                            args.pretty,
+                            &[(name, plugin)],
+                            batch,
+                            args.sort,
+                        ),
+                        reporter,
+                    );
+                } else {
+                    plugins.push((name, plugin));
+                }
+            }
+        },
+    );
+    if stream || (!batch && plugins.is_empty()) {
+        return;
+    }
+
+    if let Format::Svg | Format::Png = args.format {
+        return write_heatmaps(args, &plugins, reporter);
+    }
+    let output = render(&args.format, args.pretty, &plugins, batch, args.sort);
+    match &args.output {
+        Some(output_path) if !is_stdin(output_path) => {
+            if let Err(error) = write_atomically(output_path, &output) {
+                reporter.report(
+                    Failure::new(
+                        ErrorKind::Write,
+                        format!(
+                            "Failed to write to output file {}: {}",
+                            output_path.to_string_lossy(),
+                            error
+                        ),
+                    )
+                    .with_path(output_path),
+                )
+            }
+        }
+        _ => write_stdout(&output, reporter),
+    }
+}
+
+/// Removes the cells, and the sections of the plugin, left out by the filter arguments from the plugin
+fn filter_cells(args: &Args, plugin: &mut Plugin) {
+    if args.cells_only || args.worlds_only || args.header_only {
+        plugin.locations.clear();
+        plugin.regions.clear();
+    }
+    if let Some(bbox) = &args.bbox {
+        plugin.cells.retain(|cell| bbox.contains(cell));
+    }
+    if args.interiors_only || args.exteriors_only {
+        plugin
+            .cells
+            .retain(|cell| cell.is_interior() == args.interiors_only);
+    }
+}
+
+fn write_stdout(output: &[u8], reporter: &Reporter) {
+    if let Err(error) = io::stdout().lock().write_all(output) {
+        reporter.report(Failure::new(
+            ErrorKind::Write,
+            format!("Failed to write output: {}", error),
+        ))
+    }
+}
+
+/// Writes a heatmap image of each worldspace edited by the plugins to the output directory
+fn write_heatmaps(args: &Args, plugins: &[(&str, Plugin)], reporter: &Reporter) {
+    let background = match (&args.background, &args.background_bbox) {
+        (Some(path), Some(bbox)) => match read(path) {
+            Ok(contents) => Some(Background {
+                contents,
+                bbox: bbox.clone(),
+            }),
+            Err(error) => {
+                return reporter.report(
+                    Failure::new(
+                        ErrorKind::Read,
+                        format!(
+                            "Failed to read background image {}: {}",
+                            path.to_string_lossy(),
+                            error
+                        ),
+                    )
+                    .with_path(path),
+                )
+            }
+        },
+        _ => None,
+    };
+    let heatmaps = match render_heatmaps(&args.format, plugins, background.as_ref()) {
+        Ok(heatmaps) => heatmaps,
+        Err(error) => return reporter.report(Failure::new(ErrorKind::Read, error)),
+    };
+    let output_dir = args.output.as_deref().unwrap_or_else(|| Path::new("."));
+    if let Err(error) = create_dir_all(output_dir) {
+        return reporter.report(
+            Failure::new(
+                ErrorKind::Write,
+                format!(
+                    "Failed to create output directory {}: {}",
+                    output_dir.to_string_lossy(),
+                    error
+                ),
+            )
+            .with_path(output_dir),
+        );
+    }
+    for (file_name, contents) in heatmaps {
+        let path = output_dir.join(file_name);
+        if let Err(error) = write_atomically(&path, &contents) {
+            reporter.report(
+                Failure::new(
+                    ErrorKind::Write,
+                    format!(
+                        "Failed to write to output file {}: {}",
+                        path.to_string_lossy(),
+                        error
+                    ),
+                )
+                .with_path(&path),
+            )
+        }
+    }
+}
+
+/// Parses the arguments of the process like `argh::from_env`, but with `-` replaced so that it can be passed as a path
+fn args_from_env() -> Args {
+    let strings: Vec<String> = std::env::args()
+        .map(|arg| {
+            if arg == STDIN_ARG {
+                STDIN_PATH.to_string()
+            } else {
+                arg
+            }
+        })
+        .collect();
+    let command = Path::new(&strings[0])
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(&strings[0]);
+    let strs: Vec<&str> = strings[1..].iter().map(String::as_str).collect();
+    Args::from_args(&[command], &strs).unwrap_or_else(|early_exit| {
+        println!("{}", early_exit.output);
+        process::exit(match early_exit.status {
+            Ok(()) => 0,
+            Err(()) => 1,
+        })
+    })
+}
+
+/// Prints the cells that are only in one of the plugins, and the fields that differ for cells that are in both. Form
+/// ids are compared through the masters of the plugins, and the fields that every save changes are left out.
+fn diff(args: &DiffArgs, reporter: &Reporter) {
+    let options = ParseOptions {
+        game: args.game,
+        compression: args.compression,
+        ..Default::default()
+    };
+    let (old_contents, new_contents) = match (
+        read_plugin_file(&args.old, reporter),
+        read_plugin_file(&args.new, reporter),
+    ) {
+        (Some(old_contents), Some(new_contents)) => (old_contents, new_contents),
+        _ => return,
+    };
+    let old_plugin = match parse_plugin_file(&old_contents, &args.old, &options, reporter) {
+        Some(plugin) => plugin,
+        None => return,
+    };
+    let new_plugin = match parse_plugin_file(&new_contents, &args.new, &options, reporter) {
+        Some(plugin) => plugin,
+        None => return,
+    };
+
+    let masters = Masters::new(&old_plugin.header, &new_plugin.header);
+    let old_cells = cells_by_master(&old_plugin);
+    let mut new_cells = cells_by_master(&new_plugin);
+    let mut differ = false;
+    for (key, old_cell) in old_cells {
+        match new_cells.remove(&key) {
+            Some(new_cell) => {
+                let changes = changed_fields(
+                    &masters,
+                    old_cell,
+                    &old_plugin.header,
+                    new_cell,
+                    &new_plugin.header,
+                );
+                if !changes.is_empty() {
+                    differ = true;
+                    println!("Changed: {}", describe_cell(new_cell));
+                    for (field, old_value, new_value) in changes {
+                        println!("    {}: {} -> {}", field, old_value, new_value);
+                    }
+                }
+            }
+            None => {
+                differ = true;
+                println!(
+                    "Only in {}: {}",
+                    args.old.display(),
+                    describe_cell(old_cell)
+                )
+            }
+        }
+    }
+    for new_cell in new_cells.values() {
+        differ = true;
+        println!(
+            "Only in {}: {}",
+            args.new.display(),
+            describe_cell(new_cell)
+        );
+    }
+    if differ {
+        reporter.set_exit_code(DIFFERENCES_EXIT_CODE);
+    }
+}
+
+/// Prints counts of the cells of a plugin
+fn stats(args: &StatsArgs, reporter: &Reporter) {
+    let options = ParseOptions {
+        game: args.game,
+        compression: args.compression,
+        ..Default::default()
+    };
+    let contents = match read_plugin_file(&args.plugin, reporter) {
+        Some(contents) => contents,
+        None => return,
+    };
+    match parse_plugin_lazy(&contents, &options) {
+        Ok(plugin) => print!("{}", Stats::count(&plugin).render()),
+        Err(error) => return reporter.report(parse_failure(&args.plugin, &error)),
+    }
+    if args.records {
+        match count_record_types_with_options(&contents, &options) {
+            Ok(counts) => print!("{}", render_record_counts(&counts)),
+            Err(error) => reporter.report(parse_failure(&args.plugin, &error)),
+        }
+    }
+}
+
+/// Prints the tree of groups in a plugin
+fn tree(args: &TreeArgs, reporter: &Reporter) {
+    let options = ParseOptions {
+        game: args.game,
+        ..Default::default()
+    };
+    let contents = match read_plugin_file(&args.plugin, reporter) {
+        Some(contents) => contents,
+        None => return,
+    };
+    match parse_group_tree_with_options(&contents, &options) {
+        Ok(trees) => print!("{}", render_tree(&trees)),
+        Err(error) => reporter.report(parse_failure(&args.plugin, &error)),
+    }
+}
+
+/// Keys the cells of a plugin by the lowercase name of the master that defines them and their local id, so that cells
+/// can be matched between plugins with different masters. Cells that the plugin defines itself have no master name.
+fn cells_by_master<'a>(plugin: &'a Plugin) -> BTreeMap<(Option<String>, u32), &'a Cell<'a>> {
+    plugin
+        .cells
+        .iter()
+        .map(|cell| {
+            let form_id = FormId(cell.form_id);
+            let master = form_id
+                .resolve_master(&plugin.header)
+                .map(|master| master.to_ascii_lowercase());
+            ((master, form_id.local_id()), cell)
+        })
+        .collect()
+}
+
+/// Reads the contents of a plugin file, or of stdin if the path is `-`, reporting an error if it can't be read
+fn read_plugin_file(path: &Path, reporter: &Reporter) -> Option<Vec<u8>> {
+    let result = if is_stdin(path) {
+        let mut contents = vec![];
+        io::stdin()
+            .lock()
+            .read_to_end(&mut contents)
+            .map(|_| contents)
+    } else {
+        read(path)
+    };
+    match result {
+        Ok(contents) => Some(contents),
+        Err(error) => {
+            let message = if is_stdin(path) {
+                format!("Failed to read plugin from stdin: {}", error)
+            } else {
+                format!(
+                    "Failed to read from plugin file {}: {}",
+                    path.to_string_lossy(),
+                    error
+                )
+            };
+            reporter.report(Failure::new(ErrorKind::Read, message).with_path(path));
+            None
+        }
+    }
+}
+
+/// Parses the contents of a plugin file, reporting an error if it can't be parsed
+fn parse_plugin_file<'a>(
+    contents: &'a [u8],
+    path: &Path,
+    options: &ParseOptions,
+    reporter: &Reporter,
+) -> Option<Plugin<'a>> {
+    match parse_plugin_with_options(contents, options) {
+        Ok(plugin) => Some(plugin),
+        Err(error) => {
+            reporter.report(parse_failure(path, &error));
+            None
+        }
+    }
+}
+
+/// Parses as much of the contents of a plugin file as possible, reporting an error if it can't all be parsed
+fn parse_partial_plugin_file<'a>(
+    contents: &'a [u8],
+    path: &Path,
+    options: &ParseOptions,
+    reporter: &Reporter,
+) -> Option<Plugin<'a>> {
+    match parse_plugin_partial(contents, options) {
+        Ok((plugin, error)) => {
+            if let Some(error) = error {
+                reporter.report(parse_failure(path, &error));
+            }
+            Some(plugin)
+        }
+        Err(error) => {
+            reporter.report(parse_failure(path, &error));
+            None
+        }
+    }
+}
+
+fn parse_failure(path: &Path, error: &ParseError) -> Failure {
+    Failure::new(
+        ErrorKind::Parse,
+        format!(
+            "Failed to parse plugin file {}: {}",
+            display_path(path),
+            error
+        ),
+    )
+    .with_path(path)
+    .with_parse_error(error)
+}
+
+fn describe_cell(cell: &Cell) -> String {
+    let mut description = format!("{:08X}", cell.form_id);
+    if let Some(editor_id) = &cell.editor_id {
+        description.push_str(&format!(" {}", editor_id));
+    }
+    if let (Some(x), Some(y)) = (cell.x, cell.y) {
+        description.push_str(&format!(" ({}, {})", x, y));
+    }
+    description
+}
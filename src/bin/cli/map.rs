@@ -0,0 +1,106 @@
+use std::collections::{BTreeMap, HashSet};
+
+use skyrim_cell_dump::Plugin;
+
+/// Characters drawn for each cell of the grid, which are two wide so that cells look roughly square in a terminal
+const EDITED_CELL: &str = "##";
+const UNEDITED_CELL: &str = ". ";
+const CELL_WIDTH: usize = 2;
+/// Columns are labeled with their x coordinate at every multiple of this
+const X_LABEL_INTERVAL: i32 = 5;
+
+/// Draws a grid of the exterior cells of each worldspace that the plugins edit, with the edited cells marked.
+///
+/// North is up, and the rows and columns are labeled with their y and x coordinates. With `batch`, the maps of each
+/// plugin follow the name of the plugin.
+pub fn render_map(plugins: &[(&str, Plugin)], batch: bool) -> String {
+    let mut output = String::new();
+    for (name, plugin) in plugins {
+        if batch {
+            output.push_str(&format!("{}:\n", name));
+        }
+        output.push_str(&render_plugin_map(plugin));
+    }
+    output
+}
+
+fn render_plugin_map(plugin: &Plugin) -> String {
+    let mut worldspaces: BTreeMap<String, HashSet<(i32, i32)>> = BTreeMap::new();
+    let mut interiors = 0;
+    for cell in &plugin.cells {
+        match (cell.world_form_id, cell.x, cell.y) {
+            (Some(world_form_id), Some(x), Some(y)) if !cell.is_persistent => {
+                let worldspace = plugin
+                    .worlds
+                    .iter()
+                    .find(|world| world.form_id == world_form_id)
+                    .map(|world| world.editor_id.clone())
+                    .unwrap_or_else(|| format!("{:08X}", world_form_id));
+                worldspaces.entry(worldspace).or_default().insert((x, y));
+            }
+            (None, _, _) => interiors += 1,
+            _ => {}
+        }
+    }
+
+    let mut output = String::new();
+    for (worldspace, cells) in &worldspaces {
+        output.push_str(&format!("{} ({} cells)\n", worldspace, cells.len()));
+        output.push_str(&render_grid(cells));
+        output.push('\n');
+    }
+    if worldspaces.is_empty() {
+        output.push_str("No exterior cells\n");
+    }
+    if interiors > 0 {
+        output.push_str(&format!("Interior cells: {}\n", interiors));
+    }
+    output
+}
+
+/// Draws the cells within the bounds of the edited cells, with a row of x coordinates above them and the y coordinate
+/// at the start of each row
+fn render_grid(cells: &HashSet<(i32, i32)>) -> String {
+    let min_x = cells.iter().map(|&(x, _)| x).min().unwrap_or_default();
+    let max_x = cells.iter().map(|&(x, _)| x).max().unwrap_or_default();
+    let min_y = cells.iter().map(|&(_, y)| y).min().unwrap_or_default();
+    let max_y = cells.iter().map(|&(_, y)| y).max().unwrap_or_default();
+    let y_label_width = min_y.to_string().len().max(max_y.to_string().len());
+
+    let mut output = format!("{} ", " ".repeat(y_label_width));
+    output.push_str(&x_labels(min_x, max_x));
+    output.push('\n');
+    for y in (min_y..=max_y).rev() {
+        output.push_str(&format!("{:>width$} ", y, width = y_label_width));
+        let row: String = (min_x..=max_x)
+            .map(|x| {
+                if cells.contains(&(x, y)) {
+                    EDITED_CELL
+                } else {
+                    UNEDITED_CELL
+                }
+            })
+            .collect();
+        output.push_str(row.trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+/// Labels the first column and every column at a multiple of [`X_LABEL_INTERVAL`] with its x coordinate, skipping
+/// labels that would run into the one before them
+fn x_labels(min_x: i32, max_x: i32) -> String {
+    let mut labels = String::new();
+    for x in min_x..=max_x {
+        if x != min_x && x % X_LABEL_INTERVAL != 0 {
+            continue;
+        }
+        let column = (x - min_x) as usize * CELL_WIDTH;
+        let label = x.to_string();
+        if labels.is_empty() || labels.len() < column {
+            labels.push_str(&" ".repeat(column - labels.len()));
+            labels.push_str(&label);
+        }
+    }
+    labels
+}
@@ -0,0 +1,259 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::format::to_json_value;
+
+/// Encodes a value as [MessagePack](https://github.com/msgpack/msgpack/blob/master/spec.md), with structs encoded as
+/// maps from field names to values like the JSON format
+pub fn to_msgpack<T: Serialize>(value: &T) -> serde_json::Result<Vec<u8>> {
+    let mut output = vec![];
+    encode_value(&mut output, &to_json_value(value)?);
+    Ok(output)
+}
+
+fn encode_value(output: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::Null => output.push(0xC0),
+        Value::Bool(false) => output.push(0xC2),
+        Value::Bool(true) => output.push(0xC3),
+        Value::Number(number) => {
+            if let Some(integer) = number.as_u64() {
+                encode_unsigned(output, integer);
+            } else if let Some(integer) = number.as_i64() {
+                encode_signed(output, integer);
+            } else if let Some(float) = number.as_f64() {
+                output.push(0xCB);
+                output.extend_from_slice(&float.to_be_bytes());
+            }
+        }
+        Value::String(string) => {
+            let len = string.len();
+            if len < 32 {
+                output.push(0xA0 | len as u8);
+            } else {
+                encode_len(output, len, [0xD9, 0xDA, 0xDB]);
+            }
+            output.extend_from_slice(string.as_bytes());
+        }
+        Value::Array(elements) => {
+            let len = elements.len();
+            if len < 16 {
+                output.push(0x90 | len as u8);
+            } else {
+                encode_len(output, len, [0, 0xDC, 0xDD]);
+            }
+            for element in elements {
+                encode_value(output, element);
+            }
+        }
+        Value::Object(map) => {
+            let len = map.len();
+            if len < 16 {
+                output.push(0x80 | len as u8);
+            } else {
+                encode_len(output, len, [0, 0xDE, 0xDF]);
+            }
+            for (key, value) in map {
+                encode_value(output, &Value::String(key.clone()));
+                encode_value(output, value);
+            }
+        }
+    }
+}
+
+fn encode_unsigned(output: &mut Vec<u8>, integer: u64) {
+    if integer < 0x80 {
+        output.push(integer as u8);
+    } else if integer <= u64::from(u8::MAX) {
+        output.push(0xCC);
+        output.push(integer as u8);
+    } else if integer <= u64::from(u16::MAX) {
+        output.push(0xCD);
+        output.extend_from_slice(&(integer as u16).to_be_bytes());
+    } else if integer <= u64::from(u32::MAX) {
+        output.push(0xCE);
+        output.extend_from_slice(&(integer as u32).to_be_bytes());
+    } else {
+        output.push(0xCF);
+        output.extend_from_slice(&integer.to_be_bytes());
+    }
+}
+
+/// Encodes a negative integer, since positive integers are always encoded with [`encode_unsigned`]
+fn encode_signed(output: &mut Vec<u8>, integer: i64) {
+    if integer >= -32 {
+        output.push(integer as u8);
+    } else if integer >= i64::from(i8::MIN) {
+        output.push(0xD0);
+        output.push(integer as u8);
+    } else if integer >= i64::from(i16::MIN) {
+        output.push(0xD1);
+        output.extend_from_slice(&(integer as i16).to_be_bytes());
+    } else if integer >= i64::from(i32::MIN) {
+        output.push(0xD2);
+        output.extend_from_slice(&(integer as i32).to_be_bytes());
+    } else {
+        output.push(0xD3);
+        output.extend_from_slice(&integer.to_be_bytes());
+    }
+}
+
+/// Encodes the length of a string, array, or map with the smallest of the 8, 16, and 32 bit `markers` that fits it.
+/// Arrays and maps have no 8 bit marker, since their lengths below 16 have a fixed marker.
+fn encode_len(output: &mut Vec<u8>, len: usize, markers: [u8; 3]) {
+    if len <= usize::from(u8::MAX) && markers[0] != 0 {
+        output.push(markers[0]);
+        output.push(len as u8);
+    } else if len <= usize::from(u16::MAX) {
+        output.push(markers[1]);
+        output.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        output.push(markers[2]);
+        output.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use serde_json::{json, Map, Number};
+
+    use super::*;
+
+    /// Decodes the MessagePack value at the start of `input` with the formats that [`encode_value`] writes, returning
+    /// it and the rest of the input
+    fn decode_value(input: &[u8]) -> (Value, &[u8]) {
+        let marker = input[0];
+        let rest = &input[1..];
+        let uint = |size: usize| {
+            let mut bytes = [0; 8];
+            bytes[8 - size..].copy_from_slice(&rest[..size]);
+            (u64::from_be_bytes(bytes), &rest[size..])
+        };
+        let int = |size: usize| {
+            let mut bytes = [if rest[0] & 0x80 != 0 { 0xFF } else { 0 }; 8];
+            bytes[8 - size..].copy_from_slice(&rest[..size]);
+            (Value::from(i64::from_be_bytes(bytes)), &rest[size..])
+        };
+        let (len, rest) = match marker {
+            0x00..=0x7F => return (Value::from(marker), rest),
+            0xE0..=0xFF => return (Value::from(marker as i8), rest),
+            0xC0 => return (Value::Null, rest),
+            0xC2 => return (Value::Bool(false), rest),
+            0xC3 => return (Value::Bool(true), rest),
+            0xCB => {
+                let float = f64::from_be_bytes(rest[..8].try_into().unwrap());
+                return (Value::Number(Number::from_f64(float).unwrap()), &rest[8..]);
+            }
+            0xCC..=0xCF => {
+                let (integer, rest) = uint(1 << (marker - 0xCC));
+                return (Value::from(integer), rest);
+            }
+            0xD0..=0xD3 => return int(1 << (marker - 0xD0)),
+            0xA0..=0xBF => ((marker & 0x1F) as u64, rest),
+            0x80..=0x9F => ((marker & 0x0F) as u64, rest),
+            0xD9 | 0xDC | 0xDE => uint(if marker == 0xD9 { 1 } else { 2 }),
+            0xDA | 0xDB | 0xDD | 0xDF => uint(if marker == 0xDA { 2 } else { 4 }),
+            _ => panic!("unexpected marker {:#04X}", marker),
+        };
+        let len = len as usize;
+        match marker {
+            0xA0..=0xBF | 0xD9..=0xDB => (
+                Value::String(String::from_utf8(rest[..len].to_vec()).unwrap()),
+                &rest[len..],
+            ),
+            0x90..=0x9F | 0xDC | 0xDD => {
+                let mut elements = vec![];
+                let mut rest = rest;
+                for _ in 0..len {
+                    let (element, remaining) = decode_value(rest);
+                    elements.push(element);
+                    rest = remaining;
+                }
+                (Value::Array(elements), rest)
+            }
+            _ => {
+                let mut map = Map::new();
+                let mut rest = rest;
+                for _ in 0..len {
+                    let (key, remaining) = decode_value(rest);
+                    let (value, remaining) = decode_value(remaining);
+                    map.insert(key.as_str().unwrap().to_string(), value);
+                    rest = remaining;
+                }
+                (Value::Object(map), rest)
+            }
+        }
+    }
+
+    fn encode(value: Value) -> Vec<u8> {
+        let mut output = vec![];
+        encode_value(&mut output, &value);
+        output
+    }
+
+    #[test]
+    fn encodes_values_like_the_specification() {
+        assert_eq!(encode(json!(null)), [0xC0]);
+        assert_eq!(encode(json!([false, true])), [0x92, 0xC2, 0xC3]);
+        assert_eq!(encode(json!(127)), [0x7F]);
+        assert_eq!(encode(json!(128)), [0xCC, 0x80]);
+        assert_eq!(encode(json!(256)), [0xCD, 0x01, 0x00]);
+        assert_eq!(encode(json!(65536)), [0xCE, 0x00, 0x01, 0x00, 0x00]);
+        assert_eq!(
+            encode(json!(1u64 << 32)),
+            [0xCF, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(encode(json!(-1)), [0xFF]);
+        assert_eq!(encode(json!(-32)), [0xE0]);
+        assert_eq!(encode(json!(-33)), [0xD0, 0xDF]);
+        assert_eq!(encode(json!(-129)), [0xD1, 0xFF, 0x7F]);
+        assert_eq!(encode(json!(-32769)), [0xD2, 0xFF, 0xFF, 0x7F, 0xFF]);
+        assert_eq!(
+            encode(json!(i64::MIN)),
+            [0xD3, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(
+            encode(json!(1.5)),
+            [0xCB, 0x3F, 0xF8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        assert_eq!(encode(json!("a")), [0xA1, b'a']);
+        assert_eq!(encode(json!({ "a": 1 })), [0x81, 0xA1, b'a', 0x01]);
+    }
+
+    #[test]
+    fn encodes_lengths_with_the_smallest_marker() {
+        assert_eq!(encode(json!("a".repeat(31)))[0], 0xBF);
+        assert_eq!(encode(json!("a".repeat(32)))[..2], [0xD9, 32]);
+        assert_eq!(encode(json!("a".repeat(256)))[..3], [0xDA, 0x01, 0x00]);
+        assert_eq!(
+            encode(json!("a".repeat(65536)))[..5],
+            [0xDB, 0x00, 0x01, 0x00, 0x00]
+        );
+        assert_eq!(encode(json!(vec![0; 15]))[0], 0x9F);
+        assert_eq!(encode(json!(vec![0; 16]))[..3], [0xDC, 0x00, 0x10]);
+        assert_eq!(
+            encode(json!(vec![0; 65536]))[..5],
+            [0xDD, 0x00, 0x01, 0x00, 0x00]
+        );
+        let map: Map<String, Value> = (0..16).map(|i| (i.to_string(), json!(i))).collect();
+        assert_eq!(encode(Value::Object(map))[..3], [0xDE, 0x00, 0x10]);
+    }
+
+    #[test]
+    fn decodes_back_into_the_json_value() {
+        let value = json!({
+            "header": { "version": 1.71, "author": "Tester", "masters": ["Skyrim.esm"] },
+            "cells": [
+                { "form_id": 3444, "x": -3, "y": 200, "water_height": -2048.5, "is_persistent": false },
+                { "form_id": 33556481, "editor_id": "a".repeat(300), "regions": (0..20).collect::<Vec<_>>() },
+            ],
+            "flags": (0..20).map(|i| (format!("FLAG_{}", i), json!(i % 2 == 0))).collect::<Map<_, _>>(),
+        });
+        let output = to_msgpack(&value).unwrap();
+        let (decoded, rest) = decode_value(&output);
+        assert!(rest.is_empty());
+        assert_eq!(decoded, value);
+    }
+}
@@ -0,0 +1,28 @@
+use std::fs::{remove_file, rename, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+/// Writes the contents to a temporary file next to `path` and then renames it to `path`, so that an existing file at
+/// `path` is only replaced once all of the contents have been written
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidInput, "output path has no file name")
+    })?;
+    let temp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        process::id()
+    ));
+    let result = write_file(&temp_path, contents).and_then(|_| rename(&temp_path, path));
+    if result.is_err() {
+        let _ = remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_file(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
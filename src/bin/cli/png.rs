@@ -0,0 +1,386 @@
+use std::io::{Read, Write};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Channels of each pixel in [`Image::pixels`]
+const CHANNELS: usize = 3;
+
+/// An 8-bit RGB image
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// The red, green, and blue values of each pixel, row by row from the top left
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize, color: [u8; 3]) -> Self {
+        Image {
+            width,
+            height,
+            pixels: color.repeat(width * height),
+        }
+    }
+
+    /// Blends the color over the pixels of a rectangle, with `alpha` from 0 (transparent) to 1 (opaque)
+    pub fn blend_rect(
+        &mut self,
+        left: usize,
+        top: usize,
+        right: usize,
+        bottom: usize,
+        color: [u8; 3],
+        alpha: f32,
+    ) {
+        for y in top..bottom.min(self.height) {
+            for x in left..right.min(self.width) {
+                let offset = (y * self.width + x) * CHANNELS;
+                for (channel, value) in color.iter().enumerate() {
+                    let pixel = &mut self.pixels[offset + channel];
+                    *pixel = (*pixel as f32 * (1.0 - alpha) + *value as f32 * alpha).round() as u8;
+                }
+            }
+        }
+    }
+}
+
+/// Encodes the image as a PNG file
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    let mut output = SIGNATURE.to_vec();
+    let mut header = vec![];
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, default compression and filtering, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+    write_chunk(&mut output, b"IHDR", &header);
+
+    let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+    for row in image.pixels.chunks(image.width * CHANNELS) {
+        // every row is stored without filtering
+        encoder.write_all(&[0]).unwrap();
+        encoder.write_all(row).unwrap();
+    }
+    write_chunk(&mut output, b"IDAT", &encoder.finish().unwrap());
+    write_chunk(&mut output, b"IEND", &[]);
+    output
+}
+
+fn write_chunk(output: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(chunk_type);
+    hasher.update(data);
+    output.extend_from_slice(chunk_type);
+    output.extend_from_slice(data);
+    output.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+/// Decodes a PNG file with 8 bits per channel that isn't interlaced, which covers the images saved by most editors.
+/// Any alpha channel is dropped.
+pub fn decode_png(input: &[u8]) -> Result<Image, String> {
+    if !input.starts_with(&SIGNATURE) {
+        return Err("not a PNG file".to_string());
+    }
+    let mut rest = &input[SIGNATURE.len()..];
+    let mut header: Option<&[u8]> = None;
+    let mut palette: &[u8] = &[];
+    let mut compressed = vec![];
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        if rest.len() < 12 + length {
+            return Err("truncated chunk".to_string());
+        }
+        let chunk_type = &rest[4..8];
+        let data = &rest[8..8 + length];
+        match chunk_type {
+            b"IHDR" => header = Some(data),
+            b"PLTE" => palette = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+        rest = &rest[12 + length..];
+    }
+    let header = match header {
+        Some(header) if header.len() == 13 => header,
+        _ => return Err("missing IHDR chunk".to_string()),
+    };
+    let width = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let height = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    let (bit_depth, color_type, interlace) = (header[8], header[9], header[12]);
+    if bit_depth != 8 {
+        return Err(format!("unsupported bit depth {}", bit_depth));
+    }
+    if interlace != 0 {
+        return Err("interlaced images are not supported".to_string());
+    }
+    let input_channels = match color_type {
+        0 | 3 => 1,
+        4 => 2,
+        2 => 3,
+        6 => 4,
+        _ => return Err(format!("unsupported color type {}", color_type)),
+    };
+
+    let mut data = vec![];
+    ZlibDecoder::new(&compressed[..])
+        .read_to_end(&mut data)
+        .map_err(|error| format!("invalid image data: {}", error))?;
+    let stride = width * input_channels;
+    if data.len() < (stride + 1) * height {
+        return Err("truncated image data".to_string());
+    }
+    let mut previous_row = vec![0; stride];
+    let mut pixels = Vec::with_capacity(width * height * CHANNELS);
+    for row in data.chunks(stride + 1).take(height) {
+        let row = unfilter(row[0], &row[1..], &previous_row, input_channels)?;
+        for pixel in row.chunks(input_channels) {
+            match color_type {
+                0 | 4 => pixels.extend_from_slice(&[pixel[0]; 3]),
+                3 => {
+                    let index = pixel[0] as usize * 3;
+                    match palette.get(index..index + 3) {
+                        Some(color) => pixels.extend_from_slice(color),
+                        None => return Err("palette index out of range".to_string()),
+                    }
+                }
+                _ => pixels.extend_from_slice(&pixel[..3]),
+            }
+        }
+        previous_row = row;
+    }
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+/// Reverses the filter of a row, given the unfiltered row above it
+fn unfilter(
+    filter: u8,
+    row: &[u8],
+    previous_row: &[u8],
+    bytes_per_pixel: usize,
+) -> Result<Vec<u8>, String> {
+    let mut unfiltered: Vec<u8> = Vec::with_capacity(row.len());
+    for (i, &byte) in row.iter().enumerate() {
+        let left = if i >= bytes_per_pixel {
+            unfiltered[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let up = previous_row[i];
+        let up_left = if i >= bytes_per_pixel {
+            previous_row[i - bytes_per_pixel]
+        } else {
+            0
+        };
+        let predictor = match filter {
+            0 => 0,
+            1 => left,
+            2 => up,
+            3 => ((left as u16 + up as u16) / 2) as u8,
+            4 => paeth(left, up, up_left),
+            _ => return Err(format!("unknown filter type {}", filter)),
+        };
+        unfiltered.push(byte.wrapping_add(predictor));
+    }
+    Ok(unfiltered)
+}
+
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let left_distance = (estimate - left as i16).abs();
+    let up_distance = (estimate - up as i16).abs();
+    let up_left_distance = (estimate - up_left as i16).abs();
+    if left_distance <= up_distance && left_distance <= up_left_distance {
+        left
+    } else if up_distance <= up_left_distance {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+
+    /// Splits a PNG file into the type and data of each chunk, checking the CRC of each one
+    fn chunks(png: &[u8]) -> Vec<(&[u8], &[u8])> {
+        assert_eq!(png[..8], SIGNATURE);
+        let mut chunks = vec![];
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (chunk_type, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            let mut hasher = crc32fast::Hasher::new();
+            hasher.update(&rest[4..8 + length]);
+            assert_eq!(crc, hasher.finalize());
+            chunks.push((chunk_type, data));
+            rest = &rest[12 + length..];
+        }
+        chunks
+    }
+
+    /// Encodes a PNG with the color type and rows of pixels, each row filtered with the filter type given for it
+    fn png_with_filters(
+        width: usize,
+        color_type: u8,
+        channels: usize,
+        rows: &[(u8, Vec<u8>)],
+        palette: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let mut output = SIGNATURE.to_vec();
+        let mut header = vec![];
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(rows.len() as u32).to_be_bytes());
+        header.extend_from_slice(&[8, color_type, 0, 0, 0]);
+        write_chunk(&mut output, b"IHDR", &header);
+        if let Some(palette) = palette {
+            write_chunk(&mut output, b"PLTE", palette);
+        }
+        let mut data = vec![];
+        let mut previous_row = vec![0; width * channels];
+        for (filter, row) in rows {
+            data.push(*filter);
+            for (i, &byte) in row.iter().enumerate() {
+                let left = if i >= channels { row[i - channels] } else { 0 };
+                let up = previous_row[i];
+                let up_left = if i >= channels {
+                    previous_row[i - channels]
+                } else {
+                    0
+                };
+                let predictor = match filter {
+                    0 => 0,
+                    1 => left,
+                    2 => up,
+                    3 => ((left as u16 + up as u16) / 2) as u8,
+                    _ => paeth(left, up, up_left),
+                };
+                data.push(byte.wrapping_sub(predictor));
+            }
+            previous_row = row.clone();
+        }
+        let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(&data).unwrap();
+        // the image data may be split across several IDAT chunks
+        let compressed = encoder.finish().unwrap();
+        let (first, second) = compressed.split_at(compressed.len() / 2);
+        write_chunk(&mut output, b"IDAT", first);
+        write_chunk(&mut output, b"IDAT", second);
+        write_chunk(&mut output, b"IEND", &[]);
+        output
+    }
+
+    fn test_image() -> Image {
+        let mut image = Image::new(5, 3, [10, 20, 30]);
+        image.blend_rect(1, 1, 4, 10, [250, 0, 100], 1.0);
+        image.blend_rect(0, 0, 2, 2, [0, 0, 0], 0.5);
+        image
+    }
+
+    #[test]
+    fn blends_rectangles_within_the_image() {
+        let image = test_image();
+        assert_eq!(image.pixels[..3], [5, 10, 15]);
+        assert_eq!(image.pixels[(5 + 1) * 3..(5 + 2) * 3], [125, 0, 50]);
+        assert_eq!(
+            image.pixels[(2 * 5 + 3) * 3..(2 * 5 + 4) * 3],
+            [250, 0, 100]
+        );
+        assert_eq!(image.pixels[(2 * 5 + 4) * 3..], [10, 20, 30]);
+    }
+
+    #[test]
+    fn encodes_valid_chunks() {
+        let image = test_image();
+        let png = encode_png(&image);
+        let chunks = chunks(&png);
+        let chunk_types: Vec<&[u8]> = chunks.iter().map(|&(chunk_type, _)| chunk_type).collect();
+        assert_eq!(chunk_types, [&b"IHDR"[..], b"IDAT", b"IEND"]);
+        assert_eq!(chunks[0].1, [0, 0, 0, 5, 0, 0, 0, 3, 8, 2, 0, 0, 0]);
+        // the CRC of every IEND chunk
+        assert_eq!(png[png.len() - 4..], [0xAE, 0x42, 0x60, 0x82]);
+
+        let mut data = vec![];
+        ZlibDecoder::new(chunks[1].1)
+            .read_to_end(&mut data)
+            .unwrap();
+        let rows: Vec<&[u8]> = data.chunks(1 + 5 * CHANNELS).collect();
+        assert_eq!(rows.len(), 3);
+        for (row, pixels) in rows.iter().zip(image.pixels.chunks(5 * CHANNELS)) {
+            assert_eq!(row[0], 0);
+            assert_eq!(&row[1..], pixels);
+        }
+    }
+
+    #[test]
+    fn decodes_encoded_images() {
+        let image = test_image();
+        let decoded = decode_png(&encode_png(&image)).unwrap();
+        assert_eq!((decoded.width, decoded.height), (5, 3));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+
+    #[test]
+    fn decodes_every_filter_type() {
+        let rows: Vec<(u8, Vec<u8>)> = (0..5)
+            .map(|filter| {
+                let row = (0..4 * 3)
+                    .map(|i| (i * 37 + usize::from(filter) * 91) as u8)
+                    .collect();
+                (filter, row)
+            })
+            .collect();
+        let png = png_with_filters(4, 2, 3, &rows, None);
+        let decoded = decode_png(&png).unwrap();
+        let pixels: Vec<u8> = rows.iter().flat_map(|(_, row)| row.clone()).collect();
+        assert_eq!(decoded.pixels, pixels);
+    }
+
+    #[test]
+    fn decodes_grayscale_palette_and_alpha_images() {
+        let gray = png_with_filters(2, 0, 1, &[(1, vec![10, 200])], None);
+        assert_eq!(
+            decode_png(&gray).unwrap().pixels,
+            [10, 10, 10, 200, 200, 200]
+        );
+
+        let palette = [1, 2, 3, 4, 5, 6];
+        let indexed = png_with_filters(2, 3, 1, &[(0, vec![1, 0])], Some(&palette));
+        assert_eq!(decode_png(&indexed).unwrap().pixels, [4, 5, 6, 1, 2, 3]);
+        let out_of_range = png_with_filters(1, 3, 1, &[(0, vec![2])], Some(&palette));
+        assert!(decode_png(&out_of_range).is_err());
+
+        let rgba = png_with_filters(1, 6, 4, &[(4, vec![7, 8, 9, 255])], None);
+        assert_eq!(decode_png(&rgba).unwrap().pixels, [7, 8, 9]);
+    }
+
+    #[test]
+    fn rejects_unsupported_and_malformed_images() {
+        let png = encode_png(&test_image());
+        assert!(decode_png(b"GIF89a").is_err());
+        assert!(decode_png(&png[..40]).is_err());
+
+        let with_header = |bit_depth: u8, interlace: u8| {
+            let mut png = png.clone();
+            // IHDR data starts after the signature, length, and type
+            png[16 + 8] = bit_depth;
+            png[16 + 12] = interlace;
+            png
+        };
+        assert!(decode_png(&with_header(16, 0)).is_err());
+        assert!(decode_png(&with_header(8, 1)).is_err());
+
+        let bad_filter = png_with_filters(1, 2, 3, &[(5, vec![1, 2, 3])], None);
+        assert!(decode_png(&bad_filter).is_err());
+    }
+}
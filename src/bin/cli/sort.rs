@@ -0,0 +1,113 @@
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error, Result};
+use serde::Serialize;
+
+use skyrim_cell_dump::{
+    Cell, Land, Location, Navmesh, ParseIssue, ParseWarning, Plugin, PluginHeader, Region, World,
+};
+
+/// The order to output the cells of a plugin in
+#[derive(Clone, Copy)]
+pub enum Sort {
+    FormId,
+    EditorId,
+    /// By x and then y grid coordinate, with interior cells first
+    Coords,
+    /// By the editor id of the worldspace and then the coordinates, with interior cells first
+    Worldspace,
+}
+
+impl FromStr for Sort {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "form_id" | "formid" | "id" => Ok(Sort::FormId),
+            "editor_id" | "editorid" | "edid" => Ok(Sort::EditorId),
+            "coords" | "coordinates" => Ok(Sort::Coords),
+            "worldspace" | "world" => Ok(Sort::Worldspace),
+            _ => Err(anyhow!("Unrecognized sort {}", s)),
+        }
+    }
+}
+
+/// The cells of the plugin in the `sort` order. Cells that are equal by the order are sorted by form id.
+pub fn sorted_cells<'a, 'p>(plugin: &'a Plugin<'p>, sort: Sort) -> Vec<&'a Cell<'p>> {
+    let mut cells: Vec<&Cell> = plugin.cells.iter().collect();
+    cells.sort_by_key(|cell| cell.form_id);
+    match sort {
+        Sort::FormId => {}
+        Sort::EditorId => cells.sort_by(|a, b| a.editor_id.cmp(&b.editor_id)),
+        Sort::Coords => cells.sort_by_key(|cell| (cell.x, cell.y)),
+        Sort::Worldspace => cells.sort_by_cached_key(|cell| {
+            let worldspace = cell.world_form_id.map(|world_form_id| {
+                plugin
+                    .world(world_form_id)
+                    .map(|world| world.editor_id.clone())
+                    .unwrap_or_else(|| format!("{:08X}", world_form_id))
+            });
+            (worldspace, cell.x, cell.y)
+        }),
+    }
+    cells
+}
+
+/// A plugin with its cells in a chosen order and its worlds by form id, which is output in place of the plugin so
+/// that the output is the same every time. Without an order, the cells and worlds are in the arbitrary order of the
+/// plugin's sets.
+#[derive(Serialize)]
+pub struct SortedPlugin<'a, 'p> {
+    header: &'a PluginHeader<'p>,
+    worlds: Vec<&'a World>,
+    cells: Vec<&'a Cell<'p>>,
+    lands: &'a [Land],
+    navmeshes: &'a [Navmesh],
+    locations: &'a [Location],
+    regions: &'a [Region],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    issues: &'a [ParseIssue],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    warnings: &'a [ParseWarning],
+}
+
+impl<'a, 'p> SortedPlugin<'a, 'p> {
+    pub fn new(plugin: &'a Plugin<'p>, sort: Option<Sort>) -> Self {
+        let mut worlds: Vec<&World> = plugin.worlds.iter().collect();
+        let cells = match sort {
+            Some(sort) => {
+                worlds.sort_by_key(|world| world.form_id);
+                sorted_cells(plugin, sort)
+            }
+            None => plugin.cells.iter().collect(),
+        };
+        SortedPlugin {
+            header: &plugin.header,
+            worlds,
+            cells,
+            lands: &plugin.lands,
+            navmeshes: &plugin.navmeshes,
+            locations: &plugin.locations,
+            regions: &plugin.regions,
+            issues: &plugin.issues,
+            warnings: &plugin.warnings,
+        }
+    }
+}
+
+// written out to look the same as the text of an unsorted `Plugin`
+impl fmt::Debug for SortedPlugin<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Plugin")
+            .field("header", self.header)
+            .field("worlds", &self.worlds)
+            .field("cells", &self.cells)
+            .field("lands", &self.lands)
+            .field("navmeshes", &self.navmeshes)
+            .field("locations", &self.locations)
+            .field("regions", &self.regions)
+            .field("issues", &self.issues)
+            .field("warnings", &self.warnings)
+            .finish()
+    }
+}
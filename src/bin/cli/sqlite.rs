@@ -0,0 +1,672 @@
+//! Writes SQLite database files without depending on the SQLite library.
+//!
+//! Each table is written as a [table b-tree](https://www.sqlite.org/fileformat2.html#b_tree_pages) of leaf pages with
+//! interior pages above them, and the schema table on the first page points at the root page of each table.
+
+use skyrim_cell_dump::{LString, Plugin};
+
+const PAGE_SIZE: usize = 4096;
+/// Size of the database header at the start of the first page
+const DATABASE_HEADER_SIZE: usize = 100;
+const LEAF_PAGE_HEADER_SIZE: usize = 8;
+const INTERIOR_PAGE_HEADER_SIZE: usize = 12;
+const LEAF_TABLE_PAGE_TYPE: u8 = 0x0D;
+const INTERIOR_TABLE_PAGE_TYPE: u8 = 0x05;
+/// Most child pages of an interior page, with room for the largest cells (a page number and a 9 byte rowid, plus a
+/// cell pointer) and a right-most pointer
+const MAX_INTERIOR_CHILDREN: usize = (PAGE_SIZE - INTERIOR_PAGE_HEADER_SIZE) / (4 + 9 + 2) + 1;
+/// Most bytes of a row that are stored in a leaf page before the rest is moved to overflow pages
+const MAX_LOCAL_PAYLOAD: usize = PAGE_SIZE - 35;
+/// Fewest bytes of a row that are stored in a leaf page when the row spills to overflow pages
+const MIN_LOCAL_PAYLOAD: usize = (PAGE_SIZE - 12) * 32 / 255 - 23;
+/// Version of SQLite that the file claims to be written by
+const SQLITE_VERSION_NUMBER: u32 = 3_031_001;
+
+const PLUGINS_SQL: &str = "CREATE TABLE plugins (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    version REAL NOT NULL,
+    author TEXT,
+    description TEXT,
+    masters TEXT NOT NULL,
+    flags INTEGER NOT NULL
+)";
+const WORLDS_SQL: &str = "CREATE TABLE worlds (
+    plugin_id INTEGER NOT NULL REFERENCES plugins(id),
+    form_id INTEGER NOT NULL,
+    editor_id TEXT NOT NULL,
+    name
+)";
+const CELLS_SQL: &str = "CREATE TABLE cells (
+    plugin_id INTEGER NOT NULL REFERENCES plugins(id),
+    form_id INTEGER NOT NULL,
+    editor_id TEXT,
+    x INTEGER,
+    y INTEGER,
+    world_form_id INTEGER,
+    is_persistent INTEGER NOT NULL,
+    flags INTEGER NOT NULL,
+    water_height REAL
+)";
+
+enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Text(String),
+}
+
+/// Encodes a SQLite database with `plugins`, `worlds`, and `cells` tables holding the parsed data of the plugins.
+///
+/// The `masters` column of the plugins holds a JSON array of the master file names. The `name` column of the worlds
+/// holds the string id instead of a string for localized plugins.
+pub fn write_sqlite(plugins: &[(&str, Plugin)]) -> Vec<u8> {
+    let mut plugin_rows = vec![];
+    let mut world_rows = vec![];
+    let mut cell_rows = vec![];
+    for (index, (name, plugin)) in plugins.iter().enumerate() {
+        let plugin_id = index as i64 + 1;
+        let header = &plugin.header;
+        plugin_rows.push(vec![
+            // `id` is an alias of the rowid, so it is stored as null
+            Value::Null,
+            Value::Text(name.to_string()),
+            Value::Real(widen_float(header.version)),
+            optional_text(header.author.as_deref()),
+            optional_text(header.description.as_deref()),
+            Value::Text(serde_json::to_string(&header.masters).unwrap()),
+            Value::Integer(i64::from(header.flags.bits())),
+        ]);
+        for world in &plugin.worlds {
+            world_rows.push(vec![
+                Value::Integer(plugin_id),
+                Value::Integer(i64::from(world.form_id)),
+                Value::Text(world.editor_id.clone()),
+                match &world.name {
+                    Some(LString::String(name)) => Value::Text(name.clone()),
+                    Some(LString::Id(id)) => Value::Integer(i64::from(*id)),
+                    None => Value::Null,
+                },
+            ]);
+        }
+        let mut cells: Vec<_> = plugin.cells.iter().collect();
+        cells.sort_by_key(|cell| cell.form_id);
+        for cell in cells {
+            cell_rows.push(vec![
+                Value::Integer(plugin_id),
+                Value::Integer(i64::from(cell.form_id)),
+                optional_text(cell.editor_id.as_deref()),
+                optional_integer(cell.x.map(i64::from)),
+                optional_integer(cell.y.map(i64::from)),
+                optional_integer(cell.world_form_id.map(i64::from)),
+                Value::Integer(i64::from(cell.is_persistent)),
+                Value::Integer(i64::from(cell.flags.bits())),
+                cell.water_height
+                    .map_or(Value::Null, |height| Value::Real(widen_float(height.0))),
+            ]);
+        }
+    }
+
+    // the first page is filled in last, once the root pages of the tables are known
+    let mut pages = vec![vec![]];
+    let mut schema_rows = vec![];
+    for (name, sql, rows) in [
+        ("plugins", PLUGINS_SQL, plugin_rows),
+        ("worlds", WORLDS_SQL, world_rows),
+        ("cells", CELLS_SQL, cell_rows),
+    ] {
+        let root_page = write_table(&mut pages, &rows);
+        schema_rows.push(vec![
+            Value::Text("table".to_string()),
+            Value::Text(name.to_string()),
+            Value::Text(name.to_string()),
+            Value::Integer(i64::from(root_page)),
+            Value::Text(sql.to_string()),
+        ]);
+    }
+    let schema_cells: Vec<Vec<u8>> = schema_rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| leaf_cell(&mut pages, index as i64 + 1, row))
+        .collect();
+    pages[0] = leaf_page(&schema_cells, DATABASE_HEADER_SIZE);
+    let num_pages = pages.len() as u32;
+    pages[0][..DATABASE_HEADER_SIZE].copy_from_slice(&database_header(num_pages));
+    pages.concat()
+}
+
+/// Converts a float to the `f64` with the same shortest decimal representation, so that e.g. a version of `1.71` isn't
+/// stored as `1.7100000381469727`
+fn widen_float(float: f32) -> f64 {
+    float
+        .to_string()
+        .parse()
+        .unwrap_or_else(|_| f64::from(float))
+}
+
+fn optional_text(text: Option<&str>) -> Value {
+    text.map_or(Value::Null, |text| Value::Text(text.to_string()))
+}
+
+fn optional_integer(integer: Option<i64>) -> Value {
+    integer.map_or(Value::Null, Value::Integer)
+}
+
+fn database_header(num_pages: u32) -> [u8; DATABASE_HEADER_SIZE] {
+    let mut header = [0; DATABASE_HEADER_SIZE];
+    header[..16].copy_from_slice(b"SQLite format 3\0");
+    header[16..18].copy_from_slice(&(PAGE_SIZE as u16).to_be_bytes());
+    // legacy (not WAL) file format versions
+    header[18] = 1;
+    header[19] = 1;
+    // maximum, minimum, and leaf payload fractions, which must be these values
+    header[21] = 64;
+    header[22] = 32;
+    header[23] = 32;
+    // file change counter
+    header[24..28].copy_from_slice(&1u32.to_be_bytes());
+    header[28..32].copy_from_slice(&num_pages.to_be_bytes());
+    // schema cookie
+    header[40..44].copy_from_slice(&1u32.to_be_bytes());
+    // schema format number
+    header[44..48].copy_from_slice(&4u32.to_be_bytes());
+    // UTF-8 text encoding
+    header[56..60].copy_from_slice(&1u32.to_be_bytes());
+    // the file change counter that the database size in pages is valid for
+    header[92..96].copy_from_slice(&1u32.to_be_bytes());
+    header[96..100].copy_from_slice(&SQLITE_VERSION_NUMBER.to_be_bytes());
+    header
+}
+
+/// Writes the pages of a table b-tree holding the rows, with rowids starting from 1, and returns its root page number
+fn write_table(pages: &mut Vec<Vec<u8>>, rows: &[Vec<Value>]) -> u32 {
+    // (page number, largest rowid in the page's subtree) of the pages at the current level of the tree
+    let mut children = vec![];
+    let mut cells = vec![];
+    let mut size = LEAF_PAGE_HEADER_SIZE;
+    for (index, row) in rows.iter().enumerate() {
+        let cell = leaf_cell(pages, index as i64 + 1, row);
+        if !cells.is_empty() && size + cell.len() + 2 > PAGE_SIZE {
+            children.push((add_page(pages, leaf_page(&cells, 0)), index as i64));
+            cells.clear();
+            size = LEAF_PAGE_HEADER_SIZE;
+        }
+        size += cell.len() + 2;
+        cells.push(cell);
+    }
+    children.push((add_page(pages, leaf_page(&cells, 0)), rows.len() as i64));
+
+    while children.len() > 1 {
+        // the children are split evenly so that every interior page has at least one cell besides its right-most
+        // pointer
+        let num_parents = (children.len() + MAX_INTERIOR_CHILDREN - 1) / MAX_INTERIOR_CHILDREN;
+        let children_per_parent = (children.len() + num_parents - 1) / num_parents;
+        let mut parents = vec![];
+        for siblings in children.chunks(children_per_parent) {
+            let (&(right_most, max_rowid), siblings) =
+                siblings.split_last().expect("chunks are not empty");
+            let cells: Vec<Vec<u8>> = siblings
+                .iter()
+                .map(|&(page_number, max_rowid)| {
+                    let mut cell = page_number.to_be_bytes().to_vec();
+                    write_varint(&mut cell, max_rowid as u64);
+                    cell
+                })
+                .collect();
+            parents.push((
+                add_page(pages, interior_page(&cells, right_most)),
+                max_rowid,
+            ));
+        }
+        children = parents;
+    }
+    children[0].0
+}
+
+fn add_page(pages: &mut Vec<Vec<u8>>, page: Vec<u8>) -> u32 {
+    pages.push(page);
+    pages.len() as u32
+}
+
+/// Lays out the cells of a b-tree page from the end of the page, after the page header at `header_offset`
+fn build_page(
+    page_type: u8,
+    cells: &[Vec<u8>],
+    header_offset: usize,
+    right_most: Option<u32>,
+) -> Vec<u8> {
+    let mut page = vec![0; PAGE_SIZE];
+    let header_size = if right_most.is_some() {
+        INTERIOR_PAGE_HEADER_SIZE
+    } else {
+        LEAF_PAGE_HEADER_SIZE
+    };
+    let mut content_start = PAGE_SIZE;
+    let mut pointer = header_offset + header_size;
+    for cell in cells {
+        content_start -= cell.len();
+        page[content_start..content_start + cell.len()].copy_from_slice(cell);
+        page[pointer..pointer + 2].copy_from_slice(&(content_start as u16).to_be_bytes());
+        pointer += 2;
+    }
+    page[header_offset] = page_type;
+    page[header_offset + 3..header_offset + 5].copy_from_slice(&(cells.len() as u16).to_be_bytes());
+    // a content start of 65536 is stored as 0
+    page[header_offset + 5..header_offset + 7]
+        .copy_from_slice(&((content_start % 65536) as u16).to_be_bytes());
+    if let Some(right_most) = right_most {
+        page[header_offset + 8..header_offset + 12].copy_from_slice(&right_most.to_be_bytes());
+    }
+    page
+}
+
+fn leaf_page(cells: &[Vec<u8>], header_offset: usize) -> Vec<u8> {
+    build_page(LEAF_TABLE_PAGE_TYPE, cells, header_offset, None)
+}
+
+fn interior_page(cells: &[Vec<u8>], right_most: u32) -> Vec<u8> {
+    build_page(INTERIOR_TABLE_PAGE_TYPE, cells, 0, Some(right_most))
+}
+
+/// Encodes a row as a cell of a table leaf page, moving the end of rows that are too large into overflow pages
+fn leaf_cell(pages: &mut Vec<Vec<u8>>, rowid: i64, row: &[Value]) -> Vec<u8> {
+    let payload = encode_record(row);
+    let mut cell = vec![];
+    write_varint(&mut cell, payload.len() as u64);
+    write_varint(&mut cell, rowid as u64);
+    if payload.len() <= MAX_LOCAL_PAYLOAD {
+        cell.extend_from_slice(&payload);
+        return cell;
+    }
+
+    let overflow_size = PAGE_SIZE - 4;
+    let local_size = MIN_LOCAL_PAYLOAD + (payload.len() - MIN_LOCAL_PAYLOAD) % overflow_size;
+    let local_size = if local_size <= MAX_LOCAL_PAYLOAD {
+        local_size
+    } else {
+        MIN_LOCAL_PAYLOAD
+    };
+    cell.extend_from_slice(&payload[..local_size]);
+    // overflow pages are written in reverse so that each one can point at the next
+    let mut next_page = 0u32;
+    let chunks: Vec<&[u8]> = payload[local_size..].chunks(overflow_size).collect();
+    for chunk in chunks.into_iter().rev() {
+        let mut page = vec![0; PAGE_SIZE];
+        page[..4].copy_from_slice(&next_page.to_be_bytes());
+        page[4..4 + chunk.len()].copy_from_slice(chunk);
+        next_page = add_page(pages, page);
+    }
+    cell.extend_from_slice(&next_page.to_be_bytes());
+    cell
+}
+
+/// Encodes the values of a row in the [record format](https://www.sqlite.org/fileformat2.html#record_format)
+fn encode_record(row: &[Value]) -> Vec<u8> {
+    let mut serial_types = vec![];
+    let mut body = vec![];
+    for value in row {
+        let serial_type = match value {
+            Value::Null => 0,
+            Value::Integer(0) => 8,
+            Value::Integer(1) => 9,
+            Value::Integer(integer) => {
+                let (serial_type, size) = match integer {
+                    -0x80..=0x7F => (1, 1),
+                    -0x8000..=0x7FFF => (2, 2),
+                    -0x80_0000..=0x7F_FFFF => (3, 3),
+                    -0x8000_0000..=0x7FFF_FFFF => (4, 4),
+                    -0x8000_0000_0000..=0x7FFF_FFFF_FFFF => (5, 6),
+                    _ => (6, 8),
+                };
+                body.extend_from_slice(&integer.to_be_bytes()[8 - size..]);
+                serial_type
+            }
+            Value::Real(real) => {
+                body.extend_from_slice(&real.to_be_bytes());
+                7
+            }
+            Value::Text(text) => {
+                body.extend_from_slice(text.as_bytes());
+                text.len() as u64 * 2 + 13
+            }
+        };
+        write_varint(&mut serial_types, serial_type);
+    }
+    // the size of the header includes the varint holding it
+    let mut header_size = serial_types.len() + 1;
+    while serial_types.len() + varint_len(header_size as u64) != header_size {
+        header_size = serial_types.len() + varint_len(header_size as u64);
+    }
+    let mut record = vec![];
+    write_varint(&mut record, header_size as u64);
+    record.extend_from_slice(&serial_types);
+    record.extend_from_slice(&body);
+    record
+}
+
+fn varint_len(value: u64) -> usize {
+    let mut varint = vec![];
+    write_varint(&mut varint, value);
+    varint.len()
+}
+
+/// Writes a big-endian variable-length integer, with 7 bits in each byte except the ninth, which has all 8
+fn write_varint(output: &mut Vec<u8>, value: u64) {
+    if value > 0x00FF_FFFF_FFFF_FFFF {
+        let mut bytes = [0; 9];
+        bytes[8] = value as u8;
+        let mut value = value >> 8;
+        for byte in bytes[..8].iter_mut().rev() {
+            *byte = (value & 0x7F) as u8 | 0x80;
+            value >>= 7;
+        }
+        output.extend_from_slice(&bytes);
+        return;
+    }
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push((value & 0x7F) as u8 | 0x80);
+        value >>= 7;
+    }
+    output.extend(bytes.iter().rev());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use skyrim_cell_dump::{Cell, CellFlags, Float, PluginBuilder, World};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum Decoded {
+        Null,
+        Integer(i64),
+        Real(f64),
+        Text(String),
+    }
+
+    /// Reads a varint from the start of `bytes`, returning it and its size
+    fn read_varint(bytes: &[u8]) -> (u64, usize) {
+        let mut value = 0;
+        for (index, &byte) in bytes.iter().enumerate().take(9) {
+            if index == 8 {
+                return ((value << 8) | u64::from(byte), 9);
+            }
+            value = (value << 7) | u64::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                return (value, index + 1);
+            }
+        }
+        panic!("varint runs past the end of the input");
+    }
+
+    fn read_u16(bytes: &[u8], offset: usize) -> usize {
+        u16::from_be_bytes(bytes[offset..offset + 2].try_into().unwrap()) as usize
+    }
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn page(database: &[u8], page_number: u32) -> &[u8] {
+        let start = (page_number as usize - 1) * PAGE_SIZE;
+        &database[start..start + PAGE_SIZE]
+    }
+
+    fn decode_record(record: &[u8]) -> Vec<Decoded> {
+        let (header_size, mut position) = read_varint(record);
+        let mut body = header_size as usize;
+        let mut values = vec![];
+        while position < header_size as usize {
+            let (serial_type, size) = read_varint(&record[position..]);
+            position += size;
+            let value_size = match serial_type {
+                0 | 8 | 9 => 0,
+                1..=4 => serial_type as usize,
+                5 => 6,
+                6 | 7 => 8,
+                _ => (serial_type as usize - 13) / 2,
+            };
+            let bytes = &record[body..body + value_size];
+            body += value_size;
+            values.push(match serial_type {
+                0 => Decoded::Null,
+                8 => Decoded::Integer(0),
+                9 => Decoded::Integer(1),
+                7 => Decoded::Real(f64::from_be_bytes(bytes.try_into().unwrap())),
+                1..=6 => {
+                    // sign extend the big-endian integer
+                    let fill = if bytes[0] & 0x80 != 0 { 0xFF } else { 0 };
+                    let mut integer = [fill; 8];
+                    integer[8 - value_size..].copy_from_slice(bytes);
+                    Decoded::Integer(i64::from_be_bytes(integer))
+                }
+                _ => Decoded::Text(String::from_utf8(bytes.to_vec()).unwrap()),
+            });
+        }
+        assert_eq!(body, record.len());
+        values
+    }
+
+    /// A rowid and the values of its record
+    type Row = (i64, Vec<Decoded>);
+
+    /// Reads the rows of the table b-tree whose root is at `page_number` in the order of their rowids, following
+    /// overflow pages the way SQLite does
+    fn read_table(database: &[u8], page_number: u32, rows: &mut Vec<Row>) {
+        let page = page(database, page_number);
+        let header_offset = if page_number == 1 {
+            DATABASE_HEADER_SIZE
+        } else {
+            0
+        };
+        let num_cells = read_u16(page, header_offset + 3);
+        match page[header_offset] {
+            INTERIOR_TABLE_PAGE_TYPE => {
+                for index in 0..num_cells {
+                    let cell =
+                        read_u16(page, header_offset + INTERIOR_PAGE_HEADER_SIZE + index * 2);
+                    let (max_rowid, _) = read_varint(&page[cell + 4..]);
+                    read_table(database, read_u32(page, cell), rows);
+                    assert_eq!(rows.last().unwrap().0, max_rowid as i64);
+                }
+                read_table(database, read_u32(page, header_offset + 8), rows);
+            }
+            LEAF_TABLE_PAGE_TYPE => {
+                for index in 0..num_cells {
+                    let cell = read_u16(page, header_offset + LEAF_PAGE_HEADER_SIZE + index * 2);
+                    let (payload_size, size) = read_varint(&page[cell..]);
+                    let (rowid, rowid_size) = read_varint(&page[cell + size..]);
+                    let start = cell + size + rowid_size;
+                    let payload_size = payload_size as usize;
+                    // the formulas from the file format for how much of the payload is kept on the leaf page
+                    let max_local = PAGE_SIZE - 35;
+                    let min_local = (PAGE_SIZE - 12) * 32 / 255 - 23;
+                    let local_size = if payload_size <= max_local {
+                        payload_size
+                    } else {
+                        let local_size = min_local + (payload_size - min_local) % (PAGE_SIZE - 4);
+                        if local_size <= max_local {
+                            local_size
+                        } else {
+                            min_local
+                        }
+                    };
+                    let mut payload = page[start..start + local_size].to_vec();
+                    let mut overflow_page = if local_size < payload_size {
+                        read_u32(page, start + local_size)
+                    } else {
+                        0
+                    };
+                    while overflow_page != 0 {
+                        let overflow = self::page(database, overflow_page);
+                        let chunk_size = (payload_size - payload.len()).min(PAGE_SIZE - 4);
+                        payload.extend_from_slice(&overflow[4..4 + chunk_size]);
+                        overflow_page = read_u32(overflow, 0);
+                    }
+                    assert_eq!(payload.len(), payload_size);
+                    rows.push((rowid as i64, decode_record(&payload)));
+                }
+            }
+            page_type => panic!("page {} has unknown type {:#04X}", page_number, page_type),
+        }
+    }
+
+    /// Reads the name, root page, and rows of every table of a database, after checking the database header
+    fn read_database(database: &[u8]) -> Vec<(String, u32, Vec<Row>)> {
+        assert_eq!(&database[..16], b"SQLite format 3\0");
+        assert_eq!(read_u16(database, 16), PAGE_SIZE);
+        assert_eq!(database.len() % PAGE_SIZE, 0);
+        assert_eq!(read_u32(database, 28) as usize, database.len() / PAGE_SIZE);
+        let mut schema = vec![];
+        read_table(database, 1, &mut schema);
+        schema
+            .into_iter()
+            .map(|(_, row)| match &row[..] {
+                [Decoded::Text(kind), Decoded::Text(name), _, Decoded::Integer(root_page), _] => {
+                    assert_eq!(kind, "table");
+                    let mut rows = vec![];
+                    read_table(database, *root_page as u32, &mut rows);
+                    let rowids: Vec<i64> = rows.iter().map(|&(rowid, _)| rowid).collect();
+                    assert_eq!(rowids, (1..=rows.len() as i64).collect::<Vec<_>>());
+                    (name.clone(), *root_page as u32, rows)
+                }
+                _ => panic!("unexpected schema row {:?}", row),
+            })
+            .collect()
+    }
+
+    fn text(text: &str) -> Decoded {
+        Decoded::Text(text.to_string())
+    }
+
+    #[test]
+    fn writes_a_readable_database() {
+        let mut interior = Cell::new_interior(0x0100_0D63, Some("TestInterior".to_string()));
+        interior.flags |= CellFlags::HAS_WATER;
+        interior.water_height = Some(Float(-2048.5));
+        let exterior = Cell::new_exterior(0x0100_0D65, 0x0000_003C, -3, 200);
+        let plugin = PluginBuilder::new()
+            .author("Tester")
+            .master("Skyrim.esm")
+            .world(World {
+                name: Some(LString::Id(0x0000_1234)),
+                ..World::new(0x0000_003C, "Tamriel")
+            })
+            .cell(exterior)
+            .cell(interior)
+            .build();
+        let database = write_sqlite(&[("Test.esp", plugin)]);
+
+        let tables = read_database(&database);
+        let names: Vec<&str> = tables.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["plugins", "worlds", "cells"]);
+        assert_eq!(
+            tables[0].2[0].1,
+            [
+                Decoded::Null,
+                text("Test.esp"),
+                Decoded::Real(1.71),
+                text("Tester"),
+                Decoded::Null,
+                text("[\"Skyrim.esm\"]"),
+                Decoded::Integer(0),
+            ]
+        );
+        assert_eq!(
+            tables[1].2[0].1,
+            [
+                Decoded::Integer(1),
+                Decoded::Integer(0x3C),
+                text("Tamriel"),
+                Decoded::Integer(0x1234),
+            ]
+        );
+        let cells: Vec<&Vec<Decoded>> = tables[2].2.iter().map(|(_, row)| row).collect();
+        assert_eq!(
+            cells,
+            [
+                &vec![
+                    Decoded::Integer(1),
+                    Decoded::Integer(0x0100_0D63),
+                    text("TestInterior"),
+                    Decoded::Null,
+                    Decoded::Null,
+                    Decoded::Null,
+                    Decoded::Integer(0),
+                    Decoded::Integer(i64::from(
+                        (CellFlags::INTERIOR | CellFlags::HAS_WATER).bits()
+                    )),
+                    Decoded::Real(-2048.5),
+                ],
+                &vec![
+                    Decoded::Integer(1),
+                    Decoded::Integer(0x0100_0D65),
+                    Decoded::Null,
+                    Decoded::Integer(-3),
+                    Decoded::Integer(200),
+                    Decoded::Integer(0x3C),
+                    Decoded::Integer(0),
+                    Decoded::Integer(0),
+                    Decoded::Null,
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_large_tables_across_interior_and_overflow_pages() {
+        let description = "A long description. ".repeat(1000);
+        let names: Vec<String> = (0..3).map(|index| format!("Plugin{}.esp", index)).collect();
+        let plugins: Vec<(&str, Plugin)> = names
+            .iter()
+            .map(|name| {
+                let builder = PluginBuilder::new().description(description.clone());
+                let plugin = (0..20_000)
+                    .map(|cell| {
+                        Cell::new_interior(0x0100_0000 + cell, Some(format!("Cell{}", cell)))
+                    })
+                    .fold(builder, |builder, cell| builder.cell(cell))
+                    .build();
+                (name.as_str(), plugin)
+            })
+            .collect();
+        let database = write_sqlite(&plugins);
+
+        let tables = read_database(&database);
+        for (_, row) in &tables[0].2 {
+            assert_eq!(row[4], text(&description));
+        }
+        let (_, root_page, cells) = &tables[2];
+        assert_eq!(cells.len(), 60_000);
+        assert_eq!(cells[59_999].1[1], Decoded::Integer(0x0100_0000 + 19_999));
+        assert_eq!(cells[59_999].1[2], text("Cell19999"));
+        // there are enough leaf pages that the table needs two levels of interior pages
+        let root_page = page(&database, *root_page);
+        assert_eq!(root_page[0], INTERIOR_TABLE_PAGE_TYPE);
+        let first_child = page(&database, read_u32(root_page, read_u16(root_page, 12)));
+        assert_eq!(first_child[0], INTERIOR_TABLE_PAGE_TYPE);
+    }
+
+    #[test]
+    fn varints_round_trip() {
+        for &value in &[
+            0,
+            1,
+            0x7F,
+            0x80,
+            0x3FFF,
+            0x4000,
+            0x00FF_FFFF_FFFF_FFFF,
+            u64::MAX,
+        ] {
+            let mut varint = vec![];
+            write_varint(&mut varint, value);
+            assert_eq!(read_varint(&varint), (value, varint.len()));
+            assert_eq!(varint_len(value), varint.len());
+        }
+    }
+}
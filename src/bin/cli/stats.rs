@@ -0,0 +1,82 @@
+use std::collections::BTreeMap;
+
+use skyrim_cell_dump::{FormId, UnparsedPlugin};
+
+/// Counts of the cells in a plugin, for a quick look at what it edits
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub total: usize,
+    pub interiors: usize,
+    pub exteriors: usize,
+    /// Cells the plugin defines itself
+    pub new: usize,
+    /// Cells the plugin overrides from one of its masters
+    pub overridden: usize,
+    pub compressed: usize,
+    pub uncompressed: usize,
+    /// Exterior cells keyed by the editor id of their worldspace, or its form id if the plugin has no record for it
+    pub worldspaces: BTreeMap<String, usize>,
+}
+
+impl Stats {
+    /// Counts the cells of the plugin without decompressing or parsing them
+    pub fn count(plugin: &UnparsedPlugin) -> Self {
+        let mut stats = Stats::default();
+        for cell in &plugin.cells {
+            stats.total += 1;
+            match cell.world_form_id() {
+                Some(world_form_id) => {
+                    stats.exteriors += 1;
+                    let worldspace = plugin
+                        .worlds
+                        .iter()
+                        .find(|world| world.form_id == world_form_id)
+                        .map(|world| world.editor_id.clone())
+                        .unwrap_or_else(|| format!("{:08X}", world_form_id));
+                    *stats.worldspaces.entry(worldspace).or_default() += 1;
+                }
+                None => stats.interiors += 1,
+            }
+            if FormId(cell.form_id()).is_new_record(&plugin.header) {
+                stats.new += 1;
+            } else {
+                stats.overridden += 1;
+            }
+            if cell.is_compressed() {
+                stats.compressed += 1;
+            } else {
+                stats.uncompressed += 1;
+            }
+        }
+        stats
+    }
+
+    /// Formats the counts as an indented list
+    pub fn render(&self) -> String {
+        let mut output = format!("Cells: {}\n", self.total);
+        for (label, count) in [
+            ("Interior", self.interiors),
+            ("Exterior", self.exteriors),
+            ("New", self.new),
+            ("Overridden", self.overridden),
+            ("Compressed", self.compressed),
+            ("Uncompressed", self.uncompressed),
+        ] {
+            output.push_str(&format!("  {}: {}\n", label, count));
+        }
+        output.push_str(&format!("Worldspaces: {}\n", self.worldspaces.len()));
+        for (worldspace, count) in &self.worldspaces {
+            output.push_str(&format!("  {}: {}\n", worldspace, count));
+        }
+        output
+    }
+}
+
+/// Formats the counts of the records of each type as an indented list, like [`Stats::render`]
+pub fn render_record_counts(counts: &BTreeMap<String, usize>) -> String {
+    let mut output = format!("Records: {}\n", counts.values().sum::<usize>());
+    for (record_type, count) in counts {
+        output.push_str(&format!("  {}: {}\n", record_type, count));
+    }
+    output
+}
@@ -0,0 +1,52 @@
+use std::collections::BTreeSet;
+
+use skyrim_cell_dump::Plugin;
+
+/// Writes one line for each of the plugins with its name, author, number of masters and cells, and the worldspaces
+/// that its cells are in, e.g. `Plugin.esp (by Someone, 1 master): 4 cells, worldspaces Tamriel`
+pub fn render_summary(plugins: &[(&str, Plugin)]) -> String {
+    plugins
+        .iter()
+        .map(|(name, plugin)| format!("{}\n", summarize(name, plugin)))
+        .collect()
+}
+
+fn summarize(name: &str, plugin: &Plugin) -> String {
+    let masters = plural(plugin.header.masters.len(), "master");
+    let about = match &plugin.header.author {
+        Some(author) if !author.is_empty() => format!("by {}, {}", author, masters),
+        _ => masters,
+    };
+    let worldspaces: BTreeSet<String> = plugin
+        .cells
+        .iter()
+        .filter_map(|cell| cell.world_form_id)
+        .map(|world_form_id| {
+            plugin
+                .world(world_form_id)
+                .map(|world| world.editor_id.clone())
+                .unwrap_or_else(|| format!("{:08X}", world_form_id))
+        })
+        .collect();
+    let cells = plural(plugin.cells.len(), "cell");
+    if worldspaces.is_empty() {
+        format!("{} ({}): {}", name, about, cells)
+    } else {
+        let worldspaces: Vec<String> = worldspaces.into_iter().collect();
+        format!(
+            "{} ({}): {}, worldspaces {}",
+            name,
+            about,
+            cells,
+            worldspaces.join(", ")
+        )
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("{} {}", count, noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
@@ -0,0 +1,114 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::format::to_json_value;
+
+/// Encodes a value that serializes to a JSON object as a TOML document.
+///
+/// TOML has no null, so keys with null values are left out. Objects become tables and arrays of objects become arrays
+/// of tables, unless they are empty.
+pub fn to_toml<T: Serialize>(value: &T) -> serde_json::Result<String> {
+    let value = to_json_value(value)?;
+    let mut output = String::new();
+    if let Value::Object(table) = &value {
+        write_table(&mut output, "", table);
+    }
+    Ok(output)
+}
+
+/// Writes the keys of a table that have plain values, followed by its subtables and arrays of tables
+fn write_table(output: &mut String, path: &str, table: &Map<String, Value>) {
+    for (key, value) in table {
+        if !is_table(value) && !is_array_of_tables(value) && !value.is_null() {
+            output.push_str(&format!("{} = {}\n", encode_key(key), encode_value(value)));
+        }
+    }
+    for (key, value) in table {
+        let path = if path.is_empty() {
+            encode_key(key)
+        } else {
+            format!("{}.{}", path, encode_key(key))
+        };
+        match value {
+            Value::Object(subtable) => {
+                output.push_str(&format!("\n[{}]\n", path));
+                write_table(output, &path, subtable);
+            }
+            Value::Array(elements) if is_array_of_tables(value) => {
+                for element in elements {
+                    if let Value::Object(element) = element {
+                        output.push_str(&format!("\n[[{}]]\n", path));
+                        write_table(output, &path, element);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn is_table(value: &Value) -> bool {
+    value.is_object()
+}
+
+fn is_array_of_tables(value: &Value) -> bool {
+    match value {
+        Value::Array(elements) => !elements.is_empty() && elements.iter().all(Value::is_object),
+        _ => false,
+    }
+}
+
+/// Encodes a value that isn't a table as an inline TOML value
+fn encode_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => encode_string(string),
+        Value::Array(elements) => {
+            let elements: Vec<String> = elements
+                .iter()
+                .filter(|element| !element.is_null())
+                .map(encode_value)
+                .collect();
+            format!("[{}]", elements.join(", "))
+        }
+        Value::Object(table) => {
+            let pairs: Vec<String> = table
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| format!("{} = {}", encode_key(key), encode_value(value)))
+                .collect();
+            format!("{{ {} }}", pairs.join(", "))
+        }
+        _ => value.to_string(),
+    }
+}
+
+/// Leaves keys bare if they only have the characters allowed in bare keys, otherwise quotes them
+fn encode_key(key: &str) -> String {
+    if !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        key.to_string()
+    } else {
+        encode_string(key)
+    }
+}
+
+fn encode_string(string: &str) -> String {
+    let mut encoded = String::with_capacity(string.len() + 2);
+    encoded.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => encoded.push_str("\\\""),
+            '\\' => encoded.push_str("\\\\"),
+            '\n' => encoded.push_str("\\n"),
+            '\r' => encoded.push_str("\\r"),
+            '\t' => encoded.push_str("\\t"),
+            c if c.is_control() => encoded.push_str(&format!("\\u{:04X}", c as u32)),
+            c => encoded.push(c),
+        }
+    }
+    encoded.push('"');
+    encoded
+}
@@ -0,0 +1,31 @@
+use skyrim_cell_dump::GroupTree;
+
+/// Indentation of each level of nested groups
+const INDENT: &str = "  ";
+
+/// Draws the groups of a plugin indented under the groups that contain them, with the number of records of each type
+/// directly inside of each group, e.g. `GRUP type 5 (sub-block 4, 0): 1 CELL`
+pub fn render_tree(trees: &[GroupTree]) -> String {
+    let mut output = String::new();
+    for tree in trees {
+        render_group(&mut output, tree, 0);
+    }
+    output
+}
+
+fn render_group(output: &mut String, tree: &GroupTree, depth: usize) {
+    output.push_str(&INDENT.repeat(depth));
+    output.push_str(&tree.group.to_string());
+    if !tree.record_counts.is_empty() {
+        let counts: Vec<String> = tree
+            .record_counts
+            .iter()
+            .map(|(record_type, count)| format!("{} {}", count, record_type))
+            .collect();
+        output.push_str(&format!(": {}", counts.join(", ")));
+    }
+    output.push('\n');
+    for group in &tree.groups {
+        render_group(output, group, depth + 1);
+    }
+}
@@ -0,0 +1,54 @@
+use std::fs::metadata;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, SystemTime};
+
+use crate::inputs::find_inputs;
+
+/// How often the plugin files are checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The modification time and size of each plugin file, or `None` for files that can't be read
+type Snapshot = Vec<(PathBuf, Option<(SystemTime, u64)>)>;
+
+/// Calls `on_change` whenever the plugin files found from the `paths` change on disk, including when plugins are added
+/// to or removed from directories. Never returns, so the process has to be stopped to stop watching.
+///
+/// Files are polled for changes, and `on_change` is only called once they have stopped changing, since editors like
+/// the Creation Kit save a plugin in several writes.
+pub fn watch_inputs<F: FnMut()>(paths: &[PathBuf], recursive: bool, mut on_change: F) -> ! {
+    let mut snapshot = take_snapshot(paths, recursive);
+    loop {
+        sleep(POLL_INTERVAL);
+        let mut new_snapshot = take_snapshot(paths, recursive);
+        if new_snapshot == snapshot {
+            continue;
+        }
+        loop {
+            sleep(POLL_INTERVAL);
+            let settled_snapshot = take_snapshot(paths, recursive);
+            if settled_snapshot == new_snapshot {
+                break;
+            }
+            new_snapshot = settled_snapshot;
+        }
+        snapshot = new_snapshot;
+        on_change();
+    }
+}
+
+fn take_snapshot(paths: &[PathBuf], recursive: bool) -> Snapshot {
+    let inputs = match find_inputs(paths, recursive) {
+        Ok((inputs, _)) => inputs,
+        Err(_) => vec![],
+    };
+    inputs
+        .into_iter()
+        .map(|input| {
+            let modified = metadata(&input.path)
+                .and_then(|metadata| Ok((metadata.modified()?, metadata.len())))
+                .ok();
+            (input.path, modified)
+        })
+        .collect()
+}
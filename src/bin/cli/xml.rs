@@ -0,0 +1,97 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::format::to_json_value;
+
+/// Encodes a value that serializes to a JSON object as an XML document with a `root` element.
+///
+/// Every key of an object becomes an element of the same name and every element of an array becomes an `item`
+/// element, so the elements are the same for every plugin. Keys with null values are left out.
+pub fn to_xml<T: Serialize>(value: &T, root: &str) -> serde_json::Result<String> {
+    let value = to_json_value(value)?;
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    write_element(&mut output, 0, root, "", &value);
+    Ok(output)
+}
+
+/// Encodes the plugins of a batch as an XML document with a `plugin` element for each of them, named by its `name`
+/// attribute
+pub fn plugins_to_xml<T: Serialize>(plugins: &[(&str, T)]) -> serde_json::Result<String> {
+    let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<plugins>\n");
+    for (name, plugin) in plugins {
+        let attributes = format!(" name=\"{}\"", escape(name));
+        write_element(
+            &mut output,
+            1,
+            "plugin",
+            &attributes,
+            &to_json_value(plugin)?,
+        );
+    }
+    output.push_str("</plugins>\n");
+    Ok(output)
+}
+
+/// Writes an element holding `value` at the indentation level `depth`
+fn write_element(output: &mut String, depth: usize, name: &str, attributes: &str, value: &Value) {
+    let indent = "  ".repeat(depth);
+    let children: Vec<(&str, &Value)> = match value {
+        Value::Null => return,
+        Value::Object(object) => object
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| (key.as_str(), value))
+            .collect(),
+        Value::Array(elements) => elements
+            .iter()
+            .filter(|element| !element.is_null())
+            .map(|element| ("item", element))
+            .collect(),
+        Value::String(string) => {
+            output.push_str(&format!(
+                "{}<{}{}>{}</{}>\n",
+                indent,
+                name,
+                attributes,
+                escape(string),
+                name
+            ));
+            return;
+        }
+        _ => {
+            output.push_str(&format!(
+                "{}<{}{}>{}</{}>\n",
+                indent, name, attributes, value, name
+            ));
+            return;
+        }
+    };
+    if children.is_empty() {
+        output.push_str(&format!("{}<{}{}/>\n", indent, name, attributes));
+        return;
+    }
+    output.push_str(&format!("{}<{}{}>\n", indent, name, attributes));
+    for (child_name, child) in children {
+        write_element(output, depth + 1, child_name, "", child);
+    }
+    output.push_str(&format!("{}</{}>\n", indent, name));
+}
+
+/// Escapes the characters that can't appear as they are in text and attribute values, and replaces the control
+/// characters that XML doesn't allow at all
+fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            c if c.is_control() => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,418 @@
+//! Reading plugins out of [Bethesda archives (BSA)](https://en.uesp.net/wiki/Skyrim_Mod:Archive_File_Format).
+//!
+//! Versions 103 (Oblivion), 104 (Fallout 3, Fallout: New Vegas, and Skyrim), and 105 (Skyrim Special Edition) are
+//! supported. Files are compressed with zlib in versions 103 and 104 and with LZ4 frames in version 105.
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::io;
+
+use nom::{
+    bytes::complete::{tag, take, take_while},
+    combinator::verify,
+    number::complete::{le_u32, le_u64, le_u8},
+};
+
+use crate::archive::{archive_path, Archive};
+use crate::compression::{decompress, decompress_lz4_frame, Compression};
+use crate::error::{expecting, in_record, IResult, ParseError, Result};
+
+const INCLUDE_DIRECTORY_NAMES: u32 = 0x1;
+const INCLUDE_FILE_NAMES: u32 = 0x2;
+const COMPRESSED_BY_DEFAULT: u32 = 0x4;
+/// Prefixes the data of each file with its full path. Only used from version 104 on.
+const EMBED_FILE_NAMES: u32 = 0x100;
+/// Set in the size of a file whose compression is the opposite of the archive's default
+const COMPRESSION_TOGGLE: u32 = 0x4000_0000;
+const SIZE_MASK: u32 = 0x3FFF_FFFF;
+
+/// An opened BSA archive, which borrows the contents of the archive file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{Archive, Bsa, ParseOptions};
+///
+/// let contents = std::fs::read("Plugin.bsa").unwrap();
+/// let bsa = Bsa::parse(&contents).unwrap();
+/// for (path, plugin) in bsa.parse_plugins(&ParseOptions::default()) {
+///     match plugin {
+///         Ok(plugin) => println!("{}: {} cells", path, plugin.cells.len()),
+///         Err(error) => println!("{}: {}", path, error),
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Bsa<'a> {
+    input: &'a [u8],
+    version: u32,
+    archive_flags: u32,
+    files: Vec<BsaFile>,
+}
+
+#[derive(Debug)]
+struct BsaFile {
+    /// Path of the file in the archive, with the folder and file name separated by a backslash
+    path: String,
+    offset: usize,
+    size: usize,
+    is_compressed: bool,
+}
+
+struct BsaHeader {
+    version: u32,
+    folder_offset: u32,
+    archive_flags: u32,
+    folder_count: u32,
+}
+
+impl<'a> Bsa<'a> {
+    /// Parses the header and file records of the archive in `input`. File data is only read once it is extracted.
+    pub fn parse(input: &'a [u8]) -> Result<Bsa<'a>> {
+        let (_, header) = parse_header(input).map_err(|err| ParseError::from_nom(err, input))?;
+        if !(103..=105).contains(&header.version) {
+            return Err(ParseError::InvalidValue {
+                kind: "BSA version",
+                value: header.version.to_string(),
+            });
+        }
+        if header.archive_flags & INCLUDE_FILE_NAMES == 0 {
+            return Err(ParseError::InvalidValue {
+                kind: "BSA without file names, archive flags",
+                value: format!("{:#X}", header.archive_flags),
+            });
+        }
+        let (_, files) =
+            parse_files(input, &header).map_err(|err| ParseError::from_nom(err, input))?;
+        Ok(Bsa {
+            input,
+            version: header.version,
+            archive_flags: header.archive_flags,
+            files,
+        })
+    }
+
+    /// Version of the archive format: 103, 104, or 105
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    fn extract_file(&self, file: &BsaFile) -> Result<Cow<'a, [u8]>> {
+        let mut data = self.input.get(file.offset..file.offset + file.size).ok_or(
+            ParseError::TruncatedRecord {
+                record_type: Some("BSA".to_string()),
+                offset: self.input.len(),
+            },
+        )?;
+        if self.version >= 104 && self.archive_flags & EMBED_FILE_NAMES != 0 {
+            let name_length = data.first().map_or(0, |length| *length as usize + 1);
+            data = data.get(name_length..).unwrap_or_default();
+        }
+        if !file.is_compressed {
+            return Ok(Cow::Borrowed(data));
+        }
+        let decompressed = if self.version == 105 {
+            data.get(..4)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Compressed file is missing the decompressed size",
+                    )
+                })
+                .and_then(|size| {
+                    let size = u32::from_le_bytes(size.try_into().unwrap()) as usize;
+                    decompress_lz4_frame(&data[4..], size)
+                })
+        } else {
+            decompress(data, Compression::Zlib)
+        };
+        decompressed
+            .map(Cow::Owned)
+            .map_err(|source| ParseError::ExtractError {
+                path: file.path.clone(),
+                offset: file.offset,
+                source,
+            })
+    }
+}
+
+impl<'a> Archive<'a> for Bsa<'a> {
+    fn paths(&self) -> Vec<&str> {
+        self.files.iter().map(|file| file.path.as_str()).collect()
+    }
+
+    fn extract(&self, path: &str) -> Result<Cow<'a, [u8]>> {
+        let path = archive_path(path);
+        let file = self
+            .files
+            .iter()
+            .find(|file| file.path.eq_ignore_ascii_case(&path))
+            .ok_or(ParseError::InvalidValue {
+                kind: "path in archive",
+                value: path,
+            })?;
+        self.extract_file(file)
+    }
+}
+
+fn parse_header(input: &[u8]) -> IResult<&[u8], BsaHeader> {
+    let (input, _) =
+        verify(take(4usize), |magic: &[u8]| magic == b"BSA\0")(input).map_err(expecting("BSA"))?;
+    let (input, version) = le_u32(input).map_err(in_record("BSA"))?;
+    let (input, folder_offset) = le_u32(input).map_err(in_record("BSA"))?;
+    let (input, archive_flags) = le_u32(input).map_err(in_record("BSA"))?;
+    let (input, folder_count) = le_u32(input).map_err(in_record("BSA"))?;
+    // file count, total folder name length, total file name length, and file flags
+    let (input, _) = take(16usize)(input).map_err(in_record("BSA"))?;
+    Ok((
+        input,
+        BsaHeader {
+            version,
+            folder_offset,
+            archive_flags,
+            folder_count,
+        },
+    ))
+}
+
+/// Parses the folder records, file records, and file names that start at the folder offset of the archive
+fn parse_files<'a>(input: &'a [u8], header: &BsaHeader) -> IResult<&'a [u8], Vec<BsaFile>> {
+    let (mut input, _) = take(header.folder_offset)(input).map_err(in_record("BSA"))?;
+    // the records are read one at a time since the counts come from the file and may be garbage
+    let mut file_counts = vec![];
+    for _ in 0..header.folder_count {
+        let (remaining, _hash) = le_u64(input).map_err(in_record("BSA"))?;
+        let (remaining, file_count) = le_u32(remaining).map_err(in_record("BSA"))?;
+        // the offset of the folder's file records, which is 8 bytes and padded in version 105
+        let offset_size = if header.version == 105 { 12usize } else { 4 };
+        let (remaining, _) = take(offset_size)(remaining).map_err(in_record("BSA"))?;
+        file_counts.push(file_count);
+        input = remaining;
+    }
+
+    let default_compressed = header.archive_flags & COMPRESSED_BY_DEFAULT != 0;
+    let mut files = vec![];
+    for file_count in file_counts {
+        let folder = if header.archive_flags & INCLUDE_DIRECTORY_NAMES != 0 {
+            let (remaining, length) = le_u8(input).map_err(in_record("BSA"))?;
+            let (remaining, name) = take(length)(remaining).map_err(in_record("BSA"))?;
+            input = remaining;
+            let name = name.strip_suffix(b"\0").unwrap_or(name);
+            Some(String::from_utf8_lossy(name).into_owned())
+        } else {
+            None
+        };
+        for _ in 0..file_count {
+            let (remaining, _hash) = le_u64(input).map_err(in_record("BSA"))?;
+            let (remaining, size) = le_u32(remaining).map_err(in_record("BSA"))?;
+            let (remaining, offset) = le_u32(remaining).map_err(in_record("BSA"))?;
+            files.push(BsaFile {
+                // the folder is joined once the file names are read
+                path: folder.clone().unwrap_or_default(),
+                offset: offset as usize,
+                size: (size & SIZE_MASK) as usize,
+                is_compressed: default_compressed != (size & COMPRESSION_TOGGLE != 0),
+            });
+            input = remaining;
+        }
+    }
+
+    for file in files.iter_mut() {
+        let (remaining, name) = take_while(|byte| byte != 0)(input)?;
+        let (remaining, _) = tag(b"\0")(remaining).map_err(in_record("BSA"))?;
+        let name = String::from_utf8_lossy(name);
+        file.path = if file.path.is_empty() {
+            name.into_owned()
+        } else {
+            format!("{}\\{}", file.path, name)
+        };
+        input = remaining;
+    }
+    Ok((input, files))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::parser::{Cell, ParseOptions};
+
+    const NAMES: u32 = INCLUDE_DIRECTORY_NAMES | INCLUDE_FILE_NAMES;
+
+    fn plugin() -> Vec<u8> {
+        PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(Cell::new_interior(0x0100_0D63, Some("TestInterior".into())))
+            .write()
+            .unwrap()
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        let compressed = encoder.finish().unwrap();
+        [&(data.len() as u32).to_le_bytes()[..], &compressed].concat()
+    }
+
+    /// An LZ4 frame with a single uncompressed block, preceded by the decompressed size like in version 105 archives
+    fn lz4_frame(data: &[u8]) -> Vec<u8> {
+        [
+            &(data.len() as u32).to_le_bytes()[..],
+            &[0x04, 0x22, 0x4D, 0x18, 0x60, 0x40, 0x82],
+            &(data.len() as u32 | 0x8000_0000).to_le_bytes(),
+            data,
+            &[0; 4],
+        ]
+        .concat()
+    }
+
+    /// Builds an archive with a single folder holding `files`, which are (name, stored data, compression toggled)
+    fn bsa(
+        version: u32,
+        archive_flags: u32,
+        folder: &str,
+        files: &[(&str, &[u8], bool)],
+    ) -> Vec<u8> {
+        let folder_record_size = if version == 105 { 24 } else { 16 };
+        let folder_name = [folder.as_bytes(), b"\0"].concat();
+        let file_names: Vec<u8> = files
+            .iter()
+            .flat_map(|(name, _, _)| [name.as_bytes(), b"\0"].concat())
+            .collect();
+        let mut data_offset =
+            36 + folder_record_size + 1 + folder_name.len() + 16 * files.len() + file_names.len();
+
+        let mut bytes = b"BSA\0".to_vec();
+        for value in &[
+            version,
+            36,
+            archive_flags,
+            1,
+            files.len() as u32,
+            folder_name.len() as u32,
+            file_names.len() as u32,
+            0,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&(files.len() as u32).to_le_bytes());
+        bytes.resize(36 + folder_record_size, 0);
+        bytes.push(folder_name.len() as u8);
+        bytes.extend_from_slice(&folder_name);
+
+        let mut data = vec![];
+        for (name, contents, is_toggled) in files {
+            let mut stored = vec![];
+            if archive_flags & EMBED_FILE_NAMES != 0 {
+                let path = format!("{}\\{}", folder, name);
+                stored.push(path.len() as u8);
+                stored.extend_from_slice(path.as_bytes());
+            }
+            stored.extend_from_slice(contents);
+            let toggle = if *is_toggled { COMPRESSION_TOGGLE } else { 0 };
+            bytes.extend_from_slice(&[0; 8]);
+            bytes.extend_from_slice(&(stored.len() as u32 | toggle).to_le_bytes());
+            bytes.extend_from_slice(&(data_offset as u32).to_le_bytes());
+            data_offset += stored.len();
+            data.extend_from_slice(&stored);
+        }
+        bytes.extend_from_slice(&file_names);
+        bytes.extend_from_slice(&data);
+        bytes
+    }
+
+    fn uncompressed_bsa(data: &[u8]) -> Vec<u8> {
+        bsa(104, NAMES, "data", &[("Test.esp", data, false)])
+    }
+
+    #[test]
+    fn extracts_zlib_compressed_and_uncompressed_files() {
+        let plugin = plugin();
+        let readme = b"Read me";
+        let bytes = bsa(
+            104,
+            NAMES | COMPRESSED_BY_DEFAULT,
+            "data",
+            &[
+                ("Test.esp", &zlib(&plugin), false),
+                ("ReadMe.txt", readme, true),
+            ],
+        );
+        let bsa = Bsa::parse(&bytes).unwrap();
+        assert_eq!(bsa.version(), 104);
+        assert_eq!(bsa.paths(), vec!["data\\Test.esp", "data\\ReadMe.txt"]);
+        assert_eq!(bsa.plugin_paths(), vec!["data\\Test.esp"]);
+        assert_eq!(bsa.extract("DATA/test.esp").unwrap(), plugin);
+        assert!(
+            matches!(bsa.extract("data\\ReadMe.txt").unwrap(), Cow::Borrowed(data) if data == readme)
+        );
+        assert!(bsa.extract("data\\Missing.esp").is_err());
+
+        let plugins = bsa.parse_plugins(&ParseOptions::default());
+        assert_eq!(plugins.len(), 1);
+        let cells = &plugins["data\\Test.esp"].as_ref().unwrap().cells;
+        assert_eq!(cells.len(), 1);
+        let cell = cells.iter().next().unwrap();
+        assert_eq!(cell.editor_id.as_deref(), Some("TestInterior"));
+    }
+
+    #[test]
+    fn extracts_lz4_compressed_files_with_embedded_names() {
+        let plugin = plugin();
+        let bytes = bsa(
+            105,
+            NAMES | EMBED_FILE_NAMES,
+            "data",
+            &[
+                ("Test.esp", &lz4_frame(&plugin), true),
+                ("Test.esm", &plugin, false),
+            ],
+        );
+        let bsa = Bsa::parse(&bytes).unwrap();
+        assert_eq!(bsa.version(), 105);
+        assert_eq!(bsa.extract("data\\Test.esp").unwrap(), plugin);
+        assert_eq!(bsa.extract("data\\Test.esm").unwrap(), plugin);
+        let plugins = bsa.parse_plugins(&ParseOptions::default());
+        assert!(plugins.values().all(|plugin| plugin.is_ok()));
+    }
+
+    #[test]
+    fn reports_files_that_fail_to_extract() {
+        let bytes = bsa(
+            104,
+            NAMES | COMPRESSED_BY_DEFAULT,
+            "data",
+            &[("Broken.esp", b"\x10\0\0\0not zlib", false)],
+        );
+        let bsa = Bsa::parse(&bytes).unwrap();
+        assert!(matches!(
+            bsa.extract("data\\Broken.esp"),
+            Err(ParseError::ExtractError { .. })
+        ));
+        let plugins = bsa.parse_plugins(&ParseOptions::default());
+        assert!(plugins["data\\Broken.esp"].is_err());
+
+        let mut truncated = uncompressed_bsa(b"Test");
+        truncated.truncate(truncated.len() - 1);
+        let bsa = Bsa::parse(&truncated).unwrap();
+        assert!(matches!(
+            bsa.extract("data\\Test.esp"),
+            Err(ParseError::TruncatedRecord { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_unsupported_and_truncated_archives() {
+        let bytes = uncompressed_bsa(b"Test");
+        assert!(Bsa::parse(&bsa(102, NAMES, "data", &[])).is_err());
+        assert!(Bsa::parse(&bsa(104, INCLUDE_DIRECTORY_NAMES, "data", &[])).is_err());
+        assert!(Bsa::parse(b"BTDX\x01\0\0\0GNRL").is_err());
+        for length in [0, 20, 40, 60, 70] {
+            assert!(Bsa::parse(&bytes[..length]).is_err());
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use std::borrow::Cow;
+
+use crate::error::Result;
+use crate::form_id::FormId;
+use crate::game::Game;
+use crate::parser::{Cell, Plugin, PluginFlags, PluginHeader, World};
+use crate::writer::encode_plugin;
+
+/// Lowest local id that the Creation Kit assigns to new records in a plugin
+const FIRST_OBJECT_ID: u32 = 0x800;
+
+/// Builds a new plugin from a header and worlds and cells that are added programmatically.
+///
+/// Cells and worlds that override records from a master should use the form id of the record in that master, with the
+/// first byte being the index of the master. New records should use the index after the last master. Overrides should
+/// be copied from the master after parsing it with [`ParseOptions::include_raw_data`](crate::ParseOptions), so that
+/// the fields that aren't parsed are written too (see [`write_plugin`](crate::write_plugin)).
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin_with_options, CellFlags, ParseOptions, PluginBuilder};
+///
+/// let skyrim_contents = std::fs::read("Skyrim.esm").unwrap();
+/// let options = ParseOptions {
+///     include_raw_data: true,
+///     ..Default::default()
+/// };
+/// let skyrim = parse_plugin_with_options(&skyrim_contents, &options).unwrap();
+/// let tamriel = skyrim.worlds.iter().find(|world| world.form_id == 0x0000_003C).unwrap();
+/// let mut cell = skyrim.cells.iter().find(|cell| cell.form_id == 0x0000_9732).unwrap().clone();
+/// cell.flags |= CellFlags::HAS_WATER;
+/// let plugin_bytes = PluginBuilder::new()
+///     .author("Me")
+///     .description("Adds water to a cell")
+///     .master("Skyrim.esm")
+///     .world(tamriel.clone())
+///     .cell(cell)
+///     .write()
+///     .unwrap();
+/// std::fs::write("Patch.esp", plugin_bytes).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct PluginBuilder {
+    game: Game,
+    version: f32,
+    author: Option<String>,
+    description: Option<String>,
+    masters: Vec<String>,
+    flags: PluginFlags,
+    worlds: Vec<World>,
+    cells: Vec<Cell<'static>>,
+}
+
+impl Default for PluginBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PluginBuilder {
+    /// Starts building an empty Skyrim plugin
+    pub fn new() -> Self {
+        let game = Game::default();
+        PluginBuilder {
+            game,
+            version: latest_header_version(game),
+            author: None,
+            description: None,
+            masters: vec![],
+            flags: PluginFlags::empty(),
+            worlds: vec![],
+            cells: vec![],
+        }
+    }
+
+    /// Sets the game the plugin is made for, which also sets the header version to the latest one of the game
+    pub fn game(mut self, game: Game) -> Self {
+        self.game = game;
+        self.version = latest_header_version(game);
+        self
+    }
+
+    /// Sets the version in the HEDR field of the header
+    pub fn version(mut self, version: f32) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Adds a master file that the plugin depends on. The index of the master in the form ids of records is the number
+    /// of masters that were added before it.
+    pub fn master(mut self, master: impl Into<String>) -> Self {
+        self.masters.push(master.into());
+        self
+    }
+
+    /// Sets the flags in the header, e.g. to make the plugin a master or light plugin
+    pub fn flags(mut self, flags: PluginFlags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn world(mut self, world: World) -> Self {
+        self.worlds.push(world);
+        self
+    }
+
+    /// Adds a cell. Exterior cells also need the [`World`] they belong to to be added with [`PluginBuilder::world`].
+    pub fn cell(mut self, cell: Cell) -> Self {
+        self.cells.push(cell.into_owned());
+        self
+    }
+
+    /// Builds the [`Plugin`].
+    ///
+    /// The next object id in the header is set to the local id after the highest one used by the new records of the
+    /// plugin. The record and group count is left at `0` and is only filled in when the plugin is written.
+    pub fn build(self) -> Plugin<'static> {
+        let next_object_id = self
+            .worlds
+            .iter()
+            .map(|world| world.form_id)
+            .chain(self.cells.iter().map(|cell| cell.form_id))
+            .chain(
+                self.cells
+                    .iter()
+                    .flat_map(|cell| cell.actors.iter().map(|actor| actor.form_id)),
+            )
+            .map(FormId)
+            .filter(|form_id| form_id.master_index() as usize == self.masters.len())
+            .map(|form_id| form_id.local_id() + 1)
+            .fold(FIRST_OBJECT_ID, u32::max);
+        Plugin {
+            header: PluginHeader {
+                version: self.version,
+                num_records_and_groups: 0,
+                next_object_id,
+                author: self.author.map(Cow::Owned),
+                description: self.description.map(Cow::Owned),
+                masters: self.masters.into_iter().map(Cow::Owned).collect(),
+                overridden_forms: vec![],
+                flags: self.flags,
+            },
+            worlds: self.worlds.into_iter().collect(),
+            cells: self.cells.into_iter().collect(),
+            lands: vec![],
+            navmeshes: vec![],
+            locations: vec![],
+            regions: vec![],
+            issues: vec![],
+            warnings: vec![],
+        }
+    }
+
+    /// Builds the plugin and encodes it into the bytes of a plugin file for the game. Fails if an exterior cell was
+    /// added without its world, like [`write_plugin`](crate::write_plugin).
+    pub fn write(self) -> Result<Vec<u8>> {
+        let game = self.game;
+        encode_plugin(&self.build(), game)
+    }
+}
+
+fn latest_header_version(game: Game) -> f32 {
+    game.header_versions().last().copied().unwrap_or(1.0)
+}
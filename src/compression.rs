@@ -0,0 +1,363 @@
+use std::convert::TryInto;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use flate2::read::ZlibDecoder;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ParseError;
+
+/// The algorithm used to compress the data sections of records flagged as compressed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Compression {
+    /// zlib, used by every game up to and including Fallout 4
+    #[default]
+    Zlib,
+    /// LZ4 block compression, used by newer formats
+    Lz4,
+}
+
+impl FromStr for Compression {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s.trim().to_lowercase().as_str() {
+            "zlib" => Ok(Compression::Zlib),
+            "lz4" => Ok(Compression::Lz4),
+            _ => Err(ParseError::InvalidValue {
+                kind: "compression",
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+/// Most bytes of decompressed data that are allocated up front for each byte of compressed data, so that a corrupt
+/// decompressed size can't allocate gigabytes before decompression fails
+const MAX_PREALLOCATED_RATIO: usize = 16;
+
+/// Decompresses the data section of a compressed record, which starts with the `u32` size of the decompressed data
+pub(crate) fn decompress(data: &[u8], compression: Compression) -> io::Result<Vec<u8>> {
+    let decompressed_size = data
+        .get(..4)
+        .map(|size| u32::from_le_bytes(size.try_into().unwrap()) as usize)
+        .ok_or_else(|| invalid_data("Compressed record data is missing the decompressed size"))?;
+    decompress_sized(&data[4..], decompressed_size, compression)
+}
+
+/// Decompresses data whose decompressed size is stored somewhere else, like the files of a BA2 archive
+pub(crate) fn decompress_sized(
+    data: &[u8],
+    decompressed_size: usize,
+    compression: Compression,
+) -> io::Result<Vec<u8>> {
+    match compression {
+        Compression::Zlib => {
+            let mut buf = Vec::with_capacity(preallocated_size(data, decompressed_size));
+            let mut decoder = ZlibDecoder::new(data);
+            decoder.read_to_end(&mut buf)?;
+            Ok(buf)
+        }
+        Compression::Lz4 => decompress_lz4_block(data, decompressed_size),
+    }
+}
+
+/// Decompresses a single raw [LZ4 block](https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md)
+fn decompress_lz4_block(input: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(preallocated_size(input, decompressed_size));
+    decode_lz4_block(input, &mut output, decompressed_size)?;
+    if output.len() != decompressed_size {
+        return Err(invalid_data(format!(
+            "LZ4 block decompressed to {} bytes, expected {}",
+            output.len(),
+            decompressed_size
+        )));
+    }
+    Ok(output)
+}
+
+/// Decompresses an LZ4 block onto the end of `output`. Matches may copy from anything already in `output`, which is
+/// how blocks of a frame that aren't independent refer to the blocks before them.
+///
+/// Fails as soon as `output` would grow past `max_size` bytes.
+fn decode_lz4_block(input: &[u8], output: &mut Vec<u8>, max_size: usize) -> io::Result<()> {
+    let truncated = || invalid_data("Truncated LZ4 block");
+    let too_large = || {
+        invalid_data(format!(
+            "LZ4 data decompresses to more than the expected {} bytes",
+            max_size
+        ))
+    };
+    let mut position = 0;
+    loop {
+        let token = *input.get(position).ok_or_else(truncated)?;
+        position += 1;
+
+        let mut literal_length = (token >> 4) as usize;
+        if literal_length == 15 {
+            literal_length += read_length_extension(input, &mut position).ok_or_else(truncated)?;
+        }
+        let literals = input
+            .get(position..position + literal_length)
+            .ok_or_else(truncated)?;
+        if output.len() + literal_length > max_size {
+            return Err(too_large());
+        }
+        output.extend_from_slice(literals);
+        position += literal_length;
+
+        // The last sequence of a block only contains literals
+        if position == input.len() {
+            break;
+        }
+
+        let offset = input
+            .get(position..position + 2)
+            .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+            .ok_or_else(truncated)?;
+        position += 2;
+        if offset == 0 || offset > output.len() {
+            return Err(invalid_data(format!("Invalid LZ4 match offset {}", offset)));
+        }
+
+        let mut match_length = (token & 0x0F) as usize;
+        if match_length == 15 {
+            match_length += read_length_extension(input, &mut position).ok_or_else(truncated)?;
+        }
+        match_length += 4;
+        if output.len() + match_length > max_size {
+            return Err(too_large());
+        }
+
+        // Matches may overlap the bytes they are copying, so copy one byte at a time
+        let start = output.len() - offset;
+        for i in 0..match_length {
+            let byte = output[start + i];
+            output.push(byte);
+        }
+    }
+    Ok(())
+}
+
+/// Decompresses a whole [LZ4 frame](https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md), which is how
+/// Skyrim Special Edition compresses the files in its archives. Checksums are skipped rather than verified.
+#[cfg(feature = "bsa")]
+pub(crate) fn decompress_lz4_frame(input: &[u8], decompressed_size: usize) -> io::Result<Vec<u8>> {
+    const MAGIC: u32 = 0x184D_2204;
+    let truncated = || invalid_data("Truncated LZ4 frame");
+    let read_u32 = |position: usize| {
+        input
+            .get(position..position + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or_else(truncated)
+    };
+
+    if read_u32(0)? != MAGIC {
+        return Err(invalid_data("Missing LZ4 frame magic number"));
+    }
+    let flags = *input.get(4).ok_or_else(truncated)?;
+    if flags >> 6 != 1 {
+        return Err(invalid_data(format!(
+            "Unsupported LZ4 frame version {}",
+            flags >> 6
+        )));
+    }
+    let has_block_checksums = flags & 0x10 != 0;
+    // frame descriptor: flags, block descriptor, optional content size and dictionary id, and a header checksum
+    let mut position = 6;
+    if flags & 0x08 != 0 {
+        position += 8;
+    }
+    if flags & 0x01 != 0 {
+        position += 4;
+    }
+    position += 1;
+
+    let mut output = Vec::with_capacity(preallocated_size(input, decompressed_size));
+    loop {
+        let block_size = read_u32(position)?;
+        position += 4;
+        if block_size == 0 {
+            break;
+        }
+        let is_uncompressed = block_size & 0x8000_0000 != 0;
+        let block_size = (block_size & 0x7FFF_FFFF) as usize;
+        let block = input
+            .get(position..position + block_size)
+            .ok_or_else(truncated)?;
+        if is_uncompressed {
+            if output.len() + block.len() > decompressed_size {
+                return Err(invalid_data(format!(
+                    "LZ4 frame decompresses to more than the expected {} bytes",
+                    decompressed_size
+                )));
+            }
+            output.extend_from_slice(block);
+        } else {
+            decode_lz4_block(block, &mut output, decompressed_size)?;
+        }
+        position += block_size;
+        if has_block_checksums {
+            position += 4;
+        }
+    }
+
+    if output.len() != decompressed_size {
+        return Err(invalid_data(format!(
+            "LZ4 frame decompressed to {} bytes, expected {}",
+            output.len(),
+            decompressed_size
+        )));
+    }
+    Ok(output)
+}
+
+/// The capacity to allocate for decompressing `data`, which trusts `decompressed_size` only as far as `data` could
+/// plausibly decompress to
+fn preallocated_size(data: &[u8], decompressed_size: usize) -> usize {
+    decompressed_size.min(data.len().saturating_mul(MAX_PREALLOCATED_RATIO))
+}
+
+/// Reads the additional bytes of a literal or match length, which continue while each byte is 255
+fn read_length_extension(input: &[u8], position: &mut usize) -> Option<usize> {
+    let mut length = 0;
+    loop {
+        let byte = *input.get(*position)?;
+        *position += 1;
+        length += byte as usize;
+        if byte != 255 {
+            return Some(length);
+        }
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::write::ZlibEncoder;
+
+    use super::*;
+
+    const TEXT: &[u8] = b"The quick brown fox jumps over the lazy dog. \
+        The quick brown fox jumps over the lazy dog. \
+        The quick brown fox jumps over the lazy dog. \
+        Skyrim cell dump";
+
+    /// `TEXT` compressed by the `lz4` command line tool: 45 literals, a 90 byte match that overlaps the bytes it copies,
+    /// and 16 literals, with the lengths continued in extra bytes
+    fn lz4_block() -> Vec<u8> {
+        [
+            &[0xFF, 0x1E][..],
+            b"The quick brown fox jumps over the lazy dog. ",
+            &[0x2D, 0x00, 0x47, 0xF0, 0x01],
+            b"Skyrim cell dump",
+        ]
+        .concat()
+    }
+
+    /// [`lz4_block`] in the frame written by the `lz4` command line tool, with a content checksum at the end
+    #[cfg(feature = "bsa")]
+    fn lz4_frame() -> Vec<u8> {
+        [
+            &[
+                0x04, 0x22, 0x4D, 0x18, 0x64, 0x40, 0xA7, 0x44, 0x00, 0x00, 0x00,
+            ][..],
+            &lz4_block(),
+            &[0x00, 0x00, 0x00, 0x00, 0x3A, 0x30, 0xF8, 0x88],
+        ]
+        .concat()
+    }
+
+    fn sized(decompressed_size: usize, data: &[u8]) -> Vec<u8> {
+        [&(decompressed_size as u32).to_le_bytes()[..], data].concat()
+    }
+
+    #[test]
+    fn decompresses_zlib_record_data() {
+        let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(TEXT).unwrap();
+        let data = sized(TEXT.len(), &encoder.finish().unwrap());
+        assert_eq!(decompress(&data, Compression::Zlib).unwrap(), TEXT);
+    }
+
+    #[test]
+    fn decompresses_lz4_record_data() {
+        let data = sized(TEXT.len(), &lz4_block());
+        assert_eq!(decompress(&data, Compression::Lz4).unwrap(), TEXT);
+        // a block of only literals
+        let data = sized(5, b"\x50hello");
+        assert_eq!(decompress(&data, Compression::Lz4).unwrap(), b"hello");
+    }
+
+    #[test]
+    fn rejects_record_data_without_a_decompressed_size() {
+        assert!(decompress(&[0x10, 0x00], Compression::Lz4).is_err());
+    }
+
+    #[test]
+    fn rejects_lz4_blocks_that_decompress_to_another_size() {
+        let block = lz4_block();
+        let error = decompress_sized(&block, TEXT.len() + 1, Compression::Lz4).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(decompress_sized(&block, TEXT.len() - 1, Compression::Lz4).is_err());
+        // a huge decompressed size fails without allocating it
+        assert!(decompress_sized(&block, u32::MAX as usize, Compression::Lz4).is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_lz4_blocks() {
+        let block = lz4_block();
+        // cut off in the literals, the match offset, and the length extension
+        for cut in &[1, 10, 48, 49, 50, block.len() - 1] {
+            assert!(
+                decompress_sized(&block[..*cut], TEXT.len(), Compression::Lz4).is_err(),
+                "block cut off after {} bytes",
+                cut
+            );
+        }
+        // match offsets of zero and past the start of the output
+        let zero_offset = [0x10, b'a', 0x00, 0x00, 0x00];
+        assert!(decompress_sized(&zero_offset, 5, Compression::Lz4).is_err());
+        let distant_offset = [0x10, b'a', 0x02, 0x00, 0x00];
+        assert!(decompress_sized(&distant_offset, 5, Compression::Lz4).is_err());
+        assert!(decompress_sized(&[], 0, Compression::Lz4).is_err());
+    }
+
+    #[cfg(feature = "bsa")]
+    #[test]
+    fn decompresses_lz4_frames() {
+        assert_eq!(
+            decompress_lz4_frame(&lz4_frame(), TEXT.len()).unwrap(),
+            TEXT
+        );
+        // an uncompressed block
+        let frame = [
+            &[
+                0x04, 0x22, 0x4D, 0x18, 0x60, 0x40, 0x82, 0x05, 0x00, 0x00, 0x80,
+            ][..],
+            b"hello",
+            &[0x00, 0x00, 0x00, 0x00],
+        ]
+        .concat();
+        assert_eq!(decompress_lz4_frame(&frame, 5).unwrap(), b"hello");
+        assert!(decompress_lz4_frame(&frame, 4).is_err());
+    }
+
+    #[cfg(feature = "bsa")]
+    #[test]
+    fn rejects_malformed_lz4_frames() {
+        let frame = lz4_frame();
+        assert!(decompress_lz4_frame(&frame[..frame.len() - 12], TEXT.len()).is_err());
+        assert!(decompress_lz4_frame(&frame, TEXT.len() + 1).is_err());
+        let mut bad_magic = frame.clone();
+        bad_magic[0] = 0x05;
+        assert!(decompress_lz4_frame(&bad_magic, TEXT.len()).is_err());
+        let mut bad_version = frame;
+        bad_version[4] = 0xA4;
+        assert!(decompress_lz4_frame(&bad_version, TEXT.len()).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::form_id::FormId;
+use crate::parser::{Plugin, PluginHeader};
+
+/// A cell that is edited by more than one of the plugins passed to [`find_conflicts`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellConflict {
+    /// File name of the plugin that defines the cell, as it is written in the masters of the plugins
+    pub master: String,
+    /// Id of the cell within the plugin that defines it, which is the form id without the master index
+    pub local_id: u32,
+    /// Editor id of the cell in the last plugin that sets one
+    pub editor_id: Option<String>,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    /// Indices into the slice of plugins of the plugins that edit the cell, in the order they were passed in
+    pub plugins: Vec<usize>,
+}
+
+/// Finds the cells that are edited by more than one of the `plugins`.
+///
+/// The form ids of the cells are resolved to the master that defines them, so that the same cell is found in plugins
+/// that have different masters. Since the file names of the `plugins` aren't known, cells that one of the `plugins`
+/// defines itself are not compared with the other plugins. Use [`find_conflicts_with_names`] to include them.
+///
+/// # Arguments
+///
+/// * `plugins` - The parsed plugins, usually in load order
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{find_conflicts, parse_plugin};
+///
+/// let first_contents = std::fs::read("First.esp").unwrap();
+/// let second_contents = std::fs::read("Second.esp").unwrap();
+/// let plugins = vec![
+///     parse_plugin(&first_contents).unwrap(),
+///     parse_plugin(&second_contents).unwrap(),
+/// ];
+/// for conflict in find_conflicts(&plugins) {
+///     println!("{} {:06X} {:?}", conflict.master, conflict.local_id, conflict.plugins);
+/// }
+/// ```
+pub fn find_conflicts(plugins: &[Plugin]) -> Vec<CellConflict> {
+    find_conflicts_with_names(plugins, &[])
+}
+
+/// Finds the cells that are edited by more than one of the `plugins` like [`find_conflicts`], but also compares the
+/// cells that the `plugins` define themselves using their file `names`.
+///
+/// # Arguments
+///
+/// * `plugins` - The parsed plugins, usually in load order
+/// * `names` - The file names of the `plugins`, in the same order, e.g. `Dawnguard.esm`
+pub fn find_conflicts_with_names(plugins: &[Plugin], names: &[&str]) -> Vec<CellConflict> {
+    let mut conflicts: BTreeMap<(String, u32), CellConflict> = BTreeMap::new();
+    for (index, plugin) in plugins.iter().enumerate() {
+        let name = names.get(index).copied();
+        for cell in &plugin.cells {
+            let master = match resolve_owner(&plugin.header, name, cell.form_id) {
+                Some(master) => master,
+                None => continue,
+            };
+            let local_id = FormId(cell.form_id).local_id();
+            let conflict = conflicts
+                .entry((master.to_ascii_lowercase(), local_id))
+                .or_insert_with(|| CellConflict {
+                    master: master.to_string(),
+                    local_id,
+                    editor_id: None,
+                    x: None,
+                    y: None,
+                    plugins: vec![],
+                });
+            if cell.editor_id.is_some() {
+                conflict.editor_id = cell.editor_id.as_deref().map(str::to_string);
+            }
+            conflict.x = cell.x.or(conflict.x);
+            conflict.y = cell.y.or(conflict.y);
+            if conflict.plugins.last() != Some(&index) {
+                conflict.plugins.push(index);
+            }
+        }
+    }
+    conflicts
+        .into_values()
+        .filter(|conflict| conflict.plugins.len() > 1)
+        .collect()
+}
+
+/// Finds the file name of the plugin that defines the record with the plugin-relative `form_id`, which is the plugin
+/// with the `header` and `name` itself (if known) when it isn't one of its masters
+pub(crate) fn resolve_owner<'a>(
+    header: &'a PluginHeader,
+    name: Option<&'a str>,
+    form_id: u32,
+) -> Option<&'a str> {
+    FormId(form_id).resolve_master(header).or(name)
+}
@@ -0,0 +1,231 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{self, read_dir};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{ParseError, Result};
+use crate::file::{default_jobs, parse_plugins_with_options};
+use crate::load_order::{parse_plugins_txt, LoadOrder};
+use crate::merge::merge_plugins_with_names;
+use crate::parser::{ParseOptions, Plugin};
+
+/// Files listing the load order, in the order they are looked for in the Data folder and then in the folder above it
+const LOAD_ORDER_FILES: [&str; 2] = ["plugins.txt", "loadorder.txt"];
+
+/// The plugins of a game's Data folder parsed by [`parse_data_dir`], in load order
+#[derive(Debug)]
+pub struct DataDir {
+    /// The parsed plugins, in the same order as `load_order`
+    pub plugins: Vec<Plugin<'static>>,
+    /// File names of the parsed plugins, in load order
+    pub load_order: LoadOrder,
+    /// File names and errors of the plugins that couldn't be parsed, which are left out of the load order
+    pub errors: Vec<(String, ParseError)>,
+    /// Counts of the cells of all of the parsed plugins
+    pub stats: DataDirStats,
+}
+
+/// Counts of the cells of the plugins in a [`DataDir`]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DataDirStats {
+    /// Number of CELL records in all of the plugins, counting every override of a cell
+    pub cell_records: usize,
+    /// Number of distinct cells, after resolving the overrides of each cell to the plugin that defines it
+    pub cells: usize,
+    pub interior_cells: usize,
+    pub exterior_cells: usize,
+    /// Number of distinct cells that are edited by more than one plugin
+    pub conflicting_cells: usize,
+    /// Number of distinct exterior cells in each worldspace, by the editor id of the worldspace
+    pub worldspaces: BTreeMap<String, usize>,
+}
+
+/// Parses every plugin in the Data folder at `path` in load order, and counts their cells.
+///
+/// The load order is read from a `plugins.txt` or `loadorder.txt` file in the Data folder or the folder above it,
+/// and only the enabled plugins that are in the Data folder are parsed. Without one, every plugin in the Data folder is
+/// parsed and they are ordered by modification time, which is how older games order them. Either way, the masters of
+/// the base game come first (see [`Game::base_masters`](crate::Game::base_masters)) and master files are moved before
+/// the other plugins, like the game does. The plugins are parsed on as many threads as there are CPUs.
+///
+/// # Arguments
+///
+/// * `path` - Path to the Data folder
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::parse_data_dir;
+///
+/// let data_dir = parse_data_dir("C:/Games/Skyrim Special Edition/Data").unwrap();
+/// for (plugin, entry) in data_dir.plugins.iter().zip(data_dir.load_order.plugins()) {
+///     println!("{}: {} cells", entry.name, plugin.cells.len());
+/// }
+/// println!("{} conflicting cells", data_dir.stats.conflicting_cells);
+/// ```
+pub fn parse_data_dir<P: AsRef<Path>>(path: P) -> Result<DataDir> {
+    parse_data_dir_with_options(path, &ParseOptions::default())
+}
+
+/// Parses every plugin in the Data folder at `path` like [`parse_data_dir`], but with [`ParseOptions`] that select
+/// the game and enable parsing of additional records.
+///
+/// # Arguments
+///
+/// * `path` - Path to the Data folder
+/// * `options` - Options that control what is extracted from the plugins
+pub fn parse_data_dir_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<DataDir> {
+    let path = path.as_ref();
+    let files = find_plugin_files(path)?;
+    let names: Vec<String> = match read_load_order_file(path)? {
+        Some(contents) => parse_plugins_txt(&contents)
+            .into_iter()
+            .filter(|plugin| plugin.is_active)
+            .filter_map(|plugin| {
+                files
+                    .keys()
+                    .find(|name| name.eq_ignore_ascii_case(&plugin.name))
+                    .cloned()
+            })
+            .collect(),
+        None => {
+            let mut names: Vec<&String> = files.keys().collect();
+            names.sort_by_key(|name| (files[*name].1, name.to_lowercase()));
+            names.into_iter().cloned().collect()
+        }
+    };
+
+    let base_masters = options.game.base_masters();
+    let mut names: Vec<String> = base_masters
+        .iter()
+        .filter_map(|master| {
+            files
+                .keys()
+                .find(|name| name.eq_ignore_ascii_case(master))
+                .cloned()
+        })
+        .chain(names.into_iter().filter(|name| {
+            !base_masters
+                .iter()
+                .any(|master| name.eq_ignore_ascii_case(master))
+        }))
+        .collect();
+    let mut seen = HashSet::new();
+    names.retain(|name| seen.insert(name.to_lowercase()));
+
+    let mut masters = vec![];
+    let mut others = vec![];
+    let mut errors = vec![];
+    let paths: Vec<&PathBuf> = names.iter().map(|name| &files[name].0).collect();
+    let results = parse_plugins_with_options(&paths, options, default_jobs());
+    for (name, (_, result)) in names.into_iter().zip(results) {
+        match result {
+            Ok(plugin) => {
+                let is_light = options.game.supports_light_and_localized_plugins()
+                    && (plugin.header.is_light() || name.to_lowercase().ends_with(".esl"));
+                if plugin.header.is_master() || is_light {
+                    masters.push((name, is_light, plugin));
+                } else {
+                    others.push((name, is_light, plugin));
+                }
+            }
+            Err(error) => errors.push((name, error)),
+        }
+    }
+
+    let mut load_order = LoadOrder::new();
+    let mut plugins = vec![];
+    for (name, is_light, plugin) in masters.into_iter().chain(others) {
+        load_order.push(name, is_light);
+        plugins.push(plugin);
+    }
+    let stats = DataDirStats::count(&plugins, &load_order.names());
+    Ok(DataDir {
+        plugins,
+        load_order,
+        errors,
+        stats,
+    })
+}
+
+impl DataDirStats {
+    fn count(plugins: &[Plugin], names: &[&str]) -> Self {
+        let mut stats = DataDirStats {
+            cell_records: plugins.iter().map(|plugin| plugin.cells.len()).sum(),
+            ..Default::default()
+        };
+        for merged_cell in merge_plugins_with_names(plugins, names) {
+            stats.cells += 1;
+            if merged_cell.plugins.len() > 1 {
+                stats.conflicting_cells += 1;
+            }
+            let world_form_id = match merged_cell.cell.world_form_id {
+                Some(world_form_id) => world_form_id,
+                None => {
+                    stats.interior_cells += 1;
+                    continue;
+                }
+            };
+            stats.exterior_cells += 1;
+            // the winning cell's form ids are relative to the last plugin that edits it
+            let winner = &plugins[*merged_cell.plugins.last().unwrap()];
+            if let Some(world) = winner
+                .worlds
+                .iter()
+                .find(|world| world.form_id == world_form_id)
+            {
+                *stats
+                    .worldspaces
+                    .entry(world.editor_id.clone())
+                    .or_default() += 1;
+            }
+        }
+        stats
+    }
+}
+
+/// Finds the plugin files in `dir`, by file name, with their paths and modification times
+fn find_plugin_files(dir: &Path) -> Result<BTreeMap<String, (PathBuf, SystemTime)>> {
+    let mut files = BTreeMap::new();
+    for entry in read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let extension = name.rsplit('.').next().unwrap_or_default();
+        if !["esp", "esm", "esl"]
+            .iter()
+            .any(|plugin_extension| extension.eq_ignore_ascii_case(plugin_extension))
+            || !entry.file_type()?.is_file()
+        {
+            continue;
+        }
+        let modified = entry
+            .metadata()?
+            .modified()
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        files.insert(name, (path, modified));
+    }
+    Ok(files)
+}
+
+/// Reads the first file listing the load order that is found in the Data folder or the folder above it
+fn read_load_order_file(dir: &Path) -> Result<Option<Vec<u8>>> {
+    for dir in [Some(dir), dir.parent()].iter().flatten() {
+        for file_name in LOAD_ORDER_FILES.iter() {
+            match fs::read(dir.join(file_name)) {
+                Ok(contents) => return Ok(Some(contents)),
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+                Err(error) => return Err(ParseError::Io(error)),
+            }
+        }
+    }
+    Ok(None)
+}
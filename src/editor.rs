@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use crate::compression::Compression;
+use crate::error::{ParseError, Result};
+use crate::game::Game;
+use crate::iter::CellCollector;
+use crate::parser::{
+    decompress_record_data, offset_in, parse_plugin_header, parse_raw_fields, Cell, ParseOptions,
+    PluginHeader, RecordFlags,
+};
+use crate::raw::COMPRESSED_FLAG;
+use crate::walk::{Entry, Walker};
+use crate::writer::{
+    cell_fields, cell_record_flags, find_field, new_field_position, patch_field, split_fields,
+    write_field, CELL_FIELD_ORDER,
+};
+
+/// Fields of CELL records that are never rewritten, since only their size is parsed into a [`Cell`]
+const READ_ONLY_FIELDS: [&str; 1] = ["TVDT"];
+
+/// Edits the cells of a plugin and saves the plugin with all other bytes of the file copied verbatim.
+///
+/// Only the CELL records of cells that were changed are rewritten. Their fields that aren't parsed into a [`Cell`] are
+/// kept as is, fields that the record didn't have are added in the order the Creation Kit writes them in, and the
+/// rewritten records are stored uncompressed. Changes to the record flags of a cell, like `is_deleted` and
+/// `is_ignored`, are saved in the header of the record. Changes to the form ids, children, and actors of a cell are not
+/// saved.
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{CellFlags, Float, ParseOptions, PluginEditor};
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let mut editor = PluginEditor::new(&plugin_contents, &ParseOptions::default()).unwrap();
+/// if let Some(cell) = editor.cell_mut(0x0000_9732) {
+///     cell.flags |= CellFlags::HAS_WATER;
+///     cell.water_height = Some(Float(-2048.0));
+/// }
+/// std::fs::write("Plugin.esp", editor.save().unwrap()).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct PluginEditor<'a> {
+    input: &'a [u8],
+    game: Game,
+    compression: Compression,
+    header: PluginHeader<'a>,
+    cells: Vec<EditedCell<'a>>,
+}
+
+/// A cell of the plugin being edited, along with where its CELL record is in the file
+#[derive(Debug)]
+struct EditedCell<'a> {
+    /// The cell as it was parsed, to find which of its fields were changed
+    original: Cell<'a>,
+    cell: Cell<'a>,
+    /// Byte offset of the header of the CELL record
+    offset: usize,
+    /// Byte offset of the end of the data of the CELL record
+    end: usize,
+    /// Byte offsets of the headers of the groups that contain the CELL record, whose sizes change with the record
+    group_offsets: Vec<usize>,
+}
+
+impl<'a> PluginEditor<'a> {
+    /// Parses the cells of a plugin for editing
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - A slice of bytes read from the plugin file
+    /// * `options` - Options that control which cells are extracted for editing
+    pub fn new(input: &'a [u8], options: &ParseOptions) -> Result<Self> {
+        let (remaining, header) = parse_plugin_header(input, options.game)
+            .map_err(|err| ParseError::from_nom(err, input))?;
+        let header_size = options.game.record_header_size() as usize;
+
+        let mut walker = Walker::new(
+            input,
+            input.len() - remaining.len(),
+            options.game,
+            header.is_localized(),
+        )
+        .strict(options.strict);
+        let mut collector = CellCollector::new(input, options);
+        let mut records: HashMap<u32, (usize, usize, Vec<usize>)> = HashMap::new();
+        let mut cells = vec![];
+        while let Some(entry) = walker.next() {
+            let (entry, context) = entry?;
+            match entry {
+                Entry::Group(group_header) => {
+                    if collector.is_skipped_group(&group_header) {
+                        walker.skip_group();
+                    }
+                }
+                Entry::Record(record_header, data) => {
+                    if record_header.record_type == "CELL" {
+                        let data_offset = offset_in(input, data);
+                        records.insert(
+                            record_header.id,
+                            (
+                                data_offset - header_size,
+                                data_offset + data.len(),
+                                walker.group_offsets(),
+                            ),
+                        );
+                    }
+                    if let (Some(finished_cell), _) =
+                        collector.add_record(&record_header, data, &context)?
+                    {
+                        cells.push(finished_cell.parse()?);
+                    }
+                }
+            }
+        }
+        if let Some(finished_cell) = collector.finish() {
+            cells.push(finished_cell.parse()?);
+        }
+
+        let cells = cells
+            .into_iter()
+            .filter_map(|cell| {
+                let (offset, end, group_offsets) = records.remove(&cell.form_id)?;
+                Some(EditedCell {
+                    original: cell.clone(),
+                    cell,
+                    offset,
+                    end,
+                    group_offsets,
+                })
+            })
+            .collect();
+        Ok(PluginEditor {
+            input,
+            game: options.game,
+            compression: options.compression,
+            header,
+            cells,
+        })
+    }
+
+    pub fn header(&self) -> &PluginHeader<'a> {
+        &self.header
+    }
+
+    /// The cells of the plugin, with any changes made to them
+    pub fn cells(&self) -> impl Iterator<Item = &Cell<'a>> {
+        self.cells.iter().map(|edited_cell| &edited_cell.cell)
+    }
+
+    /// Finds the cell with the form id so that it can be changed
+    pub fn cell_mut(&mut self, form_id: u32) -> Option<&mut Cell<'a>> {
+        self.cells
+            .iter_mut()
+            .find(|edited_cell| edited_cell.cell.form_id == form_id)
+            .map(|edited_cell| &mut edited_cell.cell)
+    }
+
+    /// Encodes the plugin file with the changed cells rewritten and every other byte copied from the original file
+    pub fn save(&self) -> Result<Vec<u8>> {
+        let mut replacements = vec![];
+        let mut size_changes: HashMap<usize, i64> = HashMap::new();
+        for edited_cell in &self.cells {
+            if let Some(record) = self.encode_cell(edited_cell)? {
+                let size_change =
+                    record.len() as i64 - (edited_cell.end - edited_cell.offset) as i64;
+                for &group_offset in &edited_cell.group_offsets {
+                    *size_changes.entry(group_offset).or_default() += size_change;
+                }
+                replacements.push((edited_cell.offset, edited_cell.end, record, size_change));
+            }
+        }
+        replacements.sort_by_key(|&(offset, _, _, _)| offset);
+
+        let mut output = Vec::with_capacity(self.input.len());
+        let mut position = 0;
+        for (offset, end, record, _) in &replacements {
+            output.extend_from_slice(&self.input[position..*offset]);
+            output.extend_from_slice(record);
+            position = *end;
+        }
+        output.extend_from_slice(&self.input[position..]);
+
+        for (group_offset, size_change) in size_changes {
+            // groups are moved by the size changes of the records before them
+            let shift: i64 = replacements
+                .iter()
+                .filter(|&&(offset, _, _, _)| offset < group_offset)
+                .map(|&(_, _, _, size_change)| size_change)
+                .sum();
+            let size_offset = (group_offset as i64 + shift) as usize + 4;
+            let size = u32::from_le_bytes(
+                self.input[group_offset + 4..group_offset + 8]
+                    .try_into()
+                    .expect("group header has a size"),
+            );
+            let size = (size as i64 + size_change) as u32;
+            output[size_offset..size_offset + 4].copy_from_slice(&size.to_le_bytes());
+        }
+        Ok(output)
+    }
+
+    /// Encodes the CELL record of a cell with its changed fields replaced, or returns `None` if the cell is unchanged
+    fn encode_cell(&self, edited_cell: &EditedCell) -> Result<Option<Vec<u8>>> {
+        let original_fields = cell_fields(&edited_cell.original, self.game);
+        let edited_fields = cell_fields(&edited_cell.cell, self.game);
+        let original_fields = split_fields(&original_fields);
+        let edited_fields = split_fields(&edited_fields);
+        let mut changed_fields: Vec<(&str, Option<&[u8]>)> = vec![];
+        for &(field_type, _) in original_fields.iter().chain(&edited_fields) {
+            let original = find_field(&original_fields, field_type);
+            let edited = find_field(&edited_fields, field_type);
+            if original != edited
+                && !READ_ONLY_FIELDS.contains(&field_type)
+                && !changed_fields
+                    .iter()
+                    .any(|&(changed, _)| changed == field_type)
+            {
+                changed_fields.push((field_type, edited));
+            }
+        }
+        let record_flags = cell_record_flags(&edited_cell.cell);
+        if changed_fields.is_empty() && record_flags == cell_record_flags(&edited_cell.original) {
+            return Ok(None);
+        }
+
+        let header_size = self.game.record_header_size() as usize;
+        let header = &self.input[edited_cell.offset..edited_cell.offset + header_size];
+        let flags = u32::from_le_bytes(header[8..12].try_into().expect("record header has flags"));
+        let data = decompress_record_data(
+            self.input,
+            "CELL",
+            edited_cell.original.form_id,
+            &self.input[edited_cell.offset + header_size..edited_cell.end],
+            flags & COMPRESSED_FLAG != 0,
+            self.compression,
+        )?;
+        let (_, fields) = parse_raw_fields(&data).map_err(|err| {
+            ParseError::from_nom(err, &data).in_record(
+                "CELL",
+                Some(edited_cell.original.form_id),
+                edited_cell.offset + header_size,
+                flags & COMPRESSED_FLAG != 0,
+            )
+        })?;
+        let mut fields: Vec<(&str, Vec<u8>)> = fields
+            .into_iter()
+            .map(|(field_type, field_data)| (field_type, field_data.to_vec()))
+            .collect();
+        for (field_type, edited) in changed_fields {
+            let position = fields
+                .iter()
+                .position(|&(existing, _)| existing == field_type);
+            match (position, edited) {
+                (Some(position), Some(edited)) => {
+                    patch_field(field_type, &mut fields[position].1, edited)
+                }
+                (Some(position), None) => {
+                    fields.remove(position);
+                }
+                (None, Some(edited)) => {
+                    let position = new_field_position(&fields, field_type, &CELL_FIELD_ORDER);
+                    fields.insert(position, (field_type, edited.to_vec()));
+                }
+                (None, None) => {}
+            }
+        }
+
+        let mut record_data = vec![];
+        for (field_type, field_data) in &fields {
+            write_field(&mut record_data, field_type, field_data);
+        }
+        // flags that aren't known are dropped when parsing, so they are kept from the original header
+        let flags = (flags & !RecordFlags::all().bits()) | record_flags.bits();
+        let mut record = header.to_vec();
+        record[4..8].copy_from_slice(&(record_data.len() as u32).to_le_bytes());
+        record[8..12].copy_from_slice(&flags.to_le_bytes());
+        record.extend_from_slice(&record_data);
+        Ok(Some(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::parser::{parse_plugin_with_options, CellFlags, Float};
+
+    fn test_plugin_bytes() -> Vec<u8> {
+        let mut interior = Cell::new_interior(0x0100_0D63, Some("TestInterior".to_string()));
+        interior.music_type_form_id = Some(0x0001_7F8E);
+        interior.image_space_form_id = Some(0x0001_69D2);
+        let other = Cell::new_interior(0x0100_0D64, Some("OtherInterior".to_string()));
+        PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(interior)
+            .cell(other)
+            .write()
+            .unwrap()
+    }
+
+    fn field_types(record: &[u8]) -> Vec<&str> {
+        let (_, fields) = parse_raw_fields(&record[24..]).unwrap();
+        fields
+            .into_iter()
+            .map(|(field_type, _)| field_type)
+            .collect()
+    }
+
+    fn cell_record(plugin: &[u8], form_id: u32) -> &[u8] {
+        let form_id = form_id.to_le_bytes();
+        let offset = plugin
+            .windows(16)
+            .position(|window| window[..4] == *b"CELL" && window[12..] == form_id)
+            .unwrap();
+        let size = u32::from_le_bytes(plugin[offset + 4..offset + 8].try_into().unwrap());
+        &plugin[offset..offset + 24 + size as usize]
+    }
+
+    #[test]
+    fn unchanged_plugin_is_saved_byte_for_byte() {
+        let bytes = test_plugin_bytes();
+        let mut editor = PluginEditor::new(&bytes, &ParseOptions::default()).unwrap();
+        assert_eq!(editor.save().unwrap(), bytes);
+        // borrowing a cell mutably without changing it doesn't rewrite it either
+        editor.cell_mut(0x0100_0D63).unwrap();
+        assert_eq!(editor.save().unwrap(), bytes);
+    }
+
+    #[test]
+    fn new_fields_are_inserted_in_creation_kit_order() {
+        let bytes = test_plugin_bytes();
+        let mut editor = PluginEditor::new(&bytes, &ParseOptions::default()).unwrap();
+        let cell = editor.cell_mut(0x0100_0D63).unwrap();
+        cell.flags |= CellFlags::HAS_WATER;
+        cell.water_height = Some(Float(-2048.0));
+        let saved = editor.save().unwrap();
+
+        assert_eq!(
+            field_types(cell_record(&saved, 0x0100_0D63)),
+            vec!["EDID", "DATA", "XCLW", "XCMO", "XCIM"]
+        );
+        // the other cell is copied as is
+        assert_eq!(
+            cell_record(&saved, 0x0100_0D64),
+            cell_record(&bytes, 0x0100_0D64)
+        );
+
+        let options = ParseOptions {
+            collect_warnings: true,
+            ..Default::default()
+        };
+        let plugin = parse_plugin_with_options(&saved, &options).unwrap();
+        assert!(plugin.warnings.is_empty());
+        let cell = plugin
+            .cells
+            .iter()
+            .find(|cell| cell.form_id == 0x0100_0D63)
+            .unwrap();
+        assert!(cell.has_water());
+        assert_eq!(cell.water_height, Some(Float(-2048.0)));
+        assert_eq!(cell.music_type_form_id, Some(0x0001_7F8E));
+    }
+
+    #[test]
+    fn removed_fields_and_record_flags_are_saved() {
+        let bytes = test_plugin_bytes();
+        let mut editor = PluginEditor::new(&bytes, &ParseOptions::default()).unwrap();
+        let cell = editor.cell_mut(0x0100_0D63).unwrap();
+        cell.music_type_form_id = None;
+        cell.is_deleted = true;
+        let saved = editor.save().unwrap();
+
+        let record = cell_record(&saved, 0x0100_0D63);
+        assert_eq!(field_types(record), vec!["EDID", "DATA", "XCIM"]);
+        let flags =
+            RecordFlags::from_bits_truncate(u32::from_le_bytes(record[8..12].try_into().unwrap()));
+        assert_eq!(flags, RecordFlags::DELETED_RECORD);
+        let plugin = parse_plugin_with_options(&saved, &ParseOptions::default()).unwrap();
+        let cell = plugin
+            .cells
+            .iter()
+            .find(|cell| cell.form_id == 0x0100_0D63)
+            .unwrap();
+        assert!(cell.is_deleted);
+        assert_eq!(cell.music_type_form_id, None);
+    }
+}
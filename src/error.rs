@@ -0,0 +1,553 @@
+use std::{error, fmt, io};
+
+use nom::error::{ErrorKind, FromExternalError};
+use serde::{Deserialize, Serialize};
+
+/// Result type returned by the parsing functions of this crate
+pub type Result<T, E = ParseError> = std::result::Result<T, E>;
+
+/// Errors that can occur while parsing a plugin or its string tables
+///
+/// Offsets are byte offsets into the parsed file. Errors inside the fields of a compressed record point at the start
+/// of the record's data since the offset of a field in the decompressed data has no meaning within the file.
+#[derive(Debug)]
+pub enum ParseError {
+    /// A record or field of a different type than the one expected was found
+    UnexpectedRecordType {
+        expected: &'static str,
+        found: String,
+        offset: usize,
+    },
+    /// The input ended in the middle of a record, group, or field
+    TruncatedRecord {
+        record_type: Option<String>,
+        offset: usize,
+    },
+    /// The fields of a record could not be parsed
+    InvalidRecord {
+        record_type: Option<String>,
+        form_id: Option<u32>,
+        offset: usize,
+    },
+    /// The data section of a compressed record could not be decompressed
+    DecompressError {
+        record_type: String,
+        form_id: u32,
+        offset: usize,
+        source: io::Error,
+    },
+    /// A structural problem in a group, record, or field that is only an error when
+    /// [`ParseOptions::strict`](crate::ParseOptions::strict) is set
+    Malformed {
+        record_type: Option<String>,
+        form_id: Option<u32>,
+        offset: usize,
+        problem: String,
+    },
+    /// A record type, field type, or group label was not valid UTF-8
+    BadUtf8Label { offset: usize },
+    /// A string table file could not be parsed
+    InvalidStringTable { offset: usize },
+    /// A file in an archive could not be extracted, with the offset of the file's data in the archive
+    ExtractError {
+        path: String,
+        offset: usize,
+        source: io::Error,
+    },
+    /// A value passed in by the caller, like a game or plugin name, was not recognized
+    InvalidValue { kind: &'static str, value: String },
+    /// An option of the [`ParseOptions`](crate::ParseOptions) was set that the function it was passed to doesn't
+    /// support
+    UnsupportedOption {
+        option: &'static str,
+        function: &'static str,
+    },
+    /// Reading a file failed
+    Io(io::Error),
+    /// A plugin can't be written because it has cells of a world whose WRLD record isn't in
+    /// [`Plugin::worlds`](crate::Plugin::worlds), which the cells have to be written after
+    MissingWorld {
+        world_form_id: u32,
+        cell_form_id: u32,
+    },
+    /// An error inside the groups, records, and fields listed in `breadcrumbs`, outermost first, like
+    /// `GRUP WRLD > WRLD 0000003C > GRUP type 4 (block 1, -2) > CELL 00009B79 > field XCLC`
+    Nested {
+        breadcrumbs: Vec<String>,
+        error: Box<ParseError>,
+    },
+}
+
+impl ParseError {
+    /// Byte offset into the parsed file where the error occurred, if it occurred while parsing a file
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            ParseError::UnexpectedRecordType { offset, .. }
+            | ParseError::TruncatedRecord { offset, .. }
+            | ParseError::InvalidRecord { offset, .. }
+            | ParseError::Malformed { offset, .. }
+            | ParseError::DecompressError { offset, .. }
+            | ParseError::BadUtf8Label { offset }
+            | ParseError::InvalidStringTable { offset }
+            | ParseError::ExtractError { offset, .. } => Some(*offset),
+            ParseError::InvalidValue { .. }
+            | ParseError::UnsupportedOption { .. }
+            | ParseError::Io(_)
+            | ParseError::MissingWorld { .. } => None,
+            ParseError::Nested { error, .. } => error.offset(),
+        }
+    }
+
+    /// Type of the record that was being parsed when the error occurred, if it is known. For an unexpected record
+    /// type, this is the type that was found.
+    pub fn record_type(&self) -> Option<&str> {
+        match self {
+            ParseError::UnexpectedRecordType { found, .. } => Some(found),
+            ParseError::TruncatedRecord { record_type, .. }
+            | ParseError::InvalidRecord { record_type, .. }
+            | ParseError::Malformed { record_type, .. } => record_type.as_deref(),
+            ParseError::DecompressError { record_type, .. } => Some(record_type),
+            ParseError::BadUtf8Label { .. }
+            | ParseError::InvalidStringTable { .. }
+            | ParseError::ExtractError { .. }
+            | ParseError::InvalidValue { .. }
+            | ParseError::UnsupportedOption { .. }
+            | ParseError::Io(_)
+            | ParseError::MissingWorld { .. } => None,
+            ParseError::Nested { error, .. } => error.record_type(),
+        }
+    }
+
+    /// Form id of the record that was being parsed when the error occurred, if it is known
+    pub fn form_id(&self) -> Option<u32> {
+        match self {
+            ParseError::InvalidRecord { form_id, .. } | ParseError::Malformed { form_id, .. } => {
+                *form_id
+            }
+            ParseError::DecompressError { form_id, .. } => Some(*form_id),
+            ParseError::Nested { error, .. } => error.form_id(),
+            _ => None,
+        }
+    }
+
+    /// The groups, records, and fields that the error occurred in, outermost first, or an empty slice if they aren't
+    /// known
+    pub fn breadcrumbs(&self) -> &[String] {
+        match self {
+            ParseError::Nested { breadcrumbs, .. } => breadcrumbs,
+            _ => &[],
+        }
+    }
+
+    /// Adds the groups, records, or fields in `outer` to the start of the breadcrumbs of the error
+    pub(crate) fn within(self, mut outer: Vec<String>) -> ParseError {
+        if outer.is_empty() {
+            return self;
+        }
+        match self {
+            ParseError::Nested { breadcrumbs, error } => {
+                outer.extend(breadcrumbs);
+                ParseError::Nested {
+                    breadcrumbs: outer,
+                    error,
+                }
+            }
+            error => ParseError::Nested {
+                breadcrumbs: outer,
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Converts the error returned by a nom parser into a `ParseError` with an offset relative to the start of `input`
+    pub(crate) fn from_nom(err: nom::Err<NomError<&[u8]>>, input: &[u8]) -> ParseError {
+        let err = match err {
+            nom::Err::Error(err) | nom::Err::Failure(err) => err,
+            nom::Err::Incomplete(_) => {
+                return ParseError::TruncatedRecord {
+                    record_type: None,
+                    offset: input.len(),
+                }
+            }
+        };
+        let offset = input.len().saturating_sub(err.input.len());
+        if let Some(problem) = err.problem {
+            return ParseError::Malformed {
+                record_type: err.record_type,
+                form_id: None,
+                offset,
+                problem,
+            };
+        }
+        match (err.kind, err.expected) {
+            (ErrorKind::Eof, _) => ParseError::TruncatedRecord {
+                record_type: err.record_type,
+                offset,
+            },
+            (ErrorKind::MapRes, _) => ParseError::BadUtf8Label { offset },
+            (ErrorKind::Verify, Some(expected)) => ParseError::UnexpectedRecordType {
+                expected,
+                found: String::from_utf8_lossy(&err.input[..err.input.len().min(4)]).into_owned(),
+                offset,
+            },
+            _ => ParseError::InvalidRecord {
+                record_type: err.record_type,
+                form_id: None,
+                offset,
+            },
+        }
+    }
+
+    /// Attaches the record that was being parsed to an error from parsing the record's (possibly decompressed) data,
+    /// which starts at `data_offset` in the file
+    pub(crate) fn in_record(
+        mut self,
+        record_type: &str,
+        record_form_id: Option<u32>,
+        data_offset: usize,
+        is_compressed: bool,
+    ) -> ParseError {
+        if let ParseError::Nested { breadcrumbs, error } = self {
+            return ParseError::Nested {
+                breadcrumbs,
+                error: Box::new(error.in_record(
+                    record_type,
+                    record_form_id,
+                    data_offset,
+                    is_compressed,
+                )),
+            };
+        }
+        let file_offset = |offset: usize| {
+            if is_compressed {
+                data_offset
+            } else {
+                data_offset + offset
+            }
+        };
+        match &mut self {
+            ParseError::TruncatedRecord {
+                record_type: error_record_type,
+                offset,
+            } => {
+                error_record_type.get_or_insert_with(|| record_type.to_string());
+                *offset = file_offset(*offset);
+            }
+            ParseError::InvalidRecord {
+                record_type: error_record_type,
+                form_id,
+                offset,
+            }
+            | ParseError::Malformed {
+                record_type: error_record_type,
+                form_id,
+                offset,
+                ..
+            } => {
+                error_record_type.get_or_insert_with(|| record_type.to_string());
+                *form_id = form_id.or(record_form_id);
+                *offset = file_offset(*offset);
+            }
+            ParseError::UnexpectedRecordType { offset, .. }
+            | ParseError::BadUtf8Label { offset }
+            | ParseError::InvalidStringTable { offset } => *offset = file_offset(*offset),
+            ParseError::DecompressError { .. }
+            | ParseError::ExtractError { .. }
+            | ParseError::InvalidValue { .. }
+            | ParseError::UnsupportedOption { .. }
+            | ParseError::Io(_)
+            | ParseError::MissingWorld { .. }
+            | ParseError::Nested { .. } => {}
+        }
+        self
+    }
+}
+
+/// A record that was skipped because it couldn't be parsed, when parsing with
+/// [`ParseOptions::lenient`](crate::ParseOptions::lenient)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseIssue {
+    /// Type of the skipped record
+    pub record_type: Option<String>,
+    /// Form id of the skipped record
+    pub form_id: Option<u32>,
+    /// Byte offset into the plugin where parsing the record failed
+    pub offset: Option<usize>,
+    /// The error that the record failed with, as it is displayed
+    pub message: String,
+}
+
+impl From<&ParseError> for ParseIssue {
+    fn from(error: &ParseError) -> Self {
+        ParseIssue {
+            record_type: error.record_type().map(str::to_string),
+            form_id: error.form_id(),
+            offset: error.offset(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// The kind of problem that a [`ParseWarning`] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum WarningKind {
+    /// A group with a type that none of the games use
+    UnknownGroupType,
+    /// A top-level group with a label that isn't valid UTF-8
+    BadGroupLabel,
+    /// A group or record that extends past the end of the group that contains it
+    GroupOverrun,
+    /// A field of a type that isn't known to be in CELL records
+    UnknownField,
+    /// A field that is cut off by the end of the record
+    TruncatedField,
+    /// A field whose size doesn't match the data that was read from it
+    FieldSize,
+    /// Null bytes after the last field of a record
+    TrailingNulls,
+    /// A number of records and groups in the HEDR field of the header that doesn't match the plugin, which is a sign
+    /// of a plugin that was edited by hand or cut off
+    RecordCount,
+    /// A version in the HEDR field of the header that no plugin of the game has, which is a sign of a plugin that was
+    /// made for another game than the one it is parsed for
+    HeaderVersion,
+}
+
+/// A problem in a plugin that was skipped over while parsing it, collected when parsing with
+/// [`ParseOptions::collect_warnings`](crate::ParseOptions::collect_warnings).
+///
+/// These are the same problems that fail the parse with [`ParseOptions::strict`](crate::ParseOptions::strict), plus
+/// ones that are only suspicious, like fields of unknown types.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseWarning {
+    pub kind: WarningKind,
+    /// Type of the record (or GRUP) that the problem is in
+    pub record_type: Option<String>,
+    /// Form id of the record that the problem is in, `None` for groups
+    pub form_id: Option<u32>,
+    /// Byte offset into the plugin of the problem. Problems inside the fields of a compressed record point at the
+    /// start of the record's data, like errors do.
+    pub offset: Option<usize>,
+    /// The problem as it is displayed
+    pub message: String,
+}
+
+impl ParseWarning {
+    pub(crate) fn new(kind: WarningKind, error: &ParseError) -> Self {
+        ParseWarning {
+            kind,
+            record_type: error.record_type().map(str::to_string),
+            form_id: error.form_id(),
+            offset: error.offset(),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnexpectedRecordType {
+                expected,
+                found,
+                offset,
+            } => write!(
+                f,
+                "Expected {} but found {} at offset {:#X}",
+                expected, found, offset
+            ),
+            ParseError::TruncatedRecord {
+                record_type,
+                offset,
+            } => write!(
+                f,
+                "Unexpected end of {} data at offset {:#X}",
+                record_type.as_deref().unwrap_or("plugin"),
+                offset
+            ),
+            ParseError::InvalidRecord {
+                record_type,
+                form_id,
+                offset,
+            } => {
+                write!(f, "Failed to parse")?;
+                if let Some(record_type) = record_type {
+                    write!(f, " {}", record_type)?;
+                }
+                write!(f, " record")?;
+                if let Some(form_id) = form_id {
+                    write!(f, " {:08X}", form_id)?;
+                }
+                write!(f, " at offset {:#X}", offset)
+            }
+            ParseError::Malformed {
+                record_type,
+                form_id,
+                offset,
+                problem,
+            } => {
+                write!(f, "Malformed")?;
+                if let Some(record_type) = record_type {
+                    write!(f, " {}", record_type)?;
+                }
+                write!(f, " record")?;
+                if let Some(form_id) = form_id {
+                    write!(f, " {:08X}", form_id)?;
+                }
+                write!(f, " at offset {:#X}: {}", offset, problem)
+            }
+            ParseError::DecompressError {
+                record_type,
+                form_id,
+                offset,
+                source,
+            } => write!(
+                f,
+                "Failed to decompress {} record {:08X} at offset {:#X}: {}",
+                record_type, form_id, offset, source
+            ),
+            ParseError::BadUtf8Label { offset } => {
+                write!(f, "Invalid record type or label at offset {:#X}", offset)
+            }
+            ParseError::InvalidStringTable { offset } => {
+                write!(f, "Failed to parse string table at offset {:#X}", offset)
+            }
+            ParseError::ExtractError {
+                path,
+                offset,
+                source,
+            } => write!(
+                f,
+                "Failed to extract {} at offset {:#X}: {}",
+                path, offset, source
+            ),
+            ParseError::InvalidValue { kind, value } => {
+                write!(f, "Unrecognized {} {}", kind, value)
+            }
+            ParseError::UnsupportedOption { option, function } => {
+                write!(
+                    f,
+                    "ParseOptions::{} is not supported by {}",
+                    option, function
+                )
+            }
+            ParseError::Io(err) => write!(f, "{}", err),
+            ParseError::MissingWorld {
+                world_form_id,
+                cell_form_id,
+            } => write!(
+                f,
+                "Cell {:08X} can't be written without the WRLD record of its world {:08X}",
+                cell_form_id, world_form_id
+            ),
+            ParseError::Nested { breadcrumbs, error } => {
+                write!(f, "{}: {}", breadcrumbs.join(" > "), error)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ParseError::DecompressError { source, .. }
+            | ParseError::ExtractError { source, .. } => Some(source),
+            ParseError::Io(err) => Some(err),
+            ParseError::Nested { error, .. } => error.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(err: io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+/// Describes a panic caught at the boundary to a C or JavaScript program, which can't be unwound into
+#[cfg(any(feature = "cdylib", feature = "napi"))]
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error");
+    format!("The parser panicked: {}", message)
+}
+
+/// Error type of the internal nom parsers, which remembers the record being parsed and the record type that was
+/// expected so that they can be reported in a [`ParseError`]
+#[derive(Debug)]
+pub(crate) struct NomError<I> {
+    input: I,
+    kind: ErrorKind,
+    record_type: Option<String>,
+    expected: Option<&'static str>,
+    /// Description of a problem found in strict mode
+    problem: Option<String>,
+}
+
+impl<I> NomError<I> {
+    /// Marks the error as a failure to find a record or field of the `expected` type
+    fn expecting(mut self, expected: &'static str) -> Self {
+        self.expected = Some(expected);
+        self
+    }
+
+    /// Records the type of the record being parsed when the error occurred, if it isn't already known
+    fn in_record(mut self, record_type: &str) -> Self {
+        self.record_type
+            .get_or_insert_with(|| record_type.to_string());
+        self
+    }
+}
+
+impl<I> nom::error::ParseError<I> for NomError<I> {
+    fn from_error_kind(input: I, kind: ErrorKind) -> Self {
+        NomError {
+            input,
+            kind,
+            record_type: None,
+            expected: None,
+            problem: None,
+        }
+    }
+
+    fn append(_input: I, _kind: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<I, E> FromExternalError<I, E> for NomError<I> {
+    fn from_external_error(input: I, kind: ErrorKind, _err: E) -> Self {
+        nom::error::ParseError::from_error_kind(input, kind)
+    }
+}
+
+pub(crate) type IResult<I, O> = nom::IResult<I, O, NomError<I>>;
+
+/// Fails a nom parser at `input` because of a structural problem that is only an error in strict mode
+pub(crate) fn malformed<I, O>(input: I, problem: String) -> IResult<I, O> {
+    Err(nom::Err::Failure(NomError {
+        input,
+        kind: ErrorKind::Verify,
+        record_type: None,
+        expected: None,
+        problem: Some(problem),
+    }))
+}
+
+/// Maps a nom error to one that records the type of the record being parsed when it occurred
+pub(crate) fn in_record<I>(
+    record_type: &str,
+) -> impl Fn(nom::Err<NomError<I>>) -> nom::Err<NomError<I>> + '_ {
+    move |err| err.map(|err| err.in_record(record_type))
+}
+
+/// Maps a nom error to a failure to find a record or field of the `expected` type
+pub(crate) fn expecting<I>(
+    expected: &'static str,
+) -> impl Fn(nom::Err<NomError<I>>) -> nom::Err<NomError<I>> {
+    move |err| err.map(|err| err.expecting(expected))
+}
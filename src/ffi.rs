@@ -0,0 +1,433 @@
+//! C bindings for embedding the parser in C and C++ programs, enabled by the `cdylib` feature.
+//!
+//! The functions are declared in `include/skyrim_cell_dump.h`. Functions that fail return `NULL` and leave a message
+//! that can be read with [`scd_last_error`]. Panics in the parser are caught and reported the same way, since
+//! unwinding into the C program would abort it.
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::io;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::error::{panic_message, Result};
+use crate::game::Game;
+use crate::parser::{parse_plugin_with_options, ParseOptions, Plugin};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A parsed plugin returned by [`scd_parse_plugin`], which owns all of the strings that its accessors return
+pub struct ScdPlugin {
+    plugin: Plugin<'static>,
+    cells: Vec<ScdCell>,
+    worlds: Vec<ScdWorld>,
+    masters: Vec<CString>,
+    /// The editor ids that the `cells` and `worlds` point to, which are only kept so that the pointers stay valid
+    #[allow(dead_code)]
+    strings: Vec<CString>,
+}
+
+/// A cell of an [`ScdPlugin`], with the fields of [`Cell`](crate::Cell) that C programs most often need
+#[repr(C)]
+pub struct ScdCell {
+    pub form_id: u32,
+    /// Editor id of the cell, or `NULL` if it has none
+    pub editor_id: *const c_char,
+    /// Whether `x` and `y` are set, which they are for exterior cells
+    pub has_coordinates: bool,
+    pub x: i32,
+    pub y: i32,
+    /// Whether `world_form_id` is set, which it is for exterior cells
+    pub has_world: bool,
+    pub world_form_id: u32,
+    pub is_persistent: bool,
+    /// Bits of [`CellFlags`](crate::CellFlags)
+    pub flags: u16,
+}
+
+/// A worldspace of an [`ScdPlugin`]
+#[repr(C)]
+pub struct ScdWorld {
+    pub form_id: u32,
+    pub editor_id: *const c_char,
+}
+
+impl ScdPlugin {
+    fn new(plugin: Plugin<'static>) -> Self {
+        let mut strings = vec![];
+        let mut string_ptr = |string: &str| {
+            let string = to_c_string(string);
+            let ptr = string.as_ptr();
+            strings.push(string);
+            ptr
+        };
+        let cells = plugin
+            .cells
+            .iter()
+            .map(|cell| ScdCell {
+                form_id: cell.form_id,
+                editor_id: cell
+                    .editor_id
+                    .as_deref()
+                    .map_or(ptr::null(), &mut string_ptr),
+                has_coordinates: cell.x.is_some() && cell.y.is_some(),
+                x: cell.x.unwrap_or_default(),
+                y: cell.y.unwrap_or_default(),
+                has_world: cell.world_form_id.is_some(),
+                world_form_id: cell.world_form_id.unwrap_or_default(),
+                is_persistent: cell.is_persistent,
+                flags: cell.flags.bits(),
+            })
+            .collect();
+        let mut worlds: Vec<ScdWorld> = plugin
+            .worlds
+            .iter()
+            .map(|world| ScdWorld {
+                form_id: world.form_id,
+                editor_id: string_ptr(&world.editor_id),
+            })
+            .collect();
+        worlds.sort_by_key(|world| world.form_id);
+        let masters = plugin
+            .header
+            .masters
+            .iter()
+            .map(|master| to_c_string(master))
+            .collect();
+        ScdPlugin {
+            plugin,
+            cells,
+            worlds,
+            masters,
+            strings,
+        }
+    }
+}
+
+/// Parses a plugin from the `len` bytes at `data`.
+///
+/// `game` is the name of the game the plugin was made for, like the values of [`Game`]'s `FromStr`, or `NULL` for
+/// Skyrim. Returns `NULL` if the plugin can't be parsed. The plugin must be freed with [`scd_free_plugin`].
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `game` must be `NULL` or a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn scd_parse_plugin(
+    data: *const u8,
+    len: usize,
+    game: *const c_char,
+) -> *mut ScdPlugin {
+    match catch_errors(|| Ok(ScdPlugin::new(parse(data, len, game)?))) {
+        Some(plugin) => Box::into_raw(Box::new(plugin)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Parses a plugin like [`scd_parse_plugin`], but returns the plugin serialized as JSON, in the same format as the
+/// JSON output of the binary. Returns `NULL` if the plugin can't be parsed. The string must be freed with
+/// [`scd_free_string`].
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, and `game` must be `NULL` or a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn scd_parse_plugin_json(
+    data: *const u8,
+    len: usize,
+    game: *const c_char,
+) -> *mut c_char {
+    let json = catch_errors(|| {
+        let plugin = parse(data, len, game)?;
+        Ok(serde_json::to_string(&plugin).map_err(io::Error::from)?)
+    });
+    match json {
+        Some(json) => to_c_string(&json).into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+/// Frees a plugin returned by [`scd_parse_plugin`], which invalidates all of the pointers returned by its accessors.
+/// Does nothing if `plugin` is `NULL`.
+///
+/// # Safety
+///
+/// `plugin` must be `NULL` or a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_free_plugin(plugin: *mut ScdPlugin) {
+    if !plugin.is_null() {
+        drop(Box::from_raw(plugin));
+    }
+}
+
+/// Frees a string returned by [`scd_parse_plugin_json`]. Does nothing if `string` is `NULL`.
+///
+/// # Safety
+///
+/// `string` must be `NULL` or a string returned by [`scd_parse_plugin_json`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_free_string(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+/// Returns the message of the last error on the current thread, or `NULL` if there was none. The message is valid
+/// until the next call that fails on the same thread.
+#[no_mangle]
+pub extern "C" fn scd_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(ptr::null(), |error| error.as_ptr())
+    })
+}
+
+/// Returns the version of the plugin file format from the plugin's header
+///
+/// # Safety
+///
+/// `plugin` must be a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_plugin_version(plugin: *const ScdPlugin) -> f32 {
+    let plugin = &*plugin;
+    plugin.plugin.header.version
+}
+
+/// Returns the number of masters of the plugin
+///
+/// # Safety
+///
+/// `plugin` must be a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_plugin_master_count(plugin: *const ScdPlugin) -> usize {
+    let plugin = &*plugin;
+    plugin.masters.len()
+}
+
+/// Returns the file name of the master at `index`, or `NULL` if `index` is out of range
+///
+/// # Safety
+///
+/// `plugin` must be a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_plugin_master(
+    plugin: *const ScdPlugin,
+    index: usize,
+) -> *const c_char {
+    let plugin = &*plugin;
+    plugin
+        .masters
+        .get(index)
+        .map_or(ptr::null(), |master| master.as_ptr())
+}
+
+/// Returns the number of cells in the plugin
+///
+/// # Safety
+///
+/// `plugin` must be a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_plugin_cell_count(plugin: *const ScdPlugin) -> usize {
+    let plugin = &*plugin;
+    plugin.cells.len()
+}
+
+/// Returns the cell at `index`, or `NULL` if `index` is out of range
+///
+/// # Safety
+///
+/// `plugin` must be a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_plugin_cell(plugin: *const ScdPlugin, index: usize) -> *const ScdCell {
+    let plugin = &*plugin;
+    plugin
+        .cells
+        .get(index)
+        .map_or(ptr::null(), |cell| cell as *const ScdCell)
+}
+
+/// Returns the number of worldspaces in the plugin
+///
+/// # Safety
+///
+/// `plugin` must be a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_plugin_world_count(plugin: *const ScdPlugin) -> usize {
+    let plugin = &*plugin;
+    plugin.worlds.len()
+}
+
+/// Returns the worldspace at `index`, sorted by form id, or `NULL` if `index` is out of range
+///
+/// # Safety
+///
+/// `plugin` must be a plugin returned by [`scd_parse_plugin`] that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_plugin_world(
+    plugin: *const ScdPlugin,
+    index: usize,
+) -> *const ScdWorld {
+    let plugin = &*plugin;
+    plugin
+        .worlds
+        .get(index)
+        .map_or(ptr::null(), |world| world as *const ScdWorld)
+}
+
+unsafe fn parse(data: *const u8, len: usize, game: *const c_char) -> Result<Plugin<'static>> {
+    let game = if game.is_null() {
+        Game::default()
+    } else {
+        CStr::from_ptr(game).to_string_lossy().parse()?
+    };
+    let input = if len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(data, len)
+    };
+    let options = ParseOptions {
+        game,
+        ..Default::default()
+    };
+    Ok(parse_plugin_with_options(input, &options)?.into_owned())
+}
+
+/// Runs `f`, leaving its error or panic as the last error on the current thread instead of returning or unwinding it
+fn catch_errors<T>(f: impl FnOnce() -> Result<T>) -> Option<T> {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(error)) => {
+            set_last_error(&error.to_string());
+            None
+        }
+        Err(payload) => {
+            set_last_error(&panic_message(&*payload));
+            None
+        }
+    }
+}
+
+fn set_last_error(message: &str) {
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(to_c_string(message)));
+}
+
+/// Converts a string to a C string, cutting it off at the first null character since C strings can't contain one
+fn to_c_string(string: &str) -> CString {
+    let end = string.find('\0').unwrap_or(string.len());
+    CString::new(&string[..end]).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::{align_of, size_of, MaybeUninit};
+    use std::ptr::addr_of;
+
+    use super::*;
+
+    const HEADER: &str = include_str!("../include/skyrim_cell_dump.h");
+
+    /// Names and offsets of the fields of a `#[repr(C)]` struct, in declaration order
+    macro_rules! field_offsets {
+        ($struct:ty { $($field:ident),* }) => {{
+            let value = MaybeUninit::<$struct>::uninit();
+            let base = value.as_ptr();
+            // SAFETY: only the addresses of the fields are taken, without reading them
+            vec![$((stringify!($field), unsafe { addr_of!((*base).$field) } as usize - base as usize)),*]
+        }};
+    }
+
+    /// Names and offsets of the fields of the struct `name` declared in the header, laid out like a C compiler would
+    /// for the types used in the header
+    fn header_layout(name: &str) -> (Vec<(String, usize)>, usize) {
+        let start = HEADER
+            .find(&format!("typedef struct {} {{", name))
+            .unwrap_or_else(|| panic!("{} is not declared in the header", name));
+        let end = start + HEADER[start..].find('}').unwrap();
+        let mut fields = vec![];
+        let mut offset = 0;
+        let mut struct_align = 1;
+        for line in HEADER[start..end].lines().skip(1).map(str::trim) {
+            if line.is_empty() || line.starts_with("/*") {
+                continue;
+            }
+            let (field_type, field) = line.trim_end_matches(';').rsplit_once(' ').unwrap();
+            let (field_type, field) = match field.strip_prefix('*') {
+                Some(field) => (format!("{} *", field_type), field),
+                None => (field_type.to_string(), field),
+            };
+            let (size, align) = match field_type.as_str() {
+                "bool" => (size_of::<bool>(), align_of::<bool>()),
+                "uint16_t" => (2, 2),
+                "int32_t" | "uint32_t" => (4, 4),
+                "const char *" => (size_of::<*const c_char>(), align_of::<*const c_char>()),
+                field_type => panic!("unknown type {} of {}.{}", field_type, name, field),
+            };
+            offset = align_to(offset, align);
+            fields.push((field.to_string(), offset));
+            offset += size;
+            struct_align = struct_align.max(align);
+        }
+        (fields, align_to(offset, struct_align))
+    }
+
+    fn align_to(offset: usize, align: usize) -> usize {
+        match offset % align {
+            0 => offset,
+            rest => offset + align - rest,
+        }
+    }
+
+    fn assert_layout(name: &str, fields: Vec<(&str, usize)>, size: usize) {
+        let fields: Vec<(String, usize)> = fields
+            .into_iter()
+            .map(|(field, offset)| (field.to_string(), offset))
+            .collect();
+        assert_eq!(header_layout(name), (fields, size), "layout of {}", name);
+    }
+
+    #[test]
+    fn header_declares_cell_layout() {
+        let fields = field_offsets!(ScdCell {
+            form_id,
+            editor_id,
+            has_coordinates,
+            x,
+            y,
+            has_world,
+            world_form_id,
+            is_persistent,
+            flags
+        });
+        assert_layout("ScdCell", fields, size_of::<ScdCell>());
+    }
+
+    #[test]
+    fn header_declares_world_layout() {
+        let fields = field_offsets!(ScdWorld { form_id, editor_id });
+        assert_layout("ScdWorld", fields, size_of::<ScdWorld>());
+    }
+
+    #[test]
+    fn header_declares_every_function() {
+        let source = include_str!("ffi.rs");
+        let functions: Vec<&str> = source
+            .split("extern \"C\" fn ")
+            .skip(1)
+            .map(|rest| &rest[..rest.find('(').unwrap()])
+            .collect();
+        assert!(functions.contains(&"scd_parse_plugin"));
+        for function in functions {
+            assert!(
+                HEADER.contains(&format!(" {}(", function))
+                    || HEADER.contains(&format!("*{}(", function)),
+                "{} is not declared in the header",
+                function
+            );
+        }
+    }
+}
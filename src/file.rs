@@ -0,0 +1,383 @@
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread::{available_parallelism, scope};
+
+use crate::error::Result;
+use crate::parser::{parse_plugin_with_options, ParseOptions, Plugin};
+
+/// Parses header and cell records from the plugin file at `path` and outputs `Plugin` struct with extracted fields.
+///
+/// The file is read into memory. See [`parse_plugin_file_mmap`] for memory-mapping it instead.
+///
+/// # Arguments
+///
+/// * `path` - Path to the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::parse_plugin_file;
+///
+/// let plugin = parse_plugin_file("Plugin.esp").unwrap();
+/// ```
+pub fn parse_plugin_file<P: AsRef<Path>>(path: P) -> Result<Plugin<'static>> {
+    parse_plugin_file_with_options(path, &ParseOptions::default())
+}
+
+/// Parses header and cell records from the plugin file at `path` like [`parse_plugin_file`], but with
+/// [`ParseOptions`] that enable parsing of additional records.
+///
+/// # Arguments
+///
+/// * `path` - Path to the plugin file
+/// * `options` - Options that control what is extracted from the plugin
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin_file_with_options, ParseOptions};
+///
+/// let options = ParseOptions {
+///     include_land: true,
+///     ..Default::default()
+/// };
+/// let plugin = parse_plugin_file_with_options("Plugin.esp", &options).unwrap();
+/// ```
+pub fn parse_plugin_file_with_options<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<Plugin<'static>> {
+    let contents = std::fs::read(path.as_ref())?;
+    Ok(parse_plugin_with_options(&contents, options)?.into_owned())
+}
+
+/// Parses header and cell records from the plugin file at `path` like [`parse_plugin_file_with_options`], but
+/// memory-maps the file instead of reading it into memory, so that only the pages of the file that are parsed are
+/// loaded.
+///
+/// Only available with the `mmap` feature on Unix and Windows.
+///
+/// # Safety
+///
+/// The file must not be truncated or written to while it is being parsed, by this or any other process. On Unix,
+/// truncating it makes reading the cut off pages crash the process with `SIGBUS`, and on both Unix and Windows,
+/// writing to it can change the bytes being parsed. Only parse files that nothing else will change in the meantime,
+/// e.g. not the files of a plugin that is open in the Creation Kit or being installed by a mod manager.
+///
+/// # Arguments
+///
+/// * `path` - Path to the plugin file
+/// * `options` - Options that control what is extracted from the plugin
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin_file_mmap, ParseOptions};
+///
+/// // SAFETY: nothing else writes to Skyrim.esm while it is parsed
+/// let plugin = unsafe { parse_plugin_file_mmap("Skyrim.esm", &ParseOptions::default()) }.unwrap();
+/// ```
+#[cfg(all(feature = "mmap", any(unix, windows)))]
+pub unsafe fn parse_plugin_file_mmap<P: AsRef<Path>>(
+    path: P,
+    options: &ParseOptions,
+) -> Result<Plugin<'static>> {
+    let contents = mmap::Mmap::open(path.as_ref())?;
+    Ok(parse_plugin_with_options(&contents, options)?.into_owned())
+}
+
+/// Parses the plugin files at `paths` on as many threads as there are CPUs, and returns the path and result of each
+/// plugin in the order of `paths`.
+///
+/// A plugin that can't be read or parsed doesn't stop the others from being parsed.
+///
+/// # Arguments
+///
+/// * `paths` - Paths to the plugin files
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::parse_plugins;
+///
+/// for (path, plugin) in parse_plugins(&["Plugin.esp", "Other.esp"]) {
+///     match plugin {
+///         Ok(plugin) => println!("{}: {} cells", path.display(), plugin.cells.len()),
+///         Err(error) => eprintln!("{}: {}", path.display(), error),
+///     }
+/// }
+/// ```
+pub fn parse_plugins<P: AsRef<Path> + Sync>(
+    paths: &[P],
+) -> Vec<(PathBuf, Result<Plugin<'static>>)> {
+    parse_plugins_with_options(paths, &ParseOptions::default(), default_jobs())
+}
+
+/// Parses the plugin files at `paths` like [`parse_plugins`], but with [`ParseOptions`] that enable parsing of
+/// additional records and on up to `jobs` threads.
+///
+/// # Arguments
+///
+/// * `paths` - Paths to the plugin files
+/// * `options` - Options that control what is extracted from the plugins
+/// * `jobs` - Number of plugins to parse at the same time, where `1` parses them one by one on the current thread
+pub fn parse_plugins_with_options<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    options: &ParseOptions,
+    jobs: usize,
+) -> Vec<(PathBuf, Result<Plugin<'static>>)> {
+    let parse = |path: &P| {
+        (
+            path.as_ref().to_path_buf(),
+            parse_plugin_file_with_options(path, options),
+        )
+    };
+    if jobs <= 1 || paths.len() <= 1 {
+        return paths.iter().map(parse).collect();
+    }
+
+    let next_index = AtomicUsize::new(0);
+    // filled in by index as the threads finish, so the results stay in the order of the paths
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+    scope(|scope| {
+        for _ in 0..jobs.min(paths.len()) {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                let path = match paths.get(index) {
+                    Some(path) => path,
+                    None => return,
+                };
+                let result = parse(path);
+                let mut results = results
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                results[index] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+/// Number of plugins to parse at the same time by default, which is the number of CPUs
+pub(crate) fn default_jobs() -> usize {
+    available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+#[cfg(all(feature = "mmap", any(unix, windows)))]
+mod mmap {
+    use std::fs::File;
+    use std::io;
+    use std::ops::Deref;
+    #[cfg(unix)]
+    use std::os::unix::io::AsRawFd;
+    #[cfg(windows)]
+    use std::os::windows::io::AsRawHandle;
+    use std::path::Path;
+    use std::ptr;
+    use std::slice;
+
+    /// A read-only memory map of a whole file, which is unmapped when dropped
+    pub struct Mmap {
+        ptr: *mut std::ffi::c_void,
+        len: usize,
+    }
+
+    impl Mmap {
+        /// Maps the file at `path`.
+        ///
+        /// # Safety
+        ///
+        /// The file must not be truncated or written to while the map is alive. Mapping it privately or read-only
+        /// only keeps writes to the map out of the file, not writes to the file out of the map.
+        pub unsafe fn open(path: &Path) -> io::Result<Mmap> {
+            let file = File::open(path)?;
+            let len = file.metadata()?.len() as usize;
+            if len == 0 {
+                // Mapping an empty file fails, and there is nothing to map anyways
+                return Ok(Mmap {
+                    ptr: ptr::null_mut(),
+                    len,
+                });
+            }
+            // the mapping stays valid after the file is closed
+            let ptr = map(&file, len)?;
+            Ok(Mmap { ptr, len })
+        }
+    }
+
+    #[cfg(unix)]
+    unsafe fn map(file: &File, len: usize) -> io::Result<*mut std::ffi::c_void> {
+        let ptr = libc::mmap(
+            ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_PRIVATE,
+            file.as_raw_fd(),
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(ptr)
+    }
+
+    #[cfg(unix)]
+    unsafe fn unmap(ptr: *mut std::ffi::c_void, len: usize) {
+        libc::munmap(ptr, len);
+    }
+
+    #[cfg(windows)]
+    unsafe fn map(file: &File, _len: usize) -> io::Result<*mut std::ffi::c_void> {
+        // a size of 0 maps the whole file
+        let mapping = windows::CreateFileMappingW(
+            file.as_raw_handle(),
+            ptr::null_mut(),
+            windows::PAGE_READONLY,
+            0,
+            0,
+            ptr::null(),
+        );
+        if mapping.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let ptr = windows::MapViewOfFile(mapping, windows::FILE_MAP_READ, 0, 0, 0);
+        let error = io::Error::last_os_error();
+        // the view keeps the mapping alive until it is unmapped
+        windows::CloseHandle(mapping);
+        if ptr.is_null() {
+            return Err(error);
+        }
+        Ok(ptr)
+    }
+
+    #[cfg(windows)]
+    unsafe fn unmap(ptr: *mut std::ffi::c_void, _len: usize) {
+        windows::UnmapViewOfFile(ptr);
+    }
+
+    /// The functions of kernel32.dll for mapping files, which the standard library links on every Windows target
+    #[cfg(windows)]
+    #[allow(non_snake_case)]
+    mod windows {
+        use std::ffi::c_void;
+
+        pub const PAGE_READONLY: u32 = 0x02;
+        pub const FILE_MAP_READ: u32 = 0x04;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            pub fn CreateFileMappingW(
+                file: *mut c_void,
+                attributes: *mut c_void,
+                protect: u32,
+                maximum_size_high: u32,
+                maximum_size_low: u32,
+                name: *const u16,
+            ) -> *mut c_void;
+            pub fn MapViewOfFile(
+                mapping: *mut c_void,
+                desired_access: u32,
+                offset_high: u32,
+                offset_low: u32,
+                bytes_to_map: usize,
+            ) -> *mut c_void;
+            pub fn UnmapViewOfFile(base_address: *const c_void) -> i32;
+            pub fn CloseHandle(handle: *mut c_void) -> i32;
+        }
+    }
+
+    impl Deref for Mmap {
+        type Target = [u8];
+
+        fn deref(&self) -> &[u8] {
+            if self.len == 0 {
+                return &[];
+            }
+            // SAFETY: `ptr` points to a mapping of `len` readable bytes that lives as long as `self`, and the caller of
+            // `open` promised that the file isn't changed while it is mapped
+            unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+        }
+    }
+
+    impl Drop for Mmap {
+        fn drop(&mut self) {
+            if self.len > 0 {
+                // SAFETY: `ptr` and `len` describe a mapping created by `map` that is no longer borrowed
+                unsafe {
+                    unmap(self.ptr, self.len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::parser::Cell;
+
+    /// Writes `contents` to a file in the temporary directory that is unique to the test `name`
+    fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "skyrim-cell-dump-{}-{}.esp",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn reads_plugins_from_files() {
+        let bytes = PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(Cell::new_interior(0x0100_0D74, None))
+            .write()
+            .unwrap();
+        let path = temp_file("read", &bytes);
+        let plugin = parse_plugin_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(plugin.header.masters, vec!["Skyrim.esm"]);
+        assert_eq!(plugin.cells.len(), 1);
+    }
+
+    #[cfg(all(feature = "mmap", any(unix, windows)))]
+    #[test]
+    fn mapped_files_parse_the_same_as_read_files() {
+        let bytes = PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(Cell::new_interior(0x0100_0D74, None))
+            .cell(Cell::new_interior(
+                0x0100_0D75,
+                Some("TestInterior".to_string()),
+            ))
+            .write()
+            .unwrap();
+        let path = temp_file("mmap", &bytes);
+        let options = ParseOptions::default();
+        // SAFETY: nothing else writes to the file of this test
+        let mapped = unsafe { parse_plugin_file_mmap(&path, &options) }.unwrap();
+        let read = parse_plugin_file_with_options(&path, &options).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(mapped.header.masters, read.header.masters);
+        assert_eq!(mapped.cells, read.cells);
+    }
+
+    #[cfg(all(feature = "mmap", any(unix, windows)))]
+    #[test]
+    fn mapping_an_empty_file_fails_to_parse() {
+        let path = temp_file("empty", &[]);
+        // SAFETY: nothing else writes to the file of this test
+        let result = unsafe { parse_plugin_file_mmap(&path, &ParseOptions::default()) };
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
+}
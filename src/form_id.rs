@@ -0,0 +1,161 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::parser::{Plugin, PluginHeader};
+
+/// Load order index that all light plugins (ESLs) share in runtime form ids
+pub const LIGHT_PLUGIN_INDEX: u32 = 0xFE;
+/// Lowest local id a light plugin can use for new records with header version 1.70 and below
+const LIGHT_PLUGIN_MIN_LOCAL_ID: u32 = 0x800;
+/// Highest local id a light plugin can use for new records
+const LIGHT_PLUGIN_MAX_LOCAL_ID: u32 = 0xFFF;
+/// Header version starting from which light plugins can also use local ids below 0x800
+const EXTENDED_LIGHT_RANGE_VERSION: f32 = 1.71;
+
+/// A plugin-relative form id of a record.
+///
+/// The first byte is an index into the masters of the plugin (see [`PluginHeader::masters`]) of the master that
+/// defines the record. If the index is the number of masters (or higher), the plugin itself defines the record. The
+/// remaining three bytes are the local id of the record within the plugin that defines it.
+///
+/// # Examples
+///
+/// ```
+/// use skyrim_cell_dump::FormId;
+///
+/// let form_id = FormId(0x0100_0D74);
+/// assert_eq!(form_id.master_index(), 1);
+/// assert_eq!(form_id.local_id(), 0x000D74);
+/// assert_eq!(form_id.to_string(), "01000D74");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FormId(pub u32);
+
+impl FormId {
+    /// Index into the masters of the plugin of the plugin that defines the record
+    pub fn master_index(self) -> u8 {
+        (self.0 >> 24) as u8
+    }
+
+    /// Id of the record within the plugin that defines it
+    pub fn local_id(self) -> u32 {
+        self.0 & 0x00FF_FFFF
+    }
+
+    /// Finds the name of the master that defines the record, or `None` if the plugin with the `header` defines it
+    pub fn resolve_master<'a>(self, header: &'a PluginHeader) -> Option<&'a str> {
+        header
+            .masters
+            .get(self.master_index() as usize)
+            .map(|master| master.as_ref())
+    }
+
+    /// Whether the plugin with the `header` defines the record, rather than overrides it from one of its masters
+    pub fn is_new_record(self, header: &PluginHeader) -> bool {
+        self.master_index() as usize >= header.masters.len()
+    }
+}
+
+impl From<u32> for FormId {
+    fn from(form_id: u32) -> Self {
+        FormId(form_id)
+    }
+}
+
+impl From<FormId> for u32 {
+    fn from(form_id: FormId) -> Self {
+        form_id.0
+    }
+}
+
+impl fmt::Display for FormId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:08X}", self.0)
+    }
+}
+
+/// Computes the runtime form id of a record defined in a light plugin (ESL).
+///
+/// Light plugins are all loaded into the `FE` load order slot, and the next 12 bits of the runtime form id identify
+/// the light plugin within that slot. Only the lowest 12 bits of the plugin-relative `form_id` are kept.
+///
+/// # Arguments
+///
+/// * `form_id` - A plugin-relative form id of a record that the light plugin defines
+/// * `light_index` - The index of the plugin among the light plugins in the load order (0 to 4095)
+///
+/// # Examples
+///
+/// ```
+/// use skyrim_cell_dump::light_plugin_runtime_form_id;
+///
+/// assert_eq!(light_plugin_runtime_form_id(0x01000801, 2), 0xFE002801);
+/// ```
+pub fn light_plugin_runtime_form_id(form_id: u32, light_index: u16) -> u32 {
+    (LIGHT_PLUGIN_INDEX << 24) | ((u32::from(light_index) & 0xFFF) << 12) | (form_id & 0xFFF)
+}
+
+impl PluginHeader<'_> {
+    /// Whether `form_id` is a plugin-relative form id of a record that this plugin defines, rather than overrides
+    /// from one of its masters.
+    pub fn is_new_record(&self, form_id: u32) -> bool {
+        FormId(form_id).is_new_record(self)
+    }
+
+    /// Whether a new record with the plugin-relative `form_id` fits in the range of ids that a light plugin can use.
+    pub fn is_valid_light_form_id(&self, form_id: u32) -> bool {
+        let local_id = FormId(form_id).local_id();
+        let min_local_id = if self.version >= EXTENDED_LIGHT_RANGE_VERSION {
+            0
+        } else {
+            LIGHT_PLUGIN_MIN_LOCAL_ID
+        };
+        local_id >= min_local_id && local_id <= LIGHT_PLUGIN_MAX_LOCAL_ID
+    }
+}
+
+impl Plugin<'_> {
+    /// Finds the form ids of the new records in this plugin that could not be used if the plugin were a light plugin.
+    ///
+    /// A light plugin must keep the local ids of all of its new records between `0x800` and `0xFFF` (or `0x000` and
+    /// `0xFFF` for header version 1.71 and above), otherwise their runtime form ids will collide with other records.
+    ///
+    /// Only the records that this crate parses (worlds, cells, cell children, locations, and regions) are checked.
+    pub fn invalid_light_form_ids(&self) -> Vec<u32> {
+        let header = &self.header;
+        let form_ids = self
+            .worlds
+            .iter()
+            .map(|world| world.form_id)
+            .chain(self.cells.iter().flat_map(|cell| {
+                std::iter::once(cell.form_id)
+                    .chain(cell.persistent_children.iter().copied())
+                    .chain(cell.temporary_children.iter().copied())
+            }))
+            .chain(self.locations.iter().map(|location| location.form_id))
+            .chain(self.regions.iter().map(|region| region.form_id));
+        let mut invalid_form_ids: Vec<u32> = form_ids
+            .filter(|&form_id| {
+                header.is_new_record(form_id) && !header.is_valid_light_form_id(form_id)
+            })
+            .collect();
+        invalid_form_ids.sort_unstable();
+        invalid_form_ids.dedup();
+        invalid_form_ids
+    }
+
+    /// Computes the runtime form id of a record that this plugin defines, assuming it is loaded as a light plugin
+    /// at `light_index` among the light plugins in the load order.
+    ///
+    /// Returns `None` if `form_id` belongs to one of the plugin's masters instead, since that depends on the load order
+    /// of the masters.
+    pub fn light_runtime_form_id(&self, form_id: u32, light_index: u16) -> Option<u32> {
+        if self.header.is_new_record(form_id) {
+            Some(light_plugin_runtime_form_id(form_id, light_index))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,183 @@
+use std::convert::TryInto;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ParseError;
+
+/// The game that a plugin was made for, which determines the layout of its records
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Game {
+    /// The Elder Scrolls V: Skyrim, both the original release and Special Edition
+    #[default]
+    Skyrim,
+    /// Fallout 4
+    Fallout4,
+    /// Fallout 3
+    Fallout3,
+    /// Fallout: New Vegas
+    FalloutNewVegas,
+    /// The Elder Scrolls IV: Oblivion
+    Oblivion,
+}
+
+impl Game {
+    /// Versions from the HEDR field of the TES4 header of plugins made for the game
+    pub fn header_versions(&self) -> &'static [f32] {
+        match self {
+            Game::Skyrim => &[0.94, 1.7, 1.71],
+            Game::Fallout4 => &[0.95, 1.0],
+            Game::Fallout3 => &[0.94],
+            Game::FalloutNewVegas => &[1.32, 1.33, 1.34],
+            Game::Oblivion => &[0.8, 1.0],
+        }
+    }
+
+    /// Form version that the Creation Kit of the game writes in the headers of records, which Oblivion record headers
+    /// don't have
+    pub fn form_version(&self) -> Option<u16> {
+        match self {
+            Game::Skyrim => Some(44),
+            Game::Fallout4 => Some(131),
+            Game::Fallout3 | Game::FalloutNewVegas => Some(15),
+            Game::Oblivion => None,
+        }
+    }
+
+    /// Detects the game that a plugin was made for from its TES4 header record at the start of `input`.
+    ///
+    /// Oblivion is told apart by its shorter record header, and the other games by the version in the HEDR field.
+    /// Fallout 3 and Skyrim plugins from before Skyrim's 1.7 version share the 0.94 version, so they are told apart by
+    /// the form version of the header record. Returns `None` if the input doesn't start with a TES4 record or has a
+    /// version that none of the games use.
+    pub fn detect(input: &[u8]) -> Option<Game> {
+        if input.get(..4)? != b"TES4" {
+            return None;
+        }
+        let fields_start = if input.get(20..24)? == b"HEDR" {
+            20
+        } else {
+            24
+        };
+        if input.get(fields_start..fields_start + 4)? != b"HEDR" {
+            return None;
+        }
+        let version_start = fields_start + 6;
+        let version = f32::from_le_bytes(
+            input
+                .get(version_start..version_start + 4)?
+                .try_into()
+                .ok()?,
+        );
+        if fields_start == 20 {
+            return Some(Game::Oblivion).filter(|game| game.header_versions().contains(&version));
+        }
+        let form_version = u16::from_le_bytes(input.get(20..22)?.try_into().ok()?);
+        [
+            Game::Fallout4,
+            Game::FalloutNewVegas,
+            Game::Fallout3,
+            Game::Skyrim,
+        ]
+        .iter()
+        .copied()
+        .find(|game| {
+            game.header_versions().contains(&version)
+                && (*game != Game::Fallout3 || Some(form_version) <= Game::Fallout3.form_version())
+        })
+    }
+
+    /// Size in bytes of the header of records and groups, which is 20 bytes in Oblivion since it lacks the form version
+    pub fn record_header_size(&self) -> u32 {
+        match self {
+            Game::Oblivion => 20,
+            _ => 24,
+        }
+    }
+
+    /// Whether the game predates the Skyrim record layouts (Fallout 3 and New Vegas), which for example store NAVM
+    /// counts in a DATA field and lighting template inherit flags in a separate LNAM field
+    pub fn is_fallout3_family(&self) -> bool {
+        matches!(self, Game::Fallout3 | Game::FalloutNewVegas)
+    }
+
+    /// File names of the masters of the base game and its official add-ons, which the game always loads first and in
+    /// this order, whether or not they are listed in `plugins.txt`
+    pub fn base_masters(&self) -> &'static [&'static str] {
+        match self {
+            Game::Skyrim => &[
+                "Skyrim.esm",
+                "Update.esm",
+                "Dawnguard.esm",
+                "HearthFires.esm",
+                "Dragonborn.esm",
+            ],
+            Game::Fallout4 => &[
+                "Fallout4.esm",
+                "DLCRobot.esm",
+                "DLCworkshop01.esm",
+                "DLCCoast.esm",
+                "DLCworkshop02.esm",
+                "DLCworkshop03.esm",
+                "DLCNukaWorld.esm",
+                "DLCUltraHighResolution.esm",
+            ],
+            Game::Fallout3 => &["Fallout3.esm"],
+            Game::FalloutNewVegas => &["FalloutNV.esm"],
+            Game::Oblivion => &["Oblivion.esm"],
+        }
+    }
+
+    /// Whether the game supports light plugins (ESLs) and localized string tables
+    pub fn supports_light_and_localized_plugins(&self) -> bool {
+        matches!(self, Game::Skyrim | Game::Fallout4)
+    }
+}
+
+impl FromStr for Game {
+    type Err = ParseError;
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s.trim().to_lowercase().as_str() {
+            "skyrim" | "tes5" | "skyrimse" | "sse" => Ok(Game::Skyrim),
+            "fallout4" | "fo4" => Ok(Game::Fallout4),
+            "fallout3" | "fo3" => Ok(Game::Fallout3),
+            "falloutnv" | "falloutnewvegas" | "fnv" => Ok(Game::FalloutNewVegas),
+            "oblivion" | "tes4" => Ok(Game::Oblivion),
+            _ => Err(ParseError::InvalidValue {
+                kind: "game",
+                value: s.to_string(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PluginBuilder;
+
+    #[test]
+    fn detects_the_game_of_written_plugins() {
+        for &game in &[
+            Game::Skyrim,
+            Game::Fallout4,
+            Game::Fallout3,
+            Game::FalloutNewVegas,
+            Game::Oblivion,
+        ] {
+            let bytes = PluginBuilder::new().game(game).write().unwrap();
+            assert_eq!(Game::detect(&bytes), Some(game));
+        }
+    }
+
+    #[test]
+    fn does_not_detect_a_game_from_other_files() {
+        let bytes = PluginBuilder::new().write().unwrap();
+        assert_eq!(Game::detect(&[]), None);
+        assert_eq!(Game::detect(&bytes[..22]), None);
+        assert_eq!(Game::detect(b"BSA\0\x68\0\0\0"), None);
+        let mut unknown_version = bytes.clone();
+        unknown_version[30..34].copy_from_slice(&9.5f32.to_le_bytes());
+        assert_eq!(Game::detect(&unknown_version), None);
+    }
+}
@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+
+use crate::error::{ParseError, Result};
+use crate::parser::{
+    is_skipped_group, parse_plugin_header, Cell, GroupContext, GroupHeader, ParseOptions,
+    RecordHeader, UnparsedCell, UnparsedRecord, World,
+};
+use crate::walk::{Entry, Walker};
+
+/// Iterator over the cells of a plugin that parses each cell as it is reached. Returned by [`iter_cells`].
+pub struct CellIter<'a> {
+    input: &'a [u8],
+    walker: Option<Walker<'a>>,
+    cells: CellCollector<'a>,
+}
+
+/// Pairs CELL records with the records in their children groups as the groups of a plugin are walked in file order.
+///
+/// The children groups of a cell directly follow its CELL record, so a cell is finished once a record that isn't one
+/// of its children is reached.
+pub(crate) struct CellCollector<'a> {
+    input: &'a [u8],
+    options: ParseOptions,
+    /// Form IDs of the WRLD records left out by [`ParseOptions::worldspace_filter`]
+    skipped_worlds: HashSet<u32>,
+    /// The last CELL record found, which is finished once all of its children have been walked
+    pending_cell: Option<UnparsedCell<'a>>,
+}
+
+impl<'a> CellCollector<'a> {
+    pub(crate) fn new(input: &'a [u8], options: &ParseOptions) -> Self {
+        CellCollector {
+            input,
+            options: options.clone(),
+            skipped_worlds: HashSet::new(),
+            pending_cell: None,
+        }
+    }
+
+    /// Fails if an option is set in `options` that `function` can't honor by collecting cells in file order. Cells are
+    /// finished one at a time, so there is nowhere to collect warnings into, no cell to skip for leniency (an error is
+    /// returned for the failed cell instead), and no later cell to move a persistent reference into.
+    pub(crate) fn check_options(options: &ParseOptions, function: &'static str) -> Result<()> {
+        let unsupported = [
+            ("lenient", options.lenient),
+            ("collect_warnings", options.collect_warnings),
+            ("assign_persistent_refs", options.assign_persistent_refs),
+        ];
+        match unsupported.iter().find(|(_, is_set)| *is_set) {
+            Some(&(option, _)) => Err(ParseError::UnsupportedOption { option, function }),
+            None => Ok(()),
+        }
+    }
+
+    /// Whether none of the records in the group are needed to collect the cells
+    pub(crate) fn is_skipped_group(&self, group_header: &GroupHeader) -> bool {
+        is_skipped_group(group_header, &self.options, &self.skipped_worlds)
+    }
+
+    /// Adds a record found while walking the plugin.
+    ///
+    /// Returns the previous cell if the record is not one of its children, and the parsed world if the record is a
+    /// WRLD that isn't left out by the options.
+    pub(crate) fn add_record(
+        &mut self,
+        record_header: &RecordHeader<'a>,
+        data: &'a [u8],
+        context: &GroupContext,
+    ) -> Result<(Option<UnparsedCell<'a>>, Option<World>)> {
+        let record = UnparsedRecord::new(record_header, data, context);
+        if let Some(pending_cell) = self.pending_cell.as_mut() {
+            if context.cell_form_id == Some(pending_cell.form_id()) {
+                pending_cell.add_child(record, context.group_type, &self.options);
+                return Ok((None, None));
+            }
+        }
+
+        let finished_cell = self.pending_cell.take();
+        let mut world = None;
+        match record_header.record_type {
+            "CELL" if self.includes_cell(context) => {
+                self.pending_cell = Some(UnparsedCell::new(self.input, record, &self.options))
+            }
+            "WRLD" => {
+                let parsed_world = record.parse_world(
+                    self.input,
+                    context.is_localized,
+                    self.options.include_raw_data,
+                )?;
+                if !self.options.includes_world(&parsed_world.editor_id) {
+                    self.skipped_worlds.insert(parsed_world.form_id);
+                } else if self.options.include_worlds {
+                    world = Some(parsed_world);
+                }
+            }
+            _ => {}
+        }
+        Ok((finished_cell, world))
+    }
+
+    /// Returns the last cell once the whole plugin has been walked
+    pub(crate) fn finish(&mut self) -> Option<UnparsedCell<'a>> {
+        self.pending_cell.take()
+    }
+
+    /// Whether a CELL record found in `context` is extracted with the options. Only matters when groups aren't skipped
+    /// with [`CellCollector::is_skipped_group`].
+    fn includes_cell(&self, context: &GroupContext) -> bool {
+        self.options.include_cells
+            && match context.world_form_id {
+                Some(world_form_id) => !self.skipped_worlds.contains(&world_form_id),
+                None => self.options.includes_interiors(),
+            }
+    }
+}
+
+/// Parses the cells of a plugin one at a time as they are found in the input bytes of a plugin file.
+///
+/// Unlike [`parse_plugin`](crate::parse_plugin), the cells are not collected into a [`Plugin`](crate::Plugin), so
+/// only one cell is in memory at a time and iteration can be stopped early. The first item is an error if the plugin
+/// header can't be parsed.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::iter_cells;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// for cell in iter_cells(&plugin_contents) {
+///     let cell = cell.unwrap();
+///     println!("{:08X} {:?} {:?}", cell.form_id, cell.x, cell.y);
+/// }
+/// ```
+pub fn iter_cells(input: &[u8]) -> CellIter<'_> {
+    iter_cells_with_options(input, &ParseOptions::default())
+}
+
+/// Parses the cells of a plugin one at a time like [`iter_cells`], but with [`ParseOptions`] that control which
+/// cells and which of their data are extracted.
+///
+/// [`ParseOptions::lenient`], [`ParseOptions::collect_warnings`], and [`ParseOptions::assign_persistent_refs`] are
+/// not supported, and the first item is a [`ParseError::UnsupportedOption`] if any of them is set. Errors in a cell are
+/// returned as the item of that cell, and iteration can continue past them.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options that control what is extracted from the plugin
+pub fn iter_cells_with_options<'a>(input: &'a [u8], options: &ParseOptions) -> CellIter<'a> {
+    CellIter {
+        input,
+        walker: None,
+        cells: CellCollector::new(input, options),
+    }
+}
+
+impl<'a> CellIter<'a> {
+    fn start(&mut self) -> Result<()> {
+        CellCollector::check_options(&self.cells.options, "iter_cells_with_options")?;
+        let (remaining, header) = parse_plugin_header(self.input, self.cells.options.game)
+            .map_err(|err| ParseError::from_nom(err, self.input))?;
+        self.walker = Some(
+            Walker::new(
+                self.input,
+                self.input.len() - remaining.len(),
+                self.cells.options.game,
+                header.is_localized(),
+            )
+            .strict(self.cells.options.strict),
+        );
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for CellIter<'a> {
+    type Item = Result<Cell<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.walker.is_none() {
+            if let Err(err) = self.start() {
+                // don't try to parse the header again on the next call
+                self.walker = Some(Walker::new(
+                    self.input,
+                    self.input.len(),
+                    self.cells.options.game,
+                    false,
+                ));
+                return Some(Err(err));
+            }
+        }
+        let walker = self.walker.as_mut()?;
+        while let Some(entry) = walker.next() {
+            let (entry, context) = match entry {
+                Ok(entry) => entry,
+                Err(err) => return Some(Err(err)),
+            };
+            match entry {
+                Entry::Group(group_header) => {
+                    if self.cells.is_skipped_group(&group_header) {
+                        walker.skip_group();
+                    }
+                }
+                Entry::Record(record_header, data) => {
+                    match self.cells.add_record(&record_header, data, &context) {
+                        Ok((Some(finished_cell), _)) => return Some(finished_cell.parse()),
+                        Ok((None, _)) => {}
+                        Err(err) => return Some(Err(err)),
+                    }
+                }
+            }
+        }
+        self.cells.finish().map(|cell| cell.parse())
+    }
+}
@@ -1,9 +1,87 @@
 //! # Skyrim Cell Dump
 //!
 //! `skyrim-cell-dump` is a library for parsing Skyrim plugin files and extracting CELL data into Rust structs.
+//!
+//! Fallout 4, Fallout 3, Fallout: New Vegas, and Oblivion plugins share the same record structure and can also be
+//! parsed by selecting the [`Game`] in [`ParseOptions`], which [`Game::detect`] can pick from the header of a plugin.
+//! The fields that differ between the games are handled for CELL records, like the precombined meshes and previs data
+//! of Fallout 4 cells. Morrowind plugins, which use a different layout, can be parsed with the [`tes3`] module.
 #[macro_use]
 extern crate bitflags;
 
+#[cfg(any(feature = "bsa", feature = "ba2"))]
+mod archive;
+#[cfg(feature = "ba2")]
+mod ba2;
+#[cfg(feature = "bsa")]
+mod bsa;
+mod builder;
+mod compression;
+mod conflict;
+mod data_dir;
+mod editor;
+mod error;
+#[cfg(feature = "cdylib")]
+pub mod ffi;
+mod file;
+mod form_id;
+mod game;
+mod iter;
+mod load_order;
+mod merge;
+#[cfg(feature = "napi")]
+pub mod node;
+#[macro_use]
 mod parser;
+mod raw;
+mod reader;
+mod strings;
+pub mod tes3;
+mod visitor;
+mod walk;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+mod worldspace;
+mod writer;
+
+#[cfg(any(feature = "bsa", feature = "ba2"))]
+pub use archive::{parse_archive_plugins_file, Archive};
+#[cfg(feature = "ba2")]
+pub use ba2::Ba2;
+#[cfg(feature = "bsa")]
+pub use bsa::Bsa;
+pub use builder::PluginBuilder;
+pub use compression::Compression;
+pub use conflict::{find_conflicts, find_conflicts_with_names, CellConflict};
+pub use data_dir::{parse_data_dir, parse_data_dir_with_options, DataDir, DataDirStats};
+pub use editor::PluginEditor;
+pub use error::{ParseError, ParseIssue, ParseWarning, WarningKind};
+#[cfg(all(feature = "mmap", any(unix, windows)))]
+pub use file::parse_plugin_file_mmap;
+pub use file::{
+    parse_plugin_file, parse_plugin_file_with_options, parse_plugins, parse_plugins_with_options,
+};
+pub use form_id::{light_plugin_runtime_form_id, FormId, LIGHT_PLUGIN_INDEX};
+pub use game::Game;
+pub use iter::{iter_cells, iter_cells_with_options, CellIter};
+pub use load_order::{parse_plugins_txt, LoadOrder, LoadOrderEntry, PluginListEntry};
+pub use merge::{merge_plugins, merge_plugins_with_names, MergedCell, MergedMaster};
 
-pub use parser::{parse_plugin, Cell, Plugin, PluginHeader, World};
+pub use parser::{
+    parse_header, parse_header_with_options, parse_plugin, parse_plugin_lazy, parse_plugin_partial,
+    parse_plugin_with_options, ActorPlacement, Cell, CellFlags, Float, LString, Land,
+    LightingTemplateInheritFlags, Location, LocationCell, MapMarker, Navmesh, ParentUseFlags,
+    ParseOptions, Plugin, PluginFlags, PluginHeader, Precombined, PrecombinedReference, RecordDate,
+    RecordFlags, RecordMetadata, Region, RegionDataType, UnparsedCell, UnparsedPlugin,
+    WaterVelocity, World,
+};
+pub use raw::{
+    count_record_types, count_record_types_with_options, parse_group_tree,
+    parse_group_tree_with_options, parse_records, parse_records_with_options, scan_cell_form_ids,
+    scan_cell_form_ids_with_options, GroupTree, RawGroup, RawRecord,
+};
+pub use reader::parse_plugin_from_reader;
+pub use strings::StringTables;
+pub use visitor::{visit_plugin, visit_plugin_with_options, PluginVisitor};
+pub use worldspace::Worldspace;
+pub use writer::{write_plugin, write_plugin_to};
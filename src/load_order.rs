@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+
+use crate::form_id::{light_plugin_runtime_form_id, FormId, LIGHT_PLUGIN_INDEX};
+use crate::parser::PluginHeader;
+use crate::strings::decode_string;
+
+/// Highest index among the light plugins in the `FE` load order slot
+const MAX_LIGHT_INDEX: usize = 0xFFF;
+
+/// A plugin in a [`LoadOrder`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadOrderEntry {
+    /// File name of the plugin, e.g. `Skyrim.esm`
+    pub name: String,
+    /// Whether the plugin is loaded as a light plugin (ESL) into the `FE` slot
+    pub is_light: bool,
+}
+
+/// A plugin listed in a `plugins.txt` or `loadorder.txt` file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PluginListEntry {
+    /// File name of the plugin, e.g. `Skyrim.esm`
+    pub name: String,
+    /// Whether the plugin is enabled
+    pub is_active: bool,
+}
+
+/// Parses the contents of a `plugins.txt` or `loadorder.txt` file into the plugins it lists, in order.
+///
+/// Blank lines and comments starting with `#` are skipped. The `plugins.txt` of Skyrim Special Edition and Fallout 4
+/// lists every plugin and marks the enabled ones with a leading `*`. Files without any `*` markers, like `loadorder.txt`
+/// and the `plugins.txt` of older games, have every plugin that they list enabled. The file can be UTF-8 or
+/// Windows-1252.
+///
+/// # Examples
+///
+/// ```
+/// use skyrim_cell_dump::parse_plugins_txt;
+///
+/// let plugins = parse_plugins_txt(b"# This file is used by Skyrim to keep track of your downloaded content.\r\n*Plugin.esp\r\nDisabled.esp\r\n");
+/// assert_eq!(plugins.len(), 2);
+/// assert!(plugins[0].is_active);
+/// assert!(!plugins[1].is_active);
+/// ```
+pub fn parse_plugins_txt(contents: &[u8]) -> Vec<PluginListEntry> {
+    let contents = decode_string(contents);
+    let lines: Vec<&str> = contents
+        .trim_start_matches('\u{FEFF}')
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    let has_active_markers = lines.iter().any(|line| line.starts_with('*'));
+    lines
+        .into_iter()
+        .map(|line| match line.strip_prefix('*') {
+            Some(name) => PluginListEntry {
+                name: name.trim_start().to_string(),
+                is_active: true,
+            },
+            None => PluginListEntry {
+                name: line.to_string(),
+                is_active: !has_active_markers,
+            },
+        })
+        .collect()
+}
+
+/// The plugins loaded by the game, in the order they are loaded, for converting plugin-relative form ids into the
+/// runtime form ids shown in the in-game console.
+///
+/// Full plugins get the load order slots `00` to `FD` in order. Light plugins all share the `FE` slot and are numbered
+/// in order within it.
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin, LoadOrder};
+///
+/// let mut load_order = LoadOrder::new();
+/// load_order.push("Skyrim.esm", false);
+/// load_order.push("Update.esm", false);
+/// load_order.push("Plugin.esp", true);
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let plugin = parse_plugin(&plugin_contents).unwrap();
+/// for cell in &plugin.cells {
+///     let runtime_form_id = load_order.runtime_form_id("Plugin.esp", &plugin.header, cell.form_id);
+///     println!("{:08X} {:08X?}", cell.form_id, runtime_form_id);
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LoadOrder {
+    plugins: Vec<LoadOrderEntry>,
+}
+
+impl LoadOrder {
+    /// Starts an empty load order
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a plugin after all of the plugins that were added before it
+    pub fn push(&mut self, name: impl Into<String>, is_light: bool) {
+        self.plugins.push(LoadOrderEntry {
+            name: name.into(),
+            is_light,
+        });
+    }
+
+    /// Builds the load order from the enabled plugins of a `plugins.txt` or `loadorder.txt` file (see
+    /// [`parse_plugins_txt`]).
+    ///
+    /// Only plugins with the `.esl` extension are loaded as light plugins, since the light flag of `.esp` and `.esm`
+    /// files is in their headers. The base game's masters are enabled without being listed in the `plugins.txt` of
+    /// Skyrim Special Edition and Fallout 4, so they need to be pushed first if the file doesn't list them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use skyrim_cell_dump::{find_conflicts_with_names, parse_plugin_file, LoadOrder};
+    ///
+    /// let load_order = LoadOrder::from_plugins_txt(&std::fs::read("plugins.txt").unwrap());
+    /// let names = load_order.names();
+    /// let plugins: Vec<_> = names
+    ///     .iter()
+    ///     .map(|name| parse_plugin_file(format!("Data/{}", name)).unwrap())
+    ///     .collect();
+    /// let conflicts = find_conflicts_with_names(&plugins, &names);
+    /// ```
+    pub fn from_plugins_txt(contents: &[u8]) -> Self {
+        parse_plugins_txt(contents)
+            .into_iter()
+            .filter(|plugin| plugin.is_active)
+            .map(|plugin| {
+                let is_light = plugin.name.to_lowercase().ends_with(".esl");
+                (plugin.name, is_light)
+            })
+            .collect()
+    }
+
+    pub fn plugins(&self) -> &[LoadOrderEntry] {
+        &self.plugins
+    }
+
+    /// File names of the plugins in load order, as passed to
+    /// [`find_conflicts_with_names`](crate::find_conflicts_with_names) and
+    /// [`merge_plugins_with_names`](crate::merge_plugins_with_names)
+    pub fn names(&self) -> Vec<&str> {
+        self.plugins
+            .iter()
+            .map(|plugin| plugin.name.as_str())
+            .collect()
+    }
+
+    /// Converts the plugin-relative `form_id` of a record in the plugin named `plugin_name` into its runtime form id.
+    ///
+    /// The first byte of the form id is resolved to the master that defines the record using the `header` of the
+    /// plugin, and is replaced by the load order slot of that master (or of the plugin itself for new records). Plugin
+    /// names are compared case-insensitively.
+    ///
+    /// Returns `None` if the plugin that defines the record is not in the load order, or doesn't fit in the load order
+    /// slots.
+    pub fn runtime_form_id(
+        &self,
+        plugin_name: &str,
+        header: &PluginHeader,
+        form_id: u32,
+    ) -> Option<u32> {
+        let form_id = FormId(form_id);
+        let owner = form_id.resolve_master(header).unwrap_or(plugin_name);
+
+        let mut full_index = 0;
+        let mut light_index = 0;
+        for plugin in &self.plugins {
+            let is_owner = plugin.name.eq_ignore_ascii_case(owner);
+            if plugin.is_light {
+                if is_owner {
+                    return if light_index <= MAX_LIGHT_INDEX {
+                        Some(light_plugin_runtime_form_id(form_id.0, light_index as u16))
+                    } else {
+                        None
+                    };
+                }
+                light_index += 1;
+            } else {
+                if is_owner {
+                    return if full_index < LIGHT_PLUGIN_INDEX {
+                        Some((full_index << 24) | form_id.local_id())
+                    } else {
+                        None
+                    };
+                }
+                full_index += 1;
+            }
+        }
+        None
+    }
+}
+
+impl<S: Into<String>> std::iter::FromIterator<(S, bool)> for LoadOrder {
+    /// Collects a load order from plugin names and whether each one is a light plugin
+    fn from_iter<I: IntoIterator<Item = (S, bool)>>(iter: I) -> Self {
+        let mut load_order = LoadOrder::new();
+        for (name, is_light) in iter {
+            load_order.push(name, is_light);
+        }
+        load_order
+    }
+}
@@ -0,0 +1,467 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::conflict::resolve_owner;
+use crate::form_id::FormId;
+use crate::parser::{ActorPlacement, Cell, MapMarker, Plugin, PluginHeader};
+
+/// A cell as it is loaded by the game from the plugins passed to [`merge_plugins`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergedCell<'a> {
+    /// File name of the plugin that defines the cell, or `None` if it is defined by one of the plugins whose name isn't
+    /// known, which is then the first of [`MergedCell::plugins`]
+    pub master: Option<String>,
+    /// Id of the cell within the plugin that defines it, which is the form id without the master index
+    pub local_id: u32,
+    /// The winning override of the cell. Its CELL record fields come from the last plugin that edits it, while its
+    /// children, actors, and map markers are combined from every plugin that places records in it, since those are
+    /// separate records that the game loads from each plugin. Its form ids are relative to [`MergedCell::masters`].
+    pub cell: Cell<'a>,
+    /// The plugins that the master indices of the form ids in [`MergedCell::cell`] refer to: the masters of the
+    /// winning plugin, then the winning plugin itself, then the plugins that define the children, actors, and map
+    /// markers from the other plugins.
+    pub masters: Vec<MergedMaster>,
+    /// Indices into the slice of plugins of the plugins that edit the cell, in load order. The last one wins.
+    pub plugins: Vec<usize>,
+}
+
+/// A plugin that the form ids of a [`MergedCell`] refer to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MergedMaster {
+    /// A plugin with the file name, as it is written in the masters of the plugins or passed in the names
+    Name(String),
+    /// One of the merged plugins whose name isn't known, by its index into the slice of plugins
+    Plugin(usize),
+}
+
+/// Merges the cells of the `plugins` into the winning override of each cell, like the game does when it loads them.
+///
+/// Every edit of a cell replaces the whole CELL record, so the CELL record fields of the last plugin that edits it win.
+/// The references and actors in the cell are separate records, so they are combined from every plugin instead, with the
+/// last plugin that edits each of them winning. The form ids of the cells and their children are resolved to the
+/// master that defines them, so that the same records are found in plugins that have different masters. Since the file names of the `plugins` aren't known, cells that one of the `plugins` defines
+/// itself can't be matched with the overrides of the plugins after it. Use [`merge_plugins_with_names`] to match them.
+///
+/// # Arguments
+///
+/// * `plugins` - The parsed plugins, in load order
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{merge_plugins, parse_plugin};
+///
+/// let first_contents = std::fs::read("First.esp").unwrap();
+/// let second_contents = std::fs::read("Second.esp").unwrap();
+/// let plugins = vec![
+///     parse_plugin(&first_contents).unwrap(),
+///     parse_plugin(&second_contents).unwrap(),
+/// ];
+/// for merged_cell in merge_plugins(&plugins) {
+///     println!("{:?} wins with {:?}", merged_cell.plugins.last(), merged_cell.cell.flags);
+/// }
+/// ```
+pub fn merge_plugins<'a>(plugins: &[Plugin<'a>]) -> Vec<MergedCell<'a>> {
+    merge_plugins_with_names(plugins, &[])
+}
+
+/// Merges the cells of the `plugins` into the winning override of each cell like [`merge_plugins`], but also matches
+/// the cells that the `plugins` define themselves using their file `names`.
+///
+/// # Arguments
+///
+/// * `plugins` - The parsed plugins, in load order
+/// * `names` - The file names of the `plugins`, in the same order, e.g. `Dawnguard.esm`
+pub fn merge_plugins_with_names<'a>(plugins: &[Plugin<'a>], names: &[&str]) -> Vec<MergedCell<'a>> {
+    let mut merged_cells: BTreeMap<(Owner, u32), MergedChildren<'a>> = BTreeMap::new();
+    for (index, plugin) in plugins.iter().enumerate() {
+        let name = names.get(index).copied();
+        let resolve = |form_id| resolve_form_id(&plugin.header, name, index, form_id);
+        for cell in &plugin.cells {
+            let master = resolve_owner(&plugin.header, name, cell.form_id);
+            let (owner, local_id) = resolve(cell.form_id);
+            let merged = merged_cells
+                .entry((owner, local_id))
+                .or_insert_with(|| MergedChildren {
+                    cell: MergedCell {
+                        master: master.map(str::to_string),
+                        local_id,
+                        cell: cell.clone(),
+                        masters: vec![],
+                        plugins: vec![],
+                    },
+                    winner: index,
+                    persistent_children: vec![],
+                    temporary_children: vec![],
+                    actors: vec![],
+                    map_markers: vec![],
+                });
+            merged.cell.cell = cell.clone();
+            merged.winner = index;
+            if merged.cell.plugins.last() != Some(&index) {
+                merged.cell.plugins.push(index);
+            }
+
+            for &form_id in &cell.persistent_children {
+                let child = resolve(form_id);
+                merged.temporary_children.retain(|other| *other != child);
+                if !merged.persistent_children.contains(&child) {
+                    merged.persistent_children.push(child);
+                }
+            }
+            for &form_id in &cell.temporary_children {
+                let child = resolve(form_id);
+                merged.persistent_children.retain(|other| *other != child);
+                if !merged.temporary_children.contains(&child) {
+                    merged.temporary_children.push(child);
+                }
+            }
+            for actor in &cell.actors {
+                let base = actor.base_form_id.map(resolve);
+                replace_or_push(
+                    &mut merged.actors,
+                    (resolve(actor.form_id), base, actor.clone()),
+                    |(child, _, _)| child,
+                );
+            }
+            for map_marker in &cell.map_markers {
+                replace_or_push(
+                    &mut merged.map_markers,
+                    (resolve(map_marker.form_id), map_marker.clone()),
+                    |(child, _)| child,
+                );
+            }
+        }
+    }
+    merged_cells
+        .into_values()
+        .map(|merged| merged.into_merged_cell(plugins, names))
+        .collect()
+}
+
+/// The plugin that defines a record
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Owner {
+    /// A master with the lowercase file name
+    Master(String),
+    /// One of the merged plugins whose name isn't known
+    Plugin(usize),
+}
+
+/// A form id resolved to the plugin that defines the record and the id of the record within that plugin
+type ResolvedFormId = (Owner, u32);
+
+/// A cell being merged, with the children, actors, and map markers from every plugin that edits it so far
+struct MergedChildren<'a> {
+    cell: MergedCell<'a>,
+    /// Index of the plugin that the CELL record fields of the cell come from
+    winner: usize,
+    persistent_children: Vec<ResolvedFormId>,
+    temporary_children: Vec<ResolvedFormId>,
+    /// Actors with their resolved form ids and base form ids
+    actors: Vec<(ResolvedFormId, Option<ResolvedFormId>, ActorPlacement)>,
+    map_markers: Vec<(ResolvedFormId, MapMarker)>,
+}
+
+impl<'a> MergedChildren<'a> {
+    /// Makes the form ids of the children relative to the masters of the winning plugin, which are followed by the
+    /// plugins it doesn't have as masters
+    fn into_merged_cell(self, plugins: &[Plugin], names: &[&str]) -> MergedCell<'a> {
+        let header = &plugins[self.winner].header;
+        let mut masters: Vec<(Owner, MergedMaster)> = header
+            .masters
+            .iter()
+            .map(|master| {
+                (
+                    Owner::Master(master.to_ascii_lowercase()),
+                    MergedMaster::Name(master.to_string()),
+                )
+            })
+            .collect();
+        masters.push(match names.get(self.winner) {
+            Some(name) => (
+                Owner::Master(name.to_ascii_lowercase()),
+                MergedMaster::Name(name.to_string()),
+            ),
+            None => (
+                Owner::Plugin(self.winner),
+                MergedMaster::Plugin(self.winner),
+            ),
+        });
+        let mut form_id = |(owner, local_id): &ResolvedFormId| {
+            let index = match masters.iter().position(|(master, _)| master == owner) {
+                Some(index) => index,
+                None => {
+                    let master = match owner {
+                        Owner::Master(lowercase) => {
+                            MergedMaster::Name(original_name(lowercase, plugins, names))
+                        }
+                        Owner::Plugin(index) => MergedMaster::Plugin(*index),
+                    };
+                    masters.push((owner.clone(), master));
+                    masters.len() - 1
+                }
+            };
+            // a plugin has at most 255 masters, so the index only runs past 0xFF if hundreds of plugins that aren't
+            // masters of the winning plugin place records in the cell
+            ((index as u32) << 24) | local_id
+        };
+
+        let mut merged = self.cell;
+        let self_index = header.masters.len() as u32;
+        // the winning plugin itself may be referred to by any master index past its masters
+        merged.cell.map_form_ids(|form_id| {
+            if form_id >> 24 > self_index {
+                (self_index << 24) | (form_id & 0x00FF_FFFF)
+            } else {
+                form_id
+            }
+        });
+        merged.cell.persistent_children =
+            self.persistent_children.iter().map(&mut form_id).collect();
+        merged.cell.temporary_children = self.temporary_children.iter().map(&mut form_id).collect();
+        merged.cell.actors = self
+            .actors
+            .into_iter()
+            .map(|(child, base, actor)| ActorPlacement {
+                form_id: form_id(&child),
+                base_form_id: base.as_ref().map(&mut form_id),
+                ..actor
+            })
+            .collect();
+        merged.cell.map_markers = self
+            .map_markers
+            .into_iter()
+            .map(|(child, map_marker)| MapMarker {
+                form_id: form_id(&child),
+                ..map_marker
+            })
+            .collect();
+        merged.masters = masters.into_iter().map(|(_, master)| master).collect();
+        merged
+    }
+}
+
+fn resolve_form_id(
+    header: &PluginHeader,
+    name: Option<&str>,
+    index: usize,
+    form_id: u32,
+) -> ResolvedFormId {
+    let owner = match resolve_owner(header, name, form_id) {
+        Some(master) => Owner::Master(master.to_ascii_lowercase()),
+        None => Owner::Plugin(index),
+    };
+    (owner, FormId(form_id).local_id())
+}
+
+/// Finds the file name of a master as it is written in the plugins or names, rather than in lowercase
+fn original_name(lowercase: &str, plugins: &[Plugin], names: &[&str]) -> String {
+    plugins
+        .iter()
+        .flat_map(|plugin| plugin.header.masters.iter().map(|master| master.as_ref()))
+        .chain(names.iter().copied())
+        .find(|name| name.eq_ignore_ascii_case(lowercase))
+        .unwrap_or(lowercase)
+        .to_string()
+}
+
+/// Replaces the entry with the same resolved form id as `entry`, or adds it if there is none
+fn replace_or_push<T>(entries: &mut Vec<T>, entry: T, key: impl Fn(&T) -> &ResolvedFormId) {
+    match entries.iter().position(|other| key(other) == key(&entry)) {
+        Some(position) => entries[position] = entry,
+        None => entries.push(entry),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::parser::parse_plugin;
+
+    fn plugin(masters: &[&str], cells: Vec<Cell<'static>>) -> Plugin<'static> {
+        let bytes = masters
+            .iter()
+            .fold(PluginBuilder::new(), |builder, master| {
+                builder.master(*master)
+            })
+            .write()
+            .unwrap();
+        let mut plugin = parse_plugin(&bytes).unwrap().into_owned();
+        plugin.cells = cells.into_iter().collect();
+        plugin
+    }
+
+    fn actor(form_id: u32, is_initially_disabled: bool) -> ActorPlacement {
+        ActorPlacement {
+            form_id,
+            base_form_id: Some(0x0001_3BA3),
+            is_persistent: true,
+            is_initially_disabled,
+        }
+    }
+
+    fn map_marker(form_id: u32, name: &str) -> MapMarker {
+        MapMarker {
+            form_id,
+            name: Some(crate::parser::LString::String(name.to_string())),
+            marker_type: Some(1),
+            flags: Some(0x1),
+            position: None,
+        }
+    }
+
+    /// A cell of Master.esm with a persistent actor, a temporary reference, and a map marker
+    fn master() -> Plugin<'static> {
+        let mut cell = Cell::new_interior(0x0000_0D63, Some("MasterCell".to_string()));
+        cell.lighting_template_form_id = Some(0x0000_0D70);
+        cell.persistent_children = vec![0x0000_0D64];
+        cell.temporary_children = vec![0x0000_0D65];
+        cell.actors = vec![actor(0x0000_0D64, false)];
+        cell.map_markers = vec![map_marker(0x0000_0D65, "Master Marker")];
+        plugin(&[], vec![cell])
+    }
+
+    fn merged_cell(plugins: &[Plugin], names: &[&str]) -> MergedCell<'static> {
+        let mut merged_cells = merge_plugins_with_names(plugins, names);
+        assert_eq!(merged_cells.len(), 1);
+        let merged_cell = merged_cells.remove(0);
+        MergedCell {
+            cell: merged_cell.cell.into_owned(),
+            ..merged_cell
+        }
+    }
+
+    #[test]
+    fn cell_record_fields_of_the_last_plugin_win() {
+        let mut cell = Cell::new_interior(0x0000_0D63, Some("PatchedCell".to_string()));
+        cell.lighting_template_form_id = Some(0x0100_0800);
+        let patch = plugin(&["Master.esm"], vec![cell]);
+
+        let merged = merged_cell(&[master(), patch], &["Master.esm", "Patch.esp"]);
+        assert_eq!(merged.master.as_deref(), Some("Master.esm"));
+        assert_eq!(merged.local_id, 0x000D63);
+        assert_eq!(merged.plugins, vec![0, 1]);
+        assert_eq!(
+            merged.masters,
+            vec![
+                MergedMaster::Name("Master.esm".to_string()),
+                MergedMaster::Name("Patch.esp".to_string())
+            ]
+        );
+        assert_eq!(merged.cell.editor_id.as_deref(), Some("PatchedCell"));
+        assert_eq!(merged.cell.lighting_template_form_id, Some(0x0100_0800));
+    }
+
+    #[test]
+    fn children_and_map_markers_of_earlier_plugins_are_kept() {
+        let cell = Cell::new_interior(0x0000_0D63, Some("MasterCell".to_string()));
+        let patch = plugin(&["Master.esm"], vec![cell]);
+
+        let merged = merged_cell(&[master(), patch], &["Master.esm", "Patch.esp"]);
+        assert_eq!(merged.cell.persistent_children, vec![0x0000_0D64]);
+        assert_eq!(merged.cell.temporary_children, vec![0x0000_0D65]);
+        assert_eq!(merged.cell.actors, vec![actor(0x0000_0D64, false)]);
+        assert_eq!(
+            merged.cell.map_markers,
+            vec![map_marker(0x0000_0D65, "Master Marker")]
+        );
+    }
+
+    #[test]
+    fn overridden_children_replace_the_earlier_versions() {
+        let mut cell = Cell::new_interior(0x0000_0D63, Some("MasterCell".to_string()));
+        // the actor is disabled and the map marker is renamed and made persistent
+        cell.persistent_children = vec![0x0000_0D64, 0x0000_0D65];
+        cell.actors = vec![actor(0x0000_0D64, true)];
+        cell.map_markers = vec![map_marker(0x0000_0D65, "Patched Marker")];
+        let patch = plugin(&["Master.esm"], vec![cell]);
+
+        let merged = merged_cell(&[master(), patch], &["Master.esm", "Patch.esp"]);
+        assert_eq!(
+            merged.cell.persistent_children,
+            vec![0x0000_0D64, 0x0000_0D65]
+        );
+        assert!(merged.cell.temporary_children.is_empty());
+        assert_eq!(merged.cell.actors, vec![actor(0x0000_0D64, true)]);
+        assert_eq!(
+            merged.cell.map_markers,
+            vec![map_marker(0x0000_0D65, "Patched Marker")]
+        );
+    }
+
+    #[test]
+    fn children_of_plugins_that_are_not_masters_of_the_winner_are_resolved() {
+        let mut house = Cell::new_interior(0x0000_0D63, Some("MasterCell".to_string()));
+        house.temporary_children = vec![0x0100_0800];
+        house.map_markers = vec![map_marker(0x0100_0800, "House")];
+        let mut lighting = Cell::new_interior(0x0100_0D63, Some("LitCell".to_string()));
+        lighting.lighting_template_form_id = Some(0x0100_0D71);
+        // Lighting.esp has an unused master before Master.esm, and refers to itself with a master index past its
+        // masters
+        lighting.image_space_form_id = Some(0x0500_0801);
+        let plugins = [
+            master(),
+            plugin(&["Master.esm"], vec![house]),
+            plugin(&["Unused.esm", "Master.esm"], vec![lighting]),
+        ];
+
+        let merged = merged_cell(&plugins, &["Master.esm", "House.esp", "Lighting.esp"]);
+        assert_eq!(merged.plugins, vec![0, 1, 2]);
+        assert_eq!(
+            merged.masters,
+            vec![
+                MergedMaster::Name("Unused.esm".to_string()),
+                MergedMaster::Name("Master.esm".to_string()),
+                MergedMaster::Name("Lighting.esp".to_string()),
+                MergedMaster::Name("House.esp".to_string()),
+            ]
+        );
+        assert_eq!(merged.cell.form_id, 0x0100_0D63);
+        assert_eq!(merged.cell.lighting_template_form_id, Some(0x0100_0D71));
+        assert_eq!(merged.cell.image_space_form_id, Some(0x0200_0801));
+        assert_eq!(merged.cell.persistent_children, vec![0x0100_0D64]);
+        assert_eq!(
+            merged.cell.temporary_children,
+            vec![0x0100_0D65, 0x0300_0800]
+        );
+        assert_eq!(
+            merged.cell.actors,
+            vec![ActorPlacement {
+                base_form_id: Some(0x0101_3BA3),
+                ..actor(0x0100_0D64, false)
+            }]
+        );
+        assert_eq!(
+            merged.cell.map_markers,
+            vec![
+                map_marker(0x0100_0D65, "Master Marker"),
+                map_marker(0x0300_0800, "House")
+            ]
+        );
+    }
+
+    #[test]
+    fn plugins_without_names_are_referred_to_by_their_index() {
+        let mut cell = Cell::new_interior(0x0000_0D63, None);
+        cell.temporary_children = vec![0x0100_0800];
+        let plugins = [master(), plugin(&["Master.esm"], vec![cell])];
+
+        // the cell that Master.esm defines can't be matched without its name
+        assert_eq!(merge_plugins(&plugins).len(), 2);
+        let merged = merged_cell(&plugins, &["Master.esm"]);
+        assert_eq!(
+            merged.masters,
+            vec![
+                MergedMaster::Name("Master.esm".to_string()),
+                MergedMaster::Plugin(1)
+            ]
+        );
+        assert_eq!(
+            merged.cell.temporary_children,
+            vec![0x0000_0D65, 0x0100_0800]
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! Node.js bindings, enabled by the `napi` feature.
+//!
+//! The shared library is a Node-API addon when renamed to `skyrim_cell_dump.node`. It exports an async
+//! `parsePlugin(buffer, game)` function that parses the plugin on the libuv thread pool and resolves to the parsed
+//! plugin, with the same fields as the JSON output of the binary. The Node-API functions are declared here instead
+//! of coming from a bindings crate, and are resolved from the Node.js process: by the dynamic linker when the addon is
+//! loaded, or on Windows, where a DLL can't link against symbols of the executable that loads it, by looking them up
+//! in the process the first time they are called.
+use std::ffi::{c_void, CString};
+#[cfg(windows)]
+use std::mem;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+#[cfg(windows)]
+use std::sync::atomic::AtomicUsize;
+
+use crate::error::panic_message;
+use crate::game::Game;
+use crate::parser::{parse_plugin_with_options, ParseOptions};
+
+#[repr(C)]
+pub struct NapiEnv {
+    _private: [u8; 0],
+}
+#[repr(C)]
+pub struct NapiValue {
+    _private: [u8; 0],
+}
+#[repr(C)]
+pub struct NapiCallbackInfo {
+    _private: [u8; 0],
+}
+#[repr(C)]
+pub struct NapiDeferred {
+    _private: [u8; 0],
+}
+#[repr(C)]
+pub struct NapiAsyncWork {
+    _private: [u8; 0],
+}
+
+type Env = *mut NapiEnv;
+type Value = *mut NapiValue;
+type Status = i32;
+type Callback = unsafe extern "C" fn(Env, *mut NapiCallbackInfo) -> Value;
+type ExecuteCallback = unsafe extern "C" fn(Env, *mut c_void);
+type CompleteCallback = unsafe extern "C" fn(Env, Status, *mut c_void);
+
+const NAPI_OK: Status = 0;
+#[cfg(windows)]
+const NAPI_GENERIC_FAILURE: Status = 9;
+const NAPI_UNDEFINED: i32 = 0;
+const NAPI_STRING: i32 = 4;
+/// Length passed to Node-API for null-terminated strings
+const NAPI_AUTO_LENGTH: usize = usize::MAX;
+
+/// Declares the Node-API functions, which are linked from the Node.js process everywhere but on Windows. There they are
+/// looked up in the executable when first called, like `node-gyp` does with its delay-load hook, so that the addon also
+/// works in Electron and other executables than `node.exe`. A function that can't be found fails with
+/// `napi_generic_failure`.
+macro_rules! napi_functions {
+    ($(fn $name:ident($($argument:ident: $type:ty),* $(,)?) -> Status;)*) => {
+        #[cfg(not(windows))]
+        extern "C" {
+            $(fn $name($($argument: $type),*) -> Status;)*
+        }
+
+        $(
+            #[cfg(windows)]
+            unsafe fn $name($($argument: $type),*) -> Status {
+                static ADDRESS: AtomicUsize = AtomicUsize::new(0);
+                let address = windows::resolve(&ADDRESS, concat!(stringify!($name), "\0"));
+                if address == 0 {
+                    return NAPI_GENERIC_FAILURE;
+                }
+                let function = mem::transmute::<usize, unsafe extern "C" fn($($type),*) -> Status>(address);
+                function($($argument),*)
+            }
+        )*
+    };
+}
+
+napi_functions! {
+    fn napi_create_function(
+        env: Env,
+        name: *const c_char,
+        length: usize,
+        cb: Callback,
+        data: *mut c_void,
+        result: *mut Value,
+    ) -> Status;
+    fn napi_set_named_property(
+        env: Env,
+        object: Value,
+        name: *const c_char,
+        value: Value,
+    ) -> Status;
+    fn napi_get_named_property(
+        env: Env,
+        object: Value,
+        name: *const c_char,
+        result: *mut Value,
+    ) -> Status;
+    fn napi_get_cb_info(
+        env: Env,
+        info: *mut NapiCallbackInfo,
+        argc: *mut usize,
+        argv: *mut Value,
+        this_arg: *mut Value,
+        data: *mut *mut c_void,
+    ) -> Status;
+    fn napi_get_buffer_info(
+        env: Env,
+        value: Value,
+        data: *mut *mut c_void,
+        length: *mut usize,
+    ) -> Status;
+    fn napi_typeof(env: Env, value: Value, result: *mut i32) -> Status;
+    fn napi_get_value_string_utf8(
+        env: Env,
+        value: Value,
+        buf: *mut c_char,
+        bufsize: usize,
+        result: *mut usize,
+    ) -> Status;
+    fn napi_create_string_utf8(
+        env: Env,
+        string: *const c_char,
+        length: usize,
+        result: *mut Value,
+    ) -> Status;
+    fn napi_create_error(env: Env, code: Value, msg: Value, result: *mut Value) -> Status;
+    fn napi_throw_error(env: Env, code: *const c_char, msg: *const c_char) -> Status;
+    fn napi_throw_type_error(env: Env, code: *const c_char, msg: *const c_char) -> Status;
+    fn napi_create_promise(
+        env: Env,
+        deferred: *mut *mut NapiDeferred,
+        promise: *mut Value,
+    ) -> Status;
+    fn napi_resolve_deferred(env: Env, deferred: *mut NapiDeferred, resolution: Value) -> Status;
+    fn napi_reject_deferred(env: Env, deferred: *mut NapiDeferred, rejection: Value) -> Status;
+    fn napi_create_async_work(
+        env: Env,
+        async_resource: Value,
+        async_resource_name: Value,
+        execute: ExecuteCallback,
+        complete: CompleteCallback,
+        data: *mut c_void,
+        result: *mut *mut NapiAsyncWork,
+    ) -> Status;
+    fn napi_queue_async_work(env: Env, work: *mut NapiAsyncWork) -> Status;
+    fn napi_delete_async_work(env: Env, work: *mut NapiAsyncWork) -> Status;
+    fn napi_get_global(env: Env, result: *mut Value) -> Status;
+    fn napi_call_function(
+        env: Env,
+        recv: Value,
+        func: Value,
+        argc: usize,
+        argv: *const Value,
+        result: *mut Value,
+    ) -> Status;
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::ffi::c_void;
+    use std::os::raw::c_char;
+    use std::ptr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetModuleHandleW(module_name: *const u16) -> *mut c_void;
+        fn GetProcAddress(module: *mut c_void, proc_name: *const c_char) -> *mut c_void;
+    }
+
+    /// Looks up the function with the null-terminated `name` in the executable of the process, caching its address in
+    /// `address`. Returns 0 if the executable doesn't export it.
+    pub unsafe fn resolve(address: &AtomicUsize, name: &str) -> usize {
+        let cached = address.load(Ordering::Relaxed);
+        if cached != 0 {
+            return cached;
+        }
+        let resolved = GetProcAddress(
+            GetModuleHandleW(ptr::null()),
+            name.as_ptr() as *const c_char,
+        ) as usize;
+        address.store(resolved, Ordering::Relaxed);
+        resolved
+    }
+}
+
+/// A call to `parsePlugin` that is being parsed on the thread pool
+struct ParseWork {
+    /// A copy of the buffer, since the buffer can't be accessed from the thread pool
+    contents: Vec<u8>,
+    game: Option<String>,
+    /// The plugin serialized as JSON, or the error message if it couldn't be parsed
+    result: Option<Result<String, String>>,
+    deferred: *mut NapiDeferred,
+    work: *mut NapiAsyncWork,
+}
+
+/// Called by Node.js when the addon is loaded to add its exports
+///
+/// # Safety
+///
+/// Must only be called by Node.js.
+#[no_mangle]
+pub unsafe extern "C" fn napi_register_module_v1(env: Env, exports: Value) -> Value {
+    let mut function = ptr::null_mut();
+    let name = c_string("parsePlugin");
+    let registered = check(napi_create_function(
+        env,
+        name.as_ptr(),
+        NAPI_AUTO_LENGTH,
+        parse_plugin,
+        ptr::null_mut(),
+        &mut function,
+    ))
+    .and_then(|_| {
+        check(napi_set_named_property(
+            env,
+            exports,
+            name.as_ptr(),
+            function,
+        ))
+    });
+    if registered.is_err() {
+        return throw_error(env, "Failed to export parsePlugin");
+    }
+    exports
+}
+
+/// `parsePlugin(buffer: Buffer | Uint8Array, game?: string): Promise<object>`
+unsafe extern "C" fn parse_plugin(env: Env, info: *mut NapiCallbackInfo) -> Value {
+    let mut argc = 2;
+    let mut argv = [ptr::null_mut(); 2];
+    if napi_get_cb_info(
+        env,
+        info,
+        &mut argc,
+        argv.as_mut_ptr(),
+        ptr::null_mut(),
+        ptr::null_mut(),
+    ) != NAPI_OK
+    {
+        return throw_error(env, "Failed to read the arguments of parsePlugin");
+    }
+
+    let mut data = ptr::null_mut();
+    let mut length = 0;
+    if argc < 1 || napi_get_buffer_info(env, argv[0], &mut data, &mut length) != NAPI_OK {
+        return throw_type_error(
+            env,
+            "parsePlugin expects a Buffer with the contents of a plugin",
+        );
+    }
+    let contents = if length == 0 {
+        vec![]
+    } else {
+        std::slice::from_raw_parts(data as *const u8, length).to_vec()
+    };
+    let game = if argc < 2 {
+        None
+    } else {
+        match string_value(env, argv[1]) {
+            Ok(game) => game,
+            Err(()) => {
+                return throw_type_error(env, "The game passed to parsePlugin must be a string")
+            }
+        }
+    };
+
+    let mut promise = ptr::null_mut();
+    let mut deferred = ptr::null_mut();
+    if napi_create_promise(env, &mut deferred, &mut promise) != NAPI_OK {
+        return throw_error(env, "Failed to create the promise of parsePlugin");
+    }
+    let work = Box::into_raw(Box::new(ParseWork {
+        contents,
+        game,
+        result: None,
+        deferred,
+        work: ptr::null_mut(),
+    }));
+    if let Err(message) = queue_parse(env, work) {
+        // the work never runs, so `complete_parse` won't free it or settle the promise
+        let work = Box::from_raw(work);
+        if !work.work.is_null() {
+            napi_delete_async_work(env, work.work);
+        }
+        napi_reject_deferred(env, work.deferred, create_error(env, message));
+    }
+    promise
+}
+
+/// Creates and queues the async work that parses the plugin of `work`, returning what failed otherwise
+unsafe fn queue_parse(env: Env, work: *mut ParseWork) -> Result<(), &'static str> {
+    let resource_name = create_string(env, "skyrim-cell-dump:parsePlugin");
+    check(napi_create_async_work(
+        env,
+        ptr::null_mut(),
+        resource_name,
+        execute_parse,
+        complete_parse,
+        work as *mut c_void,
+        &mut (*work).work,
+    ))
+    .map_err(|_| "Failed to create the async work of parsePlugin")?;
+    check(napi_queue_async_work(env, (*work).work))
+        .map_err(|_| "Failed to queue the async work of parsePlugin")
+}
+
+/// Parses the plugin on the thread pool, where no JavaScript values can be touched. A panic in the parser rejects the
+/// promise instead of unwinding into Node.js, which would abort the process.
+unsafe extern "C" fn execute_parse(_env: Env, data: *mut c_void) {
+    let work = &mut *(data as *mut ParseWork);
+    let parse = AssertUnwindSafe(|| {
+        let game = match &work.game {
+            Some(game) => game.parse(),
+            None => Ok(Game::default()),
+        };
+        let plugin = game
+            .and_then(|game| {
+                let options = ParseOptions {
+                    game,
+                    ..Default::default()
+                };
+                parse_plugin_with_options(&work.contents, &options)
+            })
+            .map_err(|error| error.to_string())?;
+        serde_json::to_string(&plugin).map_err(|error| error.to_string())
+    });
+    let result = panic::catch_unwind(parse).unwrap_or_else(|payload| Err(panic_message(&*payload)));
+    work.result = Some(result);
+}
+
+/// Settles the promise back on the JavaScript thread once the plugin has been parsed
+unsafe extern "C" fn complete_parse(env: Env, _status: Status, data: *mut c_void) {
+    // nothing here is expected to panic, but unwinding into Node.js would abort the process if it did
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| settle(env, data)));
+}
+
+unsafe fn settle(env: Env, data: *mut c_void) {
+    let work = Box::from_raw(data as *mut ParseWork);
+    let plugin = match work.result {
+        Some(Ok(json)) => json_parse(env, &json).map_err(|_| "Failed to parse JSON".to_string()),
+        Some(Err(message)) => Err(message),
+        // the work was cancelled before it ran
+        None => Err("Parsing was cancelled".to_string()),
+    };
+    match plugin {
+        Ok(plugin) => napi_resolve_deferred(env, work.deferred, plugin),
+        Err(message) => napi_reject_deferred(env, work.deferred, create_error(env, &message)),
+    };
+    napi_delete_async_work(env, work.work);
+}
+
+/// Parses the JSON with `JSON.parse`, which is faster than building the objects through Node-API
+unsafe fn json_parse(env: Env, json: &str) -> Result<Value, Status> {
+    let mut global = ptr::null_mut();
+    let mut json_object = ptr::null_mut();
+    let mut parse = ptr::null_mut();
+    let mut value = ptr::null_mut();
+    check(napi_get_global(env, &mut global))?;
+    check(napi_get_named_property(
+        env,
+        global,
+        c_string("JSON").as_ptr(),
+        &mut json_object,
+    ))?;
+    check(napi_get_named_property(
+        env,
+        json_object,
+        c_string("parse").as_ptr(),
+        &mut parse,
+    ))?;
+    let json = create_string(env, json);
+    check(napi_call_function(
+        env,
+        json_object,
+        parse,
+        1,
+        &json,
+        &mut value,
+    ))?;
+    Ok(value)
+}
+
+/// Reads a string argument, which is `None` if the argument is `undefined`
+unsafe fn string_value(env: Env, value: Value) -> Result<Option<String>, ()> {
+    let mut value_type = 0;
+    check(napi_typeof(env, value, &mut value_type)).map_err(|_| ())?;
+    match value_type {
+        NAPI_UNDEFINED => Ok(None),
+        NAPI_STRING => {
+            let mut length = 0;
+            check(napi_get_value_string_utf8(
+                env,
+                value,
+                ptr::null_mut(),
+                0,
+                &mut length,
+            ))
+            .map_err(|_| ())?;
+            let mut buffer = vec![0u8; length + 1];
+            check(napi_get_value_string_utf8(
+                env,
+                value,
+                buffer.as_mut_ptr() as *mut c_char,
+                buffer.len(),
+                &mut length,
+            ))
+            .map_err(|_| ())?;
+            buffer.truncate(length);
+            Ok(Some(String::from_utf8_lossy(&buffer).into_owned()))
+        }
+        _ => Err(()),
+    }
+}
+
+unsafe fn create_string(env: Env, string: &str) -> Value {
+    let mut value = ptr::null_mut();
+    napi_create_string_utf8(
+        env,
+        string.as_ptr() as *const c_char,
+        string.len(),
+        &mut value,
+    );
+    value
+}
+
+unsafe fn create_error(env: Env, message: &str) -> Value {
+    let mut error = ptr::null_mut();
+    napi_create_error(
+        env,
+        ptr::null_mut(),
+        create_string(env, message),
+        &mut error,
+    );
+    error
+}
+
+unsafe fn throw_error(env: Env, message: &str) -> Value {
+    napi_throw_error(env, ptr::null(), c_string(message).as_ptr());
+    ptr::null_mut()
+}
+
+unsafe fn throw_type_error(env: Env, message: &str) -> Value {
+    napi_throw_type_error(env, ptr::null(), c_string(message).as_ptr());
+    ptr::null_mut()
+}
+
+fn check(status: Status) -> Result<(), Status> {
+    if status == NAPI_OK {
+        Ok(())
+    } else {
+        Err(status)
+    }
+}
+
+fn c_string(string: &str) -> CString {
+    CString::new(string).unwrap()
+}
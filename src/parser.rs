@@ -1,27 +1,58 @@
 use std::borrow::Cow;
-use std::collections::HashSet;
-use std::io::Read;
-use std::iter::FromIterator;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::{convert::TryInto, str};
 
-use anyhow::{anyhow, Result};
 use encoding_rs::WINDOWS_1252;
-use flate2::read::ZlibDecoder;
 use nom::combinator::rest;
-use nom::error::ParseError;
+use nom::multi::count;
 use nom::{
     branch::alt,
     bytes::complete::{take, take_while},
     combinator::{map, map_res, verify},
-    number::complete::{le_f32, le_i32, le_u16, le_u32},
-    IResult,
+    number::complete::{le_f32, le_i32, le_u16, le_u32, le_u8},
+    sequence::pair,
 };
 use serde::{Deserialize, Serialize};
 
-const RECORD_HEADER_SIZE: u32 = 24;
-const FIELD_HEADER_SIZE: u32 = 6;
+use crate::compression::{decompress, Compression};
+use crate::error::{
+    expecting, malformed, IResult, NomError, ParseError, ParseIssue, ParseWarning, Result,
+    WarningKind,
+};
+use crate::game::Game;
+use crate::walk::{add_breadcrumbs, Entry, Walker};
+
+/// Labels of the top-level groups that contain records that are parsed, all other top-level groups are skipped
+pub(crate) const PARSED_TOP_GROUPS: [&str; 4] = ["WRLD", "CELL", "LCTN", "REGN"];
+/// Lowest form version of records whose timestamps are packed bit fields rather than a day and a month count
+const PACKED_TIMESTAMP_FORM_VERSION: u16 = 44;
+/// Byte offset of the lighting template inherit flags within the XCLL field of a CELL record
+pub(crate) const XCLL_INHERIT_FLAGS_OFFSET: u32 = 88;
+/// Size of a vertex (three `f32` coordinates) in the NVNM field of a NAVM record
+const NAVMESH_VERTEX_SIZE: usize = 12;
+/// Size of an entry in the XWCU field of a CELL record: an offset and an angle, each followed by 4 unknown bytes
+const WATER_VELOCITY_SIZE: usize = 32;
+/// Width and height of an exterior cell in world units
+const CELL_SIZE: f32 = 4096.0;
+/// Types of the records that place something in a cell, which all store its position in their DATA field
+pub(crate) const PLACED_RECORD_TYPES: [&str; 11] = [
+    "REFR", "ACHR", "ACRE", "PGRE", "PMIS", "PARW", "PBEA", "PFLA", "PCON", "PBAR", "PHZD",
+];
+/// Form ID of the MapMarker STAT record in the master file of every game, which map marker references are placements of
+const MAP_MARKER_FORM_ID: u32 = 0x10;
+/// Types of the fields found in the CELL records of any of the games, fields of other types are reported as warnings
+const CELL_FIELD_TYPES: [&str; 38] = [
+    "EDID", "FULL", "DATA", "XCLC", "XCLL", "TVDT", "MHDT", "LTMP", "LNAM", "XCLW", "XNAM", "XCLR",
+    "XLCN", "XWCN", "XWCS", "XWCU", "XCWT", "XOWN", "XRNK", "XILL", "XWEM", "XCCM", "XCAS", "XEZN",
+    "XCMO", "XCIM", "XCMT", "XGLB", "XCET", "VISI", "RVIS", "PCMB", "XCRI", "XPRI", "XGDR", "CNAM",
+    "ZNAM", "XLRL",
+];
 
-/// A parsed TES5 Skyrim plugin file
+/// A parsed TES5 Skyrim (or Fallout 4, Fallout 3, New Vegas, or Oblivion) plugin file.
+///
+/// Previously serialized plugins can be deserialized back into a `Plugin<'static>`, which owns all of its strings.
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Plugin<'a> {
     /// Parsed [TES4 header record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/TES4) with metadata about the plugin
@@ -29,7 +60,178 @@ pub struct Plugin<'a> {
     /// Parsed [WRLD records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/WRLD) contained in the plugin
     pub worlds: HashSet<World>,
     /// Parsed [CELL records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/CELL) contained in the plugin
-    pub cells: HashSet<Cell>,
+    pub cells: HashSet<Cell<'a>>,
+    /// Parsed [LAND records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/LAND) contained in the plugin.
+    ///
+    /// Only populated if [`ParseOptions::include_land`] is set.
+    pub lands: Vec<Land>,
+    /// Parsed [NAVM records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/NAVM) contained in the plugin.
+    ///
+    /// Only populated if [`ParseOptions::include_navmeshes`] is set.
+    pub navmeshes: Vec<Navmesh>,
+    /// Parsed [LCTN records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/LCTN) contained in the plugin
+    pub locations: Vec<Location>,
+    /// Parsed [REGN records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/REGN) contained in the plugin
+    pub regions: Vec<Region>,
+    /// CELL records that were skipped because they couldn't be parsed.
+    ///
+    /// Only populated if [`ParseOptions::lenient`] is set, otherwise the first of them fails the whole parse.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub issues: Vec<ParseIssue>,
+    /// Problems in the plugin that were skipped over while parsing it.
+    ///
+    /// Only populated if [`ParseOptions::collect_warnings`] is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ParseWarning>,
+}
+
+impl Plugin<'_> {
+    /// Copies any strings borrowed from the plugin file so that the plugin can outlive it
+    pub fn into_owned(self) -> Plugin<'static> {
+        Plugin {
+            header: self.header.into_owned(),
+            worlds: self.worlds,
+            cells: self.cells.into_iter().map(Cell::into_owned).collect(),
+            lands: self.lands,
+            navmeshes: self.navmeshes,
+            locations: self.locations,
+            regions: self.regions,
+            issues: self.issues,
+            warnings: self.warnings,
+        }
+    }
+
+    /// Finds the WRLD record with the form id
+    pub fn world(&self, form_id: u32) -> Option<&World> {
+        self.worlds.iter().find(|world| world.form_id == form_id)
+    }
+
+    /// Form ID of the worldspace whose land the exterior `cell` is part of, or `None` for interior cells.
+    ///
+    /// This is the cell's own worldspace, unless that worldspace uses the land data of its parent (see
+    /// [`World::parent_land_form_id`]), in which case the parent is followed, and so on. Only the worlds in this plugin
+    /// are followed, so the chain ends at a parent that this plugin doesn't edit.
+    pub fn effective_world_form_id(&self, cell: &Cell) -> Option<u32> {
+        let mut world_form_id = cell.world_form_id?;
+        // a chain can't be longer than the number of worlds, unless the parents form a cycle
+        for _ in 0..self.worlds.len() {
+            match self
+                .world(world_form_id)
+                .and_then(World::parent_land_form_id)
+            {
+                Some(parent_form_id) => world_form_id = parent_form_id,
+                None => break,
+            }
+        }
+        Some(world_form_id)
+    }
+}
+
+/// Options that control what [`parse_plugin_with_options`] extracts from a plugin.
+///
+/// The default options extract the same data as [`parse_plugin`]. Options missing when deserializing are set to their
+/// defaults.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParseOptions {
+    /// Decompress and parse the terrain heightmaps from LAND records into [`Plugin::lands`]
+    pub include_land: bool,
+    /// Decompress and parse the vertex and triangle counts from NAVM records into [`Plugin::navmeshes`]
+    pub include_navmeshes: bool,
+    /// Parse the map markers placed by the REFR records in the children of each cell into [`Cell::map_markers`]
+    pub include_map_markers: bool,
+    /// Move the persistent references of the special persistent cell of each worldspace, and the actors among them,
+    /// into the exterior cell of the plugin that each reference's position is in. References outside of the cells of
+    /// the plugin stay in the persistent cell. Only has an effect with [`ParseOptions::include_refs`].
+    ///
+    /// Not supported by [`iter_cells_with_options`](crate::iter_cells_with_options) or
+    /// [`visit_plugin_with_options`](crate::visit_plugin_with_options).
+    pub assign_persistent_refs: bool,
+    /// Keep the data of each CELL and WRLD record exactly as it is stored in the plugin in [`Cell::raw_data`] and
+    /// [`World::raw_data`], for copying records without rewriting them and for writing the fields that aren't parsed
+    /// back out with [`write_plugin`](crate::write_plugin)
+    pub include_raw_data: bool,
+    /// Collect the form ids of the references placed in each cell and parse the actors placed in them into
+    /// [`Cell::persistent_children`], [`Cell::temporary_children`], and [`Cell::actors`]
+    pub include_refs: bool,
+    /// Extract CELL records into [`Plugin::cells`]. Without it, and without [`ParseOptions::include_land`] or
+    /// [`ParseOptions::include_navmeshes`], the groups of cells aren't walked at all.
+    pub include_cells: bool,
+    /// Extract WRLD records into [`Plugin::worlds`]. Without it, and without the groups of cells, the group of worlds
+    /// isn't walked at all.
+    pub include_worlds: bool,
+    /// Extract interior cells, which are stored outside of any worldspace
+    pub include_interiors: bool,
+    /// Only extract the worlds and cells of the worldspaces with these editor ids (matched case-insensitively).
+    ///
+    /// Interior cells don't belong to a worldspace, so they are skipped when the filter is set.
+    pub worldspace_filter: Option<Vec<String>>,
+    /// The game that the plugin was made for
+    pub game: Game,
+    /// The algorithm used to decompress records flagged as compressed
+    pub compression: Compression,
+    /// Fail with a [`ParseError::Malformed`] error on structural problems that are skipped over by default: groups of
+    /// unknown types, top-level groups with labels that aren't valid UTF-8, groups and records that extend past the end
+    /// of the group that contains them, and CELL fields whose size doesn't match the data in them. For validating
+    /// plugins rather than extracting what can be read from them.
+    pub strict: bool,
+    /// Skip CELL records that can't be decompressed or parsed, and the actors placed in them, instead of failing the
+    /// whole parse. Each skipped cell is recorded in [`Plugin::issues`].
+    ///
+    /// Not supported by [`iter_cells_with_options`](crate::iter_cells_with_options) or
+    /// [`visit_plugin_with_options`](crate::visit_plugin_with_options).
+    pub lenient: bool,
+    /// Collect the problems that are skipped over while parsing into [`Plugin::warnings`], with the byte offset and
+    /// record of each one: groups of unknown types, records that extend past the end of their group, CELL fields of
+    /// unknown types, CELL fields that are cut off or whose size doesn't match the data in them, a number of records and
+    /// groups in the header that doesn't match the plugin, and a header version that doesn't belong to the game. For
+    /// finding out why a plugin is missing data.
+    ///
+    /// Not supported by [`iter_cells_with_options`](crate::iter_cells_with_options) or
+    /// [`visit_plugin_with_options`](crate::visit_plugin_with_options).
+    pub collect_warnings: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            include_land: false,
+            include_navmeshes: false,
+            include_map_markers: false,
+            assign_persistent_refs: false,
+            include_raw_data: false,
+            include_refs: true,
+            include_cells: true,
+            include_worlds: true,
+            include_interiors: true,
+            worldspace_filter: None,
+            game: Game::default(),
+            compression: Compression::default(),
+            strict: false,
+            lenient: false,
+            collect_warnings: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub(crate) fn includes_interiors(&self) -> bool {
+        self.include_interiors && self.worldspace_filter.is_none()
+    }
+
+    /// Whether any of the records in the groups of cells, inside worlds and in the group of interior cells, are extracted
+    pub(crate) fn includes_cell_groups(&self) -> bool {
+        self.include_cells || self.include_land || self.include_navmeshes
+    }
+
+    pub(crate) fn includes_world(&self, editor_id: &str) -> bool {
+        match &self.worldspace_filter {
+            Some(filter) => filter
+                .iter()
+                .any(|world| world.eq_ignore_ascii_case(editor_id)),
+            None => true,
+        }
+    }
 }
 
 /// Parsed [TES4 header record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/TES4)
@@ -41,44 +243,590 @@ pub struct PluginHeader<'a> {
     pub author: Option<Cow<'a, str>>,
     pub description: Option<Cow<'a, str>>,
     pub masters: Vec<Cow<'a, str>>,
+    /// Form IDs of the temporary references from masters that this plugin overrides (ONAM)
+    pub overridden_forms: Vec<u32>,
+    /// Flags from the header of the TES4 record
+    pub flags: PluginFlags,
+}
+
+impl PluginHeader<'_> {
+    /// Copies any strings borrowed from the plugin file so that the header can outlive it
+    pub fn into_owned(self) -> PluginHeader<'static> {
+        PluginHeader {
+            author: self.author.map(|author| Cow::Owned(author.into_owned())),
+            description: self
+                .description
+                .map(|description| Cow::Owned(description.into_owned())),
+            masters: self
+                .masters
+                .into_iter()
+                .map(|master| Cow::Owned(master.into_owned()))
+                .collect(),
+            version: self.version,
+            num_records_and_groups: self.num_records_and_groups,
+            next_object_id: self.next_object_id,
+            overridden_forms: self.overridden_forms,
+            flags: self.flags,
+        }
+    }
+
+    /// Whether the plugin is flagged as a master file (ESM)
+    pub fn is_master(&self) -> bool {
+        self.flags.contains(PluginFlags::MASTER)
+    }
+
+    /// Whether the plugin is flagged as a light master (ESL) and is loaded into the shared FE load order slot
+    pub fn is_light(&self) -> bool {
+        self.flags.contains(PluginFlags::LIGHT_MASTER)
+    }
+
+    /// Whether the plugin's strings are stored in separate string table files, with string fields holding string ids
+    pub fn is_localized(&self) -> bool {
+        self.flags.contains(PluginFlags::LOCALIZED)
+    }
 }
 
 /// Parsed [CELL records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/CELL)
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub struct Cell {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cell<'a> {
     pub form_id: u32,
-    pub editor_id: Option<String>,
+    /// Editor id of the cell (EDID), borrowed from the plugin file unless the record is compressed or the id isn't
+    /// plain ASCII
+    pub editor_id: Option<Cow<'a, str>>,
+    /// Name of the cell shown in the game (FULL), which is a string id into the string tables in localized plugins
+    #[serde(default)]
+    pub name: Option<LString>,
     pub x: Option<i32>,
     pub y: Option<i32>,
     /// The [`World`] that this cell belongs to.
     pub world_form_id: Option<u32>,
     /// Indicates that this cell is a special persistent worldspace cell where all persistent references for the worldspace are stored
     pub is_persistent: bool,
+    /// Whether the CELL record is flagged as deleted, which removes the cell from the game when it overrides a cell of
+    /// a master and often breaks other plugins that edit the cell
+    #[serde(default)]
+    pub is_deleted: bool,
+    /// Whether the CELL record is flagged to be ignored by the game
+    #[serde(default)]
+    pub is_ignored: bool,
+    /// Every flag set in the header of the CELL record, including the ones also given by `is_persistent`,
+    /// `is_deleted`, and `is_ignored`
+    #[serde(default = "RecordFlags::empty")]
+    pub record_flags: RecordFlags,
+    /// Form ID of the MUSC music type record that overrides the music in this cell (XCMO)
+    pub music_type_form_id: Option<u32>,
+    /// Form ID of the IMGS image space record that overrides the image space in this cell (XCIM)
+    pub image_space_form_id: Option<u32>,
+    /// Form IDs of the REGN region records that this exterior cell belongs to (XCLR)
+    pub regions: Vec<u32>,
+    /// Flags from the DATA field, e.g. whether the cell is an interior or has water
+    pub flags: CellFlags,
+    /// Height of the water in this cell, if it overrides the default water height of its world (XCLW)
+    pub water_height: Option<Float>,
+    /// Form ID of the LGTM lighting template record used by this cell (LTMP)
+    pub lighting_template_form_id: Option<u32>,
+    /// Which lighting values this cell inherits from its lighting template (from the end of XCLL)
+    pub lighting_template_inherit_flags: Option<LightingTemplateInheritFlags>,
+    /// Form ID of the ECZN encounter zone record that this cell belongs to (XEZN)
+    pub encounter_zone_form_id: Option<u32>,
+    /// Size in bytes of the occlusion data (TVDT) carried by this cell, if it has any
+    pub occlusion_data_size: Option<u32>,
+    /// Currents of the water in this cell (XWCU, with their count in XWCN)
+    #[serde(default)]
+    pub water_velocities: Vec<WaterVelocity>,
+    /// Form ID of the CLMT climate record that overrides the climate of the world in this cell (XCCM)
+    #[serde(default)]
+    pub climate_form_id: Option<u32>,
+    /// Form ID of the ASPC acoustic space record that sets the ambient sounds and reverb of this cell (XCAS)
+    #[serde(default)]
+    pub acoustic_space_form_id: Option<u32>,
+    /// Form ID of the WATR water type record of the water in this cell (XCWT)
+    #[serde(default)]
+    pub water_type_form_id: Option<u32>,
+    /// Form ID of the FLST form list of the NPCs that this cell's owner lock list allows in (XILL)
+    #[serde(default)]
+    pub lock_list_form_id: Option<u32>,
+    /// Form IDs of the references that are part of the precomputed visibility (previs) data of this cell (XPRI). Only
+    /// Fallout 4 cells have previs data.
+    #[serde(default)]
+    pub previs_references: Vec<u32>,
+    /// Meshes that the Creation Kit combined the references of this cell into (XCRI). Only Fallout 4 cells have
+    /// precombined meshes.
+    #[serde(default)]
+    pub precombined: Option<Precombined>,
+    /// Form IDs of the records (REFR, ACHR, etc.) in the persistent children group of this cell
+    pub persistent_children: Vec<u32>,
+    /// Form IDs of the records (REFR, ACHR, LAND, NAVM, etc.) in the temporary children group of this cell
+    pub temporary_children: Vec<u32>,
+    /// Actors placed in this cell by the ACHR (and in Fallout 3 and New Vegas, ACRE) records in its children groups
+    pub actors: Vec<ActorPlacement>,
+    /// Map markers placed in this cell by the REFR records in its children groups.
+    ///
+    /// Only populated if [`ParseOptions::include_map_markers`] is set.
+    #[serde(default)]
+    pub map_markers: Vec<MapMarker>,
+    /// Timestamps and version control info from the header of the CELL record and its group
+    #[serde(default)]
+    pub metadata: RecordMetadata,
+    /// Form version from the header of the CELL record, which is 43 for records last saved by the Creation Kit of
+    /// Skyrim LE and 44 for Skyrim SE. `None` for Oblivion, which has no form versions.
+    #[serde(default)]
+    pub form_version: Option<u16>,
+    /// The data of the CELL record exactly as it is stored in the plugin, after the record header. The data is
+    /// compressed if `record_flags` has `COMPRESSED`.
+    ///
+    /// Only populated if [`ParseOptions::include_raw_data`] is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_data: Option<Vec<u8>>,
+}
+
+// cells are hashed by the fields that identify them, since hashing every field of a cell would be slow
+impl Hash for Cell<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.form_id.hash(state);
+        self.editor_id.hash(state);
+        self.world_form_id.hash(state);
+        self.x.hash(state);
+        self.y.hash(state);
+    }
+}
+
+impl<'a> Cell<'a> {
+    /// Creates an interior cell with no fields set other than its flags, for adding to a
+    /// [`PluginBuilder`](crate::PluginBuilder)
+    pub fn new_interior(form_id: u32, editor_id: Option<String>) -> Self {
+        Cell {
+            editor_id: editor_id.map(Cow::Owned),
+            flags: CellFlags::INTERIOR,
+            ..Self::new(form_id)
+        }
+    }
+
+    /// Creates an exterior cell of a world with no fields set other than its coordinates, for adding to a
+    /// [`PluginBuilder`](crate::PluginBuilder)
+    pub fn new_exterior(form_id: u32, world_form_id: u32, x: i32, y: i32) -> Self {
+        Cell {
+            x: Some(x),
+            y: Some(y),
+            world_form_id: Some(world_form_id),
+            ..Self::new(form_id)
+        }
+    }
+
+    /// Copies the editor id borrowed from the plugin file so that the cell can outlive it
+    pub fn into_owned(self) -> Cell<'static> {
+        Cell {
+            editor_id: self
+                .editor_id
+                .map(|editor_id| Cow::Owned(editor_id.into_owned())),
+            form_id: self.form_id,
+            name: self.name,
+            x: self.x,
+            y: self.y,
+            world_form_id: self.world_form_id,
+            is_persistent: self.is_persistent,
+            is_deleted: self.is_deleted,
+            is_ignored: self.is_ignored,
+            record_flags: self.record_flags,
+            music_type_form_id: self.music_type_form_id,
+            image_space_form_id: self.image_space_form_id,
+            regions: self.regions,
+            flags: self.flags,
+            water_height: self.water_height,
+            lighting_template_form_id: self.lighting_template_form_id,
+            lighting_template_inherit_flags: self.lighting_template_inherit_flags,
+            encounter_zone_form_id: self.encounter_zone_form_id,
+            occlusion_data_size: self.occlusion_data_size,
+            water_velocities: self.water_velocities,
+            climate_form_id: self.climate_form_id,
+            acoustic_space_form_id: self.acoustic_space_form_id,
+            water_type_form_id: self.water_type_form_id,
+            lock_list_form_id: self.lock_list_form_id,
+            previs_references: self.previs_references,
+            precombined: self.precombined,
+            persistent_children: self.persistent_children,
+            temporary_children: self.temporary_children,
+            actors: self.actors,
+            map_markers: self.map_markers,
+            metadata: self.metadata,
+            form_version: self.form_version,
+            raw_data: self.raw_data,
+        }
+    }
+
+    /// Whether the cell is an interior cell
+    pub fn is_interior(&self) -> bool {
+        self.flags.contains(CellFlags::INTERIOR)
+    }
+
+    /// Whether the cell is an exterior cell of a world, including the special persistent cell of a world
+    pub fn is_exterior(&self) -> bool {
+        !self.is_interior()
+    }
+
+    /// Whether the cell has water
+    pub fn has_water(&self) -> bool {
+        self.flags.contains(CellFlags::HAS_WATER)
+    }
+
+    /// Replaces every form id in the cell with the result of `map`, including the form ids of its children, actors,
+    /// and map markers. Useful for resolving the form ids of cells from plugins with different masters.
+    pub fn map_form_ids(&mut self, mut map: impl FnMut(u32) -> u32) {
+        self.form_id = map(self.form_id);
+        for form_id in [
+            &mut self.world_form_id,
+            &mut self.music_type_form_id,
+            &mut self.image_space_form_id,
+            &mut self.lighting_template_form_id,
+            &mut self.encounter_zone_form_id,
+            &mut self.climate_form_id,
+            &mut self.acoustic_space_form_id,
+            &mut self.water_type_form_id,
+            &mut self.lock_list_form_id,
+        ]
+        .iter_mut()
+        .filter_map(|form_id| form_id.as_mut())
+        {
+            *form_id = map(*form_id);
+        }
+        for form_id in self
+            .regions
+            .iter_mut()
+            .chain(self.previs_references.iter_mut())
+            .chain(self.persistent_children.iter_mut())
+            .chain(self.temporary_children.iter_mut())
+        {
+            *form_id = map(*form_id);
+        }
+        if let Some(precombined) = &mut self.precombined {
+            for reference in &mut precombined.references {
+                reference.form_id = map(reference.form_id);
+            }
+        }
+        for actor in &mut self.actors {
+            actor.form_id = map(actor.form_id);
+            actor.base_form_id = actor.base_form_id.map(&mut map);
+        }
+        for map_marker in &mut self.map_markers {
+            map_marker.form_id = map(map_marker.form_id);
+        }
+    }
+
+    fn new(form_id: u32) -> Self {
+        Cell {
+            form_id,
+            editor_id: None,
+            name: None,
+            x: None,
+            y: None,
+            world_form_id: None,
+            is_persistent: false,
+            is_deleted: false,
+            is_ignored: false,
+            record_flags: RecordFlags::empty(),
+            music_type_form_id: None,
+            image_space_form_id: None,
+            regions: vec![],
+            flags: CellFlags::empty(),
+            water_height: None,
+            lighting_template_form_id: None,
+            lighting_template_inherit_flags: None,
+            encounter_zone_form_id: None,
+            occlusion_data_size: None,
+            water_velocities: vec![],
+            climate_form_id: None,
+            acoustic_space_form_id: None,
+            water_type_form_id: None,
+            lock_list_form_id: None,
+            previs_references: vec![],
+            precombined: None,
+            persistent_children: vec![],
+            temporary_children: vec![],
+            actors: vec![],
+            map_markers: vec![],
+            metadata: RecordMetadata::default(),
+            form_version: None,
+            raw_data: None,
+        }
+    }
+}
+
+/// A placed NPC parsed from an [ACHR record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/ACHR) in the children of a [`Cell`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ActorPlacement {
+    pub form_id: u32,
+    /// Form ID of the NPC_ record that this actor is a placement of (NAME)
+    pub base_form_id: Option<u32>,
+    pub is_persistent: bool,
+    pub is_initially_disabled: bool,
+}
+
+/// A current of the water in a [`Cell`], from an entry of the XWCU field of the
+/// [CELL record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/CELL)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct WaterVelocity {
+    /// Position of the current relative to the cell
+    pub offset: [Float; 3],
+    /// Direction of the current
+    pub angle: [Float; 3],
+}
+
+/// An `f32` field of a record, which is compared and hashed by its bits so that the structs holding it can be `Eq` and
+/// `Hash`. Unlike comparing `f32`s, a NaN equals itself and `0.0` doesn't equal `-0.0`.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Float(pub f32);
+
+impl PartialEq for Float {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.to_bits() == other.0.to_bits()
+    }
+}
+
+impl Eq for Float {}
+
+impl Hash for Float {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.to_bits().hash(state);
+    }
+}
+
+// written out like the `f32` it holds, so that the text output shows plain numbers
+impl fmt::Debug for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Float {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Meshes that the Creation Kit of Fallout 4 combined the static references of a [`Cell`] into, from the XCRI field of
+/// the [CELL record](https://en.uesp.net/wiki/Fallout4Mod:Mod_File_Format/CELL)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Precombined {
+    /// Hashes of the combined meshes, which are the names of their files in `Meshes\PreCombined`
+    pub meshes: Vec<u32>,
+    /// The references that were combined and the mesh that each of them is in
+    pub references: Vec<PrecombinedReference>,
+}
+
+/// A reference that is part of a combined mesh of a [`Cell`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PrecombinedReference {
+    /// Form ID of the REFR record of the reference
+    pub form_id: u32,
+    /// Hash of the combined mesh that the reference is in
+    pub mesh: u32,
+}
+
+impl From<f32> for Float {
+    fn from(value: f32) -> Self {
+        Float(value)
+    }
+}
+
+impl From<Float> for f32 {
+    fn from(value: Float) -> Self {
+        value.0
+    }
+}
+
+/// A map marker parsed from a [REFR record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/REFR) of the
+/// MapMarker static in the children of a [`Cell`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct MapMarker {
+    pub form_id: u32,
+    /// Name of the marker shown on the map (FULL)
+    pub name: Option<LString>,
+    /// Type of the marker, which selects its icon on the map, like 1 for a city or 4 for a cave (TNAM)
+    pub marker_type: Option<u8>,
+    /// Flags of the marker (FNAM): 0x1 if it is visible, 0x2 if it can be fast traveled to, 0x4 if it is shown even
+    /// when hidden markers aren't
+    pub flags: Option<u8>,
+    /// Position of the reference in its world or cell (from DATA)
+    pub position: Option<[Float; 3]>,
 }
 
 #[derive(Debug)]
-struct CellData {
-    editor_id: Option<String>,
+struct CellData<'a> {
+    editor_id: Option<Cow<'a, str>>,
+    name: Option<LString>,
     x: Option<i32>,
     y: Option<i32>,
+    music_type_form_id: Option<u32>,
+    image_space_form_id: Option<u32>,
+    regions: Vec<u32>,
+    flags: CellFlags,
+    water_height: Option<Float>,
+    lighting_template_form_id: Option<u32>,
+    lighting_template_inherit_flags: Option<LightingTemplateInheritFlags>,
+    encounter_zone_form_id: Option<u32>,
+    occlusion_data_size: Option<u32>,
+    water_velocities: Vec<WaterVelocity>,
+    climate_form_id: Option<u32>,
+    acoustic_space_form_id: Option<u32>,
+    water_type_form_id: Option<u32>,
+    lock_list_form_id: Option<u32>,
+    previs_references: Vec<u32>,
+    precombined: Option<Precombined>,
 }
 
+/// A problem found in the fields of a record, at a byte offset into the (possibly decompressed) data of the record
+#[derive(Debug)]
+struct FieldWarning {
+    kind: WarningKind,
+    offset: usize,
+    problem: String,
+}
+
+/// A CELL record found by [`parse_plugin_lazy`] whose data has not been decompressed or parsed yet.
+///
+/// The cell is only decompressed and parsed when [`UnparsedCell::parse`] is called.
 #[derive(Debug)]
 pub struct UnparsedCell<'a> {
+    /// The whole plugin file, for reporting the offsets of errors
+    input: &'a [u8],
+    record: UnparsedRecord<'a>,
+    children: CellChildren,
+    actors: Vec<UnparsedRecord<'a>>,
+    /// REFR records that may be map markers
+    references: Vec<UnparsedRecord<'a>>,
+    game: Game,
+    compression: Compression,
+    strict: bool,
+    include_raw_data: bool,
+}
+
+/// A plugin whose CELL records have been found, but not decompressed or parsed yet. Returned by
+/// [`parse_plugin_lazy`].
+#[derive(Debug)]
+pub struct UnparsedPlugin<'a> {
+    pub header: PluginHeader<'a>,
+    pub worlds: HashSet<World>,
+    pub cells: Vec<UnparsedCell<'a>>,
+}
+
+/// A record that has had it's header parsed, but whose (possibly compressed) data has not been parsed yet
+#[derive(Debug)]
+pub(crate) struct UnparsedRecord<'a> {
+    record_type: &'a str,
     form_id: u32,
     world_form_id: Option<u32>,
-    is_compressed: bool,
-    is_persistent: bool,
+    /// The CELL that this record is a child of
+    cell_form_id: Option<u32>,
+    /// Whether the plugin's strings are stored in string tables, for parsing the string fields of the record
+    is_localized: bool,
+    flags: RecordFlags,
+    metadata: RecordMetadata,
+    form_version: u16,
     data: &'a [u8],
 }
 
-/// A CELL record that has had it's header parsed and data decompressed, but not yet parsed into individual fields
-#[derive(Debug)]
-struct DecompressedCell {
-    pub form_id: u32,
-    world_form_id: Option<u32>,
-    pub is_persistent: bool,
-    pub data: Vec<u8>,
+/// Where in the group hierarchy of a plugin the records currently being walked are
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct GroupContext {
+    /// The WRLD that the records belong to
+    pub(crate) world_form_id: Option<u32>,
+    /// The CELL that the records are children of
+    pub(crate) cell_form_id: Option<u32>,
+    /// Type of the innermost group containing the records
+    pub(crate) group_type: Option<i32>,
+    /// Whether the plugin stores string fields as ids into string tables
+    pub(crate) is_localized: bool,
+    /// Timestamp of the innermost group containing the records
+    pub(crate) group_timestamp: u16,
+    /// Version control info of the innermost group containing the records
+    pub(crate) group_version_control_info: u16,
+}
+
+/// Form IDs of the records in the children groups of a CELL
+#[derive(Debug, Default)]
+struct CellChildren {
+    persistent: Vec<u32>,
+    temporary: Vec<u32>,
+}
+
+/// Records collected while walking the groups of a plugin
+#[derive(Debug, Default)]
+struct GroupContents<'a> {
+    worlds: HashSet<World>,
+    /// Form IDs of the WRLD records left out by [`ParseOptions::worldspace_filter`]
+    skipped_worlds: HashSet<u32>,
+    cells: Vec<UnparsedRecord<'a>>,
+    cell_children: HashMap<u32, CellChildren>,
+    actors: Vec<UnparsedRecord<'a>>,
+    /// REFR records that may be map markers
+    references: Vec<UnparsedRecord<'a>>,
+    /// Records in the persistent children groups of the cells of worlds, for [`ParseOptions::assign_persistent_refs`]
+    persistent_refs: Vec<UnparsedRecord<'a>>,
+    locations: Vec<UnparsedRecord<'a>>,
+    regions: Vec<UnparsedRecord<'a>>,
+    lands: Vec<UnparsedRecord<'a>>,
+    navmeshes: Vec<UnparsedRecord<'a>>,
+    warnings: Vec<ParseWarning>,
+}
+
+/// Timestamps and version control info from the header of a record and the header of the innermost group containing
+/// it, which the Creation Kit updates when a record is saved.
+///
+/// The timestamps are dates packed into 16 bits in a format that differs between games, which are decoded into `date`
+/// and `group_date`. The version control info holds the ids of the users that last checked the record and group out of
+/// version control, which is usually 0 in plugins made outside of Bethesda.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct RecordMetadata {
+    pub timestamp: u16,
+    pub version_control_info: u16,
+    pub group_timestamp: u16,
+    pub group_version_control_info: u16,
+    /// The date that the record was last saved on, decoded from `timestamp`
+    #[serde(default)]
+    pub date: Option<RecordDate>,
+    /// The date that the group was last saved on, decoded from `group_timestamp` in the same format as `timestamp`
+    #[serde(default)]
+    pub group_date: Option<RecordDate>,
+}
+
+/// A calendar date decoded from the timestamp of a record or group header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RecordDate {
+    pub year: u16,
+    /// Month of the year, from 1 to 12
+    pub month: u8,
+    /// Day of the month, from 1 to 31
+    pub day: u8,
+}
+
+impl RecordDate {
+    /// Decodes the timestamp of a record with the form version `form_version`, which tells the format apart.
+    ///
+    /// Skyrim Special Edition and Fallout 4 (form version 44 and up) pack the year since 2000, the month, and the day
+    /// into 7, 4, and 5 bits. Original Skyrim and the older games store the day in the low byte and the number of months
+    /// since December 2002 in the high byte. Returns `None` for timestamps that are 0 or aren't a valid date.
+    pub fn from_timestamp(timestamp: u16, form_version: u16) -> Option<RecordDate> {
+        let (year, month, day) = if form_version >= PACKED_TIMESTAMP_FORM_VERSION {
+            (
+                2000 + (timestamp >> 9),
+                (timestamp >> 5) & 0x0F,
+                timestamp & 0x1F,
+            )
+        } else {
+            let months = (timestamp >> 8).checked_sub(1)?;
+            (2003 + months / 12, months % 12 + 1, timestamp & 0xFF)
+        };
+        if (1..=12).contains(&month) && (1..=31).contains(&day) {
+            Some(RecordDate {
+                year,
+                month: month as u8,
+                day: day as u8,
+            })
+        } else {
+            None
+        }
+    }
 }
 
 /// Parsed [WRLD records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/WRLD)
@@ -91,30 +839,216 @@ pub struct World {
     /// If the first byte of the `form_id` is the length of the `masters` array, then this plugin owns the `World`.
     pub form_id: u32,
     pub editor_id: String,
+    /// Display name of the worldspace (FULL)
+    pub name: Option<LString>,
+    /// Form ID of the parent worldspace that this worldspace is a child of (WNAM)
+    #[serde(default)]
+    pub parent_form_id: Option<u32>,
+    /// Which data this worldspace uses from its parent worldspace instead of its own (PNAM)
+    #[serde(default)]
+    pub parent_use_flags: Option<ParentUseFlags>,
+    /// Every flag set in the header of the WRLD record
+    #[serde(default = "RecordFlags::empty")]
+    pub record_flags: RecordFlags,
+    /// Timestamps and version control info from the header of the WRLD record and its group
+    #[serde(default)]
+    pub metadata: RecordMetadata,
+    /// The data of the WRLD record exactly as it is stored in the plugin, after the record header. The data is
+    /// compressed if `record_flags` has `COMPRESSED`.
+    ///
+    /// Only populated if [`ParseOptions::include_raw_data`] is set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_data: Option<Vec<u8>>,
+}
+
+impl World {
+    /// Creates a world with no fields set other than its editor id, for adding to a
+    /// [`PluginBuilder`](crate::PluginBuilder)
+    pub fn new(form_id: u32, editor_id: impl Into<String>) -> Self {
+        World {
+            form_id,
+            editor_id: editor_id.into(),
+            name: None,
+            parent_form_id: None,
+            parent_use_flags: None,
+            record_flags: RecordFlags::empty(),
+            metadata: RecordMetadata::default(),
+            raw_data: None,
+        }
+    }
+
+    /// Form ID of the parent worldspace if this worldspace uses the land data of its parent, which makes the cells of
+    /// this worldspace part of the parent's land
+    pub fn parent_land_form_id(&self) -> Option<u32> {
+        match self.parent_use_flags {
+            Some(flags) if flags.contains(ParentUseFlags::LAND_DATA) => self.parent_form_id,
+            _ => None,
+        }
+    }
 }
 
+/// A string field of a record.
+///
+/// If the plugin is localized (see [`PluginHeader::is_localized`]), the field only holds the id of the string in the
+/// plugin's string tables. The ids can be resolved to strings with [`StringTables`](crate::StringTables).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LString {
+    /// A string stored directly in the plugin
+    String(String),
+    /// An id into the plugin's string tables
+    Id(u32),
+}
+
+/// Parsed [LAND records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/LAND) with the terrain of an exterior cell
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Land {
+    pub form_id: u32,
+    /// The exterior [`Cell`] that this land belongs to.
+    pub cell_form_id: Option<u32>,
+    /// The [`World`] that this land belongs to.
+    pub world_form_id: Option<u32>,
+    /// X coordinate of the owning cell, if the cell record is also in this plugin
+    pub x: Option<i32>,
+    /// Y coordinate of the owning cell, if the cell record is also in this plugin
+    pub y: Option<i32>,
+    /// Height of the first vertex of the heightmap, in units of 8 game units (VHGT)
+    pub height_offset: Option<f32>,
+    /// 33x33 row-major grid of height deltas, each relative to the previous vertex in the row, or to the first vertex
+    /// of the previous row for the first vertex of a row (VHGT)
+    pub height_gradients: Vec<i8>,
+    /// 33x33 row-major grid of vertex normals as x, y, z triples (VNML)
+    pub normals: Vec<[u8; 3]>,
+}
+
+impl Land {
+    /// Number of vertices along each side of the heightmap grid
+    pub const GRID_SIZE: usize = 33;
+
+    /// Computes the absolute 33x33 row-major grid of vertex heights in game units from the height offset and gradients.
+    ///
+    /// Returns `None` if the LAND record has no heightmap.
+    pub fn heights(&self) -> Option<Vec<f32>> {
+        let height_offset = self.height_offset?;
+        if self.height_gradients.len() < Self::GRID_SIZE * Self::GRID_SIZE {
+            return None;
+        }
+        let mut heights = Vec::with_capacity(Self::GRID_SIZE * Self::GRID_SIZE);
+        let mut row_start = height_offset;
+        for row in self.height_gradients.chunks_exact(Self::GRID_SIZE) {
+            row_start += f32::from(row[0]);
+            let mut height = row_start;
+            heights.push(height * 8.0);
+            for &gradient in &row[1..] {
+                height += f32::from(gradient);
+                heights.push(height * 8.0);
+            }
+        }
+        Some(heights)
+    }
+}
+
+/// Parsed [NAVM records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/NAVM) describing the navmesh of part of a cell
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Navmesh {
+    pub form_id: u32,
+    /// The [`Cell`] that this navmesh belongs to.
+    pub cell_form_id: Option<u32>,
+    /// The [`World`] that this navmesh belongs to.
+    pub world_form_id: Option<u32>,
+    pub vertex_count: u32,
+    pub triangle_count: u32,
+}
+
+/// Parsed [LCTN records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/LCTN)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub form_id: u32,
+    pub editor_id: Option<String>,
+    /// Display name of the location (FULL)
+    pub name: Option<LString>,
+    /// Form ID of the LCTN record that this location is inside of (PNAM)
+    pub parent_location_form_id: Option<u32>,
+    /// Form ID of the REFR marking where this location is in the world (MNAM)
+    pub world_location_marker_form_id: Option<u32>,
+    /// Radius around the world location marker that this location covers (RNAM)
+    pub world_location_radius: Option<f32>,
+    /// Exterior cells that are part of this location (LCEC and ACEC)
+    pub cells: Vec<LocationCell>,
+}
+
+/// Coordinates of an exterior cell that is part of a [`Location`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LocationCell {
+    /// The [`World`] that the cell belongs to.
+    pub world_form_id: u32,
+    pub x: i32,
+    pub y: i32,
+}
+
+/// Parsed [REGN records](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/REGN)
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Region {
+    pub form_id: u32,
+    pub editor_id: Option<String>,
+    /// The [`World`] that this region is in (WNAM)
+    pub world_form_id: Option<u32>,
+    /// Types of the region data entries (RDAT) that this region defines
+    pub data_types: Vec<RegionDataType>,
+}
+
+/// Type of a region data entry from the RDAT field of a [`Region`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RegionDataType {
+    Objects,
+    Weather,
+    Map,
+    Land,
+    Grass,
+    Sound,
+    Unknown(u32),
+}
+
+impl From<u32> for RegionDataType {
+    fn from(data_type: u32) -> Self {
+        match data_type {
+            2 => RegionDataType::Objects,
+            3 => RegionDataType::Weather,
+            4 => RegionDataType::Map,
+            5 => RegionDataType::Land,
+            6 => RegionDataType::Grass,
+            7 => RegionDataType::Sound,
+            _ => RegionDataType::Unknown(data_type),
+        }
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Debug)]
-struct GroupHeader<'a> {
-    size: u32,
-    label: &'a [u8; 4],
-    group_type: i32,
-    timestamp: u16,
-    version_control_info: u16,
+pub(crate) struct GroupHeader<'a> {
+    pub(crate) size: u32,
+    pub(crate) label: &'a [u8; 4],
+    pub(crate) group_type: i32,
+    pub(crate) timestamp: u16,
+    pub(crate) version_control_info: u16,
 }
 
+#[allow(dead_code)]
 #[derive(Debug)]
-struct RecordHeader<'a> {
-    record_type: &'a str,
-    size: u32,
-    flags: RecordFlags,
-    id: u32,
-    timestamp: u16,
-    version_control_info: u16,
-    version: u16,
+pub(crate) struct RecordHeader<'a> {
+    pub(crate) record_type: &'a str,
+    pub(crate) size: u32,
+    pub(crate) flags: u32,
+    pub(crate) id: u32,
+    pub(crate) timestamp: u16,
+    pub(crate) version_control_info: u16,
+    pub(crate) version: u16,
 }
 
 bitflags! {
-    struct RecordFlags: u32 {
+    /// Flags from the header of a record. Some of the bits mean different things for different types of records, so
+    /// the flags are named after their most common meaning.
+    pub struct RecordFlags: u32 {
         const MASTER_FILE = 0x00000001;
         const DELETED_GROUP = 0x00000010;
         const DELETED_RECORD = 0x00000020;
@@ -142,8 +1076,180 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Flags from the record header of the [TES4 header record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/TES4)
+    pub struct PluginFlags: u32 {
+        const MASTER = 0x00000001;
+        const LOCALIZED = 0x00000080;
+        const LIGHT_MASTER = 0x00000200;
+    }
+}
+
+bitflags! {
+    /// Flags from the DATA field of a [CELL record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/CELL)
+    pub struct CellFlags: u16 {
+        const INTERIOR = 0x0001;
+        const HAS_WATER = 0x0002;
+        const CANT_TRAVEL_FROM_HERE = 0x0004;
+        const NO_LOD_WATER = 0x0008;
+        const PUBLIC_AREA = 0x0020;
+        const HAND_CHANGED = 0x0040;
+        const SHOW_SKY = 0x0080;
+        const USE_SKY_LIGHTING = 0x0100;
+        /// Fallout 4 only
+        const HIDDEN_FROM_INTERIOR_CELL_LIST = 0x0400;
+        /// Fallout 4 only
+        const SUNLIGHT_SHADOWS = 0x0800;
+        /// Fallout 4 only
+        const DISTANT_LOD_ONLY = 0x1000;
+        /// Fallout 4 only
+        const PLAYER_FOLLOWERS_CANT_TRAVEL_HERE = 0x2000;
+    }
+}
+
+/// Implements `Serialize` and `Deserialize` for a bitflags struct as a list of the names of the flags that are set.
+macro_rules! serde_flag_names {
+    ($flags:ident { $($flag:ident),* $(,)? }) => {
+        impl $flags {
+            /// Names of every flag that is set
+            pub fn names(&self) -> Vec<&'static str> {
+                let mut names = vec![];
+                $(
+                    if self.contains($flags::$flag) {
+                        names.push(stringify!($flag));
+                    }
+                )*
+                names
+            }
+        }
+
+        impl Serialize for $flags {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.names())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $flags {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+                let mut flags = $flags::empty();
+                for name in Vec::<String>::deserialize(deserializer)? {
+                    flags |= match name.as_str() {
+                        $(stringify!($flag) => $flags::$flag,)*
+                        _ => return Err(serde::de::Error::custom(format!("unknown flag {}", name))),
+                    };
+                }
+                Ok(flags)
+            }
+        }
+    };
+}
+
+serde_flag_names!(RecordFlags {
+    MASTER_FILE,
+    DELETED_GROUP,
+    DELETED_RECORD,
+    CONSTANT,
+    LOCALIZED,
+    INACCESSIBLE,
+    LIGHT_MASTER_FILE,
+    PERSISTENT_REFR,
+    INITIALLY_DISABLED,
+    IGNORED,
+    VISIBLE_WHEN_DISTANT,
+    RANDOM_ANIM_START,
+    OFF_LIMITS,
+    COMPRESSED,
+    CANT_WAIT,
+    IGNORE_OBJECT_INTERACTION,
+    IS_MARKER,
+    NO_AI_ACQUIRE,
+    NAVMESH_FILTER,
+    NAVMESH_BOUNDING_BOX,
+    REFLECTED_BY_AUTO_WATER,
+    DONT_HAVOK_SETTLE,
+    NO_RESPAWN,
+    MULTI_BOUND,
+});
+
+serde_flag_names!(PluginFlags {
+    MASTER,
+    LOCALIZED,
+    LIGHT_MASTER,
+});
+
+serde_flag_names!(CellFlags {
+    INTERIOR,
+    HAS_WATER,
+    CANT_TRAVEL_FROM_HERE,
+    NO_LOD_WATER,
+    PUBLIC_AREA,
+    HAND_CHANGED,
+    SHOW_SKY,
+    USE_SKY_LIGHTING,
+    HIDDEN_FROM_INTERIOR_CELL_LIST,
+    SUNLIGHT_SHADOWS,
+    DISTANT_LOD_ONLY,
+    PLAYER_FOLLOWERS_CANT_TRAVEL_HERE,
+});
+
+bitflags! {
+    /// Flags from the end of the XCLL field of a [CELL record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/CELL)
+    /// indicating which lighting values are inherited from the cell's lighting template
+    pub struct LightingTemplateInheritFlags: u32 {
+        const AMBIENT_COLOR = 0x0001;
+        const DIRECTIONAL_COLOR = 0x0002;
+        const FOG_COLOR = 0x0004;
+        const FOG_NEAR = 0x0008;
+        const FOG_FAR = 0x0010;
+        const DIRECTIONAL_ROTATION = 0x0020;
+        const DIRECTIONAL_FADE = 0x0040;
+        const CLIP_DISTANCE = 0x0080;
+        const FOG_POWER = 0x0100;
+        const FOG_MAX = 0x0200;
+        const LIGHT_FADE_DISTANCES = 0x0400;
+    }
+}
+
+bitflags! {
+    /// Flags from the PNAM field of a [WRLD record](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format/WRLD)
+    /// indicating which data a child worldspace uses from its parent worldspace
+    pub struct ParentUseFlags: u16 {
+        const LAND_DATA = 0x0001;
+        const LOD_DATA = 0x0002;
+        const MAP_DATA = 0x0004;
+        const WATER_DATA = 0x0008;
+        const CLIMATE_DATA = 0x0010;
+        const IMAGE_SPACE_DATA = 0x0020;
+        const SKY_CELL = 0x0040;
+    }
+}
+
+serde_flag_names!(ParentUseFlags {
+    LAND_DATA,
+    LOD_DATA,
+    MAP_DATA,
+    WATER_DATA,
+    CLIMATE_DATA,
+    IMAGE_SPACE_DATA,
+    SKY_CELL,
+});
+
+serde_flag_names!(LightingTemplateInheritFlags {
+    AMBIENT_COLOR,
+    DIRECTIONAL_COLOR,
+    FOG_COLOR,
+    FOG_NEAR,
+    FOG_FAR,
+    DIRECTIONAL_ROTATION,
+    DIRECTIONAL_FADE,
+    CLIP_DISTANCE,
+    FOG_POWER,
+    FOG_MAX,
+    LIGHT_FADE_DISTANCES,
+});
+
 #[derive(Debug)]
-enum Header<'a> {
+pub(crate) enum Header<'a> {
     Group(GroupHeader<'a>),
     Record(RecordHeader<'a>),
 }
@@ -151,202 +1257,881 @@ enum Header<'a> {
 #[derive(Debug)]
 struct FieldHeader<'a> {
     field_type: &'a str,
-    size: u16,
+    size: u32,
 }
 
-/// Parses fields from the decompressed bytes of a CELL record. Returns remaining bytes of the input after parsing and the parsed Cell struct.
+/// Parses fields from the decompressed bytes of the CELL `record`. Returns remaining bytes of the input after parsing and the parsed Cell struct.
 fn parse_cell<'a>(
     input: &'a [u8],
-    form_id: u32,
-    is_persistent: bool,
-    world_form_id: Option<u32>,
-) -> IResult<&'a [u8], Cell> {
-    let (input, cell_data) = parse_cell_fields(input)?;
+    record: &UnparsedRecord,
+    game: Game,
+    strict: bool,
+    warnings: &mut Vec<FieldWarning>,
+) -> IResult<&'a [u8], Cell<'a>> {
+    let (input, cell_data) = parse_cell_fields(input, game, record.is_localized, strict, warnings)?;
     Ok((
         input,
         Cell {
-            form_id,
+            form_id: record.form_id,
             editor_id: cell_data.editor_id,
+            name: cell_data.name,
             x: cell_data.x,
             y: cell_data.y,
-            world_form_id,
-            is_persistent,
+            world_form_id: record.world_form_id,
+            is_persistent: record.flags.contains(RecordFlags::PERSISTENT_REFR),
+            is_deleted: record.flags.contains(RecordFlags::DELETED_RECORD),
+            is_ignored: record.flags.contains(RecordFlags::IGNORED),
+            record_flags: record.flags,
+            music_type_form_id: cell_data.music_type_form_id,
+            image_space_form_id: cell_data.image_space_form_id,
+            regions: cell_data.regions,
+            flags: cell_data.flags,
+            water_height: cell_data.water_height,
+            lighting_template_form_id: cell_data.lighting_template_form_id,
+            lighting_template_inherit_flags: cell_data.lighting_template_inherit_flags,
+            encounter_zone_form_id: cell_data.encounter_zone_form_id,
+            occlusion_data_size: cell_data.occlusion_data_size,
+            water_velocities: cell_data.water_velocities,
+            climate_form_id: cell_data.climate_form_id,
+            acoustic_space_form_id: cell_data.acoustic_space_form_id,
+            water_type_form_id: cell_data.water_type_form_id,
+            lock_list_form_id: cell_data.lock_list_form_id,
+            previs_references: cell_data.previs_references,
+            precombined: cell_data.precombined,
+            persistent_children: vec![],
+            temporary_children: vec![],
+            actors: vec![],
+            map_markers: vec![],
+            metadata: record.metadata,
+            form_version: Some(record.form_version).filter(|_| game != Game::Oblivion),
+            raw_data: None,
         },
     ))
 }
 
-/// Maps the input `UnparsedCell`s to `DecompressedCell`s and decompresses the zlib compressed data sections of the record if necessary
-fn decompress_cells(unparsed_cells: Vec<UnparsedCell>) -> Result<Vec<DecompressedCell>> {
-    let mut decompressed_cells = Vec::new();
-    for unparsed_cell in unparsed_cells {
-        let decompressed_data = if unparsed_cell.is_compressed {
-            let mut buf = Vec::new();
-            let mut decoder = ZlibDecoder::new(&unparsed_cell.data[4..]);
-            decoder.read_to_end(&mut buf)?;
-            buf
-        } else {
-            unparsed_cell.data.to_vec()
-        };
-        decompressed_cells.push(DecompressedCell {
-            form_id: unparsed_cell.form_id,
-            world_form_id: unparsed_cell.world_form_id,
-            is_persistent: unparsed_cell.is_persistent,
-            data: decompressed_data,
+/// Byte offset of `data` within `input`, where `data` is a slice of `input`
+pub(crate) fn offset_in(input: &[u8], data: &[u8]) -> usize {
+    data.as_ptr() as usize - input.as_ptr() as usize
+}
+
+/// Decompresses the compressed data section of a record if necessary
+///
+/// Uncompressed data is borrowed from `data` rather than copied.
+pub(crate) fn decompress_record_data<'a>(
+    input: &[u8],
+    record_type: &str,
+    form_id: u32,
+    data: &'a [u8],
+    is_compressed: bool,
+    compression: Compression,
+) -> Result<Cow<'a, [u8]>> {
+    if is_compressed {
+        decompress(data, compression)
+            .map(Cow::Owned)
+            .map_err(|source| ParseError::DecompressError {
+                record_type: record_type.to_string(),
+                form_id,
+                offset: offset_in(input, data),
+                source,
+            })
+    } else {
+        Ok(Cow::Borrowed(data))
+    }
+}
+
+impl<'a> UnparsedRecord<'a> {
+    fn is_compressed(&self) -> bool {
+        self.flags.contains(RecordFlags::COMPRESSED)
+    }
+
+    /// Decompresses the data section of the record if necessary
+    fn decompress(&self, input: &[u8], compression: Compression) -> Result<Cow<'a, [u8]>> {
+        decompress_record_data(
+            input,
+            self.record_type,
+            self.form_id,
+            self.data,
+            self.is_compressed(),
+            compression,
+        )
+    }
+
+    /// Parses the editor id, name, and parent worldspace of a WRLD record, keeping the data of the record with
+    /// `include_raw_data`
+    pub(crate) fn parse_world(
+        &self,
+        input: &[u8],
+        is_localized: bool,
+        include_raw_data: bool,
+    ) -> Result<World> {
+        let (_, mut world) = parse_world_fields(self.data, self.form_id, is_localized)
+            .map_err(|err| self.parse_error(input, self.data, err))?;
+        world.record_flags = self.flags;
+        world.metadata = self.metadata;
+        if include_raw_data {
+            world.raw_data = Some(self.data.to_vec());
+        }
+        Ok(world)
+    }
+
+    /// Converts a problem found in the decompressed data of the record into a [`ParseWarning`]
+    fn warning(&self, input: &[u8], warning: FieldWarning) -> ParseWarning {
+        let err = ParseError::Malformed {
+            record_type: None,
+            form_id: None,
+            offset: warning.offset,
+            problem: warning.problem,
+        }
+        .in_record(
+            self.record_type,
+            Some(self.form_id),
+            offset_in(input, self.data),
+            self.is_compressed(),
+        );
+        ParseWarning::new(warning.kind, &err)
+    }
+
+    /// Converts an error from parsing the decompressed `data` of the record into a [`ParseError`], with the field
+    /// that it occurred in as its breadcrumb
+    fn parse_error(&self, input: &[u8], data: &[u8], err: nom::Err<NomError<&[u8]>>) -> ParseError {
+        let error = ParseError::from_nom(err, data);
+        let field_type = error
+            .offset()
+            .and_then(|offset| field_type_at(data, offset));
+        let error = error.in_record(
+            self.record_type,
+            Some(self.form_id),
+            offset_in(input, self.data),
+            self.is_compressed(),
+        );
+        match field_type {
+            Some(field_type) => error.within(vec![format!("field {}", field_type)]),
+            None => error,
+        }
+    }
+}
+
+impl<'a> UnparsedCell<'a> {
+    pub(crate) fn new(input: &'a [u8], record: UnparsedRecord<'a>, options: &ParseOptions) -> Self {
+        UnparsedCell {
+            input,
+            record,
+            children: CellChildren::default(),
+            actors: vec![],
+            references: vec![],
+            game: options.game,
+            compression: options.compression,
+            strict: options.strict,
+            include_raw_data: options.include_raw_data,
+        }
+    }
+
+    /// Adds a record from one of the children groups of the cell
+    pub(crate) fn add_child(
+        &mut self,
+        record: UnparsedRecord<'a>,
+        group_type: Option<i32>,
+        options: &ParseOptions,
+    ) {
+        if options.include_refs {
+            match group_type {
+                Some(8) => self.children.persistent.push(record.form_id),
+                Some(9) => self.children.temporary.push(record.form_id),
+                _ => {}
+            }
+        }
+        match record.record_type {
+            "ACHR" | "ACRE" if options.include_refs => self.actors.push(record),
+            "REFR" if options.include_map_markers => self.references.push(record),
+            _ => {}
+        }
+    }
+
+    pub fn form_id(&self) -> u32 {
+        self.record.form_id
+    }
+
+    /// Form ID of the WRLD record that the cell belongs to, `None` for interior cells
+    pub fn world_form_id(&self) -> Option<u32> {
+        self.record.world_form_id
+    }
+
+    pub fn is_persistent(&self) -> bool {
+        self.record.flags.contains(RecordFlags::PERSISTENT_REFR)
+    }
+
+    /// Whether the data of the record is compressed and will need to be decompressed when parsed
+    pub fn is_compressed(&self) -> bool {
+        self.record.is_compressed()
+    }
+
+    /// Decompresses and parses the fields of the cell and the actors placed in it
+    pub fn parse(&self) -> Result<Cell<'a>> {
+        self.parse_with_warnings(&mut vec![])
+    }
+
+    /// Parses the cell like [`UnparsedCell::parse`], adding the problems found in its fields to `warnings`
+    pub(crate) fn parse_with_warnings(&self, warnings: &mut Vec<ParseWarning>) -> Result<Cell<'a>> {
+        self.parse_records(warnings)
+            .map_err(|error| add_breadcrumbs(error, self.input, self.game))
+    }
+
+    /// Parses the fields of the CELL record from its (decompressed) `data`
+    fn parse_cell_data<'d>(
+        &self,
+        data: &'d [u8],
+        warnings: &mut Vec<FieldWarning>,
+    ) -> Result<Cell<'d>> {
+        let (_, cell) = parse_cell(data, &self.record, self.game, self.strict, warnings)
+            .map_err(|err| self.record.parse_error(self.input, data, err))?;
+        Ok(cell)
+    }
+
+    /// Parses the CELL record and the records of its children that are extracted
+    fn parse_records(&self, warnings: &mut Vec<ParseWarning>) -> Result<Cell<'a>> {
+        let mut field_warnings = vec![];
+        // only decompressed data has to be copied out of, uncompressed cells borrow from the plugin file
+        let mut cell = match self.record.decompress(self.input, self.compression)? {
+            Cow::Borrowed(data) => self.parse_cell_data(data, &mut field_warnings)?,
+            Cow::Owned(data) => self
+                .parse_cell_data(&data, &mut field_warnings)?
+                .into_owned(),
+        };
+        warnings.extend(
+            field_warnings
+                .into_iter()
+                .map(|warning| self.record.warning(self.input, warning)),
+        );
+        if self.include_raw_data {
+            cell.raw_data = Some(self.record.data.to_vec());
+        }
+        cell.persistent_children = self.children.persistent.clone();
+        cell.temporary_children = self.children.temporary.clone();
+        for unparsed_actor in &self.actors {
+            let data = unparsed_actor.decompress(self.input, self.compression)?;
+            let (_, base_form_id) = parse_actor_fields(&data)
+                .map_err(|err| unparsed_actor.parse_error(self.input, &data, err))?;
+            cell.actors.push(ActorPlacement {
+                form_id: unparsed_actor.form_id,
+                base_form_id,
+                is_persistent: unparsed_actor.flags.contains(RecordFlags::PERSISTENT_REFR),
+                is_initially_disabled: unparsed_actor
+                    .flags
+                    .contains(RecordFlags::INITIALLY_DISABLED),
+            });
+        }
+        for unparsed_reference in &self.references {
+            let data = unparsed_reference.decompress(self.input, self.compression)?;
+            let (_, map_marker) = parse_map_marker_fields(
+                &data,
+                unparsed_reference.form_id,
+                unparsed_reference.is_localized,
+            )
+            .map_err(|err| unparsed_reference.parse_error(self.input, &data, err))?;
+            cell.map_markers.extend(map_marker);
+        }
+        Ok(cell)
+    }
+}
+
+impl<'a> UnparsedPlugin<'a> {
+    /// Finds the unparsed CELL record with the form id
+    pub fn cell(&self, form_id: u32) -> Option<&UnparsedCell<'a>> {
+        self.cells.iter().find(|cell| cell.form_id() == form_id)
+    }
+}
+
+/// Takes the CELL records out of the walked `contents` and pairs them with their children, actors, and references
+fn take_unparsed_cells<'a>(
+    input: &'a [u8],
+    contents: &mut GroupContents<'a>,
+    options: &ParseOptions,
+) -> Vec<UnparsedCell<'a>> {
+    let mut cell_actors = group_by_cell(std::mem::take(&mut contents.actors));
+    let mut cell_references = group_by_cell(std::mem::take(&mut contents.references));
+    std::mem::take(&mut contents.cells)
+        .into_iter()
+        .map(|record| {
+            let form_id = record.form_id;
+            let mut cell = UnparsedCell::new(input, record, options);
+            if let Some(children) = contents.cell_children.remove(&form_id) {
+                cell.children = children;
+            }
+            if let Some(actors) = cell_actors.remove(&form_id) {
+                cell.actors = actors;
+            }
+            if let Some(references) = cell_references.remove(&form_id) {
+                cell.references = references;
+            }
+            cell
+        })
+        .collect()
+}
+
+/// Moves the persistent references in the persistent cell of each world of `plugin` into the exterior cell that the
+/// position of each reference is in, if the plugin has that cell
+fn assign_persistent_refs(
+    input: &[u8],
+    persistent_refs: Vec<UnparsedRecord>,
+    compression: Compression,
+    plugin: &mut Plugin,
+) -> Result<()> {
+    let mut ref_coords: HashMap<u32, (i32, i32)> = HashMap::new();
+    for record in persistent_refs {
+        let data = record.decompress(input, compression)?;
+        let (_, position) =
+            parse_position_fields(&data).map_err(|err| record.parse_error(input, &data, err))?;
+        if let Some([x, y, _]) = position {
+            let coords = (
+                (x / CELL_SIZE).floor() as i32,
+                (y / CELL_SIZE).floor() as i32,
+            );
+            ref_coords.insert(record.form_id, coords);
+        }
+    }
+
+    let mut cells: Vec<Cell> = plugin.cells.drain().collect();
+    let cell_indices: HashMap<(u32, i32, i32), usize> = cells
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| !cell.is_persistent)
+        .filter_map(|(index, cell)| Some(((cell.world_form_id?, cell.x?, cell.y?), index)))
+        .collect();
+    for persistent_index in 0..cells.len() {
+        let world_form_id = match cells[persistent_index].world_form_id {
+            Some(world_form_id) if cells[persistent_index].is_persistent => world_form_id,
+            _ => continue,
+        };
+        // the index of the cell that a reference is moved to, or of the persistent cell if it stays there
+        let target_index = |form_id: u32| {
+            ref_coords
+                .get(&form_id)
+                .and_then(|&(x, y)| cell_indices.get(&(world_form_id, x, y)).copied())
+                .unwrap_or(persistent_index)
+        };
+        let persistent_children = std::mem::take(&mut cells[persistent_index].persistent_children);
+        for form_id in persistent_children {
+            cells[target_index(form_id)]
+                .persistent_children
+                .push(form_id);
+        }
+        let actors = std::mem::take(&mut cells[persistent_index].actors);
+        for actor in actors {
+            cells[target_index(actor.form_id)].actors.push(actor);
+        }
+    }
+    plugin.cells = cells.into_iter().collect();
+    Ok(())
+}
+
+/// Groups records from the children groups of cells by the form id of their cell
+fn group_by_cell(records: Vec<UnparsedRecord>) -> HashMap<u32, Vec<UnparsedRecord>> {
+    let mut cell_records: HashMap<u32, Vec<UnparsedRecord>> = HashMap::new();
+    for record in records {
+        if let Some(cell_form_id) = record.cell_form_id {
+            cell_records.entry(cell_form_id).or_default().push(record);
+        }
+    }
+    cell_records
+}
+
+/// Parses the plugin header and walks the groups of the plugin, collecting the records of interest without parsing
+/// or decompressing their data.
+///
+/// If the walk fails partway through the plugin, the records collected before the failure are returned along with the
+/// error.
+fn parse_header_and_contents<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(PluginHeader<'a>, GroupContents<'a>, Option<ParseError>)> {
+    let (remaining, header) =
+        parse_plugin_header(input, options.game).map_err(|err| ParseError::from_nom(err, input))?;
+    let mut contents = GroupContents::default();
+    let mut walker = Walker::new(
+        input,
+        input.len() - remaining.len(),
+        options.game,
+        header.is_localized(),
+    )
+    .strict(options.strict);
+    let mut error = None;
+    while let Some(entry) = walker.next() {
+        let collected = match entry {
+            Ok((Entry::Group(group_header), _)) => {
+                if is_skipped_group(&group_header, options, &contents.skipped_worlds) {
+                    walker.skip_group();
+                }
+                Ok(())
+            }
+            Ok((Entry::Record(record_header, data), context)) => collect_record(
+                input,
+                &record_header,
+                data,
+                &context,
+                options,
+                &mut contents,
+            ),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = collected {
+            error = Some(err);
+            break;
+        }
+    }
+    contents.warnings = walker.take_warnings();
+    if options.collect_warnings {
+        contents
+            .warnings
+            .extend(check_header_version(&header, options.game));
+    }
+    if options.collect_warnings && error.is_none() {
+        let position = input.len() - remaining.len();
+        contents
+            .warnings
+            .extend(check_record_count(input, position, &header, options));
+    }
+    Ok((header, contents, error))
+}
+
+/// Checks the version in the HEDR field of the header against the versions of the plugins of the game
+fn check_header_version(header: &PluginHeader, game: Game) -> Option<ParseWarning> {
+    if game.header_versions().contains(&header.version) {
+        return None;
+    }
+    let err = ParseError::Malformed {
+        record_type: Some("TES4".to_string()),
+        form_id: Some(0),
+        offset: 0,
+        problem: format!(
+            "the header has version {} which no {:?} plugin has",
+            header.version, game
+        ),
+    };
+    Some(ParseWarning::new(WarningKind::HeaderVersion, &err))
+}
+
+/// Checks the number of records and groups in the HEDR field of the header against the number of them after the
+/// header, which takes another walk through the plugin since the groups that aren't parsed are skipped
+fn check_record_count(
+    input: &[u8],
+    position: usize,
+    header: &PluginHeader,
+    options: &ParseOptions,
+) -> Option<ParseWarning> {
+    let mut count: i64 = 0;
+    for entry in Walker::new(input, position, options.game, header.is_localized()) {
+        entry.ok()?;
+        count += 1;
+    }
+    record_count_warning(header, count)
+}
+
+/// Warns if the number of records and groups in the HEDR field of the header isn't `count`
+pub(crate) fn record_count_warning(header: &PluginHeader, count: i64) -> Option<ParseWarning> {
+    if count == i64::from(header.num_records_and_groups) {
+        return None;
+    }
+    let err = ParseError::Malformed {
+        record_type: Some("TES4".to_string()),
+        form_id: Some(0),
+        offset: 0,
+        problem: format!(
+            "the header counts {} records and groups but the plugin has {}",
+            header.num_records_and_groups, count
+        ),
+    };
+    Some(ParseWarning::new(WarningKind::RecordCount, &err))
+}
+
+/// Parses only the header (the TES4 record) from the start of the input bytes of a plugin file, without walking the
+/// groups of records after it.
+///
+/// Only the bytes of the header need to be passed in, so reading the first few kilobytes of a plugin is usually
+/// enough.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the start of the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::parse_header;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let header = parse_header(&plugin_contents).unwrap();
+/// println!("{:?} by {:?}", header.masters, header.author);
+/// ```
+pub fn parse_header(input: &[u8]) -> Result<PluginHeader<'_>> {
+    parse_header_with_options(input, &ParseOptions::default())
+}
+
+/// Parses only the header of a plugin like [`parse_header`], but for the game in the [`ParseOptions`]. The other
+/// options don't affect the header.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the start of the plugin file
+/// * `options` - Options with the game that the plugin was made for
+pub fn parse_header_with_options<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> Result<PluginHeader<'a>> {
+    let (_, header) =
+        parse_plugin_header(input, options.game).map_err(|err| ParseError::from_nom(err, input))?;
+    Ok(header)
+}
+
+/// Parses header and cell records from input bytes of a plugin file and outputs `Plugin` struct with extracted fields.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::parse_plugin;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let plugin = parse_plugin(&plugin_contents).unwrap();
+/// ```
+pub fn parse_plugin(input: &[u8]) -> Result<Plugin<'_>> {
+    parse_plugin_with_options(input, &ParseOptions::default())
+}
+
+/// Parses header and cell records from input bytes of a plugin file like [`parse_plugin`], but with [`ParseOptions`]
+/// that enable parsing of additional records.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options that control what is extracted from the plugin
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin_with_options, ParseOptions};
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let options = ParseOptions {
+///     include_land: true,
+///     ..Default::default()
+/// };
+/// let plugin = parse_plugin_with_options(&plugin_contents, &options).unwrap();
+/// ```
+pub fn parse_plugin_with_options<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> Result<Plugin<'a>> {
+    match parse_plugin_partial(input, options)? {
+        (plugin, None) => Ok(plugin),
+        (_, Some(error)) => Err(error),
+    }
+}
+
+/// Parses a plugin file like [`parse_plugin_with_options`], but if parsing fails partway through the plugin, returns
+/// everything that was parsed before the failure along with the error instead of only the error.
+///
+/// Only fails outright if the header of the plugin can't be parsed. Otherwise, the plugin is returned with the error
+/// that stopped the parse, or `None` if the whole plugin was parsed. Worlds and cells are collected while walking the
+/// groups of the plugin and then parsed, so a plugin that is cut off partway through still has all of the cells before
+/// the cut, but a cell that fails to parse stops the parse of the cells after it (unless [`ParseOptions::lenient`]
+/// is set).
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options that control what is extracted from the plugin
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin_partial, ParseOptions};
+///
+/// let plugin_contents = std::fs::read("Truncated.esp").unwrap();
+/// let (plugin, error) = parse_plugin_partial(&plugin_contents, &ParseOptions::default()).unwrap();
+/// if let Some(error) = error {
+///     eprintln!("Only parsed {} cells: {}", plugin.cells.len(), error);
+/// }
+/// ```
+pub fn parse_plugin_partial<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> Result<(Plugin<'a>, Option<ParseError>)> {
+    let (header, mut contents, walk_error) = parse_header_and_contents(input, options)?;
+    let mut plugin = Plugin {
+        header,
+        worlds: std::mem::take(&mut contents.worlds),
+        cells: HashSet::new(),
+        lands: vec![],
+        navmeshes: vec![],
+        locations: vec![],
+        regions: vec![],
+        issues: vec![],
+        warnings: vec![],
+    };
+    let parse_error = parse_contents(input, contents, options, &mut plugin).err();
+    // the records are only parsed after the walk, so an error from the walk comes first
+    let error = walk_error
+        .or(parse_error)
+        .map(|error| add_breadcrumbs(error, input, options.game));
+    Ok((plugin, error))
+}
+
+/// Parses the records collected from the groups of a plugin into `plugin`, stopping at the first one that fails
+fn parse_contents<'a>(
+    input: &'a [u8],
+    mut contents: GroupContents<'a>,
+    options: &ParseOptions,
+    plugin: &mut Plugin<'a>,
+) -> Result<()> {
+    let mut ignored_warnings = vec![];
+    let warnings = if options.collect_warnings {
+        &mut plugin.warnings
+    } else {
+        &mut ignored_warnings
+    };
+    warnings.append(&mut contents.warnings);
+    for unparsed_cell in take_unparsed_cells(input, &mut contents, options) {
+        match unparsed_cell.parse_with_warnings(warnings) {
+            Ok(cell) => {
+                plugin.cells.insert(cell);
+            }
+            Err(error) if options.lenient => {
+                // errors in the actors of the cell are recorded against the cell, since the whole cell is skipped
+                plugin.issues.push(ParseIssue {
+                    record_type: Some("CELL".to_string()),
+                    form_id: Some(unparsed_cell.form_id()),
+                    ..ParseIssue::from(&error)
+                });
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    if options.assign_persistent_refs {
+        let persistent_refs = std::mem::take(&mut contents.persistent_refs);
+        assign_persistent_refs(input, persistent_refs, options.compression, plugin)?;
+    }
+
+    let cell_coords: HashMap<u32, (Option<i32>, Option<i32>)> = plugin
+        .cells
+        .iter()
+        .map(|cell| (cell.form_id, (cell.x, cell.y)))
+        .collect();
+    for unparsed_land in contents.lands {
+        let data = unparsed_land.decompress(input, options.compression)?;
+        let (_, (height_offset, height_gradients, normals)) =
+            parse_land_fields(&data).map_err(|err| unparsed_land.parse_error(input, &data, err))?;
+        let (x, y) = unparsed_land
+            .cell_form_id
+            .and_then(|cell_form_id| cell_coords.get(&cell_form_id).copied())
+            .unwrap_or((None, None));
+        plugin.lands.push(Land {
+            form_id: unparsed_land.form_id,
+            cell_form_id: unparsed_land.cell_form_id,
+            world_form_id: unparsed_land.world_form_id,
+            x,
+            y,
+            height_offset,
+            height_gradients,
+            normals,
         });
     }
-    Ok(decompressed_cells)
-}
 
-/// Parses the plugin header and finds and extracts the headers and unparsed (and possibly compressed) data sections of every CELL record in the file.
-fn parse_header_and_cell_bytes(
-    input: &[u8],
-) -> IResult<&[u8], (PluginHeader, HashSet<World>, Vec<UnparsedCell>)> {
-    let (input, header) = parse_plugin_header(input)?;
-    let (input, (worlds, unparsed_cells)) = parse_group_data(input, input.len() as u32, 0, None)?;
-    Ok((input, (header, worlds, unparsed_cells)))
+    for unparsed_navmesh in contents.navmeshes {
+        let data = unparsed_navmesh.decompress(input, options.compression)?;
+        let (_, (vertex_count, triangle_count)) = parse_navmesh_fields(&data, options.game)
+            .map_err(|err| unparsed_navmesh.parse_error(input, &data, err))?;
+        plugin.navmeshes.push(Navmesh {
+            form_id: unparsed_navmesh.form_id,
+            cell_form_id: unparsed_navmesh.cell_form_id,
+            world_form_id: unparsed_navmesh.world_form_id,
+            vertex_count,
+            triangle_count,
+        });
+    }
+
+    let is_localized = plugin.header.is_localized();
+    for unparsed_location in contents.locations {
+        let data = unparsed_location.decompress(input, options.compression)?;
+        let (_, location) = parse_location_fields(&data, unparsed_location.form_id, is_localized)
+            .map_err(|err| unparsed_location.parse_error(input, &data, err))?;
+        plugin.locations.push(location);
+    }
+
+    for unparsed_region in contents.regions {
+        let data = unparsed_region.decompress(input, options.compression)?;
+        let (_, region) = parse_region_fields(&data, unparsed_region.form_id)
+            .map_err(|err| unparsed_region.parse_error(input, &data, err))?;
+        plugin.regions.push(region);
+    }
+    Ok(())
 }
 
-/// Parses header and cell records from input bytes of a plugin file and outputs `Plugin` struct with extracted fields.
+/// Finds the header and CELL records of a plugin file without decompressing or parsing the cells.
+///
+/// Each cell is only decompressed and parsed when [`UnparsedCell::parse`] is called on it, which is much faster than
+/// [`parse_plugin_with_options`] when only a few of the cells in a large plugin are needed.
 ///
 /// # Arguments
 ///
 /// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options that control how the plugin is parsed
 ///
 /// # Examples
 ///
-/// ```
-/// use skyrim_cell_dump::parse_plugin;
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin_lazy, ParseOptions};
 ///
-/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
-/// let plugin = parse_plugin(&plugin_contents).unwrap();
+/// let plugin_contents = std::fs::read("Skyrim.esm").unwrap();
+/// let plugin = parse_plugin_lazy(&plugin_contents, &ParseOptions::default()).unwrap();
+/// let cell = plugin.cell(0x0000_9732).unwrap().parse().unwrap();
 /// ```
-pub fn parse_plugin(input: &[u8]) -> Result<Plugin> {
-    let (_, (header, worlds, unparsed_cells)) = parse_header_and_cell_bytes(&input)
-        .map_err(|_err| anyhow!("Failed to parse plugin header and find CELL data"))?;
-    let decompressed_cells = decompress_cells(unparsed_cells)?;
-
-    let mut cells = HashSet::new();
-    for decompressed_cell in decompressed_cells {
-        let (_, cell) = parse_cell(
-            &decompressed_cell.data,
-            decompressed_cell.form_id,
-            decompressed_cell.is_persistent,
-            decompressed_cell.world_form_id,
-        )
-        .unwrap();
-        cells.insert(cell);
+pub fn parse_plugin_lazy<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> Result<UnparsedPlugin<'a>> {
+    let (header, mut contents, error) = parse_header_and_contents(input, options)?;
+    if let Some(error) = error {
+        return Err(add_breadcrumbs(error, input, options.game));
     }
-
-    Ok(Plugin {
+    let cells = take_unparsed_cells(input, &mut contents, options);
+    Ok(UnparsedPlugin {
         header,
-        worlds,
+        worlds: contents.worlds,
         cells,
     })
 }
 
-fn parse_group_data<'a>(
+impl<'a> UnparsedRecord<'a> {
+    pub(crate) fn new(
+        record_header: &RecordHeader<'a>,
+        data: &'a [u8],
+        context: &GroupContext,
+    ) -> Self {
+        UnparsedRecord {
+            record_type: record_header.record_type,
+            form_id: record_header.id,
+            world_form_id: context.world_form_id,
+            cell_form_id: context.cell_form_id,
+            is_localized: context.is_localized,
+            // Okay to truncate since we only care about bits we know about and don't want to crash on unknown bits.
+            flags: RecordFlags::from_bits_truncate(record_header.flags),
+            metadata: RecordMetadata {
+                timestamp: record_header.timestamp,
+                version_control_info: record_header.version_control_info,
+                group_timestamp: context.group_timestamp,
+                group_version_control_info: context.group_version_control_info,
+                date: RecordDate::from_timestamp(record_header.timestamp, record_header.version),
+                group_date: RecordDate::from_timestamp(
+                    context.group_timestamp,
+                    record_header.version,
+                ),
+            },
+            form_version: record_header.version,
+            data,
+        }
+    }
+}
+
+/// Whether the walk should skip over a group because none of the records in it are extracted
+pub(crate) fn is_skipped_group(
+    group_header: &GroupHeader,
+    options: &ParseOptions,
+    skipped_worlds: &HashSet<u32>,
+) -> bool {
+    match group_header.group_type {
+        0 => match str::from_utf8(group_header.label) {
+            Ok(label) => {
+                !PARSED_TOP_GROUPS.contains(&label)
+                    || (label == "CELL"
+                        && !(options.includes_interiors() && options.includes_cell_groups()))
+                    || (label == "WRLD"
+                        && !options.include_worlds
+                        && !options.includes_cell_groups())
+            }
+            Err(_) => true,
+        },
+        // world children groups are labeled with the form id of the world they belong to
+        1 => {
+            !options.includes_cell_groups()
+                || skipped_worlds.contains(&u32::from_le_bytes(*group_header.label))
+        }
+        7 => true,
+        _ => false,
+    }
+}
+
+/// Adds a record found while walking the plugin to `contents` if it is one of the records of interest
+fn collect_record<'a>(
     input: &'a [u8],
-    remaining_bytes: u32,
-    depth: usize,
-    world_form_id: Option<u32>,
-) -> IResult<&'a [u8], (HashSet<World>, Vec<UnparsedCell>)> {
-    let mut input = input;
-    let mut worlds = HashSet::new();
-    let mut cells = vec![];
-    let mut consumed_bytes = 0;
-    let mut world_form_id = world_form_id;
-    while !input.is_empty() && consumed_bytes < remaining_bytes {
-        let (remaining, record_header) = parse_header(input)?;
-        match record_header {
-            Header::Group(group_header) => {
-                if group_header.group_type == 0 {
-                    // TODO: get rid of unwrap
-                    let label = str::from_utf8(group_header.label).unwrap();
-                    if label != "WRLD" && label != "CELL" {
-                        let (remaining, _) =
-                            take(group_header.size - RECORD_HEADER_SIZE)(remaining)?;
-                        input = remaining;
-                        consumed_bytes += group_header.size;
-                        continue;
-                    } else {
-                        // reset world_form_id when entering new worldspace/cell group
-                        world_form_id = None;
-                    }
-                } else if group_header.group_type == 7 {
-                    // TODO: DRY
-                    let (remaining, _) = take(group_header.size - RECORD_HEADER_SIZE)(remaining)?;
-                    input = remaining;
-                    consumed_bytes += group_header.size;
-                    continue;
-                }
-                let (remaining, (inner_worlds, mut inner_cells)) = parse_group_data(
-                    remaining,
-                    group_header.size - RECORD_HEADER_SIZE,
-                    depth + 1,
-                    world_form_id,
-                )?;
-                worlds.extend(inner_worlds.into_iter());
-                cells.append(&mut inner_cells);
-                input = remaining;
-                consumed_bytes += group_header.size;
+    record_header: &RecordHeader<'a>,
+    data: &'a [u8],
+    context: &GroupContext,
+    options: &ParseOptions,
+    contents: &mut GroupContents<'a>,
+) -> Result<()> {
+    if let (Some(cell_form_id), true) = (context.cell_form_id, options.include_refs) {
+        let children = contents.cell_children.entry(cell_form_id).or_default();
+        match context.group_type {
+            Some(8) => children.persistent.push(record_header.id),
+            Some(9) => children.temporary.push(record_header.id),
+            _ => {}
+        }
+    }
+    if options.assign_persistent_refs
+        && context.group_type == Some(8)
+        && context.world_form_id.is_some()
+        && PLACED_RECORD_TYPES.contains(&record_header.record_type)
+    {
+        contents
+            .persistent_refs
+            .push(UnparsedRecord::new(record_header, data, context));
+    }
+    let record = UnparsedRecord::new(record_header, data, context);
+    match record_header.record_type {
+        "CELL" if options.include_cells => contents.cells.push(record),
+        "WRLD" => {
+            let world =
+                record.parse_world(input, context.is_localized, options.include_raw_data)?;
+            if !options.includes_world(&world.editor_id) {
+                contents.skipped_worlds.insert(world.form_id);
+            } else if options.include_worlds {
+                contents.worlds.insert(world);
             }
-            Header::Record(record_header) => match record_header.record_type {
-                "CELL" => {
-                    let (remaining, data) = take(record_header.size)(remaining)?;
-                    cells.push(UnparsedCell {
-                        form_id: record_header.id,
-                        world_form_id,
-                        is_compressed: record_header.flags.contains(RecordFlags::COMPRESSED),
-                        is_persistent: record_header.flags.contains(RecordFlags::PERSISTENT_REFR),
-                        data,
-                    });
-                    input = remaining;
-                    consumed_bytes += record_header.size + RECORD_HEADER_SIZE;
-                }
-                "WRLD" => {
-                    world_form_id = Some(record_header.id);
-                    let (remaining, editor_id) = parse_world_fields(remaining, &record_header)?;
-                    worlds.insert(World {
-                        form_id: record_header.id,
-                        editor_id,
-                    });
-                    input = remaining;
-                    consumed_bytes += record_header.size + RECORD_HEADER_SIZE;
-                }
-                _ => {
-                    let (remaining, _) = take(record_header.size)(remaining)?;
-                    input = remaining;
-                    consumed_bytes += record_header.size + RECORD_HEADER_SIZE;
-                }
-            },
         }
+        "LAND" if options.include_land => contents.lands.push(record),
+        "LCTN" => contents.locations.push(record),
+        "REGN" => contents.regions.push(record),
+        "ACHR" | "ACRE" if options.include_refs => contents.actors.push(record),
+        "REFR" if options.include_map_markers => contents.references.push(record),
+        "NAVM" if options.include_navmeshes => contents.navmeshes.push(record),
+        _ => {}
     }
-    Ok((input, (worlds, cells)))
+    Ok(())
 }
 
-fn parse_plugin_header(input: &[u8]) -> IResult<&[u8], PluginHeader> {
-    let (mut input, tes4) = verify(parse_record_header, |record_header| {
-        record_header.record_type == "TES4"
-    })(input)?;
-    let mut consumed_bytes = 0;
-    let (remaining, hedr) = verify(parse_field_header, |field_header| {
+pub(crate) fn parse_plugin_header(input: &[u8], game: Game) -> IResult<&[u8], PluginHeader<'_>> {
+    let (mut input, tes4) = verify(
+        |input| parse_record_header(input, game),
+        |record_header| record_header.record_type == "TES4",
+    )(input)
+    .map_err(expecting("TES4"))?;
+    let data = input;
+    let (remaining, _) = verify(parse_field_header, |field_header| {
         field_header.field_type == "HEDR"
-    })(input)?;
-    consumed_bytes += hedr.size as u32 + FIELD_HEADER_SIZE;
+    })(input)
+    .map_err(expecting("HEDR"))?;
     input = remaining;
     let (remaining, (version, num_records_and_groups, next_object_id)) = parse_hedr_fields(input)?;
     input = remaining;
     let mut author = None;
     let mut description = None;
     let mut masters = vec![];
-    let mut large_size = None;
-    while consumed_bytes < tes4.size as u32 {
+    let mut overridden_forms = vec![];
+    while offset_in(data, input) < tes4.size as usize {
         let (remaining, field) = parse_field_header(input)?;
-        if let Some(size) = large_size {
-            consumed_bytes += size + FIELD_HEADER_SIZE;
-        } else {
-            consumed_bytes += field.size as u32 + FIELD_HEADER_SIZE;
-        }
         input = remaining;
         match field.field_type {
             "CNAM" => {
@@ -364,27 +2149,21 @@ fn parse_plugin_header(input: &[u8]) -> IResult<&[u8], PluginHeader> {
                 input = remaining;
                 masters.push(master_str);
             }
-            "INTV" => {
-                let (remaining, _) = take(field.size)(input)?;
-                input = remaining;
-            }
-            "XXXX" => {
-                let (remaining, size) = le_u32(input)?;
+            "ONAM" => {
+                let (remaining, form_ids) = count(le_u32, field.size as usize / 4)(input)?;
                 input = remaining;
-                large_size = Some(size);
+                overridden_forms = form_ids;
             }
             _ => {
-                if let Some(size) = large_size {
-                    let (remaining, _) = take(size)(input)?;
-                    input = remaining;
-                    large_size = None;
-                } else {
-                    let (remaining, _) = take(field.size)(input)?;
-                    input = remaining;
-                }
+                let (remaining, _) = take(field.size)(input)?;
+                input = remaining;
             }
         }
     }
+    let mut flags = PluginFlags::from_bits_truncate(tes4.flags);
+    if !game.supports_light_and_localized_plugins() {
+        flags.remove(PluginFlags::LIGHT_MASTER | PluginFlags::LOCALIZED);
+    }
     Ok((
         input,
         PluginHeader {
@@ -394,11 +2173,13 @@ fn parse_plugin_header(input: &[u8]) -> IResult<&[u8], PluginHeader> {
             author,
             description,
             masters,
+            overridden_forms,
+            flags,
         },
     ))
 }
 
-fn parse_group_header(input: &[u8]) -> IResult<&[u8], GroupHeader> {
+fn parse_group_header(input: &[u8], game: Game) -> IResult<&[u8], GroupHeader<'_>> {
     let (input, _record_type) =
         verify(parse_4char, |record_type: &str| record_type == "GRUP")(input)?;
     let (input, size) = le_u32(input)?;
@@ -406,7 +2187,7 @@ fn parse_group_header(input: &[u8]) -> IResult<&[u8], GroupHeader> {
     let (input, group_type) = le_i32(input)?;
     let (input, timestamp) = le_u16(input)?;
     let (input, version_control_info) = le_u16(input)?;
-    let (input, _) = take(4usize)(input)?;
+    let (input, _) = take(game.record_header_size() - 20)(input)?;
     Ok((
         input,
         GroupHeader {
@@ -419,18 +2200,22 @@ fn parse_group_header(input: &[u8]) -> IResult<&[u8], GroupHeader> {
     ))
 }
 
-fn parse_record_header(input: &[u8]) -> IResult<&[u8], RecordHeader> {
+fn parse_record_header(input: &[u8], game: Game) -> IResult<&[u8], RecordHeader<'_>> {
     let (input, record_type) =
         verify(parse_4char, |record_type: &str| record_type != "GRUP")(input)?;
     let (input, size) = le_u32(input)?;
-    let (input, flag_bits) = le_u32(input)?;
-    // Okay to truncate since we only care about bits we know about and don't want to crash on unknown bits.
-    let flags = RecordFlags::from_bits_truncate(flag_bits);
+    let (input, flags) = le_u32(input)?;
     let (input, id) = le_u32(input)?;
     let (input, timestamp) = le_u16(input)?;
     let (input, version_control_info) = le_u16(input)?;
-    let (input, version) = le_u16(input)?;
-    let (input, _) = take(2usize)(input)?;
+    let (input, version) = if game == Game::Oblivion {
+        // Oblivion record headers end after the version control info
+        (input, 0)
+    } else {
+        let (input, version) = le_u16(input)?;
+        let (input, _) = take(2usize)(input)?;
+        (input, version)
+    };
     Ok((
         input,
         RecordHeader {
@@ -445,21 +2230,72 @@ fn parse_record_header(input: &[u8]) -> IResult<&[u8], RecordHeader> {
     ))
 }
 
-fn parse_header(input: &[u8]) -> IResult<&[u8], Header> {
+pub(crate) fn parse_group_or_record_header(input: &[u8], game: Game) -> IResult<&[u8], Header<'_>> {
     alt((
-        map(parse_group_header, |group_header| {
-            Header::Group(group_header)
-        }),
-        map(parse_record_header, |record_header| {
-            Header::Record(record_header)
-        }),
+        map(|input| parse_group_header(input, game), Header::Group),
+        map(|input| parse_record_header(input, game), Header::Record),
     ))(input)
 }
 
-fn parse_field_header(input: &[u8]) -> IResult<&[u8], FieldHeader> {
+/// Parses the header of the next field of a record.
+///
+/// Fields with more data than their 16-bit size can hold (like the OFST field of large worlds) are preceded by an XXXX
+/// field holding their real size. The XXXX field is combined with the header that follows it, so that parsers of every
+/// record type get the real size of the field.
+fn parse_field_header(input: &[u8]) -> IResult<&[u8], FieldHeader<'_>> {
     let (input, field_type) = parse_4char(input)?;
     let (input, size) = le_u16(input)?;
-    Ok((input, FieldHeader { field_type, size }))
+    if field_type == "XXXX" && size == 4 {
+        let (input, large_size) = le_u32(input)?;
+        let (input, field) = parse_field_header(input)?;
+        return Ok((
+            input,
+            FieldHeader {
+                field_type: field.field_type,
+                size: large_size,
+            },
+        ));
+    }
+    Ok((
+        input,
+        FieldHeader {
+            field_type,
+            size: size.into(),
+        },
+    ))
+}
+
+/// Type of the field of the decompressed record `data` that `offset` is in, if it has a valid field type
+fn field_type_at(data: &[u8], offset: usize) -> Option<&str> {
+    let mut input = data;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input).ok()?;
+        // a field that is cut off by the end of the record still contains the offset
+        let end = (offset_in(data, remaining) + field.size as usize).min(data.len());
+        if offset < end {
+            return Some(field.field_type).filter(|field_type| {
+                field_type
+                    .bytes()
+                    .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+            });
+        }
+        input = &data[end..];
+    }
+    None
+}
+
+/// Splits the decompressed data of a record into the type and data of each of its fields. The size in an XXXX field is
+/// used as the size of the field that follows it, and the XXXX field itself is left out.
+pub(crate) fn parse_raw_fields(input: &[u8]) -> IResult<&[u8], Vec<(&str, &[u8])>> {
+    let mut input = input;
+    let mut fields = vec![];
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        input = remaining;
+        fields.push((field.field_type, field_data));
+    }
+    Ok((input, fields))
 }
 
 fn parse_hedr_fields(input: &[u8]) -> IResult<&[u8], (f32, i32, u32)> {
@@ -469,21 +2305,52 @@ fn parse_hedr_fields(input: &[u8]) -> IResult<&[u8], (f32, i32, u32)> {
     Ok((input, (version, num_records_and_groups, next_object_id)))
 }
 
-fn parse_cell_fields<'a>(input: &'a [u8]) -> IResult<&'a [u8], CellData> {
+fn parse_cell_fields<'a>(
+    input: &'a [u8],
+    game: Game,
+    is_localized: bool,
+    strict: bool,
+    warnings: &mut Vec<FieldWarning>,
+) -> IResult<&'a [u8], CellData<'a>> {
+    let fields = input;
     let mut cell_data = CellData {
         editor_id: None,
+        name: None,
         x: None,
         y: None,
+        music_type_form_id: None,
+        image_space_form_id: None,
+        regions: vec![],
+        flags: CellFlags::empty(),
+        water_height: None,
+        lighting_template_form_id: None,
+        lighting_template_inherit_flags: None,
+        encounter_zone_form_id: None,
+        occlusion_data_size: None,
+        water_velocities: vec![],
+        climate_form_id: None,
+        acoustic_space_form_id: None,
+        water_type_form_id: None,
+        lock_list_form_id: None,
+        previs_references: vec![],
+        precombined: None,
     };
     let mut input = input;
-    let mut large_size = None;
     while !input.is_empty() {
+        let field_start = input;
         let (remaining, field) = parse_field_header(input)?;
         input = remaining;
+        let field_size = field.size as usize;
+        let data_start = input;
         match field.field_type {
             "EDID" => {
                 let (remaining, editor_id) = parse_zstring(input)?;
-                cell_data.editor_id = Some(editor_id.to_string());
+                cell_data.editor_id = Some(editor_id);
+                input = remaining;
+            }
+            "FULL" => {
+                let (remaining, name) = parse_lstring(input, is_localized)?;
+                cell_data.name = Some(name);
                 input = remaining;
             }
             "XCLC" => {
@@ -499,54 +2366,495 @@ fn parse_cell_fields<'a>(input: &'a [u8]) -> IResult<&'a [u8], CellData> {
                     input = remaining;
                 }
             }
-            "XXXX" => {
-                let (remaining, size) = le_u32(input)?;
+            "XCMO" => {
+                let (remaining, music_type_form_id) = le_u32(input)?;
+                cell_data.music_type_form_id = Some(music_type_form_id);
+                input = remaining;
+            }
+            "XCIM" => {
+                let (remaining, image_space_form_id) = le_u32(input)?;
+                cell_data.image_space_form_id = Some(image_space_form_id);
+                input = remaining;
+            }
+            "DATA" => {
+                // older (v. 0.94) files store the flags in a single byte
+                let (remaining, flag_bits) = if field.size == 1 {
+                    map(le_u8, u16::from)(input)?
+                } else {
+                    let (remaining, flag_bits) = le_u16(input)?;
+                    let (remaining, _) = take(field.size.saturating_sub(2))(remaining)?;
+                    (remaining, flag_bits)
+                };
+                cell_data.flags = CellFlags::from_bits_truncate(flag_bits);
+                input = remaining;
+            }
+            "XCLL" if field.size >= XCLL_INHERIT_FLAGS_OFFSET + 4 => {
+                let (remaining, _) = take(XCLL_INHERIT_FLAGS_OFFSET)(input)?;
+                let (remaining, flag_bits) = le_u32(remaining)?;
+                let (remaining, _) = take(field.size - XCLL_INHERIT_FLAGS_OFFSET - 4)(remaining)?;
+                cell_data.lighting_template_inherit_flags =
+                    Some(LightingTemplateInheritFlags::from_bits_truncate(flag_bits));
+                input = remaining;
+            }
+            "LNAM" if game.is_fallout3_family() => {
+                let (remaining, flag_bits) = le_u32(input)?;
+                cell_data.lighting_template_inherit_flags =
+                    Some(LightingTemplateInheritFlags::from_bits_truncate(flag_bits));
+                input = remaining;
+            }
+            "XCLW" => {
+                let (remaining, water_height) = le_f32(input)?;
+                cell_data.water_height = Some(Float(water_height));
+                input = remaining;
+            }
+            "LTMP" => {
+                let (remaining, lighting_template_form_id) = le_u32(input)?;
+                cell_data.lighting_template_form_id = Some(lighting_template_form_id);
+                input = remaining;
+            }
+            "XCLR" => {
+                let (remaining, regions) = count(le_u32, field.size as usize / 4)(input)?;
+                cell_data.regions = regions;
+                input = remaining;
+            }
+            "XEZN" => {
+                let (remaining, encounter_zone_form_id) = le_u32(input)?;
+                cell_data.encounter_zone_form_id = Some(encounter_zone_form_id);
+                input = remaining;
+            }
+            "TVDT" => {
+                let (remaining, _) = take(field.size)(input)?;
+                cell_data.occlusion_data_size = Some(field.size);
+                input = remaining;
+            }
+            "XWCN" => {
+                // the count of the currents in the following XWCU field, which is also given by its size
+                let (remaining, _) = le_u32(input)?;
+                input = remaining;
+            }
+            "XWCU" => {
+                let (remaining, water_velocities) = count(
+                    parse_water_velocity,
+                    field.size as usize / WATER_VELOCITY_SIZE,
+                )(input)?;
+                cell_data.water_velocities = water_velocities;
+                input = remaining;
+            }
+            "XCCM" => {
+                let (remaining, climate_form_id) = le_u32(input)?;
+                cell_data.climate_form_id = Some(climate_form_id);
+                input = remaining;
+            }
+            "XCAS" => {
+                let (remaining, acoustic_space_form_id) = le_u32(input)?;
+                cell_data.acoustic_space_form_id = Some(acoustic_space_form_id);
+                input = remaining;
+            }
+            "XCWT" => {
+                let (remaining, water_type_form_id) = le_u32(input)?;
+                cell_data.water_type_form_id = Some(water_type_form_id);
+                input = remaining;
+            }
+            "XILL" => {
+                let (remaining, lock_list_form_id) = le_u32(input)?;
+                cell_data.lock_list_form_id = Some(lock_list_form_id);
+                input = remaining;
+            }
+            "XPRI" => {
+                let (remaining, previs_references) = count(le_u32, field.size as usize / 4)(input)?;
+                cell_data.previs_references = previs_references;
+                input = remaining;
+            }
+            "XCRI" => {
+                let (remaining, precombined) = parse_precombined(input, field.size)?;
+                if precombined.is_none() {
+                    let problem = format!(
+                        "XCRI field has counts of meshes and references that don't match its size of {} bytes",
+                        field.size
+                    );
+                    if strict {
+                        return malformed(field_start, problem);
+                    }
+                    warnings.push(FieldWarning {
+                        kind: WarningKind::FieldSize,
+                        offset: offset_in(fields, field_start),
+                        problem,
+                    });
+                }
+                cell_data.precombined = precombined;
                 input = remaining;
-                large_size = Some(size);
             }
             "\0\0\0\0" => {
+                let problem = "trailing null bytes after the fields".to_string();
+                if strict {
+                    return malformed(field_start, problem);
+                }
+                warnings.push(FieldWarning {
+                    kind: WarningKind::TrailingNulls,
+                    offset: offset_in(fields, field_start),
+                    problem,
+                });
                 // Some plugins have 8-9 bytes of trailing null bytes in the decompressed interior CELL data
                 // This detects and skips those null bytes
                 let (remaining, _) = take_while(|c| c == b'\0')(input)?;
                 input = remaining;
+                continue;
             }
             _ => {
-                if let Some(size) = large_size {
-                    let (remaining, _) = take(size)(input)?;
-                    input = remaining;
-                    large_size = None;
-                } else {
-                    // Some plugins have decompressed cell data that is short 1-2 bytes and the parser is supposed
-                    // to substitute zero bytes there. Since we are not actually reading the fields at the end of
-                    // the CELL records, we just skip over the remaining bytes if the full 4 are not there.
-                    let (remaining, _) = alt((take(field.size), rest))(input)?;
-                    input = remaining;
+                if !CELL_FIELD_TYPES.contains(&field.field_type) {
+                    warnings.push(FieldWarning {
+                        kind: WarningKind::UnknownField,
+                        offset: offset_in(fields, field_start),
+                        problem: format!("unknown {} field", field.field_type),
+                    });
                 }
+                // Some plugins have decompressed cell data that is short 1-2 bytes and the parser is supposed
+                // to substitute zero bytes there. Since we are not actually reading the fields at the end of
+                // the CELL records, we just skip over the remaining bytes if the full 4 are not there.
+                let (remaining, _) = alt((take(field.size), rest))(input)?;
+                input = remaining;
             }
         }
+        let read_size = data_start.len() - input.len();
+        if read_size != field_size {
+            let (kind, problem) = if input.is_empty() && read_size < field_size {
+                (
+                    WarningKind::TruncatedField,
+                    format!(
+                        "{} field has a size of {} bytes but is cut off after {} bytes",
+                        field.field_type, field_size, read_size
+                    ),
+                )
+            } else {
+                (
+                    WarningKind::FieldSize,
+                    format!(
+                        "{} field has a size of {} bytes but {} bytes of it were read",
+                        field.field_type, field_size, read_size
+                    ),
+                )
+            };
+            if strict {
+                return malformed(field_start, problem);
+            }
+            warnings.push(FieldWarning {
+                kind,
+                offset: offset_in(fields, field_start),
+                problem,
+            });
+        }
     }
     Ok((input, cell_data))
 }
 
-fn parse_world_fields<'a>(
-    input: &'a [u8],
-    record_header: &RecordHeader,
-) -> IResult<&'a [u8], String> {
-    let (remaining, field) = verify(parse_field_header, |field_header| {
+/// Parses the XCRI field of `size` bytes of a Fallout 4 CELL record, which holds the counts of the meshes and the
+/// references followed by the meshes and then the references with the mesh each of them is in. Returns `None` after
+/// skipping the field if the counts don't match its size.
+fn parse_precombined(input: &[u8], size: u32) -> IResult<&[u8], Option<Precombined>> {
+    if size < 8 {
+        return Ok((take(size)(input)?.0, None));
+    }
+    let (data, mesh_count) = le_u32(input)?;
+    let (data, reference_count) = le_u32(data)?;
+    // the counts come from the plugin, so they are checked before anything is allocated for them
+    let listed_size = 8 + (u64::from(mesh_count) + 2 * u64::from(reference_count)) * 4;
+    if listed_size != u64::from(size) {
+        return Ok((take(size)(input)?.0, None));
+    }
+    let (data, meshes) = count(le_u32, mesh_count as usize)(data)?;
+    let (data, references) = count(
+        map(pair(le_u32, le_u32), |(form_id, mesh)| {
+            PrecombinedReference { form_id, mesh }
+        }),
+        reference_count as usize,
+    )(data)?;
+    Ok((data, Some(Precombined { meshes, references })))
+}
+
+fn parse_water_velocity(input: &[u8]) -> IResult<&[u8], WaterVelocity> {
+    let (input, offset) = count(le_f32, 3)(input)?;
+    let (input, _) = take(4usize)(input)?;
+    let (input, angle) = count(le_f32, 3)(input)?;
+    let (input, _) = take(4usize)(input)?;
+    Ok((
+        input,
+        WaterVelocity {
+            offset: [Float(offset[0]), Float(offset[1]), Float(offset[2])],
+            angle: [Float(angle[0]), Float(angle[1]), Float(angle[2])],
+        },
+    ))
+}
+
+type LandFields = (Option<f32>, Vec<i8>, Vec<[u8; 3]>);
+
+fn parse_land_fields(input: &[u8]) -> IResult<&[u8], LandFields> {
+    let mut height_offset = None;
+    let mut height_gradients = vec![];
+    let mut normals = vec![];
+    let mut input = input;
+    let grid_len = Land::GRID_SIZE * Land::GRID_SIZE;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        match field.field_type {
+            "VHGT" => {
+                let (field_data, offset) = le_f32(field_data)?;
+                let (_, gradients) = take(grid_len)(field_data)?;
+                height_offset = Some(offset);
+                height_gradients = gradients.iter().map(|&byte| byte as i8).collect();
+            }
+            "VNML" => {
+                let (_, normal_bytes) = take(grid_len * 3)(field_data)?;
+                normals = normal_bytes
+                    .chunks_exact(3)
+                    .map(|normal| [normal[0], normal[1], normal[2]])
+                    .collect();
+            }
+            _ => {}
+        }
+        input = remaining;
+    }
+    Ok((input, (height_offset, height_gradients, normals)))
+}
+
+fn parse_actor_fields(input: &[u8]) -> IResult<&[u8], Option<u32>> {
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        if field.field_type == "NAME" {
+            let (_, base_form_id) = le_u32(field_data)?;
+            return Ok((remaining, Some(base_form_id)));
+        }
+        input = remaining;
+    }
+    Ok((input, None))
+}
+
+/// Parses the position from the DATA field of a record that places something in a cell
+fn parse_position_fields(input: &[u8]) -> IResult<&[u8], Option<[f32; 3]>> {
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        if field.field_type == "DATA" {
+            let (field_data, x) = le_f32(field_data)?;
+            let (field_data, y) = le_f32(field_data)?;
+            let (_, z) = le_f32(field_data)?;
+            return Ok((remaining, Some([x, y, z])));
+        }
+        input = remaining;
+    }
+    Ok((input, None))
+}
+
+/// Parses the fields of a REFR record, returning the map marker if the reference is one
+fn parse_map_marker_fields(
+    input: &[u8],
+    form_id: u32,
+    is_localized: bool,
+) -> IResult<&[u8], Option<MapMarker>> {
+    let mut map_marker = MapMarker {
+        form_id,
+        name: None,
+        marker_type: None,
+        flags: None,
+        position: None,
+    };
+    let mut is_map_marker = false;
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        match field.field_type {
+            "NAME" => {
+                let (_, base_form_id) = le_u32(field_data)?;
+                is_map_marker |= base_form_id == MAP_MARKER_FORM_ID;
+            }
+            // the marker data starts with an empty XMRK field
+            "XMRK" => is_map_marker = true,
+            "FNAM" => {
+                let (_, flags) = le_u8(field_data)?;
+                map_marker.flags = Some(flags);
+            }
+            "FULL" => {
+                let (_, name) = parse_lstring(field_data, is_localized)?;
+                map_marker.name = Some(name);
+            }
+            "TNAM" => {
+                let (_, marker_type) = le_u8(field_data)?;
+                map_marker.marker_type = Some(marker_type);
+            }
+            "DATA" => {
+                let (field_data, x) = le_f32(field_data)?;
+                let (field_data, y) = le_f32(field_data)?;
+                let (_, z) = le_f32(field_data)?;
+                map_marker.position = Some([Float(x), Float(y), Float(z)]);
+            }
+            _ => {}
+        }
+        input = remaining;
+    }
+    Ok((input, Some(map_marker).filter(|_| is_map_marker)))
+}
+
+fn parse_location_fields(
+    input: &[u8],
+    form_id: u32,
+    is_localized: bool,
+) -> IResult<&[u8], Location> {
+    let mut location = Location {
+        form_id,
+        editor_id: None,
+        name: None,
+        parent_location_form_id: None,
+        world_location_marker_form_id: None,
+        world_location_radius: None,
+        cells: vec![],
+    };
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        match field.field_type {
+            "EDID" => {
+                let (_, editor_id) = parse_zstring(field_data)?;
+                location.editor_id = Some(editor_id.to_string());
+            }
+            "FULL" => {
+                let (_, name) = parse_lstring(field_data, is_localized)?;
+                location.name = Some(name);
+            }
+            "PNAM" => {
+                let (_, parent_location_form_id) = le_u32(field_data)?;
+                location.parent_location_form_id = Some(parent_location_form_id);
+            }
+            "MNAM" => {
+                let (_, world_location_marker_form_id) = le_u32(field_data)?;
+                location.world_location_marker_form_id = Some(world_location_marker_form_id);
+            }
+            "RNAM" => {
+                let (_, world_location_radius) = le_f32(field_data)?;
+                location.world_location_radius = Some(world_location_radius);
+            }
+            "LCEC" | "ACEC" => {
+                let (field_data, world_form_id) = le_u32(field_data)?;
+                for coords in field_data.chunks_exact(4) {
+                    location.cells.push(LocationCell {
+                        world_form_id,
+                        x: i16::from_le_bytes([coords[0], coords[1]]).into(),
+                        y: i16::from_le_bytes([coords[2], coords[3]]).into(),
+                    });
+                }
+            }
+            _ => {}
+        }
+        input = remaining;
+    }
+    Ok((input, location))
+}
+
+fn parse_region_fields(input: &[u8], form_id: u32) -> IResult<&[u8], Region> {
+    let mut region = Region {
+        form_id,
+        editor_id: None,
+        world_form_id: None,
+        data_types: vec![],
+    };
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        match field.field_type {
+            "EDID" => {
+                let (_, editor_id) = parse_zstring(field_data)?;
+                region.editor_id = Some(editor_id.to_string());
+            }
+            "WNAM" => {
+                let (_, world_form_id) = le_u32(field_data)?;
+                region.world_form_id = Some(world_form_id);
+            }
+            "RDAT" => {
+                let (_, data_type) = le_u32(field_data)?;
+                region.data_types.push(data_type.into());
+            }
+            _ => {}
+        }
+        input = remaining;
+    }
+    Ok((input, region))
+}
+
+fn parse_navmesh_fields(input: &[u8], game: Game) -> IResult<&[u8], (u32, u32)> {
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, field) = parse_field_header(input)?;
+        let (remaining, field_data) = take(field.size)(remaining)?;
+        if game.is_fallout3_family() && field.field_type == "DATA" {
+            // skip parent cell
+            let (field_data, _) = take(4usize)(field_data)?;
+            let (field_data, vertex_count) = le_u32(field_data)?;
+            let (_, triangle_count) = le_u32(field_data)?;
+            return Ok((remaining, (vertex_count, triangle_count)));
+        } else if field.field_type == "NVNM" {
+            // skip version, CRC, parent worldspace, and parent cell (or grid coordinates)
+            let (field_data, _) = take(16usize)(field_data)?;
+            let (field_data, vertex_count) = le_u32(field_data)?;
+            // a vertex count too large for usize can't fit in the field either
+            let vertices_size = (vertex_count as usize).saturating_mul(NAVMESH_VERTEX_SIZE);
+            let (field_data, _) = take(vertices_size)(field_data)?;
+            let (_, triangle_count) = le_u32(field_data)?;
+            return Ok((remaining, (vertex_count, triangle_count)));
+        }
+        input = remaining;
+    }
+    Ok((input, (0, 0)))
+}
+
+fn parse_world_fields(input: &[u8], form_id: u32, is_localized: bool) -> IResult<&[u8], World> {
+    let (data, _) = verify(parse_field_header, |field_header| {
         field_header.field_type == "EDID"
-    })(input)?;
-    let (remaining, editor_id) = parse_zstring(remaining)?;
-    let record_bytes_left =
-        record_header.size as usize - field.size as usize - FIELD_HEADER_SIZE as usize;
-    let (remaining, _) = take(record_bytes_left)(remaining)?;
-    Ok((remaining, editor_id.to_string()))
+    })(input)
+    .map_err(expecting("EDID"))?;
+    let (mut data, editor_id) = parse_zstring(data)?;
+    let mut world = World::new(form_id, editor_id);
+    while !data.is_empty() {
+        let (fields, field) = parse_field_header(data)?;
+        let (fields, field_data) = take(field.size)(fields)?;
+        match field.field_type {
+            "FULL" => {
+                let (_, name) = parse_lstring(field_data, is_localized)?;
+                world.name = Some(name);
+            }
+            "WNAM" => {
+                let (_, parent_form_id) = le_u32(field_data)?;
+                world.parent_form_id = Some(parent_form_id);
+            }
+            "PNAM" => {
+                let (_, flag_bits) = le_u16(field_data)?;
+                world.parent_use_flags = Some(ParentUseFlags::from_bits_truncate(flag_bits));
+            }
+            _ => {}
+        }
+        data = fields;
+    }
+    Ok((data, world))
+}
+
+/// Parses a string field that holds either the string itself or, in localized plugins, an id into the string tables
+fn parse_lstring(input: &[u8], is_localized: bool) -> IResult<&[u8], LString> {
+    if is_localized {
+        map(le_u32, LString::Id)(input)
+    } else {
+        map(parse_zstring, |string| LString::String(string.to_string()))(input)
+    }
 }
 
-fn parse_4char(input: &[u8]) -> IResult<&[u8], &str> {
+pub(crate) fn parse_4char(input: &[u8]) -> IResult<&[u8], &str> {
     map_res(take(4usize), |bytes: &[u8]| str::from_utf8(bytes))(input)
 }
 
-fn parse_zstring(input: &[u8]) -> IResult<&[u8], Cow<str>> {
+pub(crate) fn parse_zstring(input: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
     let (input, bytes) = take_while(|byte| byte != 0)(input)?;
     let (zstring, _, _) = WINDOWS_1252.decode(bytes);
     let (input, _) = take(1usize)(input)?;
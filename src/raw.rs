@@ -0,0 +1,349 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::compression::Compression;
+use crate::error::Result;
+use crate::parser::{
+    decompress_record_data, GroupContext, GroupHeader, ParseOptions, RecordHeader,
+};
+use crate::walk::{describe_group, Entry, Walker};
+
+/// Flag set in the header of records whose data section is compressed
+pub(crate) const COMPRESSED_FLAG: u32 = 0x00040000;
+
+/// Header of a [group](https://en.uesp.net/wiki/Skyrim_Mod:Mod_File_Format#Groups) found in a plugin
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RawGroup {
+    /// Meaning depends on the group type: the record type of top-level groups, or the form id or grid coordinates of
+    /// the parent record of other groups
+    pub label: [u8; 4],
+    /// Type of the group, `0` for top-level groups
+    pub group_type: i32,
+    /// Size of the group, including its header
+    pub size: u32,
+}
+
+/// A group of a plugin along with the groups nested inside of it, as returned by [`parse_group_tree`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupTree {
+    pub group: RawGroup,
+    /// Number of records of each type directly inside of the group, not counting the records of nested groups
+    pub record_counts: BTreeMap<String, usize>,
+    /// The groups directly inside of the group, in the order they are found in the file
+    pub groups: Vec<GroupTree>,
+}
+
+/// A record found in a plugin whose (possibly compressed) data section has not been parsed
+#[derive(Debug, Clone)]
+pub struct RawRecord<'a> {
+    /// The whole plugin file, for reporting the offsets of errors
+    input: &'a [u8],
+    /// Four character record type, like `CELL` or `REFR`
+    pub record_type: &'a str,
+    /// Flags from the header of the record
+    pub flags: u32,
+    pub form_id: u32,
+    /// Size of the data section of the record, as stored in the plugin
+    pub size: u32,
+    /// The data section of the record as stored in the plugin, which is compressed if [`RawRecord::is_compressed`]
+    pub data: &'a [u8],
+    /// Form ID of the WRLD record whose children groups contain the record
+    pub world_form_id: Option<u32>,
+    /// Form ID of the CELL record whose children groups contain the record
+    pub cell_form_id: Option<u32>,
+}
+
+impl RawGroup {
+    pub(crate) fn new(group_header: &GroupHeader) -> Self {
+        RawGroup {
+            label: *group_header.label,
+            group_type: group_header.group_type,
+            size: group_header.size,
+        }
+    }
+}
+
+/// Describes the group by what it contains, like `GRUP WRLD`, `GRUP type 4 (block 1, -2)`, or
+/// `CELL 00009B79 temporary children`
+impl fmt::Display for RawGroup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", describe_group(self.group_type, &self.label))
+    }
+}
+
+impl GroupTree {
+    fn new(group: RawGroup) -> Self {
+        GroupTree {
+            group,
+            record_counts: BTreeMap::new(),
+            groups: vec![],
+        }
+    }
+}
+
+impl<'a> RawRecord<'a> {
+    pub(crate) fn new(
+        input: &'a [u8],
+        record_header: &RecordHeader<'a>,
+        data: &'a [u8],
+        context: &GroupContext,
+    ) -> Self {
+        RawRecord {
+            input,
+            record_type: record_header.record_type,
+            flags: record_header.flags,
+            form_id: record_header.id,
+            size: record_header.size,
+            data,
+            world_form_id: context.world_form_id,
+            cell_form_id: context.cell_form_id,
+        }
+    }
+
+    /// Whether the data section of the record is compressed
+    pub fn is_compressed(&self) -> bool {
+        self.flags & COMPRESSED_FLAG != 0
+    }
+
+    /// Returns the data section of the record, decompressing it with `compression` if it is compressed
+    pub fn decompress(&self, compression: Compression) -> Result<Vec<u8>> {
+        decompress_record_data(
+            self.input,
+            self.record_type,
+            self.form_id,
+            self.data,
+            self.is_compressed(),
+            compression,
+        )
+        .map(Cow::into_owned)
+    }
+}
+
+/// Parses the header of every record in a plugin, including the TES4 header record, and returns them in the order they
+/// are found in the file.
+///
+/// The data sections of the records are not parsed or decompressed. Use [`RawRecord::decompress`] to get the
+/// decompressed data of a record.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_records, Compression};
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// for record in parse_records(&plugin_contents).unwrap() {
+///     let data = record.decompress(Compression::Zlib).unwrap();
+///     println!("{} {:08X} {}", record.record_type, record.form_id, data.len());
+/// }
+/// ```
+pub fn parse_records(input: &[u8]) -> Result<Vec<RawRecord<'_>>> {
+    parse_records_with_options(input, &ParseOptions::default())
+}
+
+/// Parses the header of every record in a plugin like [`parse_records`], but for the [`Game`](crate::Game) set in the
+/// [`ParseOptions`].
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options with the game that the plugin was made for
+pub fn parse_records_with_options<'a>(
+    input: &'a [u8],
+    options: &ParseOptions,
+) -> Result<Vec<RawRecord<'a>>> {
+    let mut records = vec![];
+    for entry in Walker::new(input, 0, options.game, false).strict(options.strict) {
+        if let (Entry::Record(record_header, data), context) = entry? {
+            records.push(RawRecord::new(input, &record_header, data, &context));
+        }
+    }
+    Ok(records)
+}
+
+/// Counts the records of each type in a plugin, including the TES4 header record, without parsing or decompressing
+/// them. Useful for telling what kind of mod a plugin is at a glance.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::count_record_types;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// for (record_type, count) in count_record_types(&plugin_contents).unwrap() {
+///     println!("{}: {}", record_type, count);
+/// }
+/// ```
+pub fn count_record_types(input: &[u8]) -> Result<BTreeMap<String, usize>> {
+    count_record_types_with_options(input, &ParseOptions::default())
+}
+
+/// Counts the records of each type in a plugin like [`count_record_types`], but for the [`Game`](crate::Game) set in
+/// the [`ParseOptions`].
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options with the game that the plugin was made for
+pub fn count_record_types_with_options(
+    input: &[u8],
+    options: &ParseOptions,
+) -> Result<BTreeMap<String, usize>> {
+    let mut counts = BTreeMap::new();
+    for entry in Walker::new(input, 0, options.game, false).strict(options.strict) {
+        if let (Entry::Record(record_header, _), _) = entry? {
+            *counts
+                .entry(record_header.record_type.to_string())
+                .or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Collects the form id of every CELL record in a plugin along with the form id of the worldspace that it belongs to,
+/// or `None` for interior cells, from the record headers alone. The cells are not decompressed or parsed and the groups
+/// that can't contain cells are skipped over, which makes it much faster than [`parse_plugin`](crate::parse_plugin)
+/// for building an index of which plugins edit which cells.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::scan_cell_form_ids;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// for (form_id, world_form_id) in scan_cell_form_ids(&plugin_contents).unwrap() {
+///     println!("{:08X} in {:?}", form_id, world_form_id);
+/// }
+/// ```
+pub fn scan_cell_form_ids(input: &[u8]) -> Result<Vec<(u32, Option<u32>)>> {
+    scan_cell_form_ids_with_options(input, &ParseOptions::default())
+}
+
+/// Collects the form ids of the cells in a plugin like [`scan_cell_form_ids`], but for the [`Game`](crate::Game) set
+/// in the [`ParseOptions`].
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options with the game that the plugin was made for
+pub fn scan_cell_form_ids_with_options(
+    input: &[u8],
+    options: &ParseOptions,
+) -> Result<Vec<(u32, Option<u32>)>> {
+    let mut form_ids = vec![];
+    let mut walker = Walker::new(input, 0, options.game, false).strict(options.strict);
+    while let Some(entry) = walker.next() {
+        match entry? {
+            (Entry::Group(group_header), _) => {
+                let is_skipped = match group_header.group_type {
+                    0 => group_header.label != b"CELL" && group_header.label != b"WRLD",
+                    // the children of cells and dialogue topics
+                    6..=10 => true,
+                    _ => false,
+                };
+                if is_skipped {
+                    walker.skip_group();
+                }
+            }
+            (Entry::Record(record_header, _), context) => {
+                if record_header.record_type == "CELL" {
+                    form_ids.push((record_header.id, context.world_form_id));
+                }
+            }
+        }
+    }
+    Ok(form_ids)
+}
+
+/// Parses the headers of the groups of a plugin into a tree of the groups nested inside of each other, with the
+/// number of records of each type in every group. The data sections of the records are not parsed or decompressed,
+/// which makes it useful for finding out how a plugin that fails to parse is laid out.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::parse_group_tree;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// for tree in parse_group_tree(&plugin_contents).unwrap() {
+///     println!("{}: {} groups inside", tree.group, tree.groups.len());
+/// }
+/// ```
+pub fn parse_group_tree(input: &[u8]) -> Result<Vec<GroupTree>> {
+    parse_group_tree_with_options(input, &ParseOptions::default())
+}
+
+/// Parses the headers of the groups of a plugin into a tree like [`parse_group_tree`], but for the
+/// [`Game`](crate::Game) set in the [`ParseOptions`].
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options with the game that the plugin was made for
+pub fn parse_group_tree_with_options(
+    input: &[u8],
+    options: &ParseOptions,
+) -> Result<Vec<GroupTree>> {
+    let mut trees = vec![];
+    // the groups that are being walked, outermost first
+    let mut open_groups: Vec<GroupTree> = vec![];
+    let mut walker = Walker::new(input, 0, options.game, false).strict(options.strict);
+    while let Some(entry) = walker.next() {
+        let (entry, _) = entry?;
+        // a group is already counted in the depth when it is returned
+        let depth = match entry {
+            Entry::Group(_) => walker.depth() - 1,
+            Entry::Record(_, _) => walker.depth(),
+        };
+        while open_groups.len() > depth {
+            close_group(&mut open_groups, &mut trees);
+        }
+        match entry {
+            Entry::Group(group_header) => {
+                open_groups.push(GroupTree::new(RawGroup::new(&group_header)))
+            }
+            Entry::Record(record_header, _) => {
+                // the TES4 header record isn't in a group
+                if let Some(group) = open_groups.last_mut() {
+                    *group
+                        .record_counts
+                        .entry(record_header.record_type.to_string())
+                        .or_default() += 1;
+                }
+            }
+        }
+    }
+    while !open_groups.is_empty() {
+        close_group(&mut open_groups, &mut trees);
+    }
+    Ok(trees)
+}
+
+/// Moves the innermost group that is being walked into the group that contains it, or into `trees` if it is a
+/// top-level group
+fn close_group(open_groups: &mut Vec<GroupTree>, trees: &mut Vec<GroupTree>) {
+    if let Some(group) = open_groups.pop() {
+        match open_groups.last_mut() {
+            Some(parent) => parent.groups.push(group),
+            None => trees.push(group),
+        }
+    }
+}
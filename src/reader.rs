@@ -0,0 +1,386 @@
+use std::convert::TryFrom;
+use std::io::{self, Read};
+use std::str;
+
+use crate::error::{ParseError, Result, WarningKind};
+use crate::parser::{
+    parse_group_or_record_header, parse_plugin_with_options, record_count_warning, Header,
+    ParseOptions, Plugin, PARSED_TOP_GROUPS, PLACED_RECORD_TYPES,
+};
+
+/// Parses header and cell records from a reader of a plugin file like
+/// [`parse_plugin_with_options`](crate::parse_plugin_with_options), without reading the whole file into memory.
+///
+/// Groups and records that aren't parsed are skipped as they are read, so only the data of the records that are
+/// extracted is kept in memory. The reader is read in small pieces, so wrapping files or network streams in a
+/// [`BufReader`](std::io::BufReader) is recommended. With [`ParseOptions::collect_warnings`], the headers of the
+/// records in skipped groups are still read so that the record count in the plugin header is checked against the whole
+/// plugin.
+///
+/// Byte offsets in returned [`ParseError`]s that occur after the plugin has been read refer to the filtered copy of
+/// the plugin instead of the original file.
+///
+/// # Arguments
+///
+/// * `reader` - A reader of the contents of the plugin file
+/// * `options` - Options that control what is extracted from the plugin
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{fs::File, io::BufReader};
+/// use skyrim_cell_dump::{parse_plugin_from_reader, ParseOptions};
+///
+/// let reader = BufReader::new(File::open("Skyrim.esm").unwrap());
+/// let plugin = parse_plugin_from_reader(reader, &ParseOptions::default()).unwrap();
+/// ```
+pub fn parse_plugin_from_reader<R: Read>(
+    mut reader: R,
+    options: &ParseOptions,
+) -> Result<Plugin<'static>> {
+    let mut filtered = vec![];
+    let header_size = options.game.record_header_size() as usize;
+
+    let header_bytes =
+        read_header(&mut reader, header_size)?.ok_or(ParseError::TruncatedRecord {
+            record_type: None,
+            offset: 0,
+        })?;
+    let tes4_size = match parse_group_or_record_header(&header_bytes, options.game)
+        .map_err(|err| ParseError::from_nom(err, &header_bytes))?
+    {
+        (_, Header::Record(record_header)) => record_header.size,
+        (_, Header::Group(_)) => {
+            return Err(ParseError::UnexpectedRecordType {
+                expected: "TES4",
+                found: "GRUP".to_string(),
+                offset: 0,
+            })
+        }
+    };
+    filtered.extend_from_slice(&header_bytes);
+    read_into(&mut reader, tes4_size as u64, &mut filtered)?;
+
+    // records and groups are only counted for the record count warning
+    let mut record_count = if options.collect_warnings {
+        Some(0)
+    } else {
+        None
+    };
+    copy_group_data(&mut reader, None, options, &mut filtered, &mut record_count)?;
+
+    let mut plugin = parse_plugin_with_options(&filtered, options)?;
+    if options.collect_warnings {
+        // the filtered copy is missing the skipped groups and records, so the count of the original is checked instead
+        plugin
+            .warnings
+            .retain(|warning| warning.kind != WarningKind::RecordCount);
+        plugin.warnings.extend(
+            record_count
+                .and_then(|record_count| record_count_warning(&plugin.header, record_count)),
+        );
+    }
+    Ok(plugin.into_owned())
+}
+
+/// Copies the groups and records from `reader` into `filtered`, skipping the groups that aren't parsed and the data of
+/// records that aren't parsed.
+///
+/// Reads until the end of the reader if `group_size` is `None`. Skipped records are kept with an empty data section
+/// since the form ids of the children of cells are still extracted from their headers. Every group and record read,
+/// including the ones in skipped groups, is added to `record_count` unless it is `None`.
+fn copy_group_data<R: Read>(
+    reader: &mut R,
+    group_size: Option<u32>,
+    options: &ParseOptions,
+    filtered: &mut Vec<u8>,
+    record_count: &mut Option<i64>,
+) -> Result<()> {
+    let header_size = options.game.record_header_size();
+    let mut consumed_bytes = 0;
+    while !matches!(group_size, Some(size) if consumed_bytes >= size) {
+        let header_bytes = match read_header(reader, header_size as usize)? {
+            Some(header_bytes) => header_bytes,
+            None if group_size.is_none() => break,
+            None => {
+                return Err(ParseError::TruncatedRecord {
+                    record_type: Some("GRUP".to_string()),
+                    offset: filtered.len(),
+                })
+            }
+        };
+        let (_, header) = parse_group_or_record_header(&header_bytes, options.game)
+            .map_err(|err| ParseError::from_nom(err, &header_bytes))?;
+        if let Some(count) = record_count {
+            *count += 1;
+        }
+        match header {
+            Header::Group(group_header) => {
+                consumed_bytes = consume(
+                    consumed_bytes,
+                    u64::from(group_header.size),
+                    "GRUP",
+                    None,
+                    filtered,
+                )?;
+                let data_size = group_header.size.checked_sub(header_size).ok_or_else(|| {
+                    ParseError::TruncatedRecord {
+                        record_type: Some("GRUP".to_string()),
+                        offset: filtered.len(),
+                    }
+                })?;
+                let is_skipped = match group_header.group_type {
+                    0 => match str::from_utf8(group_header.label) {
+                        Ok(label) => !PARSED_TOP_GROUPS.contains(&label),
+                        Err(_) => {
+                            // the group is kept without its data, so that the parse fails on the label in strict mode
+                            // and warns about it otherwise, like when parsing the whole file
+                            let start = filtered.len();
+                            filtered.extend_from_slice(&header_bytes);
+                            filtered[start + 4..start + 8]
+                                .copy_from_slice(&header_size.to_le_bytes());
+                            true
+                        }
+                    },
+                    7 => true,
+                    _ => false,
+                };
+                if is_skipped {
+                    skip_group_data(reader, data_size, options, record_count)?;
+                    continue;
+                }
+                let start = filtered.len();
+                filtered.extend_from_slice(&header_bytes);
+                copy_group_data(reader, Some(data_size), options, filtered, record_count)?;
+                // Skipped data makes the group smaller, so its size needs to be updated
+                let filtered_size = (filtered.len() - start) as u32;
+                filtered[start + 4..start + 8].copy_from_slice(&filtered_size.to_le_bytes());
+            }
+            Header::Record(record_header) => {
+                consumed_bytes = consume(
+                    consumed_bytes,
+                    u64::from(record_header.size) + u64::from(header_size),
+                    record_header.record_type,
+                    Some(record_header.id),
+                    filtered,
+                )?;
+                let start = filtered.len();
+                filtered.extend_from_slice(&header_bytes);
+                if is_parsed_record_type(record_header.record_type, options) {
+                    read_into(reader, record_header.size as u64, filtered)?;
+                } else {
+                    skip(reader, record_header.size as u64)?;
+                    filtered[start + 4..start + 8].copy_from_slice(&0u32.to_le_bytes());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Skips the data of a group that isn't parsed, counting the groups and records in it unless `record_count` is `None`.
+///
+/// Only the headers of the records are read. If the headers inside the group don't add up to its size, the rest of the
+/// group is skipped and `record_count` is set to `None`, since the plugin can't be counted like it is when walking the
+/// whole file.
+fn skip_group_data<R: Read>(
+    reader: &mut R,
+    group_size: u32,
+    options: &ParseOptions,
+    record_count: &mut Option<i64>,
+) -> Result<()> {
+    let header_size = options.game.record_header_size();
+    let mut consumed_bytes = 0;
+    while record_count.is_some() && consumed_bytes < group_size {
+        if group_size - consumed_bytes < header_size {
+            *record_count = None;
+            break;
+        }
+        let header_bytes = read_header(reader, header_size as usize)?
+            .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        consumed_bytes += header_size;
+        let (size, is_group) = match parse_group_or_record_header(&header_bytes, options.game) {
+            Ok((_, Header::Group(group_header))) if group_header.size >= header_size => {
+                (group_header.size - header_size, true)
+            }
+            Ok((_, Header::Record(record_header))) => (record_header.size, false),
+            _ => {
+                *record_count = None;
+                break;
+            }
+        };
+        if size > group_size - consumed_bytes {
+            *record_count = None;
+            break;
+        }
+        if let Some(count) = record_count {
+            *count += 1;
+        }
+        if is_group {
+            skip_group_data(reader, size, options, record_count)?;
+        } else {
+            skip(reader, size as u64)?;
+        }
+        consumed_bytes += size;
+    }
+    skip(reader, (group_size - consumed_bytes) as u64)
+}
+
+/// Adds the size of a group or record to the bytes consumed of the group that contains it, failing if the sum
+/// overflows
+fn consume(
+    consumed_bytes: u32,
+    size: u64,
+    record_type: &str,
+    form_id: Option<u32>,
+    filtered: &[u8],
+) -> Result<u32> {
+    u32::try_from(u64::from(consumed_bytes) + size).map_err(|_err| ParseError::Malformed {
+        record_type: Some(record_type.to_string()),
+        form_id,
+        offset: filtered.len(),
+        problem: format!("size of {} bytes overflows the size of its group", size),
+    })
+}
+
+/// Whether the data of records of the type is needed by [`parse_plugin_with_options`]
+fn is_parsed_record_type(record_type: &str, options: &ParseOptions) -> bool {
+    match record_type {
+        "CELL" => options.include_cells,
+        "WRLD" | "LCTN" | "REGN" => true,
+        "ACHR" | "ACRE" => options.include_refs,
+        "LAND" => options.include_land,
+        "NAVM" => options.include_navmeshes,
+        "REFR" => options.include_map_markers || options.assign_persistent_refs,
+        record_type if PLACED_RECORD_TYPES.contains(&record_type) => options.assign_persistent_refs,
+        _ => false,
+    }
+}
+
+/// Reads the next group or record header, or returns `None` if the reader is at its end
+fn read_header<R: Read>(reader: &mut R, header_size: usize) -> Result<Option<Vec<u8>>> {
+    let mut header_bytes = vec![0; header_size];
+    let mut read_bytes = 0;
+    while read_bytes < header_size {
+        match reader.read(&mut header_bytes[read_bytes..]) {
+            Ok(0) if read_bytes == 0 => return Ok(None),
+            Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            Ok(size) => read_bytes += size,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(Some(header_bytes))
+}
+
+fn read_into<R: Read>(reader: &mut R, size: u64, buf: &mut Vec<u8>) -> Result<()> {
+    let read_bytes = reader.take(size).read_to_end(buf)?;
+    if (read_bytes as u64) < size {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+fn skip<R: Read>(reader: &mut R, size: u64) -> Result<()> {
+    let skipped_bytes = io::copy(&mut reader.take(size), &mut io::sink())?;
+    if skipped_bytes < size {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::parser::Cell;
+
+    /// Offset of the number of records and groups in the HEDR field of a Skyrim plugin
+    const RECORD_COUNT_OFFSET: usize = 34;
+
+    fn record(record_type: &[u8], form_id: u32, data: &[u8]) -> Vec<u8> {
+        let mut record = record_type.to_vec();
+        record.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        record.extend_from_slice(&0u32.to_le_bytes());
+        record.extend_from_slice(&form_id.to_le_bytes());
+        record.extend_from_slice(&[0; 8]);
+        record.extend_from_slice(data);
+        record
+    }
+
+    fn group(label: &[u8], group_type: i32, contents: &[u8]) -> Vec<u8> {
+        let mut group = b"GRUP".to_vec();
+        group.extend_from_slice(&(24 + contents.len() as u32).to_le_bytes());
+        group.extend_from_slice(label);
+        group.extend_from_slice(&group_type.to_le_bytes());
+        group.extend_from_slice(&[0; 8]);
+        group.extend_from_slice(contents);
+        group
+    }
+
+    /// A plugin with a cell and a top group of statics, which the reader skips, that has a nested group in it
+    fn plugin_with_skipped_groups() -> Vec<u8> {
+        let mut bytes = PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(Cell::new_interior(0x0100_0D63, Some("TestInterior".into())))
+            .write()
+            .unwrap();
+        let statics = [
+            record(b"STAT", 0x0100_0D70, b"EDID\x05\0Rock\0"),
+            group(&[0; 4], 7, &record(b"STAT", 0x0100_0D71, &[])),
+        ]
+        .concat();
+        bytes.extend_from_slice(&group(b"STAT", 0, &statics));
+        let count = i32::from_le_bytes(
+            bytes[RECORD_COUNT_OFFSET..RECORD_COUNT_OFFSET + 4]
+                .try_into()
+                .unwrap(),
+        );
+        bytes[RECORD_COUNT_OFFSET..RECORD_COUNT_OFFSET + 4]
+            .copy_from_slice(&(count + 4).to_le_bytes());
+        bytes
+    }
+
+    fn record_count_warnings(bytes: &[u8]) -> (usize, usize) {
+        let options = ParseOptions {
+            collect_warnings: true,
+            ..Default::default()
+        };
+        let count = |plugin: Plugin| {
+            plugin
+                .warnings
+                .iter()
+                .filter(|warning| warning.kind == WarningKind::RecordCount)
+                .count()
+        };
+        (
+            count(parse_plugin_with_options(bytes, &options).unwrap()),
+            count(parse_plugin_from_reader(bytes, &options).unwrap()),
+        )
+    }
+
+    #[test]
+    fn counts_the_records_of_skipped_groups() {
+        let bytes = plugin_with_skipped_groups();
+        assert_eq!(record_count_warnings(&bytes), (0, 0));
+    }
+
+    #[test]
+    fn warns_about_the_same_record_count_as_parsing_the_whole_file() {
+        let mut bytes = plugin_with_skipped_groups();
+        bytes[RECORD_COUNT_OFFSET] += 1;
+        assert_eq!(record_count_warnings(&bytes), (1, 1));
+    }
+
+    #[test]
+    fn reads_the_same_cells_as_parsing_the_whole_file() {
+        let bytes = plugin_with_skipped_groups();
+        let options = ParseOptions::default();
+        let plugin = parse_plugin_with_options(&bytes, &options).unwrap();
+        let read_plugin = parse_plugin_from_reader(&bytes[..], &options).unwrap();
+        assert_eq!(read_plugin.cells, plugin.cells);
+        assert_eq!(read_plugin.worlds, plugin.worlds);
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::fs::{read, read_dir};
+use std::path::Path;
+use std::str;
+
+use encoding_rs::WINDOWS_1252;
+use nom::{
+    bytes::complete::{take, take_while},
+    multi::count,
+    number::complete::le_u32,
+    sequence::pair,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{IResult, ParseError, Result};
+use crate::parser::{LString, Plugin};
+
+/// The strings of a localized plugin, loaded from its `.STRINGS`, `.DLSTRINGS`, and `.ILSTRINGS` string table files.
+///
+/// Localized plugins (see [`PluginHeader::is_localized`](crate::PluginHeader::is_localized)) store the ids of strings
+/// in their string fields instead of the strings themselves.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StringTables {
+    strings: HashMap<u32, String>,
+}
+
+impl StringTables {
+    /// Parses the contents of a plugin's string table files.
+    ///
+    /// # Arguments
+    ///
+    /// * `strings` - Contents of the `.STRINGS` file, if there is one
+    /// * `dlstrings` - Contents of the `.DLSTRINGS` file, if there is one
+    /// * `ilstrings` - Contents of the `.ILSTRINGS` file, if there is one
+    pub fn parse(
+        strings: Option<&[u8]>,
+        dlstrings: Option<&[u8]>,
+        ilstrings: Option<&[u8]>,
+    ) -> Result<StringTables> {
+        let mut tables = StringTables::default();
+        if let Some(strings) = strings {
+            tables.extend_from_table(strings, false)?;
+        }
+        if let Some(dlstrings) = dlstrings {
+            tables.extend_from_table(dlstrings, true)?;
+        }
+        if let Some(ilstrings) = ilstrings {
+            tables.extend_from_table(ilstrings, true)?;
+        }
+        Ok(tables)
+    }
+
+    /// Loads the string table files of a plugin from a directory (usually `Data/Strings`).
+    ///
+    /// The files are expected to be named `<plugin name without extension>_<language>.STRINGS` (and `.DLSTRINGS` and
+    /// `.ILSTRINGS`), matched case-insensitively. Missing files are skipped.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Directory containing the string table files
+    /// * `plugin_name` - File name of the plugin, e.g. `Skyrim.esm`
+    /// * `language` - Language of the strings to load, e.g. `english`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use skyrim_cell_dump::{parse_plugin, StringTables};
+    ///
+    /// let plugin_contents = std::fs::read("Data/Skyrim.esm").unwrap();
+    /// let mut plugin = parse_plugin(&plugin_contents).unwrap();
+    /// let string_tables = StringTables::from_dir("Data/Strings", "Skyrim.esm", "english").unwrap();
+    /// plugin.resolve_strings(&string_tables);
+    /// ```
+    pub fn from_dir<P: AsRef<Path>>(
+        dir: P,
+        plugin_name: &str,
+        language: &str,
+    ) -> Result<StringTables> {
+        let plugin_stem = Path::new(plugin_name)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or_else(|| ParseError::InvalidValue {
+                kind: "plugin name",
+                value: plugin_name.to_string(),
+            })?;
+        let prefix = format!("{}_{}", plugin_stem, language).to_lowercase();
+        let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            let file_name = match path.file_name().and_then(|name| name.to_str()) {
+                Some(file_name) => file_name.to_lowercase(),
+                None => continue,
+            };
+            for extension in &["strings", "dlstrings", "ilstrings"] {
+                if file_name == format!("{}.{}", prefix, extension) {
+                    files.insert(extension.to_string(), read(&path)?);
+                }
+            }
+        }
+        StringTables::parse(
+            files.get("strings").map(Vec::as_slice),
+            files.get("dlstrings").map(Vec::as_slice),
+            files.get("ilstrings").map(Vec::as_slice),
+        )
+    }
+
+    /// Looks up the string with the given id
+    pub fn get(&self, id: u32) -> Option<&str> {
+        self.strings.get(&id).map(String::as_str)
+    }
+
+    /// Resolves a string field to its text, looking up the string id in the tables if necessary
+    pub fn resolve<'a>(&'a self, lstring: &'a LString) -> Option<&'a str> {
+        match lstring {
+            LString::String(string) => Some(string),
+            LString::Id(id) => self.get(*id),
+        }
+    }
+
+    /// Number of strings in the tables
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether the tables contain no strings
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    fn extend_from_table(&mut self, input: &[u8], is_length_prefixed: bool) -> Result<()> {
+        let (_, strings) =
+            parse_string_table(input, is_length_prefixed).map_err(
+                |err| match ParseError::from_nom(err, input) {
+                    ParseError::TruncatedRecord { offset, .. }
+                    | ParseError::InvalidRecord { offset, .. } => {
+                        ParseError::InvalidStringTable { offset }
+                    }
+                    err => err,
+                },
+            )?;
+        self.strings.extend(strings);
+        Ok(())
+    }
+}
+
+impl Plugin<'_> {
+    /// Replaces every string id in the plugin's string fields with the string from `string_tables`.
+    ///
+    /// Ids that are not found in the tables are left as they are.
+    pub fn resolve_strings(&mut self, string_tables: &StringTables) {
+        let resolve = |name: &mut Option<LString>| {
+            if let Some(LString::Id(id)) = name {
+                if let Some(string) = string_tables.get(*id) {
+                    *name = Some(LString::String(string.to_string()));
+                }
+            }
+        };
+        self.worlds = self
+            .worlds
+            .drain()
+            .map(|mut world| {
+                resolve(&mut world.name);
+                world
+            })
+            .collect();
+        for location in self.locations.iter_mut() {
+            resolve(&mut location.name);
+        }
+        self.cells = self
+            .cells
+            .drain()
+            .map(|mut cell| {
+                resolve(&mut cell.name);
+                for map_marker in cell.map_markers.iter_mut() {
+                    resolve(&mut map_marker.name);
+                }
+                cell
+            })
+            .collect();
+    }
+}
+
+/// Parses a string table file into a list of string ids and strings.
+///
+/// `.STRINGS` files store null-terminated strings while `.DLSTRINGS` and `.ILSTRINGS` files prefix each string with
+/// its length.
+fn parse_string_table(
+    input: &[u8],
+    is_length_prefixed: bool,
+) -> IResult<&[u8], Vec<(u32, String)>> {
+    let (remaining, string_count) = le_u32(input)?;
+    let (remaining, data_size) = le_u32(remaining)?;
+    let (remaining, directory) = count(pair(le_u32, le_u32), string_count as usize)(remaining)?;
+    let (remaining, data) = take(data_size)(remaining)?;
+    let mut strings = Vec::with_capacity(directory.len());
+    for (id, offset) in directory {
+        let (entry, _) = take(offset)(data)?;
+        let (_, bytes) = if is_length_prefixed {
+            let (entry, length) = le_u32(entry)?;
+            take(length.saturating_sub(1))(entry)?
+        } else {
+            take_while(|byte| byte != 0)(entry)?
+        };
+        strings.push((id, decode_string(bytes)));
+    }
+    Ok((remaining, strings))
+}
+
+/// Decodes string table text, which is UTF-8 for some languages and Windows-1252 for others
+pub(crate) fn decode_string(bytes: &[u8]) -> String {
+    match str::from_utf8(bytes) {
+        Ok(string) => string.to_string(),
+        Err(_) => WINDOWS_1252.decode(bytes).0.into_owned(),
+    }
+}
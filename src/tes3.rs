@@ -0,0 +1,264 @@
+//! Parsing of [Morrowind (TES3) plugin files](https://en.uesp.net/wiki/Morrowind_Mod:Mod_File_Format).
+//!
+//! TES3 plugins have a different layout from later games: there are no GRUPs or form ids, every record is stored at
+//! the top level of the file, and cells are identified by their name or exterior grid coordinates instead. The types
+//! here mirror the shape of the main [`Plugin`](crate::Plugin) and [`Cell`](crate::Cell) structs so that tools working
+//! across games can handle both in similar ways.
+use std::borrow::Cow;
+
+use encoding_rs::WINDOWS_1252;
+use nom::{
+    bytes::complete::take,
+    combinator::verify,
+    number::complete::{le_f32, le_i32, le_u32},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::{expecting, in_record, IResult, ParseError, Result};
+use crate::parser::{parse_4char, parse_zstring};
+
+const AUTHOR_SIZE: usize = 32;
+const DESCRIPTION_SIZE: usize = 256;
+
+/// A parsed TES3 Morrowind plugin file
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Plugin<'a> {
+    /// Parsed [TES3 header record](https://en.uesp.net/wiki/Morrowind_Mod:Mod_File_Format/TES3) with metadata about the plugin
+    pub header: PluginHeader<'a>,
+    /// Parsed [CELL records](https://en.uesp.net/wiki/Morrowind_Mod:Mod_File_Format/CELL) contained in the plugin
+    pub cells: Vec<Cell>,
+}
+
+/// Parsed [TES3 header record](https://en.uesp.net/wiki/Morrowind_Mod:Mod_File_Format/TES3)
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct PluginHeader<'a> {
+    pub version: f32,
+    /// 0 for plugins (ESP), 1 for masters (ESM), and 32 for saves (ESS)
+    pub file_type: u32,
+    pub num_records: u32,
+    pub author: Option<Cow<'a, str>>,
+    pub description: Option<Cow<'a, str>>,
+    pub masters: Vec<Cow<'a, str>>,
+}
+
+impl PluginHeader<'_> {
+    /// Whether the plugin is a master file (ESM)
+    pub fn is_master(&self) -> bool {
+        self.file_type == 1
+    }
+}
+
+bitflags! {
+    /// Flags from the DATA field of a TES3 CELL record
+    pub struct CellFlags: u32 {
+        const INTERIOR = 0x0001;
+        const HAS_WATER = 0x0002;
+        const ILLEGAL_TO_SLEEP = 0x0004;
+        const BEHAVE_LIKE_EXTERIOR = 0x0080;
+    }
+}
+
+serde_flag_names!(CellFlags {
+    INTERIOR,
+    HAS_WATER,
+    ILLEGAL_TO_SLEEP,
+    BEHAVE_LIKE_EXTERIOR,
+});
+
+/// A parsed TES3 CELL record
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cell {
+    /// Name of the cell (NAME). Interior cells are identified by their name, exterior cells usually have none.
+    pub name: Option<String>,
+    /// Exterior grid x coordinate, `None` for interior cells
+    pub x: Option<i32>,
+    /// Exterior grid y coordinate, `None` for interior cells
+    pub y: Option<i32>,
+    /// Name of the region the exterior cell belongs to (RGNN)
+    pub region: Option<String>,
+    pub flags: CellFlags,
+    /// Number of references placed in the cell (FRMR)
+    pub reference_count: u32,
+}
+
+impl Cell {
+    /// Whether the cell is an interior cell
+    pub fn is_interior(&self) -> bool {
+        self.flags.contains(CellFlags::INTERIOR)
+    }
+}
+
+struct Record<'a> {
+    record_type: &'a str,
+    data: &'a [u8],
+}
+
+struct SubrecordHeader<'a> {
+    subrecord_type: &'a str,
+    size: u32,
+}
+
+/// Parses the header and cell records from input bytes of a Morrowind plugin file.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::tes3::parse_plugin;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let plugin = parse_plugin(&plugin_contents).unwrap();
+/// ```
+pub fn parse_plugin(input: &[u8]) -> Result<Plugin<'_>> {
+    let file = input;
+    let (input, header) =
+        parse_plugin_header(file).map_err(|err| ParseError::from_nom(err, file))?;
+
+    let mut cells = vec![];
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, data) =
+            parse_record(input).map_err(|err| ParseError::from_nom(err, file))?;
+        if data.record_type == "CELL" {
+            let (_, cell) = parse_cell_fields(data.data).map_err(|err| {
+                let data_offset = file.len() - remaining.len() - data.data.len();
+                ParseError::from_nom(err, data.data).in_record("CELL", None, data_offset, false)
+            })?;
+            cells.push(cell);
+        }
+        input = remaining;
+    }
+
+    Ok(Plugin { header, cells })
+}
+
+fn parse_plugin_header(input: &[u8]) -> IResult<&[u8], PluginHeader<'_>> {
+    let (input, record) = verify(parse_record, |record| record.record_type == "TES3")(input)
+        .map_err(expecting("TES3"))?;
+    let data = record.data;
+
+    let mut header = PluginHeader {
+        version: 0.0,
+        file_type: 0,
+        num_records: 0,
+        author: None,
+        description: None,
+        masters: vec![],
+    };
+    let mut data = data;
+    while !data.is_empty() {
+        let (remaining, subrecord_header) = parse_subrecord_header(data)?;
+        let (remaining, field) = take(subrecord_header.size)(remaining)?;
+        match subrecord_header.subrecord_type {
+            "HEDR" => {
+                let (field, version) = le_f32(field)?;
+                let (field, file_type) = le_u32(field)?;
+                let (field, author) = parse_fixed_string(field, AUTHOR_SIZE)?;
+                let (field, description) = parse_fixed_string(field, DESCRIPTION_SIZE)?;
+                let (_, num_records) = le_u32(field)?;
+                header.version = version;
+                header.file_type = file_type;
+                header.num_records = num_records;
+                header.author = Some(author).filter(|author| !author.is_empty());
+                header.description =
+                    Some(description).filter(|description| !description.is_empty());
+            }
+            "MAST" => {
+                let (_, master) = parse_zstring(field)?;
+                header.masters.push(master);
+            }
+            _ => {}
+        }
+        data = remaining;
+    }
+    Ok((input, header))
+}
+
+fn parse_cell_fields(input: &[u8]) -> IResult<&[u8], Cell> {
+    let mut name = None;
+    let mut coords = None;
+    let mut region = None;
+    let mut flags = CellFlags::empty();
+    let mut reference_count = 0;
+
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, subrecord_header) = parse_subrecord_header(input)?;
+        let (remaining, field) = take(subrecord_header.size)(remaining)?;
+        match subrecord_header.subrecord_type {
+            // Only the first NAME belongs to the cell, references also have a NAME field with their base object id
+            "NAME" if reference_count == 0 => {
+                let (_, cell_name) = parse_zstring(field)?;
+                if !cell_name.is_empty() {
+                    name = Some(cell_name.into_owned());
+                }
+            }
+            "DATA" if reference_count == 0 => {
+                let (field, cell_flags) = le_u32(field)?;
+                let (field, x) = le_i32(field)?;
+                let (_, y) = le_i32(field)?;
+                flags = CellFlags::from_bits_truncate(cell_flags);
+                coords = Some((x, y));
+            }
+            "RGNN" => {
+                let (_, region_name) = parse_zstring(field)?;
+                region = Some(region_name.into_owned());
+            }
+            "FRMR" => {
+                reference_count += 1;
+            }
+            _ => {}
+        }
+        input = remaining;
+    }
+
+    let (x, y) = match coords {
+        Some((x, y)) if !flags.contains(CellFlags::INTERIOR) => (Some(x), Some(y)),
+        _ => (None, None),
+    };
+    Ok((
+        input,
+        Cell {
+            name,
+            x,
+            y,
+            region,
+            flags,
+            reference_count,
+        },
+    ))
+}
+
+fn parse_record(input: &[u8]) -> IResult<&[u8], Record<'_>> {
+    let (input, record_type) = parse_4char(input)?;
+    let (input, size) = le_u32(input)?;
+    // Unused header value
+    let (input, _) = le_u32(input)?;
+    // Record flags (persistent, blocked), not needed for cells
+    let (input, _) = le_u32(input)?;
+    let (input, data) = take(size)(input).map_err(in_record(record_type))?;
+    Ok((input, Record { record_type, data }))
+}
+
+fn parse_subrecord_header(input: &[u8]) -> IResult<&[u8], SubrecordHeader<'_>> {
+    let (input, subrecord_type) = parse_4char(input)?;
+    let (input, size) = le_u32(input)?;
+    Ok((
+        input,
+        SubrecordHeader {
+            subrecord_type,
+            size,
+        },
+    ))
+}
+
+/// Parses a null-padded string stored in a fixed number of bytes
+fn parse_fixed_string(input: &[u8], size: usize) -> IResult<&[u8], Cow<'_, str>> {
+    let (input, bytes) = take(size)(input)?;
+    let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(size);
+    let (string, _, _) = WINDOWS_1252.decode(&bytes[..end]);
+    Ok((input, string))
+}
@@ -0,0 +1,120 @@
+use crate::error::{ParseError, Result};
+use crate::iter::CellCollector;
+use crate::parser::{parse_plugin_header, Cell, ParseOptions, PluginHeader, World};
+use crate::raw::{RawGroup, RawRecord};
+use crate::walk::{Entry, Walker};
+
+/// Callbacks that are called by [`visit_plugin`] for the groups and records of a plugin in the order they are found in
+/// the file.
+///
+/// All methods have empty default implementations, so only the ones that are needed have to be implemented. Records
+/// that this crate doesn't parse can be handled in [`PluginVisitor::on_record`].
+pub trait PluginVisitor {
+    /// Called with the parsed TES4 header record before any groups are visited
+    fn on_header(&mut self, _header: &PluginHeader) {}
+
+    /// Called when a group is entered. Returning `false` skips all of the groups and records inside of it.
+    fn on_group(&mut self, _group: &RawGroup) -> bool {
+        true
+    }
+
+    /// Called for every record in the groups that aren't skipped, before its data is parsed
+    fn on_record(&mut self, _record: &RawRecord) {}
+
+    /// Called with every parsed WRLD record that isn't left out by [`ParseOptions::worldspace_filter`]
+    fn on_world(&mut self, _world: &World) {}
+
+    /// Called with every parsed CELL record that isn't left out by the [`ParseOptions`].
+    ///
+    /// Cells are visited once all of the records in their children groups have been visited, so that their children
+    /// and actors can be included.
+    fn on_cell(&mut self, _cell: &Cell) {}
+}
+
+/// Walks all groups and records of a plugin, calling the methods of `visitor` for each one.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `visitor` - The callbacks to call while walking the plugin
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{visit_plugin, PluginVisitor, RawRecord};
+///
+/// /// Counts the placed objects in a plugin
+/// struct RefCounter(usize);
+///
+/// impl PluginVisitor for RefCounter {
+///     fn on_record(&mut self, record: &RawRecord) {
+///         if record.record_type == "REFR" {
+///             self.0 += 1;
+///         }
+///     }
+/// }
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let mut counter = RefCounter(0);
+/// visit_plugin(&plugin_contents, &mut counter).unwrap();
+/// ```
+pub fn visit_plugin<V: PluginVisitor>(input: &[u8], visitor: &mut V) -> Result<()> {
+    visit_plugin_with_options(input, &ParseOptions::default(), visitor)
+}
+
+/// Walks all groups and records of a plugin like [`visit_plugin`], but with [`ParseOptions`] that control which
+/// worlds and cells are passed to [`PluginVisitor::on_world`] and [`PluginVisitor::on_cell`].
+///
+/// Every group that the visitor doesn't skip is still walked and passed to [`PluginVisitor::on_record`] regardless of
+/// the options. [`ParseOptions::lenient`], [`ParseOptions::collect_warnings`], and
+/// [`ParseOptions::assign_persistent_refs`] are not supported, and fail with a [`ParseError::UnsupportedOption`] before
+/// anything is visited.
+///
+/// # Arguments
+///
+/// * `input` - A slice of bytes read from the plugin file
+/// * `options` - Options that control what is extracted from the plugin
+/// * `visitor` - The callbacks to call while walking the plugin
+pub fn visit_plugin_with_options<V: PluginVisitor>(
+    input: &[u8],
+    options: &ParseOptions,
+    visitor: &mut V,
+) -> Result<()> {
+    CellCollector::check_options(options, "visit_plugin_with_options")?;
+    let (remaining, header) =
+        parse_plugin_header(input, options.game).map_err(|err| ParseError::from_nom(err, input))?;
+    visitor.on_header(&header);
+
+    let mut walker = Walker::new(
+        input,
+        input.len() - remaining.len(),
+        options.game,
+        header.is_localized(),
+    )
+    .strict(options.strict);
+    let mut cells = CellCollector::new(input, options);
+    while let Some(entry) = walker.next() {
+        let (entry, context) = entry?;
+        match entry {
+            Entry::Group(group_header) => {
+                if !visitor.on_group(&RawGroup::new(&group_header)) {
+                    walker.skip_group();
+                }
+            }
+            Entry::Record(record_header, data) => {
+                visitor.on_record(&RawRecord::new(input, &record_header, data, &context));
+                let (finished_cell, world) = cells.add_record(&record_header, data, &context)?;
+                if let Some(finished_cell) = finished_cell {
+                    visitor.on_cell(&finished_cell.parse()?);
+                }
+                if let Some(world) = world {
+                    visitor.on_world(&world);
+                }
+            }
+        }
+    }
+    if let Some(finished_cell) = cells.finish() {
+        visitor.on_cell(&finished_cell.parse()?);
+    }
+    Ok(())
+}
@@ -0,0 +1,310 @@
+use crate::error::{ParseError, ParseWarning, Result, WarningKind};
+use crate::game::Game;
+use crate::parser::{
+    offset_in, parse_group_or_record_header, GroupContext, GroupHeader, Header, RecordHeader,
+};
+
+/// Highest group type used by any of the games
+const MAX_GROUP_TYPE: i32 = 10;
+
+/// A group or record found while walking a plugin
+pub(crate) enum Entry<'a> {
+    Group(GroupHeader<'a>),
+    /// A record header and the (possibly compressed) data section of the record
+    Record(RecordHeader<'a>, &'a [u8]),
+}
+
+/// Walks the groups and records of a plugin in file order, keeping track of which world, cell, and group each one is
+/// in.
+///
+/// Each item is an entry along with the context it was found in. Groups are descended into unless
+/// [`Walker::skip_group`] is called right after the group is returned.
+pub(crate) struct Walker<'a> {
+    /// The whole plugin file, so that positions are byte offsets into the file
+    input: &'a [u8],
+    position: usize,
+    game: Game,
+    context: GroupContext,
+    /// Start and end positions of the groups currently being walked, along with the context of their parents
+    groups: Vec<(usize, usize, GroupContext)>,
+    /// Fail on structural problems that are otherwise walked past, see [`ParseOptions::strict`](crate::ParseOptions::strict)
+    strict: bool,
+    /// The structural problems that were walked past
+    warnings: Vec<ParseWarning>,
+    /// Error for a group that extends past the end of the file, returned once the records before the end of the file
+    /// have been walked
+    truncated_group: Option<ParseError>,
+}
+
+impl<'a> Walker<'a> {
+    /// Starts walking `input` at `position`, which should be right after the TES4 header record
+    pub(crate) fn new(input: &'a [u8], position: usize, game: Game, is_localized: bool) -> Self {
+        Walker {
+            input,
+            position,
+            game,
+            context: GroupContext {
+                is_localized,
+                ..Default::default()
+            },
+            groups: vec![],
+            strict: false,
+            warnings: vec![],
+            truncated_group: None,
+        }
+    }
+
+    /// Makes the walk fail on groups of unknown types, top-level groups with labels that aren't valid UTF-8, and groups
+    /// and records that extend past the end of the group that contains them, instead of recording them as warnings
+    pub(crate) fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Takes the structural problems that have been walked past so far, which would have failed the walk in strict
+    /// mode
+    pub(crate) fn take_warnings(&mut self) -> Vec<ParseWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Skips the contents of the group that was just returned
+    pub(crate) fn skip_group(&mut self) {
+        if let Some((_, end, parent_context)) = self.groups.pop() {
+            self.position = end;
+            self.context = parent_context;
+        }
+    }
+
+    /// Number of groups currently being walked. Right after a group is returned, this includes the group itself.
+    pub(crate) fn depth(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Byte offsets of the headers of the groups currently being walked, outermost first. Right after a record is
+    /// returned, these are the groups that contain the record.
+    pub(crate) fn group_offsets(&self) -> Vec<usize> {
+        self.groups.iter().map(|&(start, _, _)| start).collect()
+    }
+
+    /// Fails with `err` in strict mode, or records it as a warning otherwise
+    fn check(&mut self, kind: WarningKind, err: ParseError) -> Result<()> {
+        if self.strict {
+            return Err(err);
+        }
+        self.warnings.push(ParseWarning::new(kind, &err));
+        Ok(())
+    }
+
+    /// Checks an entry that ends at `end` against the group that contains it
+    fn check_group_end(
+        &mut self,
+        record_type: &str,
+        form_id: Option<u32>,
+        end: usize,
+    ) -> Result<()> {
+        match self.groups.last() {
+            Some(&(group_start, group_end, _)) if end > group_end => {
+                let err = ParseError::Malformed {
+                    record_type: Some(record_type.to_string()),
+                    form_id,
+                    offset: self.position,
+                    problem: format!(
+                        "extends {} bytes past the end of the group at offset {:#X}",
+                        end - group_end,
+                        group_start
+                    ),
+                };
+                self.check(WarningKind::GroupOverrun, err)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn fail(&mut self, err: ParseError) -> Option<Result<(Entry<'a>, GroupContext)>> {
+        self.position = self.input.len();
+        self.groups.clear();
+        self.truncated_group = None;
+        Some(Err(err))
+    }
+}
+
+impl<'a> Iterator for Walker<'a> {
+    type Item = Result<(Entry<'a>, GroupContext)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(&(_, end, parent_context)) = self.groups.last() {
+            if self.position < end {
+                break;
+            }
+            self.groups.pop();
+            self.context = parent_context;
+        }
+        if self.position >= self.input.len() {
+            return self.truncated_group.take().map(Err);
+        }
+
+        let header_size = self.game.record_header_size() as usize;
+        let header = match parse_group_or_record_header(&self.input[self.position..], self.game) {
+            Ok((_, header)) => header,
+            Err(err) => {
+                let err = ParseError::from_nom(err, self.input);
+                return self.fail(err);
+            }
+        };
+        match header {
+            Header::Group(group_header) => {
+                let mut end = self.position + group_header.size as usize;
+                if (group_header.size as usize) < header_size {
+                    return self.fail(ParseError::TruncatedRecord {
+                        record_type: Some("GRUP".to_string()),
+                        offset: self.position,
+                    });
+                }
+                if end > self.input.len() {
+                    // the records of a plugin that is cut off are still walked up to the cut, so that partial results
+                    // can be returned
+                    self.truncated_group
+                        .get_or_insert(ParseError::TruncatedRecord {
+                            record_type: Some("GRUP".to_string()),
+                            offset: self.position,
+                        });
+                    end = self.input.len();
+                }
+                if !(0..=MAX_GROUP_TYPE).contains(&group_header.group_type) {
+                    let err = ParseError::Malformed {
+                        record_type: Some("GRUP".to_string()),
+                        form_id: None,
+                        offset: self.position,
+                        problem: format!("unknown group type {}", group_header.group_type),
+                    };
+                    if let Err(err) = self.check(WarningKind::UnknownGroupType, err) {
+                        return self.fail(err);
+                    }
+                }
+                if group_header.group_type == 0 && std::str::from_utf8(group_header.label).is_err()
+                {
+                    // the label starts after the GRUP type and size
+                    let err = ParseError::BadUtf8Label {
+                        offset: self.position + 8,
+                    };
+                    if let Err(err) = self.check(WarningKind::BadGroupLabel, err) {
+                        return self.fail(err);
+                    }
+                }
+                if let Err(err) = self.check_group_end("GRUP", None, end) {
+                    return self.fail(err);
+                }
+                let parent_context = self.context;
+                self.context = GroupContext {
+                    group_type: Some(group_header.group_type),
+                    group_timestamp: group_header.timestamp,
+                    group_version_control_info: group_header.version_control_info,
+                    ..parent_context
+                };
+                match group_header.group_type {
+                    // reset world_form_id when entering new worldspace/cell group
+                    0 => self.context.world_form_id = None,
+                    // cell children groups are labeled with the form id of the cell they belong to
+                    6 | 8 | 9 => {
+                        self.context.cell_form_id = Some(u32::from_le_bytes(*group_header.label))
+                    }
+                    _ => {}
+                }
+                self.groups.push((self.position, end, parent_context));
+                self.position += header_size;
+                Some(Ok((Entry::Group(group_header), parent_context)))
+            }
+            Header::Record(record_header) => {
+                let data_start = self.position + header_size;
+                let end = data_start + record_header.size as usize;
+                if end > self.input.len() {
+                    return self.fail(ParseError::TruncatedRecord {
+                        record_type: Some(record_header.record_type.to_string()),
+                        offset: self.position,
+                    });
+                }
+                if let Err(err) =
+                    self.check_group_end(record_header.record_type, Some(record_header.id), end)
+                {
+                    return self.fail(err);
+                }
+                if record_header.record_type == "WRLD" {
+                    // the children group of a world follows its WRLD record
+                    self.context.world_form_id = Some(record_header.id);
+                }
+                self.position = end;
+                let data = &self.input[data_start..end];
+                Some(Ok((Entry::Record(record_header, data), self.context)))
+            }
+        }
+    }
+}
+
+/// Describes the groups that contain `offset` in the plugin `input`, outermost first, followed by the record that
+/// contains it, if any
+fn breadcrumbs(input: &[u8], game: Game, offset: usize) -> Vec<String> {
+    let mut walker = Walker::new(input, 0, game, false);
+    let mut breadcrumbs = vec![];
+    loop {
+        let start = walker.position;
+        if offset < start {
+            break;
+        }
+        match walker.next() {
+            Some(Ok((Entry::Group(group_header), _))) => {
+                if offset >= start + group_header.size as usize {
+                    walker.skip_group();
+                } else {
+                    breadcrumbs.push(describe_group(group_header.group_type, group_header.label));
+                }
+            }
+            Some(Ok((Entry::Record(record_header, data), _))) => {
+                if offset < offset_in(input, data) + data.len() {
+                    breadcrumbs.push(format!(
+                        "{} {:08X}",
+                        record_header.record_type, record_header.id
+                    ));
+                    break;
+                }
+            }
+            Some(Err(_)) | None => break,
+        }
+    }
+    breadcrumbs
+}
+
+/// Describes a group by what it contains, like `GRUP WRLD` or `GRUP type 4 (block 1, -2)`
+pub(crate) fn describe_group(group_type: i32, label: &[u8; 4]) -> String {
+    let number = u32::from_le_bytes(*label);
+    // exterior block and sub-block labels store the grid coordinates as Y then X
+    let y = i16::from_le_bytes([label[0], label[1]]);
+    let x = i16::from_le_bytes([label[2], label[3]]);
+    match group_type {
+        0 => format!("GRUP {}", String::from_utf8_lossy(label)),
+        1 => format!("WRLD {:08X}", number),
+        2 => format!("GRUP type 2 (block {})", number),
+        3 => format!("GRUP type 3 (sub-block {})", number),
+        4 => format!("GRUP type 4 (block {}, {})", x, y),
+        5 => format!("GRUP type 5 (sub-block {}, {})", x, y),
+        6 => format!("CELL {:08X} children", number),
+        8 => format!("CELL {:08X} persistent children", number),
+        9 => format!("CELL {:08X} temporary children", number),
+        group_type => format!("GRUP type {} (children of {:08X})", group_type, number),
+    }
+}
+
+/// Adds the groups and the record of the plugin `input` that contain the offset of `error` to its breadcrumbs, so that
+/// errors deep in nested groups say where they are
+pub(crate) fn add_breadcrumbs(error: ParseError, input: &[u8], game: Game) -> ParseError {
+    let has_record = match error.breadcrumbs().first() {
+        Some(breadcrumb) => !breadcrumb.starts_with("field "),
+        None => false,
+    };
+    match error.offset() {
+        Some(offset) if !has_record => {
+            let breadcrumbs = breadcrumbs(input, game, offset);
+            error.within(breadcrumbs)
+        }
+        _ => error,
+    }
+}
@@ -0,0 +1,86 @@
+//! Exports for calling the parser from JavaScript in a WebAssembly build, enabled by the `wasm` feature.
+//!
+//! JavaScript can't pass a byte array to a function of the module directly, so it copies the plugin into memory
+//! allocated with [`scd_alloc`] and passes the pointer to [`scd_parse_plugin_json`](crate::ffi::scd_parse_plugin_json).
+//! `js/skyrim_cell_dump.js` wraps this in a `parsePlugin(bytes)` function that returns the parsed plugin.
+use std::ptr;
+
+/// Allocates `len` bytes in the memory of the module, which must be freed with [`scd_dealloc`]
+#[no_mangle]
+pub extern "C" fn scd_alloc(len: usize) -> *mut u8 {
+    Box::into_raw(vec![0; len].into_boxed_slice()) as *mut u8
+}
+
+/// Frees memory allocated with [`scd_alloc`]
+///
+/// # Safety
+///
+/// `ptr` must have been returned by [`scd_alloc`] with the same `len`, and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn scd_dealloc(ptr: *mut u8, len: usize) {
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(ptr, len)));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::ffi::{scd_free_string, scd_last_error, scd_parse_plugin_json};
+    use crate::parser::Cell;
+
+    /// Copies `bytes` into memory allocated with `scd_alloc`, like `copyIn` in `js/skyrim_cell_dump.js`
+    fn copy_in(bytes: &[u8]) -> *mut u8 {
+        let ptr = scd_alloc(bytes.len());
+        // SAFETY: `scd_alloc` returned `bytes.len()` writable bytes
+        unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len()) };
+        ptr
+    }
+
+    /// Calls the exports in the same order as `parsePlugin` in `js/skyrim_cell_dump.js`
+    fn parse_plugin(bytes: &[u8], game: &str) -> Result<serde_json::Value, String> {
+        let game = format!("{}\0", game);
+        let data_ptr = copy_in(bytes);
+        let game_ptr = copy_in(game.as_bytes());
+        // SAFETY: the pointers point to the copies, and the game is null-terminated
+        unsafe {
+            let json_ptr = scd_parse_plugin_json(data_ptr, bytes.len(), game_ptr as *const _);
+            let result = if json_ptr.is_null() {
+                Err(CStr::from_ptr(scd_last_error())
+                    .to_string_lossy()
+                    .into_owned())
+            } else {
+                let json = CStr::from_ptr(json_ptr).to_str().unwrap().to_owned();
+                scd_free_string(json_ptr);
+                Ok(serde_json::from_str(&json).unwrap())
+            };
+            scd_dealloc(data_ptr, bytes.len());
+            scd_dealloc(game_ptr, game.len());
+            result
+        }
+    }
+
+    #[test]
+    fn parses_plugins_copied_into_allocated_memory() {
+        let bytes = PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(Cell::new_interior(0x0100_0D74, None))
+            .write()
+            .unwrap();
+        let plugin = parse_plugin(&bytes, "skyrim").unwrap();
+        assert_eq!(
+            plugin["header"]["masters"],
+            serde_json::json!(["Skyrim.esm"])
+        );
+        assert_eq!(plugin["cells"][0]["form_id"], 0x0100_0D74);
+    }
+
+    #[test]
+    fn returns_the_error_of_invalid_plugins() {
+        assert!(parse_plugin(b"garbage", "skyrim").is_err());
+        assert!(parse_plugin(&[], "skyrim").is_err());
+        let bytes = PluginBuilder::new().write().unwrap();
+        assert!(parse_plugin(&bytes, "morrowind2").is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use crate::parser::{Cell, Plugin, World};
+
+/// The exterior cells of one worldspace of a [`Plugin`], laid out on the grid of cell coordinates.
+///
+/// Borrows the cells from the plugin. Returned by [`Plugin::worldspace`] and [`Plugin::worldspaces`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::parse_plugin;
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let plugin = parse_plugin(&plugin_contents).unwrap();
+/// for worldspace in plugin.worldspaces() {
+///     if let Some(cell) = worldspace.cell_at(0, 0) {
+///         println!("{:08X} has cell {:08X} at 0, 0", worldspace.form_id, cell.form_id);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Worldspace<'a> {
+    /// Form ID of the WRLD record of the worldspace
+    pub form_id: u32,
+    /// The WRLD record of the worldspace, if the plugin has one
+    pub world: Option<&'a World>,
+    /// The special persistent cell of the worldspace that holds its persistent references, which has no coordinates
+    pub persistent_cell: Option<&'a Cell<'a>>,
+    /// Cells by their `(y, x)` coordinates, so that they are ordered by row
+    cells: BTreeMap<(i32, i32), &'a Cell<'a>>,
+}
+
+impl<'a> Worldspace<'a> {
+    fn new(plugin: &'a Plugin, form_id: u32) -> Self {
+        let mut worldspace = Worldspace {
+            form_id,
+            world: plugin.world(form_id),
+            persistent_cell: None,
+            cells: BTreeMap::new(),
+        };
+        for cell in plugin
+            .cells
+            .iter()
+            .filter(|cell| cell.world_form_id == Some(form_id))
+        {
+            match (cell.x, cell.y) {
+                (Some(x), Some(y)) if !cell.is_persistent => {
+                    worldspace.cells.insert((y, x), cell);
+                }
+                _ => worldspace.persistent_cell = Some(cell),
+            }
+        }
+        worldspace
+    }
+
+    /// The cell at the coordinates `x` and `y`, if the plugin has one there
+    pub fn cell_at(&self, x: i32, y: i32) -> Option<&'a Cell<'a>> {
+        self.cells.get(&(y, x)).copied()
+    }
+
+    /// The cells of the worldspace in row-major order: from the southernmost row of cells to the northernmost, and from
+    /// west to east within each row
+    pub fn cells(&self) -> impl Iterator<Item = &'a Cell<'a>> + '_ {
+        self.cells.values().copied()
+    }
+
+    /// The lowest and highest coordinates of the cells, as `((min_x, min_y), (max_x, max_y))`, or `None` if the
+    /// worldspace has no cells with coordinates
+    pub fn bounds(&self) -> Option<((i32, i32), (i32, i32))> {
+        let (&(min_y, _), _) = self.cells.iter().next()?;
+        let (&(max_y, _), _) = self.cells.iter().next_back()?;
+        let min_x = self.cells.keys().map(|&(_, x)| x).min()?;
+        let max_x = self.cells.keys().map(|&(_, x)| x).max()?;
+        Some(((min_x, min_y), (max_x, max_y)))
+    }
+
+    /// Number of cells with coordinates in the worldspace, not counting the persistent cell
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+}
+
+impl Plugin<'_> {
+    /// Lays out the exterior cells of the worldspace with `form_id` on its grid
+    pub fn worldspace(&self, form_id: u32) -> Worldspace<'_> {
+        Worldspace::new(self, form_id)
+    }
+
+    /// Lays out the exterior cells of each worldspace that the plugin has cells or a WRLD record for on their grids,
+    /// ordered by the form ids of the worldspaces
+    pub fn worldspaces(&self) -> Vec<Worldspace<'_>> {
+        let mut form_ids: Vec<u32> = self
+            .worlds
+            .iter()
+            .map(|world| world.form_id)
+            .chain(self.cells.iter().filter_map(|cell| cell.world_form_id))
+            .collect();
+        form_ids.sort_unstable();
+        form_ids.dedup();
+        form_ids
+            .into_iter()
+            .map(|form_id| self.worldspace(form_id))
+            .collect()
+    }
+}
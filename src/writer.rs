@@ -0,0 +1,1198 @@
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::io::Write;
+
+use encoding_rs::WINDOWS_1252;
+
+use crate::compression::Compression;
+use crate::error::{ParseError, Result};
+use crate::form_id::FormId;
+use crate::game::Game;
+use crate::parser::{
+    decompress_record_data, parse_raw_fields, ActorPlacement, Cell, CellFlags, LString,
+    ParentUseFlags, Plugin, PluginHeader, RecordFlags, World, XCLL_INHERIT_FLAGS_OFFSET,
+};
+
+/// Record header flag of persistent CELL and reference records
+const PERSISTENT_FLAG: u32 = 0x00000400;
+/// Record header flag of references that are disabled when the game starts
+const INITIALLY_DISABLED_FLAG: u32 = 0x00000800;
+/// Size of the XCLL field of a CELL record in Skyrim, which ends with the lighting template inherit flags
+const XCLL_SIZE: usize = 92;
+/// Size of the XCLL field of a CELL record in Fallout 4, which has the height fog settings after the inherit flags
+const FALLOUT4_XCLL_SIZE: usize = 136;
+/// Size of the DATA field of an ACHR record with the position and rotation of the actor
+const ACHR_DATA_SIZE: usize = 24;
+/// Size of each water velocity in the XWCU field of a CELL record, of which only the offset and angle are parsed
+const WATER_VELOCITY_SIZE: usize = 32;
+/// Fields of a WRLD record that are parsed into a [`World`]
+const WORLD_FIELDS: [&str; 4] = ["EDID", "FULL", "WNAM", "PNAM"];
+/// Fields of a CELL record that are parsed into a [`Cell`] in every game, besides the fields of the lighting and the
+/// precombined data that only some games have
+const CELL_FIELDS: [&str; 16] = [
+    "EDID", "FULL", "DATA", "XCLC", "LTMP", "XCLW", "XCLR", "XWCN", "XWCU", "XCWT", "XILL", "XCCM",
+    "XCAS", "XEZN", "XCMO", "XCIM",
+];
+/// Fields of a CELL record of which only a part is parsed, and which are kept from the record when the part that is
+/// parsed is missing, e.g. an XCLL field too short to have lighting template inherit flags
+const PARTLY_PARSED_CELL_FIELDS: [&str; 2] = ["XCLL", "TVDT"];
+/// Order of the fields of a Skyrim or Fallout 4 CELL record that the Creation Kit and xEdit write them in, including
+/// the fields that aren't parsed into a [`Cell`]
+pub(crate) const CELL_FIELD_ORDER: [&str; 32] = [
+    "EDID", "FULL", "DATA", "VISI", "RVIS", "PCMB", "XCLC", "XCLL", "TVDT", "MHDT", "LTMP", "LNAM",
+    "XCLW", "XNAM", "XCLR", "XLCN", "XWCN", "XWCS", "XWCU", "XCWT", "XOWN", "XRNK", "XILL", "XWEM",
+    "XCCM", "XCAS", "XEZN", "XCMO", "XCIM", "XGDR", "XPRI", "XCRI",
+];
+
+/// Order of the fields of a Skyrim WRLD record that the Creation Kit writes them in, including the fields that aren't
+/// parsed into a [`World`]
+const WORLD_FIELD_ORDER: [&str; 29] = [
+    "EDID", "RNAM", "MHDT", "FULL", "WCTR", "LTMP", "XEZN", "XLCN", "WNAM", "PNAM", "CNAM", "NAM2",
+    "NAM3", "NAM4", "DNAM", "ICON", "MODL", "MNAM", "ONAM", "NAMA", "DATA", "NAM0", "NAM9", "ZNAM",
+    "NNAM", "XNAM", "TNAM", "UNAM", "OFST",
+];
+
+/// Cells sorted into the block and then sub-block groups they are stored in, keyed by block numbers or coordinates
+type CellBlocks<'a, K> = BTreeMap<K, BTreeMap<K, Vec<&'a Cell<'a>>>>;
+
+/// Encodes a plugin into the bytes of a Skyrim plugin file.
+///
+/// The TES4 header record is written along with WRLD records and CELL records in the block and sub-block groups the
+/// game expects, with the record flags of the worlds and cells (like deleted and ignored). Records are written
+/// uncompressed, and the record and group count in the header is recomputed.
+///
+/// **Only the fields that are parsed into a [`World`] or [`Cell`] are known to the writer.** Worlds and cells parsed
+/// with [`ParseOptions::include_raw_data`](crate::ParseOptions::include_raw_data) keep every other field of their
+/// record from [`World::raw_data`] and [`Cell::raw_data`], as well as the parts of fields that aren't parsed, like the
+/// lighting in the XCLL field of a cell around its lighting template inherit flags. Without the raw data, a record is
+/// written with only the parsed fields, which drops everything else the record had, like the map data and water of a
+/// world, or the ownership and location of a cell. The lighting of a cell is then all zeros apart from its inherit
+/// flags, and the TVDT occlusion data is left out, since only its size is parsed. Only write worlds and cells without
+/// raw data if they are new, like the ones built with a [`PluginBuilder`](crate::PluginBuilder), or if the dropped
+/// fields aren't needed.
+///
+/// The WRLD record of every world that the plugin has cells of has to be in [`Plugin::worlds`], since its cells are
+/// written in the group after it. Writing a placeholder WRLD record in its place would override every field of the
+/// world in the master it is from, so a [`ParseError::MissingWorld`] is returned instead.
+///
+/// The actors that the plugin adds to each cell are written as ACHR records in the children groups of the cell.
+/// **An [`ActorPlacement`] doesn't have a position, so the actors are placed at the origin of the worldspace (0, 0, 0)
+/// with no rotation**, which is outside of every exterior cell other than the cell at (0, 0). Actors that override an
+/// actor of a master aren't written, so that they don't move the actor of the master. Other records of the plugin
+/// (like LAND, NAVM, LCTN, REGN, and references that aren't actors) aren't stored in a [`Plugin`] and are not written.
+///
+/// # Arguments
+///
+/// * `plugin` - The plugin to encode
+///
+/// # Examples
+///
+/// ```no_run
+/// use skyrim_cell_dump::{parse_plugin_with_options, write_plugin, ParseOptions};
+///
+/// let plugin_contents = std::fs::read("Plugin.esp").unwrap();
+/// let options = ParseOptions {
+///     include_raw_data: true,
+///     ..Default::default()
+/// };
+/// let plugin = parse_plugin_with_options(&plugin_contents, &options).unwrap();
+/// std::fs::write("Cells.esp", write_plugin(&plugin).unwrap()).unwrap();
+/// ```
+pub fn write_plugin(plugin: &Plugin) -> Result<Vec<u8>> {
+    encode_plugin(plugin, Game::default())
+}
+
+/// Encodes a plugin made for `game` like [`write_plugin`] and writes it to `writer`
+///
+/// # Arguments
+///
+/// * `writer` - Where to write the plugin file to
+/// * `plugin` - The plugin to encode
+/// * `game` - The game to use the record layouts of
+pub fn write_plugin_to<W: Write>(mut writer: W, plugin: &Plugin, game: Game) -> Result<()> {
+    writer.write_all(&encode_plugin(plugin, game)?)?;
+    Ok(())
+}
+
+pub(crate) fn encode_plugin(plugin: &Plugin, game: Game) -> Result<Vec<u8>> {
+    let mut groups = GroupWriter::new(game, &plugin.header);
+
+    let mut interior_blocks: CellBlocks<i32> = BTreeMap::new();
+    let mut world_cells: BTreeMap<u32, Vec<&Cell>> = BTreeMap::new();
+    for cell in &plugin.cells {
+        match cell.world_form_id {
+            Some(world_form_id) => world_cells.entry(world_form_id).or_default().push(cell),
+            None => {
+                let (block, sub_block) = interior_block(cell.form_id);
+                interior_blocks
+                    .entry(block)
+                    .or_default()
+                    .entry(sub_block)
+                    .or_default()
+                    .push(cell);
+            }
+        }
+    }
+
+    let mut worlds: Vec<&World> = plugin.worlds.iter().collect();
+    worlds.sort_by_key(|world| world.form_id);
+    let world_form_ids: HashSet<u32> = worlds.iter().map(|world| world.form_id).collect();
+    if let Some((&world_form_id, cells)) = world_cells
+        .iter()
+        .find(|(form_id, _)| !world_form_ids.contains(form_id))
+    {
+        return Err(ParseError::MissingWorld {
+            world_form_id,
+            cell_form_id: cells.iter().map(|cell| cell.form_id).min().unwrap_or(0),
+        });
+    }
+
+    if !interior_blocks.is_empty() {
+        groups.group(*b"CELL", 0, |groups| {
+            for (block, sub_blocks) in &interior_blocks {
+                groups.group(block.to_le_bytes(), 2, |groups| {
+                    for (sub_block, cells) in sub_blocks {
+                        groups.group(sub_block.to_le_bytes(), 3, |groups| groups.cells(cells))?;
+                    }
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+    }
+
+    if !worlds.is_empty() {
+        groups.group(*b"WRLD", 0, |groups| {
+            for world in &worlds {
+                groups.record(
+                    "WRLD",
+                    written_flags(world.record_flags).bits(),
+                    world.form_id,
+                    &world_record_data(world)?,
+                );
+                if let Some(cells) = world_cells.get(&world.form_id) {
+                    groups.group(world.form_id.to_le_bytes(), 1, |groups| {
+                        groups.world_cells(cells)
+                    })?;
+                }
+            }
+            Ok(())
+        })?;
+    }
+
+    let mut bytes = vec![];
+    let header_fields = header_fields(&plugin.header, groups.num_records_and_groups);
+    write_record_header(
+        &mut bytes,
+        "TES4",
+        plugin.header.flags.bits(),
+        0,
+        header_fields.len(),
+        game,
+    );
+    bytes.extend_from_slice(&header_fields);
+    bytes.extend_from_slice(&groups.bytes);
+    Ok(bytes)
+}
+
+/// Writes groups and records, keeping count of how many have been written for the TES4 header
+struct GroupWriter<'p> {
+    game: Game,
+    /// Header of the plugin being written, to tell the records it adds from the ones it overrides
+    header: &'p PluginHeader<'p>,
+    bytes: Vec<u8>,
+    num_records_and_groups: i32,
+}
+
+impl<'p> GroupWriter<'p> {
+    fn new(game: Game, header: &'p PluginHeader<'p>) -> Self {
+        GroupWriter {
+            game,
+            header,
+            bytes: vec![],
+            num_records_and_groups: 0,
+        }
+    }
+
+    /// Writes a group whose contents are written by `contents`
+    fn group(
+        &mut self,
+        label: [u8; 4],
+        group_type: i32,
+        contents: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(b"GRUP");
+        // the size of the group is filled in once its contents are written
+        self.bytes.extend_from_slice(&0u32.to_le_bytes());
+        self.bytes.extend_from_slice(&label);
+        self.bytes.extend_from_slice(&group_type.to_le_bytes());
+        self.bytes
+            .resize(start + self.game.record_header_size() as usize, 0);
+        self.num_records_and_groups += 1;
+        contents(self)?;
+        let size = (self.bytes.len() - start) as u32;
+        self.bytes[start + 4..start + 8].copy_from_slice(&size.to_le_bytes());
+        Ok(())
+    }
+
+    fn record(&mut self, record_type: &str, flags: u32, form_id: u32, fields: &[u8]) {
+        write_record_header(
+            &mut self.bytes,
+            record_type,
+            flags,
+            form_id,
+            fields.len(),
+            self.game,
+        );
+        self.bytes.extend_from_slice(fields);
+        self.num_records_and_groups += 1;
+    }
+
+    /// Writes CELL records followed by the children groups with their actors
+    fn cells(&mut self, cells: &[&Cell]) -> Result<()> {
+        let mut cells = cells.to_vec();
+        cells.sort_by_key(|cell| cell.form_id);
+        for cell in cells {
+            self.record(
+                "CELL",
+                cell_record_flags(cell).bits(),
+                cell.form_id,
+                &cell_record_data(cell, self.game)?,
+            );
+            let header = self.header;
+            let actors: Vec<&ActorPlacement> = cell
+                .actors
+                .iter()
+                .filter(|actor| FormId(actor.form_id).is_new_record(header))
+                .collect();
+            if actors.is_empty() {
+                continue;
+            }
+            self.group(cell.form_id.to_le_bytes(), 6, |groups| {
+                let (persistent, temporary): (Vec<_>, Vec<_>) =
+                    actors.into_iter().partition(|actor| actor.is_persistent);
+                for (group_type, actors) in [(8, persistent), (9, temporary)] {
+                    if actors.is_empty() {
+                        continue;
+                    }
+                    groups.group(cell.form_id.to_le_bytes(), group_type, |groups| {
+                        for actor in actors {
+                            groups.actor(actor);
+                        }
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Writes the cells of a world, with the exterior cells sorted into the block and sub-block groups of their
+    /// coordinates
+    fn world_cells(&mut self, cells: &[&Cell]) -> Result<()> {
+        let mut blocks: CellBlocks<(i32, i32)> = BTreeMap::new();
+        let mut unplaced_cells = vec![];
+        for &cell in cells {
+            match (cell.x, cell.y) {
+                (Some(x), Some(y)) if !cell.is_persistent => blocks
+                    .entry((y >> 5, x >> 5))
+                    .or_default()
+                    .entry((y >> 3, x >> 3))
+                    .or_default()
+                    .push(cell),
+                _ => unplaced_cells.push(cell),
+            }
+        }
+        // the persistent cell of a world comes before its blocks
+        self.cells(&unplaced_cells)?;
+        for ((block_y, block_x), sub_blocks) in &blocks {
+            self.group(grid_label(*block_x, *block_y), 4, |groups| {
+                for ((sub_block_y, sub_block_x), cells) in sub_blocks {
+                    groups.group(grid_label(*sub_block_x, *sub_block_y), 5, |groups| {
+                        groups.cells(cells)
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    fn actor(&mut self, actor: &ActorPlacement) {
+        let mut flags = 0;
+        if actor.is_persistent {
+            flags |= PERSISTENT_FLAG;
+        }
+        if actor.is_initially_disabled {
+            flags |= INITIALLY_DISABLED_FLAG;
+        }
+        let mut fields = vec![];
+        if let Some(base_form_id) = actor.base_form_id {
+            write_field(&mut fields, "NAME", &base_form_id.to_le_bytes());
+        }
+        // the position and rotation, which aren't known
+        write_field(&mut fields, "DATA", &[0; ACHR_DATA_SIZE]);
+        self.record("ACHR", flags, actor.form_id, &fields);
+    }
+}
+
+/// Flags of a record header with `record_flags`, except for the compressed flag since records are written uncompressed
+fn written_flags(record_flags: RecordFlags) -> RecordFlags {
+    record_flags - RecordFlags::COMPRESSED
+}
+
+/// Flags of the header of a CELL record, where `is_persistent`, `is_deleted`, and `is_ignored` override the flags
+/// they stand for in `record_flags`
+pub(crate) fn cell_record_flags(cell: &Cell) -> RecordFlags {
+    let mut flags = written_flags(cell.record_flags);
+    flags.set(RecordFlags::PERSISTENT_REFR, cell.is_persistent);
+    flags.set(RecordFlags::DELETED_RECORD, cell.is_deleted);
+    flags.set(RecordFlags::IGNORED, cell.is_ignored);
+    flags
+}
+
+fn write_record_header(
+    bytes: &mut Vec<u8>,
+    record_type: &str,
+    flags: u32,
+    form_id: u32,
+    size: usize,
+    game: Game,
+) {
+    let start = bytes.len();
+    bytes.extend_from_slice(record_type.as_bytes());
+    bytes.extend_from_slice(&(size as u32).to_le_bytes());
+    bytes.extend_from_slice(&flags.to_le_bytes());
+    bytes.extend_from_slice(&form_id.to_le_bytes());
+    // timestamp and version control info
+    bytes.extend_from_slice(&[0; 4]);
+    if let Some(form_version) = game.form_version() {
+        bytes.extend_from_slice(&form_version.to_le_bytes());
+    }
+    bytes.resize(start + game.record_header_size() as usize, 0);
+}
+
+/// Writes a field, preceded by an XXXX field with the size if it is too large for the size of the field header
+pub(crate) fn write_field(bytes: &mut Vec<u8>, field_type: &str, data: &[u8]) {
+    let size = match u16::try_from(data.len()) {
+        Ok(size) => size,
+        Err(_) => {
+            write_field(bytes, "XXXX", &(data.len() as u32).to_le_bytes());
+            0
+        }
+    };
+    bytes.extend_from_slice(field_type.as_bytes());
+    bytes.extend_from_slice(&size.to_le_bytes());
+    bytes.extend_from_slice(data);
+}
+
+fn encode_zstring(string: &str) -> Vec<u8> {
+    let (bytes, _, _) = WINDOWS_1252.encode(string);
+    let mut zstring = bytes.into_owned();
+    zstring.push(0);
+    zstring
+}
+
+fn encode_lstring(string: &LString) -> Vec<u8> {
+    match string {
+        LString::String(string) => encode_zstring(string),
+        LString::Id(id) => id.to_le_bytes().to_vec(),
+    }
+}
+
+fn header_fields(header: &PluginHeader, num_records_and_groups: i32) -> Vec<u8> {
+    let mut fields = vec![];
+    let mut hedr = vec![];
+    hedr.extend_from_slice(&header.version.to_le_bytes());
+    hedr.extend_from_slice(&num_records_and_groups.to_le_bytes());
+    hedr.extend_from_slice(&header.next_object_id.to_le_bytes());
+    write_field(&mut fields, "HEDR", &hedr);
+    if let Some(author) = &header.author {
+        write_field(&mut fields, "CNAM", &encode_zstring(author));
+    }
+    if let Some(description) = &header.description {
+        write_field(&mut fields, "SNAM", &encode_zstring(description));
+    }
+    for master in &header.masters {
+        write_field(&mut fields, "MAST", &encode_zstring(master));
+        // the size of the master file, which the game doesn't check
+        write_field(&mut fields, "DATA", &0u64.to_le_bytes());
+    }
+    if !header.overridden_forms.is_empty() {
+        let onam: Vec<u8> = header
+            .overridden_forms
+            .iter()
+            .flat_map(|form_id| form_id.to_le_bytes())
+            .collect();
+        write_field(&mut fields, "ONAM", &onam);
+    }
+    fields
+}
+
+fn world_fields(world: &World) -> Vec<u8> {
+    let mut fields = vec![];
+    write_field(&mut fields, "EDID", &encode_zstring(&world.editor_id));
+    if let Some(name) = &world.name {
+        write_field(&mut fields, "FULL", &encode_lstring(name));
+    }
+    if let Some(parent_form_id) = world.parent_form_id {
+        write_field(&mut fields, "WNAM", &parent_form_id.to_le_bytes());
+    }
+    if let Some(parent_use_flags) = world.parent_use_flags {
+        write_field(&mut fields, "PNAM", &parent_use_flags.bits().to_le_bytes());
+    }
+    fields
+}
+
+/// Encodes the fields of a CELL record in the order of [`CELL_FIELD_ORDER`]
+pub(crate) fn cell_fields(cell: &Cell, game: Game) -> Vec<u8> {
+    let is_older_game = game == Game::Oblivion || game.is_fallout3_family();
+    let mut fields = vec![];
+    if let Some(editor_id) = &cell.editor_id {
+        write_field(&mut fields, "EDID", &encode_zstring(editor_id));
+    }
+    if let Some(name) = &cell.name {
+        write_field(&mut fields, "FULL", &encode_lstring(name));
+    }
+    let flag_bits = cell.flags.bits().to_le_bytes();
+    if is_older_game {
+        // older games store the flags in a single byte
+        write_field(&mut fields, "DATA", &flag_bits[..1]);
+    } else {
+        write_field(&mut fields, "DATA", &flag_bits);
+    }
+    if let (Some(x), Some(y)) = (cell.x, cell.y) {
+        let mut xclc = vec![];
+        xclc.extend_from_slice(&x.to_le_bytes());
+        xclc.extend_from_slice(&y.to_le_bytes());
+        if !is_older_game {
+            // land flags
+            xclc.extend_from_slice(&[0; 4]);
+        }
+        write_field(&mut fields, "XCLC", &xclc);
+    }
+    if let Some(inherit_flags) = cell.lighting_template_inherit_flags {
+        let flag_bits = inherit_flags.bits().to_le_bytes();
+        if game.is_fallout3_family() {
+            write_field(&mut fields, "LNAM", &flag_bits);
+        } else {
+            let size = if game == Game::Fallout4 {
+                FALLOUT4_XCLL_SIZE
+            } else {
+                XCLL_SIZE
+            };
+            let mut xcll = vec![0; size];
+            let offset = XCLL_INHERIT_FLAGS_OFFSET as usize;
+            xcll[offset..offset + 4].copy_from_slice(&flag_bits);
+            write_field(&mut fields, "XCLL", &xcll);
+        }
+    }
+    if let Some(occlusion_data_size) = cell.occlusion_data_size {
+        write_field(&mut fields, "TVDT", &vec![0; occlusion_data_size as usize]);
+    }
+    if let Some(lighting_template_form_id) = cell.lighting_template_form_id {
+        write_field(
+            &mut fields,
+            "LTMP",
+            &lighting_template_form_id.to_le_bytes(),
+        );
+    }
+    if let Some(water_height) = cell.water_height {
+        write_field(&mut fields, "XCLW", &water_height.0.to_le_bytes());
+    }
+    if !cell.regions.is_empty() {
+        let xclr: Vec<u8> = cell
+            .regions
+            .iter()
+            .flat_map(|form_id| form_id.to_le_bytes())
+            .collect();
+        write_field(&mut fields, "XCLR", &xclr);
+    }
+    if !cell.water_velocities.is_empty() {
+        let count = cell.water_velocities.len() as u32;
+        write_field(&mut fields, "XWCN", &count.to_le_bytes());
+        let mut xwcu = vec![];
+        for water_velocity in &cell.water_velocities {
+            for value in &water_velocity.offset {
+                xwcu.extend_from_slice(&value.0.to_le_bytes());
+            }
+            xwcu.extend_from_slice(&[0; 4]);
+            for value in &water_velocity.angle {
+                xwcu.extend_from_slice(&value.0.to_le_bytes());
+            }
+            xwcu.extend_from_slice(&[0; 4]);
+        }
+        write_field(&mut fields, "XWCU", &xwcu);
+    }
+    if let Some(water_type_form_id) = cell.water_type_form_id {
+        write_field(&mut fields, "XCWT", &water_type_form_id.to_le_bytes());
+    }
+    if let Some(lock_list_form_id) = cell.lock_list_form_id {
+        write_field(&mut fields, "XILL", &lock_list_form_id.to_le_bytes());
+    }
+    if let Some(climate_form_id) = cell.climate_form_id {
+        write_field(&mut fields, "XCCM", &climate_form_id.to_le_bytes());
+    }
+    if let Some(acoustic_space_form_id) = cell.acoustic_space_form_id {
+        write_field(&mut fields, "XCAS", &acoustic_space_form_id.to_le_bytes());
+    }
+    if let Some(encounter_zone_form_id) = cell.encounter_zone_form_id {
+        write_field(&mut fields, "XEZN", &encounter_zone_form_id.to_le_bytes());
+    }
+    if let Some(music_type_form_id) = cell.music_type_form_id {
+        write_field(&mut fields, "XCMO", &music_type_form_id.to_le_bytes());
+    }
+    if let Some(image_space_form_id) = cell.image_space_form_id {
+        write_field(&mut fields, "XCIM", &image_space_form_id.to_le_bytes());
+    }
+    if game != Game::Fallout4 {
+        return fields;
+    }
+    if !cell.previs_references.is_empty() {
+        let xpri: Vec<u8> = cell
+            .previs_references
+            .iter()
+            .flat_map(|form_id| form_id.to_le_bytes())
+            .collect();
+        write_field(&mut fields, "XPRI", &xpri);
+    }
+    if let Some(precombined) = &cell.precombined {
+        let mut xcri = vec![];
+        xcri.extend_from_slice(&(precombined.meshes.len() as u32).to_le_bytes());
+        xcri.extend_from_slice(&(precombined.references.len() as u32).to_le_bytes());
+        for mesh in &precombined.meshes {
+            xcri.extend_from_slice(&mesh.to_le_bytes());
+        }
+        for reference in &precombined.references {
+            xcri.extend_from_slice(&reference.form_id.to_le_bytes());
+            xcri.extend_from_slice(&reference.mesh.to_le_bytes());
+        }
+        write_field(&mut fields, "XCRI", &xcri);
+    }
+    fields
+}
+
+/// Encodes the data of the WRLD record of a world, keeping the fields that aren't parsed from its raw data
+fn world_record_data(world: &World) -> Result<Vec<u8>> {
+    let fields = world_fields(world);
+    match &world.raw_data {
+        Some(raw_data) => merge_raw_fields(
+            "WRLD",
+            world.form_id,
+            raw_data,
+            world.record_flags,
+            &fields,
+            &FieldLayout {
+                parsed: &WORLD_FIELDS,
+                partly_parsed: &[],
+                order: &WORLD_FIELD_ORDER,
+            },
+        ),
+        None => Ok(fields),
+    }
+}
+
+/// Encodes the data of the CELL record of a cell, keeping the fields that aren't parsed from its raw data, or leaving
+/// out the TVDT occlusion data that only the size of is known without it
+fn cell_record_data(cell: &Cell, game: Game) -> Result<Vec<u8>> {
+    let fields = cell_fields(cell, game);
+    let raw_data = match &cell.raw_data {
+        Some(raw_data) => raw_data,
+        None => {
+            let mut record_data = vec![];
+            for (field_type, field_data) in split_fields(&fields) {
+                if field_type != "TVDT" {
+                    write_field(&mut record_data, field_type, field_data);
+                }
+            }
+            return Ok(record_data);
+        }
+    };
+    let mut parsed_fields = CELL_FIELDS.to_vec();
+    parsed_fields.extend_from_slice(&PARTLY_PARSED_CELL_FIELDS);
+    if game.is_fallout3_family() {
+        parsed_fields.push("LNAM");
+    }
+    if game == Game::Fallout4 {
+        parsed_fields.extend_from_slice(&["XPRI", "XCRI"]);
+    }
+    merge_raw_fields(
+        "CELL",
+        cell.form_id,
+        raw_data,
+        cell.record_flags,
+        &fields,
+        &FieldLayout {
+            parsed: &parsed_fields,
+            partly_parsed: &PARTLY_PARSED_CELL_FIELDS,
+            order: &CELL_FIELD_ORDER,
+        },
+    )
+}
+
+/// Which fields of a record type are parsed, and the order they are written in
+struct FieldLayout<'a> {
+    parsed: &'a [&'a str],
+    /// Parsed fields that are kept from the raw data when they aren't encoded, since only a part of them is parsed
+    partly_parsed: &'a [&'a str],
+    order: &'a [&'a str],
+}
+
+/// Patches the `fields` encoded from a parsed record into the fields of the record's `raw_data`, keeping the fields
+/// that aren't parsed and the parts of fields that aren't parsed. Parsed fields that aren't in `fields` are removed,
+/// unless they are only partly parsed, and new fields are added in the order of the `layout`.
+fn merge_raw_fields(
+    record_type: &str,
+    form_id: u32,
+    raw_data: &[u8],
+    record_flags: RecordFlags,
+    fields: &[u8],
+    layout: &FieldLayout,
+) -> Result<Vec<u8>> {
+    let is_compressed = record_flags.contains(RecordFlags::COMPRESSED);
+    // every game the writer supports compresses records with zlib
+    let data = decompress_record_data(
+        raw_data,
+        record_type,
+        form_id,
+        raw_data,
+        is_compressed,
+        Compression::Zlib,
+    )?;
+    let (_, raw_fields) = parse_raw_fields(&data).map_err(|err| {
+        ParseError::from_nom(err, &data).in_record(record_type, Some(form_id), 0, is_compressed)
+    })?;
+    let fields = split_fields(fields);
+    let mut merged_fields: Vec<(&str, Vec<u8>)> = raw_fields
+        .into_iter()
+        .filter(|&(field_type, _)| {
+            !layout.parsed.contains(&field_type)
+                || layout.partly_parsed.contains(&field_type)
+                || find_field(&fields, field_type).is_some()
+        })
+        .map(|(field_type, field_data)| (field_type, field_data.to_vec()))
+        .collect();
+    for (field_type, field_data) in fields {
+        match merged_fields
+            .iter()
+            .position(|&(existing, _)| existing == field_type)
+        {
+            Some(position) => patch_field(field_type, &mut merged_fields[position].1, field_data),
+            None => {
+                let position = new_field_position(&merged_fields, field_type, layout.order);
+                merged_fields.insert(position, (field_type, field_data.to_vec()));
+            }
+        }
+    }
+    let mut record_data = vec![];
+    for (field_type, field_data) in &merged_fields {
+        write_field(&mut record_data, field_type, field_data);
+    }
+    Ok(record_data)
+}
+
+/// Splits fields encoded by [`cell_fields`] or [`world_fields`] into their types and data
+pub(crate) fn split_fields(fields: &[u8]) -> Vec<(&str, &[u8])> {
+    parse_raw_fields(fields)
+        .map(|(_, fields)| fields)
+        .expect("encoded fields are valid")
+}
+
+pub(crate) fn find_field<'a>(fields: &[(&str, &'a [u8])], field_type: &str) -> Option<&'a [u8]> {
+    fields
+        .iter()
+        .find(|&&(existing, _)| existing == field_type)
+        .map(|&(_, field_data)| field_data)
+}
+
+/// Index to insert a field that the record didn't have at, which is before the first field that comes after it in
+/// `field_order`
+pub(crate) fn new_field_position(
+    fields: &[(&str, Vec<u8>)],
+    field_type: &str,
+    field_order: &[&str],
+) -> usize {
+    let rank = |field_type: &str| {
+        field_order
+            .iter()
+            .position(|&ordered| ordered == field_type)
+    };
+    match rank(field_type) {
+        Some(new_rank) => fields
+            .iter()
+            .position(|&(existing, _)| matches!(rank(existing), Some(existing_rank) if existing_rank > new_rank))
+            .unwrap_or(fields.len()),
+        None => fields.len(),
+    }
+}
+
+/// Replaces the data of a field with the `edited` data encoded from the parsed record, keeping the parts of the field
+/// that aren't parsed
+pub(crate) fn patch_field(field_type: &str, field_data: &mut Vec<u8>, edited: &[u8]) {
+    let inherit_flags_offset = XCLL_INHERIT_FLAGS_OFFSET as usize;
+    match field_type {
+        // only the lighting template inherit flags at the end of the lighting are parsed
+        "XCLL"
+            if field_data.len() >= inherit_flags_offset + 4
+                && edited.len() >= inherit_flags_offset + 4 =>
+        {
+            field_data[inherit_flags_offset..inherit_flags_offset + 4]
+                .copy_from_slice(&edited[inherit_flags_offset..inherit_flags_offset + 4]);
+        }
+        // only the size of the occlusion data is parsed
+        "TVDT" if field_data.len() == edited.len() => {}
+        // the coordinates are followed by land flags
+        "XCLC" if field_data.len() >= 8 && edited.len() >= 8 => {
+            field_data[..8].copy_from_slice(&edited[..8]);
+        }
+        // each offset and angle is followed by a value that isn't parsed
+        "XWCU" if field_data.len() == edited.len() => {
+            for (velocity, edited_velocity) in field_data
+                .chunks_mut(WATER_VELOCITY_SIZE)
+                .zip(edited.chunks(WATER_VELOCITY_SIZE))
+            {
+                for range in &[0..12, 16..28] {
+                    if let (Some(values), Some(edited_values)) = (
+                        velocity.get_mut(range.clone()),
+                        edited_velocity.get(range.clone()),
+                    ) {
+                        values.copy_from_slice(edited_values);
+                    }
+                }
+            }
+        }
+        // flags that aren't known are dropped when parsing, so they are kept from the original field
+        "DATA" if field_data.len() >= edited.len() => {
+            patch_flags(field_data, edited, &CellFlags::all().bits().to_le_bytes())
+        }
+        "PNAM" if field_data.len() >= edited.len() => patch_flags(
+            field_data,
+            edited,
+            &ParentUseFlags::all().bits().to_le_bytes(),
+        ),
+        _ => *field_data = edited.to_vec(),
+    }
+}
+
+/// Replaces the `known_bits` of a flags field with the `edited` flags
+fn patch_flags(field_data: &mut [u8], edited: &[u8], known_bits: &[u8]) {
+    for ((byte, edited_byte), known_bits) in field_data.iter_mut().zip(edited).zip(known_bits) {
+        *byte = (*byte & !known_bits) | edited_byte;
+    }
+}
+
+/// Block and sub-block numbers of the groups that an interior cell is stored in, from the last two decimal digits of
+/// the local part of its form id
+fn interior_block(form_id: u32) -> (i32, i32) {
+    let local_id = FormId(form_id).local_id() as i32;
+    (local_id % 10, local_id / 10 % 10)
+}
+
+/// Label of an exterior cell block or sub-block group, which stores the grid coordinates as Y then X
+fn grid_label(x: i32, y: i32) -> [u8; 4] {
+    let mut label = [0; 4];
+    label[..2].copy_from_slice(&(y as i16).to_le_bytes());
+    label[2..].copy_from_slice(&(x as i16).to_le_bytes());
+    label
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::PluginBuilder;
+    use crate::error::WarningKind;
+    use crate::parser::{
+        parse_plugin, parse_plugin_with_options, Float, LightingTemplateInheritFlags, ParseOptions,
+        Precombined, PrecombinedReference, WaterVelocity,
+    };
+    use flate2::write::ZlibEncoder;
+
+    fn test_world() -> World {
+        World {
+            name: Some(LString::String("Test World".to_string())),
+            parent_form_id: Some(0x0000_003C),
+            ..World::new(0x0100_0D62, "TestWorld")
+        }
+    }
+
+    /// Encodes fields into the data of a record
+    fn record_data(fields: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = vec![];
+        for (field_type, field_data) in fields {
+            write_field(&mut data, field_type, field_data);
+        }
+        data
+    }
+
+    /// Compresses the data of a record, which starts with its decompressed size
+    fn compressed(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        let mut compressed = (data.len() as u32).to_le_bytes().to_vec();
+        compressed.extend(encoder.finish().unwrap());
+        compressed
+    }
+
+    /// The fields of Tamriel that it was parsed with, including the climate, water, and land data that aren't parsed
+    fn tamriel_fields() -> Vec<u8> {
+        record_data(&[
+            ("EDID", b"Tamriel\0"),
+            ("CNAM", &0x0000_015F_u32.to_le_bytes()),
+            ("NAM2", &0x0000_0018_u32.to_le_bytes()),
+            ("DNAM", &[0, 0, 0x80, 0xC5, 0, 0, 0x7A, 0x44]),
+        ])
+    }
+
+    fn tamriel() -> World {
+        World {
+            raw_data: Some(tamriel_fields()),
+            ..World::new(0x0000_003C, "Tamriel")
+        }
+    }
+
+    fn test_cells() -> Vec<Cell<'static>> {
+        let mut interior = Cell::new_interior(0x0100_0D63, Some("TestInterior".to_string()));
+        interior.name = Some(LString::String("Test Interior".to_string()));
+        interior.flags |= CellFlags::HAS_WATER;
+        interior.water_height = Some(Float(-2048.0));
+        interior.lighting_template_form_id = Some(0x0003_1A55);
+        interior.lighting_template_inherit_flags = Some(
+            LightingTemplateInheritFlags::AMBIENT_COLOR | LightingTemplateInheritFlags::FOG_POWER,
+        );
+        interior.climate_form_id = Some(0x0000_015F);
+        interior.acoustic_space_form_id = Some(0x0001_0D79);
+        interior.lock_list_form_id = Some(0x0010_A4C3);
+        interior.music_type_form_id = Some(0x0001_7F8E);
+        interior.image_space_form_id = Some(0x0001_69D2);
+        interior.encounter_zone_form_id = Some(0x0002_BE3C);
+        interior.is_ignored = true;
+        interior.record_flags = RecordFlags::IGNORED;
+        interior.actors = vec![ActorPlacement {
+            form_id: 0x0100_0D64,
+            base_form_id: Some(0x0001_3BA3),
+            is_persistent: true,
+            is_initially_disabled: true,
+        }];
+
+        let mut exterior = Cell::new_exterior(0x0100_0D65, 0x0100_0D62, -3, 5);
+        exterior.regions = vec![0x0001_8A4B, 0x0100_0D66];
+        exterior.water_type_form_id = Some(0x0001_8B3A);
+        exterior.water_velocities = vec![WaterVelocity {
+            offset: [Float(1.0), Float(-2.5), Float(0.0)],
+            angle: [Float(0.0), Float(0.0), Float(1.5)],
+        }];
+        exterior.actors = vec![ActorPlacement {
+            form_id: 0x0100_0D67,
+            base_form_id: Some(0x0001_3BA4),
+            is_persistent: false,
+            is_initially_disabled: false,
+        }];
+
+        let mut deleted = Cell::new_exterior(0x0000_9B79, 0x0000_003C, 12, -8);
+        deleted.is_deleted = true;
+        deleted.record_flags = RecordFlags::DELETED_RECORD | RecordFlags::COMPRESSED;
+        vec![interior, exterior, deleted]
+    }
+
+    fn test_plugin_bytes() -> Vec<u8> {
+        let builder = PluginBuilder::new()
+            .author("Tester")
+            .master("Skyrim.esm")
+            .world(tamriel())
+            .world(test_world());
+        test_cells()
+            .into_iter()
+            .fold(builder, |builder, cell| builder.cell(cell))
+            .write()
+            .unwrap()
+    }
+
+    /// The cell as it is parsed back, with the fields that writing doesn't preserve set to what the parser fills in
+    fn as_parsed(mut cell: Cell<'static>) -> Cell<'static> {
+        cell.record_flags = cell_record_flags(&cell);
+        cell.form_version = Some(44);
+        cell.persistent_children = cell
+            .actors
+            .iter()
+            .filter(|actor| actor.is_persistent)
+            .map(|actor| actor.form_id)
+            .collect();
+        cell.temporary_children = cell
+            .actors
+            .iter()
+            .filter(|actor| !actor.is_persistent)
+            .map(|actor| actor.form_id)
+            .collect();
+        cell
+    }
+
+    #[test]
+    fn written_plugin_parses_back_into_the_same_worlds_and_cells() {
+        let bytes = test_plugin_bytes();
+        let options = ParseOptions {
+            include_refs: true,
+            ..Default::default()
+        };
+        let plugin = parse_plugin_with_options(&bytes, &options).unwrap();
+
+        assert_eq!(plugin.header.author.as_deref(), Some("Tester"));
+        assert_eq!(plugin.header.masters, vec!["Skyrim.esm"]);
+        let mut worlds: Vec<World> = plugin.worlds.into_iter().collect();
+        worlds.sort_by_key(|world| world.form_id);
+        assert_eq!(worlds.len(), 2);
+        assert_eq!(
+            worlds[0],
+            World {
+                metadata: worlds[0].metadata,
+                raw_data: None,
+                ..tamriel()
+            }
+        );
+        assert_eq!(
+            worlds[1],
+            World {
+                metadata: worlds[1].metadata,
+                ..test_world()
+            }
+        );
+
+        for expected in test_cells().into_iter().map(as_parsed) {
+            let cell = plugin
+                .cells
+                .iter()
+                .find(|cell| cell.form_id == expected.form_id)
+                .unwrap();
+            assert_eq!(
+                cell,
+                &Cell {
+                    metadata: cell.metadata,
+                    ..expected
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn written_cells_keep_their_record_flags_except_compressed() {
+        let bytes = test_plugin_bytes();
+        let plugin = parse_plugin(&bytes).unwrap();
+        let record_flags = |form_id| {
+            plugin
+                .cells
+                .iter()
+                .find(|cell| cell.form_id == form_id)
+                .unwrap()
+                .record_flags
+        };
+        assert_eq!(record_flags(0x0000_9B79), RecordFlags::DELETED_RECORD);
+        assert_eq!(record_flags(0x0100_0D63), RecordFlags::IGNORED);
+        assert_eq!(record_flags(0x0100_0D65), RecordFlags::empty());
+    }
+
+    #[test]
+    fn actor_overrides_of_masters_are_not_written() {
+        let mut cell = Cell::new_interior(0x0100_0D63, None);
+        cell.actors = vec![ActorPlacement {
+            form_id: 0x0001_3BA5,
+            base_form_id: Some(0x0001_3BA3),
+            is_persistent: true,
+            is_initially_disabled: false,
+        }];
+        let bytes = PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(cell)
+            .write()
+            .unwrap();
+        assert!(!bytes.windows(4).any(|window| window == b"ACHR"));
+    }
+
+    #[test]
+    fn cells_of_worlds_that_are_not_in_the_plugin_are_not_written() {
+        let cell = Cell::new_exterior(0x0000_9B79, 0x0000_003C, 12, -8);
+        let error = PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(cell)
+            .write()
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ParseError::MissingWorld {
+                world_form_id: 0x0000_003C,
+                cell_form_id: 0x0000_9B79,
+            }
+        ));
+    }
+
+    #[test]
+    fn worlds_keep_the_fields_that_are_not_parsed_from_their_raw_data() {
+        let options = ParseOptions {
+            include_raw_data: true,
+            ..Default::default()
+        };
+        let bytes = test_plugin_bytes();
+        let plugin = parse_plugin_with_options(&bytes, &options).unwrap();
+        let written = plugin
+            .worlds
+            .iter()
+            .find(|world| world.form_id == 0x0000_003C)
+            .unwrap();
+        assert_eq!(written.raw_data, Some(tamriel_fields()));
+
+        let mut renamed = tamriel();
+        renamed.editor_id = "Skyrim".to_string();
+        renamed.parent_use_flags = Some(ParentUseFlags::LAND_DATA);
+        let data = world_record_data(&renamed).unwrap();
+        let field_types: Vec<&str> = split_fields(&data)
+            .into_iter()
+            .map(|(field_type, _)| field_type)
+            .collect();
+        assert_eq!(field_types, vec!["EDID", "PNAM", "CNAM", "NAM2", "DNAM"]);
+    }
+
+    #[test]
+    fn cells_keep_the_fields_that_are_not_parsed_from_their_raw_data() {
+        let inherit_flags_offset = XCLL_INHERIT_FLAGS_OFFSET as usize;
+        let mut lighting = vec![0xAB; XCLL_SIZE];
+        lighting[inherit_flags_offset..].copy_from_slice(
+            &LightingTemplateInheritFlags::AMBIENT_COLOR
+                .bits()
+                .to_le_bytes(),
+        );
+        let occlusion = [0xCD; 16];
+        let owner = 0x0001_3BA3_u32.to_le_bytes();
+        let raw_fields = record_data(&[
+            ("EDID", b"OldInterior\0"),
+            // an unknown flag along with the interior flag
+            ("DATA", &0x0011_u16.to_le_bytes()),
+            ("XCLL", &lighting),
+            ("TVDT", &occlusion),
+            ("XCMO", &0x0001_7F8E_u32.to_le_bytes()),
+            ("XOWN", &owner),
+        ]);
+        let mut cell = Cell::new_interior(0x0100_0D63, Some("NewInterior".to_string()));
+        cell.flags |= CellFlags::HAS_WATER;
+        cell.lighting_template_inherit_flags = Some(LightingTemplateInheritFlags::FOG_POWER);
+        cell.occlusion_data_size = Some(occlusion.len() as u32);
+        cell.record_flags = RecordFlags::COMPRESSED;
+        cell.raw_data = Some(compressed(&raw_fields));
+
+        let bytes = PluginBuilder::new()
+            .master("Skyrim.esm")
+            .cell(cell)
+            .write()
+            .unwrap();
+        let options = ParseOptions {
+            include_raw_data: true,
+            ..Default::default()
+        };
+        let plugin = parse_plugin_with_options(&bytes, &options).unwrap();
+        let written = plugin.cells.iter().next().unwrap();
+        assert_eq!(written.record_flags, RecordFlags::empty());
+        let mut lighting = lighting.clone();
+        lighting[inherit_flags_offset..]
+            .copy_from_slice(&LightingTemplateInheritFlags::FOG_POWER.bits().to_le_bytes());
+        let expected = record_data(&[
+            ("EDID", b"NewInterior\0"),
+            ("DATA", &0x0013_u16.to_le_bytes()),
+            ("XCLL", &lighting),
+            ("TVDT", &occlusion),
+            ("XOWN", &owner),
+        ]);
+        assert_eq!(written.raw_data, Some(expected));
+    }
+
+    #[test]
+    fn cells_without_raw_data_leave_out_their_occlusion_data() {
+        let mut cell = Cell::new_interior(0x0100_0D63, None);
+        cell.occlusion_data_size = Some(16);
+        let fields = cell_record_data(&cell, Game::Skyrim).unwrap();
+        assert!(!fields.windows(4).any(|window| window == b"TVDT"));
+    }
+
+    #[test]
+    fn written_record_count_matches_the_plugin() {
+        let bytes = test_plugin_bytes();
+        let options = ParseOptions {
+            collect_warnings: true,
+            ..Default::default()
+        };
+        let plugin = parse_plugin_with_options(&bytes, &options).unwrap();
+        assert!(plugin.header.num_records_and_groups > 0);
+        assert!(!plugin
+            .warnings
+            .iter()
+            .any(|warning| warning.kind == WarningKind::RecordCount));
+    }
+
+    #[test]
+    fn written_cell_fields_are_in_creation_kit_order() {
+        let game = Game::Fallout4;
+        let mut cell = test_cells().remove(0);
+        cell.regions = vec![0x0001_8A4B];
+        cell.previs_references = vec![0x0100_0D70];
+        cell.precombined = Some(Precombined {
+            meshes: vec![0x1234_5678],
+            references: vec![PrecombinedReference {
+                form_id: 0x0100_0D70,
+                mesh: 0x1234_5678,
+            }],
+        });
+        let fields = cell_fields(&cell, game);
+        let mut field_types = vec![];
+        let mut position = 0;
+        while position < fields.len() {
+            field_types.push(std::str::from_utf8(&fields[position..position + 4]).unwrap());
+            let size = u16::from_le_bytes([fields[position + 4], fields[position + 5]]);
+            position += 6 + size as usize;
+        }
+        let ranks: Vec<usize> = field_types
+            .iter()
+            .map(|field_type| {
+                CELL_FIELD_ORDER
+                    .iter()
+                    .position(|ordered| ordered == field_type)
+                    .unwrap()
+            })
+            .collect();
+        assert!(ranks.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(field_types.contains(&"XCRI"));
+    }
+
+    #[test]
+    fn fallout4_cells_round_trip_their_precombined_data() {
+        let mut cell = Cell::new_interior(0x0100_0D63, Some("Vault".to_string()));
+        cell.lighting_template_inherit_flags = Some(LightingTemplateInheritFlags::FOG_FAR);
+        cell.previs_references = vec![0x0100_0D70, 0x0100_0D71];
+        cell.precombined = Some(Precombined {
+            meshes: vec![0x1234_5678, 0x9ABC_DEF0],
+            references: vec![PrecombinedReference {
+                form_id: 0x0100_0D70,
+                mesh: 0x9ABC_DEF0,
+            }],
+        });
+        let bytes = PluginBuilder::new()
+            .game(Game::Fallout4)
+            .master("Fallout4.esm")
+            .cell(cell.clone())
+            .write()
+            .unwrap();
+        assert_eq!(Game::detect(&bytes), Some(Game::Fallout4));
+        let options = ParseOptions {
+            game: Game::Fallout4,
+            collect_warnings: true,
+            ..Default::default()
+        };
+        let plugin = parse_plugin_with_options(&bytes, &options).unwrap();
+        assert!(plugin.warnings.is_empty());
+        let parsed = plugin.cells.into_iter().next().unwrap();
+        assert_eq!(
+            parsed.lighting_template_inherit_flags,
+            cell.lighting_template_inherit_flags
+        );
+        assert_eq!(parsed.previs_references, cell.previs_references);
+        assert_eq!(parsed.precombined, cell.precombined);
+        assert_eq!(parsed.form_version, Some(131));
+    }
+}